@@ -0,0 +1,166 @@
+use glam::{Mat4, Vec3};
+
+/// Axis aligned bounding box
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self {
+            min: Vec3::ZERO,
+            max: Vec3::ZERO,
+        }
+    }
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Calculates the smallest box that contains all the points. If there are no points, then a box with
+    /// zero size at the origin is returned
+    pub fn from_points<'a, T: IntoIterator<Item = &'a Vec3>>(points: T) -> Self {
+        let mut points = points.into_iter();
+        if let Some(first_point) = points.next() {
+            let (min, max) = points.fold((*first_point, *first_point), |(min, max), point| {
+                (min.min(*point), max.max(*point))
+            });
+
+            Self { min, max }
+        } else {
+            Self::default()
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Half of the size of the box along each axis
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /// Transforms the box with the given matrix and returns the axis aligned box that contains the result
+    // Based on "Transforming Axis-Aligned Bounding Boxes" by Jim Arvo, Graphics Gems 1990
+    pub fn transform(&self, matrix: &Mat4) -> Self {
+        let center = matrix.transform_point3(self.center());
+        let half_extents = self.half_extents();
+
+        let new_half_extents = matrix.x_axis.truncate().abs() * half_extents.x
+            + matrix.y_axis.truncate().abs() * half_extents.y
+            + matrix.z_axis.truncate().abs() * half_extents.z;
+
+        Self {
+            min: center - new_half_extents,
+            max: center + new_half_extents,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Creates a sphere around the center of the bounding box of the points. This is not the smallest possible
+    /// sphere, but it's cheap to calculate and good enough for culling
+    pub fn from_points<'a, T: IntoIterator<Item = &'a Vec3> + Clone>(points: T) -> Self {
+        let center = Aabb::from_points(points.clone()).center();
+        let radius_squared = points.into_iter().fold(0.0_f32, |radius, point| {
+            radius.max(center.distance_squared(*point))
+        });
+
+        Self {
+            center,
+            radius: radius_squared.sqrt(),
+        }
+    }
+
+    /// Transforms the sphere with the given matrix. In case of non-uniform scaling, the largest scale is used,
+    /// so the result will still contain the transformed object
+    pub fn transform(&self, matrix: &Mat4) -> Self {
+        let max_scale = matrix
+            .x_axis
+            .truncate()
+            .length()
+            .max(matrix.y_axis.truncate().length())
+            .max(matrix.z_axis.truncate().length());
+
+        Self {
+            center: matrix.transform_point3(self.center),
+            radius: self.radius * max_scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Quat;
+
+    use super::*;
+
+    #[test]
+    fn aabb_from_points() {
+        let points = [
+            Vec3::new(1.0, -2.0, 3.0),
+            Vec3::new(-1.0, 4.0, 0.0),
+            Vec3::new(0.5, 0.0, -3.0),
+        ];
+
+        let aabb = Aabb::from_points(&points);
+        assert_eq!(aabb.min, Vec3::new(-1.0, -2.0, -3.0));
+        assert_eq!(aabb.max, Vec3::new(1.0, 4.0, 3.0));
+    }
+
+    #[test]
+    fn aabb_transform_contains_rotated_box() {
+        let aabb = Aabb::new(Vec3::splat(-1.0), Vec3::splat(1.0));
+        let matrix = Mat4::from_scale_rotation_translation(
+            Vec3::splat(2.0),
+            Quat::from_rotation_y(std::f32::consts::FRAC_PI_4),
+            Vec3::new(10.0, 0.0, 0.0),
+        );
+
+        let transformed = aabb.transform(&matrix);
+        let expected_half_size = 2.0 * std::f32::consts::SQRT_2;
+        assert!(transformed
+            .center()
+            .abs_diff_eq(Vec3::new(10.0, 0.0, 0.0), 0.0001));
+        assert!(transformed.half_extents().abs_diff_eq(
+            Vec3::new(expected_half_size, 2.0, expected_half_size),
+            0.0001
+        ));
+    }
+
+    #[test]
+    fn sphere_transform_uses_largest_scale() {
+        let sphere = BoundingSphere::new(Vec3::ZERO, 1.0);
+        let matrix = Mat4::from_scale_rotation_translation(
+            Vec3::new(1.0, 3.0, 2.0),
+            Quat::IDENTITY,
+            Vec3::new(0.0, 5.0, 0.0),
+        );
+
+        let transformed = sphere.transform(&matrix);
+        assert_eq!(transformed.center, Vec3::new(0.0, 5.0, 0.0));
+        assert_eq!(transformed.radius, 3.0);
+    }
+}
@@ -0,0 +1,137 @@
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::{Aabb, BoundingSphere};
+
+/// A plane described by its normal and its signed distance from the origin.
+/// Points with `normal.dot(point) + distance >= 0` are on the positive side of the plane
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+impl Plane {
+    fn from_vec4_normalized(plane: Vec4) -> Self {
+        let length = plane.truncate().length();
+        Self {
+            normal: plane.truncate() / length,
+            distance: plane.w / length,
+        }
+    }
+
+    /// The plane going through the point, `normal` has to be normalized
+    pub fn from_point_and_normal(point: Vec3, normal: Vec3) -> Self {
+        Self {
+            normal,
+            distance: -normal.dot(point),
+        }
+    }
+
+    pub fn signed_distance(&self, point: Vec3) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+/// The 6 planes of a view frustum. The normals of the planes point to the inside of the frustum
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frustum {
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extracts the frustum planes from a view-projection matrix. The matrix is expected to map the depth into
+    /// the [0, 1] range (as is the case with wgpu), reversed-z projection matrices work as well, because
+    /// only the near and the far plane swap in that case
+    // Based on "Fast Extraction of Viewing Frustum Planes from the World-View-Projection Matrix" by Gribb and Hartmann
+    pub fn from_view_proj(view_proj: &Mat4) -> Self {
+        let row0 = view_proj.row(0);
+        let row1 = view_proj.row(1);
+        let row2 = view_proj.row(2);
+        let row3 = view_proj.row(3);
+
+        Self {
+            planes: [
+                Plane::from_vec4_normalized(row3 + row0),
+                Plane::from_vec4_normalized(row3 - row0),
+                Plane::from_vec4_normalized(row3 + row1),
+                Plane::from_vec4_normalized(row3 - row1),
+                Plane::from_vec4_normalized(row2),
+                Plane::from_vec4_normalized(row3 - row2),
+            ],
+        }
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(sphere.center) >= -sphere.radius)
+    }
+
+    /// Conservative test: might return true for some boxes that are close to the corners of the frustum, but are
+    /// in fact outside of it. This is fine for culling purposes
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner of the box that is the furthest along the normal of the plane
+            let positive_vertex = Vec3::select(plane.normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            plane.signed_distance(positive_vertex) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reverse_z_matrix;
+
+    fn test_view_proj(use_reverse_z: bool) -> Mat4 {
+        let view = Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+        let proj = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0);
+        if use_reverse_z {
+            reverse_z_matrix() * proj * view
+        } else {
+            proj * view
+        }
+    }
+
+    #[test]
+    fn sphere_culling() {
+        for use_reverse_z in [false, true] {
+            let frustum = Frustum::from_view_proj(&test_view_proj(use_reverse_z));
+
+            assert!(
+                frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(0.0, 0.0, -10.0), 1.0))
+            );
+            // Behind the camera
+            assert!(
+                !frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(0.0, 0.0, 10.0), 1.0))
+            );
+            // Further than the far plane
+            assert!(
+                !frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(0.0, 0.0, -150.0), 1.0))
+            );
+            // Outside on the side, but big enough to reach into the frustum
+            assert!(
+                frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(15.0, 0.0, -10.0), 6.0))
+            );
+            assert!(
+                !frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(15.0, 0.0, -10.0), 1.0))
+            );
+        }
+    }
+
+    #[test]
+    fn aabb_culling() {
+        for use_reverse_z in [false, true] {
+            let frustum = Frustum::from_view_proj(&test_view_proj(use_reverse_z));
+
+            let inside = Aabb::new(Vec3::new(-1.0, -1.0, -11.0), Vec3::new(1.0, 1.0, -9.0));
+            assert!(frustum.intersects_aabb(&inside));
+
+            let above = Aabb::new(Vec3::new(-1.0, 20.0, -11.0), Vec3::new(1.0, 22.0, -9.0));
+            assert!(!frustum.intersects_aabb(&above));
+
+            let behind = Aabb::new(Vec3::new(-1.0, -1.0, 5.0), Vec3::new(1.0, 1.0, 7.0));
+            assert!(!frustum.intersects_aabb(&behind));
+        }
+    }
+}
@@ -1,7 +1,15 @@
+mod bounding_volume;
 mod degrees;
+mod frustum;
 mod line;
 mod matrix;
+mod mesh_simplification;
+mod spline;
 
+pub use bounding_volume::*;
 pub use degrees::*;
+pub use frustum::*;
 pub use line::*;
 pub use matrix::*;
+pub use mesh_simplification::*;
+pub use spline::*;
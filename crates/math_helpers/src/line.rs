@@ -1,5 +1,7 @@
 use glam::Vec3;
 
+use crate::{Aabb, Plane};
+
 #[derive(Debug, Copy, Clone)]
 pub struct Line {
     pub position: Vec3,
@@ -39,6 +41,98 @@ impl Line {
 
         (self_closest_point, other_closest_point)
     }
+
+    /// The point where the line crosses the plane. None if the line is parallel to the plane
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<Vec3> {
+        let direction_along_normal = plane.normal.dot(self.direction);
+        if direction_along_normal.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        Some(self.evaluate(-plane.signed_distance(self.position) / direction_along_normal))
+    }
+
+    /// The parameter where the line enters the box going along its direction, and the normal of the hit face facing
+    /// against the direction. If the line starts inside of the box, the face where it leaves the box is returned.
+    /// None if the box is missed or it's behind the start of the line
+    // Based on the slab method
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<(f32, Vec3)> {
+        let (mut near_value, mut near_axis) = (f32::NEG_INFINITY, 0);
+        let (mut far_value, mut far_axis) = (f32::INFINITY, 0);
+        for axis in 0..3 {
+            let position = self.position[axis];
+            let direction = self.direction[axis];
+            if direction.abs() <= f32::EPSILON {
+                if position < aabb.min[axis] || position > aabb.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let min_value = (aabb.min[axis] - position) / direction;
+            let max_value = (aabb.max[axis] - position) / direction;
+            if min_value.min(max_value) > near_value {
+                near_value = min_value.min(max_value);
+                near_axis = axis;
+            }
+            if min_value.max(max_value) < far_value {
+                far_value = min_value.max(max_value);
+                far_axis = axis;
+            }
+        }
+
+        if near_value > far_value || far_value < 0.0 {
+            return None;
+        }
+
+        let (value, axis) = if near_value >= 0.0 {
+            (near_value, near_axis)
+        } else {
+            (far_value, far_axis)
+        };
+        let mut normal = Vec3::ZERO;
+        normal[axis] = -self.direction[axis].signum();
+
+        Some((value, normal))
+    }
+
+    /// Both sides of the triangle are hit. Returns the line parameter of the intersection and the normal of the
+    /// triangle facing the start of the line
+    // Based on the Möller–Trumbore algorithm
+    pub fn intersect_triangle(&self, triangle: &[Vec3; 3]) -> Option<(f32, Vec3)> {
+        let edge1 = triangle[1] - triangle[0];
+        let edge2 = triangle[2] - triangle[0];
+        let direction_cross_edge2 = self.direction.cross(edge2);
+        let determinant = edge1.dot(direction_cross_edge2);
+        if determinant == 0.0 {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+        let to_position = self.position - triangle[0];
+        let u = to_position.dot(direction_cross_edge2) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let to_position_cross_edge1 = to_position.cross(edge1);
+        let v = self.direction.dot(to_position_cross_edge1) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let value = edge2.dot(to_position_cross_edge1) * inverse_determinant;
+        if value < 0.0 {
+            return None;
+        }
+
+        let normal = edge1.cross(edge2).normalize();
+        if normal.dot(self.direction) > 0.0 {
+            Some((value, -normal))
+        } else {
+            Some((value, normal))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -77,4 +171,88 @@ mod tests {
             MAX_VEC_DIFF
         ));
     }
+
+    #[test]
+    fn line_plane_intersection() {
+        let plane = Plane::from_point_and_normal(Vec3::new(0., 2., 0.), Vec3::Y);
+        let line = Line {
+            position: Vec3::new(1., 5., -1.),
+            direction: Vec3::new(1., -1., 0.).normalize(),
+        };
+
+        let intersection = line.intersect_plane(&plane).unwrap();
+        assert!(intersection.abs_diff_eq(Vec3::new(4., 2., -1.), MAX_VEC_DIFF));
+
+        let parallel_line = Line {
+            position: Vec3::new(1., 5., -1.),
+            direction: Vec3::X,
+        };
+        assert!(parallel_line.intersect_plane(&plane).is_none());
+    }
+
+    #[test]
+    fn line_aabb_intersection() {
+        let aabb = Aabb::new(Vec3::new(-1., 0., -1.), Vec3::new(1., 2., 1.));
+        let line = Line {
+            position: Vec3::new(0., 5., 0.),
+            direction: -Vec3::Y,
+        };
+
+        let (value, normal) = line.intersect_aabb(&aabb).unwrap();
+        assert!((value - 3.).abs() < MAX_VEC_DIFF);
+        assert_eq!(normal, Vec3::Y);
+
+        // From the inside the far face is hit, the normal faces the start of the line
+        let inner_line = Line {
+            position: Vec3::new(0., 1., 0.),
+            direction: Vec3::X,
+        };
+        let (value, normal) = inner_line.intersect_aabb(&aabb).unwrap();
+        assert!((value - 1.).abs() < MAX_VEC_DIFF);
+        assert_eq!(normal, -Vec3::X);
+
+        let missing_line = Line {
+            position: Vec3::new(0., 5., 0.),
+            direction: Vec3::Y,
+        };
+        assert!(missing_line.intersect_aabb(&aabb).is_none());
+    }
+
+    #[test]
+    fn line_triangle_intersection() {
+        let triangle = [
+            Vec3::new(0., 1., 0.),
+            Vec3::new(2., 1., 0.),
+            Vec3::new(0., 3., 2.),
+        ];
+        let slope_normal = Vec3::new(0., -1., 1.).normalize();
+        let line = Line {
+            position: Vec3::new(0.5, 1.5, 5.),
+            direction: -Vec3::Z,
+        };
+
+        let (value, normal) = line.intersect_triangle(&triangle).unwrap();
+        assert!((value - 4.5).abs() < MAX_VEC_DIFF);
+        assert!(normal.abs_diff_eq(slope_normal, MAX_VEC_DIFF));
+
+        // From the other side the normal is flipped
+        let opposite_line = Line {
+            position: Vec3::new(0.5, 1.5, -5.),
+            direction: Vec3::Z,
+        };
+        let (value, normal) = opposite_line.intersect_triangle(&triangle).unwrap();
+        assert!((value - 5.5).abs() < MAX_VEC_DIFF);
+        assert!(normal.abs_diff_eq(-slope_normal, MAX_VEC_DIFF));
+
+        let missing_line = Line {
+            position: Vec3::new(1.5, 2.5, 5.),
+            direction: -Vec3::Z,
+        };
+        assert!(missing_line.intersect_triangle(&triangle).is_none());
+        let behind_line = Line {
+            position: Vec3::new(0.5, 1.5, 5.),
+            direction: Vec3::Z,
+        };
+        assert!(behind_line.intersect_triangle(&triangle).is_none());
+    }
 }
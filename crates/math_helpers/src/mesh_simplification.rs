@@ -0,0 +1,169 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::{UVec3, Vec3};
+
+use crate::Aabb;
+
+/// The result of simplifying a triangle mesh. The simplified mesh reuses a subset of the original vertices,
+/// so the other vertex attributes (normals, texture coordinates, etc.) can be taken over from the original mesh
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimplifiedMesh {
+    /// The index of the original vertex for each vertex of the simplified mesh
+    pub vertex_indices: Vec<u32>,
+    /// Triangle list, indexing into `vertex_indices`
+    pub indices: Vec<u32>,
+}
+
+impl SimplifiedMesh {
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+/// Simplifies the mesh by putting a grid over its bounding box and merging all the vertices that fall into the
+/// same grid cell. Each cell is represented by the original vertex that is the closest to the average position
+/// of the vertices in the cell. Triangles that collapse into lines or points are removed, as well as the
+/// triangles that became duplicates of other triangles.
+/// The higher `grid_resolution` is, the more details are kept
+// Based on "Multi-resolution 3D approximations for rendering complex scenes" by Rossignac and Borrel, 1993
+pub fn simplify_by_vertex_clustering(
+    positions: &[Vec3],
+    indices: &[u32],
+    grid_resolution: u32,
+) -> SimplifiedMesh {
+    let grid_resolution = grid_resolution.max(1);
+    let bounding_box = Aabb::from_points(positions);
+    let cell_size = (bounding_box.max - bounding_box.min) / grid_resolution as f32;
+
+    let get_cell = |position: Vec3| -> UVec3 {
+        // Flat meshes have zero size along some axes, everything is in the same cell along those
+        let cell = ((position - bounding_box.min) / cell_size.max(Vec3::splat(f32::EPSILON)))
+            .floor()
+            .as_uvec3();
+        cell.min(UVec3::splat(grid_resolution - 1))
+    };
+
+    // Only the vertices that are referenced by triangles are taken into account
+    let mut position_sums: HashMap<UVec3, (Vec3, u32)> = HashMap::new();
+    for &index in indices {
+        let position = positions[index as usize];
+        let (sum, count) = position_sums
+            .entry(get_cell(position))
+            .or_insert((Vec3::ZERO, 0));
+        *sum += position;
+        *count += 1;
+    }
+
+    let mut representatives: HashMap<UVec3, (u32, f32)> = HashMap::new();
+    for &index in indices {
+        let position = positions[index as usize];
+        let cell = get_cell(position);
+        let (sum, count) = position_sums[&cell];
+        let distance = position.distance_squared(sum / count as f32);
+
+        let representative = representatives.entry(cell).or_insert((index, distance));
+        if distance < representative.1 {
+            *representative = (index, distance);
+        }
+    }
+
+    let mut vertex_indices = Vec::new();
+    let mut new_vertex_indices: HashMap<u32, u32> = HashMap::new();
+    let mut remap = |original_index: u32| -> u32 {
+        let (representative, _) = representatives[&get_cell(positions[original_index as usize])];
+        *new_vertex_indices
+            .entry(representative)
+            .or_insert_with(|| {
+                vertex_indices.push(representative);
+                vertex_indices.len() as u32 - 1
+            })
+    };
+
+    let mut new_indices = Vec::with_capacity(indices.len());
+    let mut added_triangles = HashSet::new();
+    for triangle in indices.chunks_exact(3) {
+        let new_triangle = [remap(triangle[0]), remap(triangle[1]), remap(triangle[2])];
+
+        if new_triangle[0] == new_triangle[1]
+            || new_triangle[1] == new_triangle[2]
+            || new_triangle[0] == new_triangle[2]
+        {
+            continue;
+        }
+
+        // Rotate the triangle, so the smallest index is the first, without changing the winding order
+        let smallest_position = (0..3).min_by_key(|&i| new_triangle[i]).unwrap_or(0);
+        let mut triangle_key = new_triangle;
+        triangle_key.rotate_left(smallest_position);
+
+        if added_triangles.insert(triangle_key) {
+            new_indices.extend_from_slice(&new_triangle);
+        }
+    }
+
+    SimplifiedMesh {
+        vertex_indices,
+        indices: new_indices,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A flat, square grid on the XZ plane, with `size` quads along each side
+    fn create_grid(size: u32) -> (Vec<Vec3>, Vec<u32>) {
+        let positions = (0..=size)
+            .flat_map(|z| (0..=size).map(move |x| Vec3::new(x as f32, 0.0, z as f32)))
+            .collect::<Vec<_>>();
+
+        let indices = (0..size)
+            .flat_map(|z| {
+                (0..size).flat_map(move |x| {
+                    let top_left = z * (size + 1) + x;
+                    let bottom_left = top_left + size + 1;
+                    [
+                        top_left,
+                        bottom_left,
+                        top_left + 1,
+                        top_left + 1,
+                        bottom_left,
+                        bottom_left + 1,
+                    ]
+                })
+            })
+            .collect::<Vec<_>>();
+
+        (positions, indices)
+    }
+
+    #[test]
+    fn high_resolution_keeps_every_triangle() {
+        let (positions, indices) = create_grid(4);
+
+        let simplified = simplify_by_vertex_clustering(&positions, &indices, 64);
+        assert_eq!(simplified.triangle_count(), indices.len() / 3);
+        assert_eq!(simplified.vertex_indices.len(), positions.len());
+    }
+
+    #[test]
+    fn low_resolution_reduces_triangles() {
+        let (positions, indices) = create_grid(16);
+
+        let simplified = simplify_by_vertex_clustering(&positions, &indices, 4);
+        assert!(simplified.triangle_count() > 0);
+        assert!(simplified.triangle_count() < indices.len() / 3 / 4);
+        assert!(simplified
+            .indices
+            .iter()
+            .all(|&index| (index as usize) < simplified.vertex_indices.len()));
+    }
+
+    #[test]
+    fn collapsed_triangles_are_removed() {
+        let (positions, indices) = create_grid(8);
+
+        let simplified = simplify_by_vertex_clustering(&positions, &indices, 1);
+        assert!(simplified.indices.is_empty());
+    }
+}
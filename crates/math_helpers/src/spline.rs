@@ -0,0 +1,45 @@
+use glam::Vec3;
+
+/// Evaluates the uniform Catmull-Rom spline segment between `p1` and `p2` where `t` goes from 0 to 1. `p0` and `p3`
+/// are the neighbouring control points, they shape the tangents at the ends of the segment
+pub fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_VEC_DIFF: f32 = 0.00001;
+
+    #[test]
+    fn catmull_rom_passes_through_the_inner_points() {
+        let points = [
+            Vec3::new(-1.0, 0.0, 2.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(3.0, 1.0, -1.0),
+            Vec3::new(4.0, -2.0, 0.0),
+        ];
+
+        let start = catmull_rom(points[0], points[1], points[2], points[3], 0.0);
+        let end = catmull_rom(points[0], points[1], points[2], points[3], 1.0);
+
+        assert!(start.abs_diff_eq(points[1], MAX_VEC_DIFF));
+        assert!(end.abs_diff_eq(points[2], MAX_VEC_DIFF));
+    }
+
+    #[test]
+    fn catmull_rom_of_evenly_spaced_collinear_points_is_linear() {
+        let p = |x: f32| Vec3::new(x, 2.0 * x, 0.0);
+
+        let middle = catmull_rom(p(0.0), p(1.0), p(2.0), p(3.0), 0.25);
+
+        assert!(middle.abs_diff_eq(p(1.25), MAX_VEC_DIFF));
+    }
+}
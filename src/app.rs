@@ -1,22 +1,41 @@
 use crate::actions::RenderingAction;
 use crate::bind_group_layout_descriptors;
 use crate::buffer::GpuBufferCreationOptions;
-use crate::camera_controller::CameraController;
+use crate::camera_controller::{CameraController, CameraControllerEvent};
+use crate::camera_path::CameraKeyframe;
+use crate::components::{
+    CameraComponent, RenderableComponent, SceneComponentType, TransformComponent,
+};
 use crate::custom_event::CustomEvent;
+use crate::editor_history::EditorHistory;
+use crate::editor_settings::EditorSettings;
 use crate::global_params::{GlobalCPUParams, GlobalGPUParams};
 use crate::gpu_buffer::GpuBuffer;
-use crate::gui::{Gui, GuiButton, GuiEvent, GuiUpdateEvent};
+use crate::gui::{CameraViewpointAction, Gui, GuiButton, GuiEvent, GuiUpdateEvent, OutlinerItem};
 use crate::gui_settable_value::GuiSettableValue;
 use crate::light_controller::LightController;
 use crate::object_picker::ObjectPickManager;
 use crate::player_controller::PlayerController;
+use crate::prefab::{
+    add_prefab_instance, apply_instance_to_prefab, create_prefab_from_objects,
+    find_prefab_instance_root, load_prefab, PrefabInstance,
+};
 use crate::resource_loader::ResourceLoader;
 use crate::world::World;
-use crate::world_loader::{load_level, save_level};
-use crate::world_renderer::WorldRenderer;
+use crate::world_events::{WorldEvent, WorldEventFilter, WorldEventKind};
+use crate::world_loader::{
+    add_level, copy_world_objects, load_level, paste_world_objects, read_level_file, save_level,
+    serialize_level_objects, LevelSaveOptions,
+};
+use crate::world_object::WorldObject;
+use crate::world_renderer::{OutlinedObjects, WorldRenderer};
 use crate::{frame_timer::BasicTimer, renderer::Renderer};
 use crossbeam_channel::{unbounded, Receiver};
-use std::path::Path;
+use glam::Vec3;
+use math_helpers::{Aabb, BoundingSphere};
+use rfd::{FileDialog, MessageButtons, MessageDialog, MessageDialogResult, MessageLevel};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use ui_item::{UiDisplayable, UiSettableNew};
 use wgpu::TextureViewDescriptor;
@@ -25,9 +44,28 @@ use winit::event_loop::EventLoopProxy;
 use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::Window;
 
+/// The levels reference the prefabs and the skybox by their path, relative paths keep working when the project is
+/// moved
+fn get_project_relative_path(path: PathBuf) -> PathBuf {
+    std::env::current_dir()
+        .ok()
+        .and_then(|working_directory| path.strip_prefix(working_directory).ok())
+        .map(Path::to_path_buf)
+        .unwrap_or(path)
+}
+
 pub enum WindowEventHandlingAction {
     Exit,
     RecompileShaders,
+    Undo,
+    Redo,
+    Copy,
+    Paste,
+    Duplicate,
+    /// Moves the camera to the bookmark of the digit key
+    RecallCameraBookmark(u32),
+    /// Saves the current viewpoint as the bookmark of the digit key
+    StoreCameraBookmark(u32),
 }
 
 pub enum WindowEventHandlingResult {
@@ -46,38 +84,93 @@ pub struct App {
     frame_timer: BasicTimer,
     gui: Gui,
     player_controller: PlayerController,
+    history: EditorHistory,
+    editor_settings: EditorSettings,
     gpu_params: GuiSettableValue<GpuBuffer<GlobalGPUParams>>,
-    cpu_rendering_params: GlobalCPUParams,
+    cpu_rendering_params: GuiSettableValue<GlobalCPUParams>,
 
     light_controller: LightController,
 
     should_draw_gui: bool,
     gui_event_receiver: Receiver<GuiEvent>,
+    /// Changes of the hierarchy, the outliner is rebuilt when one is received
+    outliner_event_receiver: Receiver<WorldEvent>,
+
+    /// The file of the level being edited, None if the level was never saved
+    level_path: Option<PathBuf>,
+    /// The objects of the level when it was last loaded or saved, see `serialize_level_objects`
+    saved_level_content: String,
+    has_unsaved_changes: bool,
+    /// Every change of the world. A change of a saved object makes the level unsaved, see `handle_level_events`
+    level_event_receiver: Receiver<WorldEvent>,
+    /// Set when the changes might have brought back the saved state of the level (eg. undo), so the level has to be
+    /// compared with the saved one
+    should_compare_with_saved_level: bool,
 }
 
+const DEFAULT_LEVEL_PATH: &str = "levels/test.lvl";
+const LEVEL_FOLDER: &str = "levels";
+const LEVEL_FILE_EXTENSION: &str = "lvl";
+const PREFAB_FOLDER: &str = "assets/prefabs";
+const PREFAB_FILE_EXTENSION: &str = "prefab";
+/// How far the copies are placed from the copied objects, if they are not placed under the cursor
+const PASTE_OFFSET: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+
 impl App {
-    pub fn new(window: &Window, event_loop_proxy: EventLoopProxy<CustomEvent>) -> Self {
+    /// Opens the level at `level_path`, or the default level if no path is given
+    pub fn new(
+        window: &Window,
+        event_loop_proxy: EventLoopProxy<CustomEvent>,
+        level_path: Option<&Path>,
+    ) -> Self {
         let renderer = Renderer::new(window);
         let (gui_event_sender, gui_event_receiver) = unbounded::<GuiEvent>();
         let mut resource_loader = ResourceLoader::new(&renderer);
 
-        let gui = Gui::new(&window, &renderer.device, gui_event_sender);
+        let mut gui = Gui::new(&window, &renderer.device, gui_event_sender);
 
-        let mut world_renderer: WorldRenderer = WorldRenderer::new(&renderer, &mut resource_loader);
-
-        let camera_controller = CameraController::new(
-            &renderer.device,
-            renderer.config.width,
-            renderer.config.height,
-        );
+        let camera_controller =
+            CameraController::new(renderer.config.width, renderer.config.height);
 
         let mut world = World::new(camera_controller);
 
-        load_level(&mut world, Path::new("levels/test.lvl")).unwrap();
+        // Everyone subscribes before the level is loaded, so they receive the objects of the level
+        let mut world_renderer: WorldRenderer =
+            WorldRenderer::new(&renderer, &mut resource_loader, &mut world);
+        let light_controller = LightController::new(&renderer.device, &mut world);
+        let outliner_event_receiver = world.subscribe(WorldEventFilter::all().with_kinds(&[
+            WorldEventKind::ObjectAdded,
+            WorldEventKind::ObjectRemoved,
+            WorldEventKind::ParentChanged,
+            WorldEventKind::ComponentAdded,
+            WorldEventKind::ComponentRemoved,
+            WorldEventKind::ComponentChanged,
+        ]));
+        let level_event_receiver = world.subscribe(WorldEventFilter::all());
 
-        let player_controller = PlayerController::new();
+        let level_path = level_path.unwrap_or(Path::new(DEFAULT_LEVEL_PATH));
+        let level_path = match load_level(&mut world, level_path) {
+            Ok(()) => Some(level_path.to_path_buf()),
+            Err(error) => {
+                log::error!("Failed to load level {level_path:?}: {error}");
+                gui.push_display_info_update(GuiUpdateEvent::LevelLoadResult(Err(error)));
+                world.clear();
+                None
+            }
+        };
+        let saved_level_content = serialize_level_objects(&world).unwrap_or_default();
 
-        let light_controller = LightController::new(&renderer.device);
+        let editor_settings = EditorSettings::load().unwrap_or_else(|error| {
+            log::warn!("Failed to load the editor settings, using the defaults: {error}");
+            EditorSettings::default()
+        });
+        let mut player_controller = PlayerController::new();
+        player_controller.set_gizmo_orientation(editor_settings.gizmo_orientation, &mut world);
+        player_controller.set_snap_settings(editor_settings.snap_settings.clone());
+        gui.push_display_info_update(GuiUpdateEvent::SnapSettings(
+            editor_settings.snap_settings.clone(),
+        ));
+        let history = EditorHistory::new(&mut world);
 
         let frame_timer = BasicTimer::new();
 
@@ -100,26 +193,48 @@ impl App {
             ui_items,
         );
 
+        let cpu_rendering_params = GlobalCPUParams::default();
+        let ui_items = cpu_rendering_params.get_ui_description();
+        let cpu_rendering_params = GuiSettableValue::new(
+            cpu_rendering_params,
+            "cpu_params".to_owned(),
+            &event_loop_proxy,
+            ui_items,
+        );
+
         let object_picker = ObjectPickManager::new(&renderer);
 
         // Initial environment cubemap generation from the equirectangular map
         world_renderer.add_action(RenderingAction::GenerateCubeMapFromEquirectangular);
 
-        Self {
+        let mut app = Self {
             renderer,
             world_renderer,
             frame_timer,
             gui,
             should_draw_gui: true,
             gui_event_receiver,
+            outliner_event_receiver,
             world,
             light_controller,
             resource_loader,
             player_controller,
-            cpu_rendering_params: GlobalCPUParams::default(),
+            history,
+            editor_settings,
+            cpu_rendering_params,
             gpu_params,
             object_picker,
-        }
+            level_path,
+            saved_level_content,
+            has_unsaved_changes: false,
+            level_event_receiver,
+            should_compare_with_saved_level: false,
+        };
+        // The later updates are only pushed when these change
+        app.push_history_update();
+        app.push_level_settings_update();
+        app.push_camera_controller_update();
+        app
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -170,16 +285,51 @@ impl App {
         match self.player_controller.handle_window_event(
             &event,
             &mut self.world,
+            &mut self.history,
             &self.object_picker,
+            &self.world_renderer,
         ) {
-            WindowEventHandlingResult::RequestAction(action) => {
-                if matches!(action, WindowEventHandlingAction::RecompileShaders) {
+            WindowEventHandlingResult::RequestAction(action) => match action {
+                WindowEventHandlingAction::RecompileShaders => {
                     self.recompile_shaders();
                     return WindowEventHandlingResult::Handled;
-                } else {
+                }
+                WindowEventHandlingAction::Undo => {
+                    self.undo();
+                    return WindowEventHandlingResult::Handled;
+                }
+                WindowEventHandlingAction::Redo => {
+                    self.redo();
+                    return WindowEventHandlingResult::Handled;
+                }
+                WindowEventHandlingAction::Copy => {
+                    self.copy_selected_objects();
+                    return WindowEventHandlingResult::Handled;
+                }
+                WindowEventHandlingAction::Paste => {
+                    self.paste_objects();
+                    return WindowEventHandlingResult::Handled;
+                }
+                WindowEventHandlingAction::Duplicate => {
+                    self.duplicate_selected_objects();
+                    return WindowEventHandlingResult::Handled;
+                }
+                WindowEventHandlingAction::RecallCameraBookmark(hotkey) => {
+                    self.handle_camera_viewpoint_action(CameraViewpointAction::RecallBookmark(
+                        hotkey,
+                    ));
+                    return WindowEventHandlingResult::Handled;
+                }
+                WindowEventHandlingAction::StoreCameraBookmark(hotkey) => {
+                    self.handle_camera_viewpoint_action(CameraViewpointAction::StoreBookmark(
+                        hotkey,
+                    ));
+                    return WindowEventHandlingResult::Handled;
+                }
+                WindowEventHandlingAction::Exit => {
                     return WindowEventHandlingResult::RequestAction(action);
                 }
-            }
+            },
             WindowEventHandlingResult::Handled => return WindowEventHandlingResult::Handled,
             WindowEventHandlingResult::Unhandled => {}
         }
@@ -191,7 +341,11 @@ impl App {
                 WindowEventHandlingResult::Handled
             }
             WindowEvent::CloseRequested => {
-                WindowEventHandlingResult::RequestAction(WindowEventHandlingAction::Exit)
+                if self.confirm_discarding_unsaved_changes() {
+                    WindowEventHandlingResult::RequestAction(WindowEventHandlingAction::Exit)
+                } else {
+                    WindowEventHandlingResult::Handled
+                }
             }
             // WindowEvent::ScaleFactorChanged {
             //     scale_factor,
@@ -225,6 +379,10 @@ impl App {
             if let PhysicalKey::Code(key) = key_event.physical_key {
                 match key {
                     KeyCode::KeyF => {
+                        self.focus_on_selection();
+                        WindowEventHandlingResult::Handled
+                    }
+                    KeyCode::KeyH => {
                         self.toggle_should_draw_gui();
                         WindowEventHandlingResult::Handled
                     }
@@ -250,6 +408,7 @@ impl App {
             .texture
             .create_view(&TextureViewDescriptor::default());
 
+        let outlined_objects = self.get_outlined_objects();
         self.world_renderer.render(
             &self.renderer,
             &mut encoder,
@@ -258,8 +417,18 @@ impl App {
             &self.world.camera_controller,
             &self.gpu_params.bind_group,
             &mut self.object_picker,
+            &outlined_objects,
         )?;
 
+        self.gui
+            .push_display_info_update(GuiUpdateEvent::CullingStats(
+                self.world_renderer.culling_stats,
+            ));
+        self.gui.update_camera_view_textures(
+            &self.renderer.device,
+            self.world_renderer.get_camera_view_textures(),
+        );
+
         if self.should_draw_gui {
             self.gui.render(
                 &window,
@@ -279,7 +448,6 @@ impl App {
     }
 
     pub fn on_end_frame(&mut self) {
-        self.world.on_end_frame();
         self.object_picker.on_end_frame();
     }
 
@@ -299,13 +467,490 @@ impl App {
         Ok(())
     }
 
+    /// Removes every object of the current level from the world
+    fn unload_level(&mut self) {
+        // The gizmo is part of the world as well
+        self.player_controller.select_object(None, &mut self.world);
+        self.world.clear();
+    }
+
+    fn save_editor_settings(&self) {
+        if let Err(error) = self.editor_settings.save() {
+            log::warn!("Failed to save the editor settings: {error}");
+        }
+    }
+
+    fn undo(&mut self) {
+        self.history.undo(&mut self.world);
+        self.after_history_change();
+    }
+
+    fn redo(&mut self) {
+        self.history.redo(&mut self.world);
+        self.after_history_change();
+    }
+
+    fn after_history_change(&mut self) {
+        self.should_compare_with_saved_level = true;
+        self.push_history_update();
+        let selected_object_ids = self.player_controller.get_selected_object_ids();
+        let existing_object_ids = selected_object_ids
+            .iter()
+            .copied()
+            .filter(|id| self.world.get_world_object(id).is_some())
+            .collect::<Vec<_>>();
+        if existing_object_ids.len() != selected_object_ids.len() {
+            self.player_controller
+                .select_objects(&existing_object_ids, &mut self.world);
+        }
+        self.player_controller.refresh_selected_object_gui();
+    }
+
+    fn get_object_ids_with_descendants(&self, object_ids: &[u32]) -> HashSet<u32> {
+        let mut collected_object_ids = HashSet::new();
+        let mut ids_to_visit = object_ids.to_vec();
+        while let Some(id) = ids_to_visit.pop() {
+            if collected_object_ids.insert(id) {
+                ids_to_visit.extend_from_slice(self.world.get_children(&id));
+            }
+        }
+        collected_object_ids
+    }
+
+    /// The selected and the hovered objects together with their descendants, the hovered object is only outlined
+    /// when it's not part of the selection
+    fn get_outlined_objects(&self) -> OutlinedObjects {
+        let selected_object_ids =
+            self.get_object_ids_with_descendants(self.player_controller.get_selected_object_ids());
+        let hovered_object_id = self
+            .player_controller
+            .get_hovered_object_id()
+            .filter(|object_id| !selected_object_ids.contains(object_id));
+
+        OutlinedObjects {
+            hovered_object_ids: self.get_object_ids_with_descendants(hovered_object_id.as_slice()),
+            selected_object_ids,
+        }
+    }
+
+    /// Frames the meshes of the selected objects and their descendants. The objects without a mesh are framed around
+    /// their position
+    fn focus_on_selection(&mut self) {
+        let bounding_box = self
+            .get_object_ids_with_descendants(self.player_controller.get_selected_object_ids())
+            .iter()
+            .filter_map(|object_id| {
+                self.world_renderer.get_bounding_box(object_id).or_else(|| {
+                    self.world.get_world_transform(object_id).map(|transform| {
+                        Aabb::new(transform.get_position(), transform.get_position())
+                    })
+                })
+            })
+            .reduce(|bounding_box, other| bounding_box.union(&other));
+
+        if let Some(bounding_box) = bounding_box {
+            self.world.camera_controller.focus_on(&BoundingSphere::new(
+                bounding_box.center(),
+                bounding_box.half_extents().length(),
+            ));
+        }
+    }
+
+    /// The bookmarks and paths are saved with the level, but changing them is not part of the undo history (like the
+    /// environment settings)
+    fn handle_camera_viewpoint_action(&mut self, action: CameraViewpointAction) {
+        let mut camera_viewpoints = self.world.get_camera_viewpoints().clone();
+        let camera_controller = &mut self.world.camera_controller;
+        let current_keyframe = CameraKeyframe::from_camera(&camera_controller.camera);
+
+        match action {
+            CameraViewpointAction::RecallBookmark(hotkey) => {
+                if let Some(bookmark) = camera_viewpoints.get_bookmark_by_hotkey(hotkey) {
+                    camera_controller.move_to(&bookmark.keyframe);
+                }
+                return;
+            }
+            CameraViewpointAction::GoToBookmark(index) => {
+                if let Some(bookmark) = camera_viewpoints.bookmarks.get(index) {
+                    camera_controller.move_to(&bookmark.keyframe);
+                }
+                return;
+            }
+            CameraViewpointAction::PlayPath(index) => {
+                if let Some(path) = camera_viewpoints.paths.get(index) {
+                    camera_controller.play_path(path);
+                }
+                return;
+            }
+            CameraViewpointAction::AddBookmark => camera_viewpoints.add_bookmark(current_keyframe),
+            CameraViewpointAction::StoreBookmark(hotkey) => {
+                camera_viewpoints.store_bookmark(hotkey, current_keyframe)
+            }
+            CameraViewpointAction::UpdateBookmark(index) => {
+                if let Some(bookmark) = camera_viewpoints.bookmarks.get_mut(index) {
+                    bookmark.keyframe = current_keyframe;
+                }
+            }
+            CameraViewpointAction::AddKeyframe { path_index } => {
+                if let Some(path) = camera_viewpoints.paths.get_mut(path_index) {
+                    path.keyframes.push(current_keyframe);
+                }
+            }
+        }
+
+        self.world.set_camera_viewpoints(camera_viewpoints);
+        self.push_level_settings_update();
+        // Changing the viewpoints isn't a world event
+        self.has_unsaved_changes = true;
+    }
+
+    fn copy_selected_objects(&mut self) {
+        let selected_object_ids = self
+            .player_controller
+            .get_top_level_selected_object_ids(&self.world);
+        if selected_object_ids.is_empty() {
+            return;
+        }
+
+        match copy_world_objects(&self.world, &selected_object_ids) {
+            Ok(clipboard_text) => self.gui.set_clipboard_text(clipboard_text),
+            Err(error) => self
+                .gui
+                .push_display_info_update(GuiUpdateEvent::ClipboardResult(Err(error))),
+        }
+    }
+
+    /// The copies are placed under the cursor, or next to the copied objects if the cursor is not over the world
+    fn paste_objects(&mut self) {
+        let Some(clipboard_text) = self.gui.get_clipboard_text() else {
+            return;
+        };
+
+        match paste_world_objects(&mut self.world, &clipboard_text) {
+            Ok(pasted_object_ids) => {
+                let first_position = pasted_object_ids.first().and_then(|object_id| {
+                    self.world
+                        .get_world_transform(object_id)
+                        .map(|transform| transform.get_position())
+                });
+                let offset = first_position
+                    .and_then(|first_position| {
+                        self.player_controller
+                            .get_cursor_position_at_height(&self.world, first_position.y)
+                            .map(|cursor_position| cursor_position - first_position)
+                    })
+                    .unwrap_or(PASTE_OFFSET);
+                self.place_pasted_objects(&pasted_object_ids, offset);
+                self.history.name_next_changes("Paste objects", None);
+            }
+            Err(error) => self
+                .gui
+                .push_display_info_update(GuiUpdateEvent::ClipboardResult(Err(error))),
+        }
+    }
+
+    /// The copies are placed next to the selected objects, under the same parents. The clipboard is left as it is
+    fn duplicate_selected_objects(&mut self) {
+        let selected_object_ids = self
+            .player_controller
+            .get_top_level_selected_object_ids(&self.world);
+        if selected_object_ids.is_empty() {
+            return;
+        }
+
+        let result = copy_world_objects(&self.world, &selected_object_ids)
+            .and_then(|copied_objects| paste_world_objects(&mut self.world, &copied_objects));
+        match result {
+            Ok(duplicated_object_ids) => {
+                for (object_id, duplicated_object_id) in
+                    selected_object_ids.iter().zip(&duplicated_object_ids)
+                {
+                    let parent_id = self.world.get_parent(object_id);
+                    if let Err(error) = self.world.set_parent(*duplicated_object_id, parent_id) {
+                        log::warn!(
+                            "Failed to move the duplicated object {duplicated_object_id} in the hierarchy: {error}"
+                        );
+                    }
+                }
+                self.place_pasted_objects(&duplicated_object_ids, PASTE_OFFSET);
+                self.history.name_next_changes("Duplicate objects", None);
+            }
+            Err(error) => self
+                .gui
+                .push_display_info_update(GuiUpdateEvent::ClipboardResult(Err(error))),
+        }
+    }
+
+    /// The selected objects with a mesh get the image of the camera component as their albedo texture (eg. screens)
+    fn show_camera_view_on_selection(&mut self, camera_object_id: u32) {
+        let Some(camera_id) = self.world.get_persistent_id(&camera_object_id) else {
+            return;
+        };
+
+        for object_id in self.player_controller.get_selected_object_ids().to_vec() {
+            if let Some(renderable_component) = self
+                .world
+                .get_component_mut::<RenderableComponent>(&object_id)
+            {
+                let material = renderable_component
+                    .model_descriptor
+                    .material_descriptor
+                    .with_camera_view_albedo(camera_id);
+                renderable_component.update_material(material);
+            }
+        }
+        self.history.name_next_changes("Show camera view", None);
+    }
+
+    /// Moves the pasted objects by the offset, then selects them
+    fn place_pasted_objects(&mut self, object_ids: &[u32], offset: Vec3) {
+        for object_id in object_ids {
+            if let Some(world_transform) = self.world.get_world_transform(object_id) {
+                self.world
+                    .set_world_position(object_id, world_transform.get_position() + offset);
+            }
+        }
+
+        self.player_controller
+            .select_objects(object_ids, &mut self.world);
+    }
+
+    fn mark_level_as_saved(&mut self, level_path: Option<PathBuf>) {
+        self.level_path = level_path;
+        self.saved_level_content = serialize_level_objects(&self.world).unwrap_or_default();
+        self.has_unsaved_changes = false;
+        self.should_compare_with_saved_level = false;
+        // The changes up to this point are part of the saved level
+        self.level_event_receiver.try_iter().for_each(drop);
+    }
+
+    /// Serializing the level is too slow to do it on every change (eg. in every frame of a drag), so the level is only
+    /// compared with the saved one when the changes might have brought it back to the saved state, or when it can't be
+    /// told whether the changed object is saved into the level
+    fn handle_level_events(&mut self) {
+        let mut should_compare_with_saved_level =
+            std::mem::take(&mut self.should_compare_with_saved_level);
+        for event in self.level_event_receiver.try_iter() {
+            let id = event.get_object_id();
+            match self.world.get_world_object(&id) {
+                Some(world_object) if world_object.is_transient() => {}
+                Some(_world_object) => self.has_unsaved_changes = true,
+                None if self.world.get_omnipresent_object(&id).is_some() => {
+                    self.has_unsaved_changes = true
+                }
+                // The object is already removed, it might have been transient
+                None => should_compare_with_saved_level = true,
+            }
+        }
+
+        if should_compare_with_saved_level {
+            self.has_unsaved_changes = serialize_level_objects(&self.world)
+                .map_or(true, |level_content| {
+                    level_content != self.saved_level_content
+                });
+        }
+    }
+
+    /// The open level is only unloaded once the file was read, a level that can't be read keeps it open
+    fn open_level(&mut self, level_path: &Path) -> anyhow::Result<()> {
+        let level_file = read_level_file(level_path)?;
+        self.unload_level();
+        let result = add_level(&mut self.world, level_file);
+        if result.is_err() {
+            // Don't keep a partially loaded level
+            self.world.clear();
+            self.mark_level_as_saved(None);
+        } else {
+            self.mark_level_as_saved(Some(level_path.to_path_buf()));
+        }
+        self.history.reset(&self.world);
+        self.push_history_update();
+        self.push_level_settings_update();
+
+        result
+    }
+
+    fn save_level_to(&mut self, level_path: &Path) -> anyhow::Result<()> {
+        save_level(&self.world, level_path, &LevelSaveOptions::default())?;
+        self.mark_level_as_saved(Some(level_path.to_path_buf()));
+
+        Ok(())
+    }
+
+    fn pick_level_file_to_save() -> Option<PathBuf> {
+        FileDialog::new()
+            .add_filter("level", &[LEVEL_FILE_EXTENSION])
+            .set_directory(LEVEL_FOLDER)
+            .set_file_name(format!("level.{LEVEL_FILE_EXTENSION}"))
+            .save_file()
+    }
+
+    /// Saves into the file of the level, or asks for a file if the level was never saved.
+    /// Returns false if the level wasn't saved
+    fn save_current_level(&mut self, always_ask_for_file: bool) -> bool {
+        let level_path = match &self.level_path {
+            Some(level_path) if !always_ask_for_file => Some(level_path.clone()),
+            _ => Self::pick_level_file_to_save(),
+        };
+        let Some(level_path) = level_path else {
+            return false;
+        };
+
+        let result = self.save_level_to(&level_path);
+        let is_saved = result.is_ok();
+        self.gui
+            .push_display_info_update(GuiUpdateEvent::LevelSaveResult(result));
+
+        is_saved
+    }
+
+    fn add_prefab(&mut self, prefab_path: &Path) -> anyhow::Result<()> {
+        let prefab = load_prefab(prefab_path)?;
+        let instance_id = self
+            .world
+            .add_world_object(WorldObject::new(vec![], TransformComponent::default()));
+        add_prefab_instance(&mut self.world, instance_id, prefab_path, prefab, &[])?;
+        self.player_controller
+            .select_object(Some(instance_id), &mut self.world);
+
+        Ok(())
+    }
+
+    /// Asks the user what to do with the unsaved changes of the level, before it's closed.
+    /// Returns false if the level should stay open
+    fn confirm_discarding_unsaved_changes(&mut self) -> bool {
+        if !self.has_unsaved_changes {
+            return true;
+        }
+
+        let answer = MessageDialog::new()
+            .set_level(MessageLevel::Warning)
+            .set_title("Unsaved changes")
+            .set_description("The level has unsaved changes. Do you want to save them?")
+            .set_buttons(MessageButtons::YesNoCancel)
+            .show();
+
+        match answer {
+            MessageDialogResult::Yes => self.save_current_level(false),
+            MessageDialogResult::No => true,
+            _ => false,
+        }
+    }
+
     fn handle_gui_button_pressed(&mut self, button: GuiButton) {
         match button {
+            GuiButton::NewLevel => {
+                if self.confirm_discarding_unsaved_changes() {
+                    self.unload_level();
+                    self.mark_level_as_saved(None);
+                    self.history.reset(&self.world);
+                    self.push_history_update();
+                    self.push_level_settings_update();
+                }
+            }
+            GuiButton::OpenLevel => {
+                if !self.confirm_discarding_unsaved_changes() {
+                    return;
+                }
+
+                if let Some(level_path) = FileDialog::new()
+                    .add_filter("level", &[LEVEL_FILE_EXTENSION])
+                    .set_directory(LEVEL_FOLDER)
+                    .pick_file()
+                {
+                    let result = self.open_level(&level_path);
+                    self.gui
+                        .push_display_info_update(GuiUpdateEvent::LevelLoadResult(result));
+                }
+            }
             GuiButton::SaveLevel => {
-                let result = save_level(&self.world, "test.lvl");
+                self.save_current_level(false);
+            }
+            GuiButton::SaveLevelAs => {
+                self.save_current_level(true);
+            }
+            GuiButton::AddEmptyObject => {
+                self.world
+                    .add_world_object(WorldObject::new(vec![], TransformComponent::default()));
+                self.history.name_next_changes("Add object", None);
+            }
+            GuiButton::AddCamera => {
+                // The camera components look along -Z of their object, just like the editor camera
+                let camera = &self.world.camera_controller.camera;
+                let transform =
+                    TransformComponent::new(camera.position, Vec3::ONE, camera.orientation);
+                self.world.add_world_object(WorldObject::new(
+                    vec![SceneComponentType::Camera(CameraComponent::default())],
+                    transform,
+                ));
+                self.history.name_next_changes("Add camera", None);
+            }
+            GuiButton::AddPrefab => {
+                if let Some(prefab_path) = FileDialog::new()
+                    .add_filter("prefab", &[PREFAB_FILE_EXTENSION])
+                    .set_directory(PREFAB_FOLDER)
+                    .pick_file()
+                {
+                    let result = self.add_prefab(&get_project_relative_path(prefab_path));
+                    self.history.name_next_changes("Add prefab", None);
+                    self.gui
+                        .push_display_info_update(GuiUpdateEvent::PrefabResult(result));
+                }
+            }
+            GuiButton::CreatePrefabFromSelection => {
+                let selected_object_ids = self
+                    .player_controller
+                    .get_top_level_selected_object_ids(&self.world);
+                if selected_object_ids.is_empty() {
+                    return;
+                }
+                if let Some(prefab_path) = FileDialog::new()
+                    .add_filter("prefab", &[PREFAB_FILE_EXTENSION])
+                    .set_directory(PREFAB_FOLDER)
+                    .set_file_name(format!("prefab.{PREFAB_FILE_EXTENSION}"))
+                    .save_file()
+                {
+                    // The selected object is replaced by the instance
+                    self.player_controller.select_object(None, &mut self.world);
+                    self.history.name_next_changes("Create prefab", None);
+                    let result = create_prefab_from_objects(
+                        &mut self.world,
+                        &selected_object_ids,
+                        &get_project_relative_path(prefab_path),
+                    )
+                    .map(|instance_id| {
+                        self.player_controller
+                            .select_object(Some(instance_id), &mut self.world);
+                    });
+                    self.gui
+                        .push_display_info_update(GuiUpdateEvent::PrefabResult(result));
+                }
+            }
+            GuiButton::ApplyPrefabChanges => {
+                let Some(selected_object_id) = self.player_controller.get_selected_object_id()
+                else {
+                    return;
+                };
+                let Some(instance_id) = find_prefab_instance_root(&self.world, selected_object_id)
+                else {
+                    self.gui
+                        .push_display_info_update(GuiUpdateEvent::PrefabResult(Err(
+                            anyhow::anyhow!("The selected object is not part of a prefab instance"),
+                        )));
+                    return;
+                };
+
+                // The objects of the instances are recreated, the selected one might be among them
+                self.player_controller.select_object(None, &mut self.world);
+                let result = apply_instance_to_prefab(&mut self.world, instance_id);
+                self.history.name_next_changes("Apply prefab changes", None);
+                self.player_controller
+                    .select_object(Some(instance_id), &mut self.world);
                 self.gui
-                    .push_display_info_update(GuiUpdateEvent::LevelSaveResult(result));
+                    .push_display_info_update(GuiUpdateEvent::PrefabResult(result));
             }
+            GuiButton::Undo => self.undo(),
+            GuiButton::Redo => self.redo(),
         };
     }
 
@@ -318,11 +963,92 @@ impl App {
         }
     }
 
+    fn handle_cpu_params_changed_events(&mut self) {
+        let changes = self.cpu_rendering_params.get_gui_changes();
+        for change in changes {
+            self.cpu_rendering_params.set_value_from_ui(&change);
+        }
+
+        self.world_renderer.culling_mode = self.cpu_rendering_params.culling_mode;
+    }
+
     fn handle_events_received_from_gui(&mut self) {
         while let Ok(event) = self.gui_event_receiver.try_recv() {
             match event {
                 GuiEvent::RecompileShaders => self.recompile_shaders(),
                 GuiEvent::ButtonClicked(button) => self.handle_gui_button_pressed(button),
+                GuiEvent::ObjectSelected {
+                    object_id,
+                    add_to_selection,
+                } => {
+                    let mut selected_object_ids = if add_to_selection {
+                        self.player_controller.get_selected_object_ids().to_vec()
+                    } else {
+                        vec![]
+                    };
+                    if selected_object_ids.contains(&object_id) {
+                        selected_object_ids.retain(|id| *id != object_id);
+                    } else {
+                        selected_object_ids.push(object_id);
+                    }
+                    self.player_controller
+                        .select_objects(&selected_object_ids, &mut self.world);
+                }
+                GuiEvent::SelectionPivotChanged(pivot) => {
+                    self.player_controller
+                        .set_selection_pivot(pivot, &mut self.world);
+                }
+                GuiEvent::GizmoModeChanged(mode) => {
+                    self.player_controller.set_gizmo_mode(mode, &mut self.world);
+                }
+                GuiEvent::GizmoOrientationChanged(orientation) => {
+                    self.player_controller
+                        .set_gizmo_orientation(orientation, &mut self.world);
+                    self.editor_settings.gizmo_orientation = orientation;
+                    self.save_editor_settings();
+                }
+                GuiEvent::SnapSettingsChanged(snap_settings) => {
+                    self.player_controller
+                        .set_snap_settings(snap_settings.clone());
+                    self.editor_settings.snap_settings = snap_settings;
+                    self.save_editor_settings();
+                }
+                GuiEvent::ObjectReparented {
+                    object_id,
+                    new_parent,
+                } => {
+                    if let Err(error) = self.world.set_parent(object_id, new_parent) {
+                        log::warn!("Failed to move object {object_id} in the hierarchy: {error}");
+                    }
+                    self.history.name_next_changes("Move in hierarchy", None);
+                }
+                GuiEvent::EnvironmentChanged(mut environment) => {
+                    environment.skybox_path = get_project_relative_path(environment.skybox_path);
+                    self.world.set_environment_settings(environment);
+                    self.push_level_settings_update();
+                    // Changing the settings isn't a world event
+                    self.has_unsaved_changes = true;
+                }
+                GuiEvent::CameraViewpointsChanged(camera_viewpoints) => {
+                    self.world.set_camera_viewpoints(camera_viewpoints);
+                    self.push_level_settings_update();
+                    self.has_unsaved_changes = true;
+                }
+                GuiEvent::CameraViewpointAction(action) => {
+                    self.handle_camera_viewpoint_action(action);
+                }
+                GuiEvent::CameraProjectionChanged(projection) => {
+                    self.world.camera_controller.set_projection(projection);
+                }
+                GuiEvent::CameraModeChanged(camera_mode) => {
+                    self.world.camera_controller.set_mode(camera_mode);
+                }
+                GuiEvent::AxisViewSelected(axis_view) => {
+                    self.world.camera_controller.set_axis_view(axis_view);
+                }
+                GuiEvent::CameraViewShownOnSelection(camera_object_id) => {
+                    self.show_camera_view_on_selection(camera_object_id);
+                }
             }
         }
     }
@@ -348,18 +1074,98 @@ impl App {
             .push_display_info_update(GuiUpdateEvent::ShaderCompilationResult(result));
     }
 
+    fn push_history_update(&mut self) {
+        let (entry_names, applied_entry_count) = self.history.get_entry_names();
+        self.gui.push_display_info_update(GuiUpdateEvent::History {
+            entry_names,
+            applied_entry_count,
+        });
+    }
+
+    /// The settings saved into the level that aren't world objects
+    fn push_level_settings_update(&mut self) {
+        self.gui
+            .push_display_info_update(GuiUpdateEvent::Environment(
+                self.world.get_environment_settings().clone(),
+            ));
+        self.gui
+            .push_display_info_update(GuiUpdateEvent::CameraViewpoints {
+                camera_viewpoints: self.world.get_camera_viewpoints().clone(),
+                is_playing_path: self.world.camera_controller.is_playing_path(),
+            });
+    }
+
+    fn push_camera_controller_update(&mut self) {
+        self.gui
+            .push_display_info_update(GuiUpdateEvent::CameraProjection(
+                self.world.camera_controller.get_projection(),
+            ));
+        self.gui
+            .push_display_info_update(GuiUpdateEvent::CameraMode(
+                self.world.camera_controller.get_mode(),
+            ));
+    }
+
+    fn push_selection_update(&mut self) {
+        self.gui
+            .push_display_info_update(GuiUpdateEvent::Selection {
+                object_ids: self.player_controller.get_selected_object_ids().to_vec(),
+                pivot: self.player_controller.get_selection_pivot(),
+                gizmo_mode: self.player_controller.get_gizmo_mode(),
+                gizmo_orientation: self.player_controller.get_gizmo_orientation(),
+                rectangle: self.player_controller.get_selection_rectangle(),
+            });
+    }
+
     fn update(&mut self, delta: Duration, event_loop_proxy: &mut EventLoopProxy<CustomEvent>) {
         self.handle_events_received_from_gui();
         self.handle_gpu_params_changed_events();
+        self.handle_cpu_params_changed_events();
 
         self.player_controller
-            .update(&mut self.world, event_loop_proxy);
+            .update(&mut self.world, &mut self.history, event_loop_proxy);
+
+        // Everyone receiving the world events after this point sees up to date world transforms
+        self.world.publish_changes();
+
+        if self.history.record_changes(&self.world) {
+            self.push_history_update();
+        }
+        // A drag merges its changes into one entry until it ends
+        if !self.gui.is_using_pointer() && !self.player_controller.is_moving_object() {
+            self.history.stop_merging();
+        }
+
+        self.handle_level_events();
+        self.gui.push_display_info_update(GuiUpdateEvent::Level {
+            path: self.level_path.clone(),
+            has_unsaved_changes: self.has_unsaved_changes,
+        });
+
+        if self.outliner_event_receiver.try_iter().count() > 0 {
+            let root_ids = self.world.get_root_world_object_ids();
+            self.gui
+                .push_display_info_update(GuiUpdateEvent::Outliner(Self::get_outliner_items(
+                    &self.world,
+                    &root_ids,
+                )));
+        }
+        if self.player_controller.take_selection_change() {
+            self.push_selection_update();
+        }
 
-        // Light controller might add light debug objects to the world, so we update it before the world
         self.light_controller
             .update(delta, &self.renderer, &mut self.world);
 
-        self.world.update(delta, &self.renderer);
+        self.world.update(delta);
+        for event in self.world.camera_controller.take_events() {
+            match event {
+                CameraControllerEvent::ModeChanged | CameraControllerEvent::ProjectionChanged => {
+                    self.push_camera_controller_update();
+                }
+                CameraControllerEvent::PathPlaybackChanged => self.push_level_settings_update(),
+            }
+        }
 
         self.world_renderer
             .update(&self.renderer, &self.world, &self.resource_loader);
@@ -369,6 +1175,37 @@ impl App {
         self.gui.update(delta);
     }
 
+    fn get_outliner_items(world: &World, object_ids: &[u32]) -> Vec<OutlinerItem> {
+        object_ids
+            .iter()
+            .filter_map(|id| {
+                let world_object = world.get_world_object(id)?;
+                if world_object.is_transient() {
+                    return None;
+                }
+
+                let name = match world.get_component::<PrefabInstance>(id) {
+                    Some(prefab_instance) => {
+                        let prefab_name = prefab_instance
+                            .prefab_path
+                            .file_stem()
+                            .map(|file_stem| file_stem.to_string_lossy().into_owned())
+                            .unwrap_or_default();
+                        format!("{prefab_name} (prefab)")
+                    }
+                    None => world_object.get_display_name(),
+                };
+
+                Some(OutlinerItem {
+                    id: *id,
+                    persistent_id: world.get_persistent_id(id),
+                    name,
+                    children: Self::get_outliner_items(world, world.get_children(id)),
+                })
+            })
+            .collect()
+    }
+
     fn toggle_should_draw_gui(&mut self) {
         self.should_draw_gui = !self.should_draw_gui
     }
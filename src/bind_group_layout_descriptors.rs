@@ -398,3 +398,95 @@ pub const COMPUTE_SHADER_SDR_TEXTURE_DESTINATION: wgpu::BindGroupLayoutDescripto
         label: Some("ComputeRenderToFrameBuffer"),
         entries: &[COMPUTE_SHADER_SDR_DESTINATION_TEXTURE_LAYOUT_ENTRY],
     };
+
+const fn compute_storage_buffer_layout_entry(
+    binding: u32,
+    read_only: bool,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+/// Inputs and outputs of the GPU culling pass: the draws to cull, the views to cull them against, the indirect draw
+/// arguments to write and the general culling parameters
+pub const GPU_CULLING: wgpu::BindGroupLayoutDescriptor = wgpu::BindGroupLayoutDescriptor {
+    label: Some("GPU culling"),
+    entries: &[
+        compute_storage_buffer_layout_entry(0, true),
+        compute_storage_buffer_layout_entry(1, true),
+        compute_storage_buffer_layout_entry(2, false),
+        wgpu::BindGroupLayoutEntry {
+            binding: 3,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        },
+    ],
+};
+
+/// A texture that is read with `textureLoad` in a compute shader. Depth textures can be bound with this layout as well
+pub const COMPUTE_SHADER_UNFILTERABLE_TEXTURE: wgpu::BindGroupLayoutDescriptor =
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Compute shader unfilterable texture"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        }],
+    };
+
+pub const COMPUTE_SHADER_R32_FLOAT_TEXTURE_DESTINATION: wgpu::BindGroupLayoutDescriptor =
+    wgpu::BindGroupLayoutDescriptor {
+        label: Some("Compute shader R32Float destination texture"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: wgpu::TextureFormat::R32Float,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        }],
+    };
+
+const fn compute_uint_texture_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Uint,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+/// The object ID texture of the visible objects, then the object IDs of the selected and of the hovered objects drawn
+/// without depth test
+pub const OUTLINE_MASKS: wgpu::BindGroupLayoutDescriptor = wgpu::BindGroupLayoutDescriptor {
+    label: Some("Outline masks"),
+    entries: &[
+        compute_uint_texture_layout_entry(0),
+        compute_uint_texture_layout_entry(1),
+        compute_uint_texture_layout_entry(2),
+    ],
+};
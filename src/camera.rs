@@ -1,15 +1,58 @@
-use glam::{EulerRot, Quat, Vec2, Vec3};
-use std::f32::consts::PI;
+use glam::{Mat3, Mat4, Quat, Vec2, Vec3};
+use math_helpers::reverse_z_matrix;
+use std::f32::consts::{FRAC_PI_2, PI};
 use std::time::Duration;
 use winit::event::*;
 use winit::keyboard::{KeyCode, PhysicalKey};
 
-const REFERENCE_DIRECTION: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+/// The camera looks along the negative Z axis of its orientation, the Y axis is its up direction
+const LOCAL_FORWARD: Vec3 = Vec3::NEG_Z;
 const CAMERA_UP_VECTOR: Vec3 = Vec3::new(0 as f32, 1 as f32, 0 as f32);
 
-const DEFAULT_FOV_Y: f32 = 45.0;
+pub const DEFAULT_FOV_Y: f32 = 45.0;
+const DEFAULT_ORTHOGRAPHIC_HEIGHT: f32 = 10.0;
 const DEFAULT_MOVEMENT_SENSITIVITY: f32 = 20.0;
 const DEFAULT_MOUSE_LOOK_SENSITIVITY: f32 = 0.005;
+/// In radians per second
+const DEFAULT_ROLL_SPEED: f32 = 1.0;
+/// The mouse look stops this close to looking straight up or down, so the camera doesn't turn upside down
+const MAX_VERTICAL_LOOK_COSINE: f32 = 0.9999;
+/// The distance moved when panning by one pixel, relative to the distance from the pivot
+const PAN_SENSITIVITY: f32 = 0.0015;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    /// The size of the objects on the screen doesn't depend on their distance, it's set by the zoom instead
+    Orthographic,
+}
+
+/// The directions of the axis aligned editor views, they look at the named side of the objects
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AxisView {
+    Top,
+    Bottom,
+    Front,
+    Back,
+    Right,
+    Left,
+}
+
+impl AxisView {
+    /// The camera orientation of the view. The top view has the -Z axis pointing up on the screen, the bottom view has
+    /// the +Z axis
+    pub fn get_orientation(&self) -> Quat {
+        match self {
+            AxisView::Top => Quat::from_rotation_x(-FRAC_PI_2),
+            AxisView::Bottom => Quat::from_rotation_x(FRAC_PI_2),
+            AxisView::Front => Quat::IDENTITY,
+            AxisView::Back => Quat::from_rotation_y(PI),
+            AxisView::Right => Quat::from_rotation_y(FRAC_PI_2),
+            AxisView::Left => Quat::from_rotation_y(-FRAC_PI_2),
+        }
+    }
+}
 
 pub enum CameraEvent {
     Motion((f64, f64)),
@@ -22,12 +65,15 @@ pub enum CameraEvent {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Camera {
     pub position: Vec3,
-    pub up: Vec3,
     pub aspect: f32,
     pub znear: f32,
     pub zfar: f32,
-    pub orientation: (f32, f32, f32),
+    /// Rotates the camera's local axes into world space, see `LOCAL_FORWARD`
+    pub orientation: Quat,
     pub fov_y: f32,
+    pub projection: Projection,
+    /// The height of the visible area in world units, when the projection is orthographic
+    pub orthographic_height: f32,
 
     look_sensitivity: Vec2,
     movement_sensitivity: Vec3,
@@ -38,25 +84,32 @@ pub struct Camera {
     #[serde(skip_serializing)]
     #[serde(default)]
     current_speed_negative: Vec3,
+    /// -1, 0 or 1, the direction the camera is rolling in
+    #[serde(skip_serializing)]
+    #[serde(default)]
+    current_roll: f32,
+}
+
+/// The orientation that looks in the direction, with its up direction as close to `up` as possible
+fn get_look_rotation(forward: Vec3, up: Vec3) -> Quat {
+    let forward = forward.normalize();
+    // Any up direction is as good as the other when looking straight up or down
+    let right = forward
+        .cross(up)
+        .try_normalize()
+        .unwrap_or_else(|| forward.any_orthonormal_vector());
+
+    Quat::from_mat3(&Mat3::from_cols(right, right.cross(forward), -forward))
 }
 
 impl Camera {
     pub fn new(width: u32, height: u32) -> Self {
         let eye: Vec3 = Vec3::new(-12.0, 10.0, 0.0);
         let target: Vec3 = Vec3::new(0.0, 0.0, 0.0);
-        let view_dir = (target - eye).normalize();
-        let rotation_quat = Quat::from_axis_angle(
-            view_dir.cross(REFERENCE_DIRECTION).normalize(),
-            -view_dir.angle_between(REFERENCE_DIRECTION),
-        );
-        // TODO: sort out this 3-tuple and probably use quaternions
-        let orientation = rotation_quat.to_euler(EulerRot::ZYX);
-
-        // TODO: calculate orientation properly. Now the camera can flip
+        let orientation = get_look_rotation(target - eye, CAMERA_UP_VECTOR);
 
         Self {
             position: eye,
-            up: CAMERA_UP_VECTOR,
             aspect: width as f32 / height as f32,
             znear: 0.1,
             zfar: 300.0,
@@ -72,7 +125,10 @@ impl Camera {
             ),
             current_speed_positive: Vec3::ZERO,
             current_speed_negative: Vec3::ZERO,
+            current_roll: 0.0,
             fov_y: DEFAULT_FOV_Y,
+            projection: Projection::default(),
+            orthographic_height: DEFAULT_ORTHOGRAPHIC_HEIGHT,
         }
     }
 
@@ -81,17 +137,70 @@ impl Camera {
     }
 
     pub fn get_forward(&self) -> Vec3 {
-        let pitch_rotation = Quat::from_rotation_y(self.orientation.0);
-        let yaw_rotation = Quat::from_rotation_z(self.orientation.2);
-        (pitch_rotation * yaw_rotation).mul_vec3(REFERENCE_DIRECTION)
+        self.orientation * LOCAL_FORWARD
     }
 
     pub fn get_right(&self) -> Vec3 {
-        self.get_forward().cross(CAMERA_UP_VECTOR).normalize()
+        self.orientation * Vec3::X
+    }
+
+    /// The up direction of the view, it's tilted by the roll of the camera
+    pub fn get_up(&self) -> Vec3 {
+        self.orientation * Vec3::Y
+    }
+
+    pub fn get_view_matrix(&self) -> Mat4 {
+        Mat4::look_to_rh(self.position, self.get_forward(), self.get_up())
+    }
+
+    /// Reversed-Z projection: the near plane is at depth 1 and the far plane is at depth 0
+    pub fn get_projection_matrix(&self) -> Mat4 {
+        let projection = match self.projection {
+            Projection::Perspective => self.get_perspective_matrix(),
+            Projection::Orthographic => {
+                let half_height = self.orthographic_height / 2.0;
+                let half_width = half_height * self.aspect;
+                Mat4::orthographic_rh(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.znear,
+                    self.zfar,
+                )
+            }
+        };
+
+        reverse_z_matrix() * projection
+    }
+
+    /// Without reversed-Z, regardless of the current projection
+    pub fn get_perspective_matrix(&self) -> Mat4 {
+        Mat4::perspective_rh(self.fov_y, self.aspect, self.znear, self.zfar)
     }
 
-    pub fn get_target(&self) -> Vec3 {
-        self.position + self.get_forward()
+    /// The distance where something at `distance` from the camera would look as big with perspective projection as
+    /// it looks with the current projection. Used for the things that scale with the distance (eg. the gizmo or the
+    /// panning speed), so they behave the same in both projections
+    pub fn get_perspective_distance(&self, distance: f32) -> f32 {
+        match self.projection {
+            Projection::Perspective => distance,
+            // The perspective projection scales the y coordinate by cot(fov_y / 2), the visible height at a distance
+            // is 2 * distance / cot(fov_y / 2)
+            Projection::Orthographic => {
+                self.orthographic_height * self.get_perspective_matrix().y_axis.y.abs() / 2.0
+            }
+        }
+    }
+
+    /// The orthographic height that shows the same area as the perspective projection at `distance`
+    pub fn get_orthographic_height_at(&self, distance: f32) -> f32 {
+        2.0 * distance / self.get_perspective_matrix().y_axis.y.abs()
+    }
+
+    /// Rotates the camera around its forward direction, positive angles roll it counterclockwise
+    pub fn roll(&mut self, angle: f32) {
+        self.orientation = (self.orientation * Quat::from_rotation_z(angle)).normalize();
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
@@ -109,6 +218,8 @@ impl Camera {
                         KeyCode::KeyD => self.current_speed_positive.x = 1.0,
                         KeyCode::KeyQ => self.current_speed_positive.y = 1.0,
                         KeyCode::KeyE => self.current_speed_negative.y = 1.0,
+                        KeyCode::KeyZ => self.current_roll = 1.0,
+                        KeyCode::KeyC => self.current_roll = -1.0,
                         _ => (),
                     }
                 }
@@ -122,6 +233,7 @@ impl Camera {
                         KeyCode::KeyD => self.current_speed_positive.x = 0.0,
                         KeyCode::KeyQ => self.current_speed_positive.y = 0.0,
                         KeyCode::KeyE => self.current_speed_negative.y = 0.0,
+                        KeyCode::KeyZ | KeyCode::KeyC => self.current_roll = 0.0,
                         _ => (),
                     }
                 }
@@ -132,6 +244,7 @@ impl Camera {
     pub fn stop_movement(&mut self) {
         self.current_speed_negative = Vec3::ZERO;
         self.current_speed_positive = Vec3::ZERO;
+        self.current_roll = 0.0;
     }
 
     pub fn process_event(&mut self, event: &CameraEvent) {
@@ -142,6 +255,10 @@ impl Camera {
     }
 
     pub fn update(&mut self, delta: Duration) {
+        if self.current_roll != 0.0 {
+            self.roll(self.current_roll * DEFAULT_ROLL_SPEED * delta.as_secs_f32());
+        }
+
         let current_speed = self.current_speed_positive - self.current_speed_negative;
         if current_speed == Vec3::ZERO {
             return;
@@ -157,12 +274,32 @@ impl Camera {
         self.position += v;
     }
 
+    /// Rotates the camera around the pivot in front of it, the pivot stays in the center of the view
+    pub fn orbit(&mut self, pivot_distance: f32, delta: (f32, f32)) {
+        let pivot = self.position + self.get_forward() * pivot_distance;
+        self.rotate(delta);
+        self.position = pivot - self.get_forward() * pivot_distance;
+    }
+
+    /// Moves the camera in the plane of the view, so the content under the cursor follows the cursor at the pivot
+    /// distance
+    pub fn pan(&mut self, pivot_distance: f32, (delta_x, delta_y): (f32, f32)) {
+        let scale = pivot_distance * PAN_SENSITIVITY;
+        self.position += (self.get_up() * delta_y - self.get_right() * delta_x) * scale;
+    }
+
+    /// Turns around the world's up direction horizontally and around the camera's right direction vertically
     fn rotate(&mut self, (delta_x, delta_y): (f32, f32)) {
-        self.orientation.0 += self.look_sensitivity.x * -delta_x;
-        self.orientation.2 += self.look_sensitivity.y * -delta_y;
-        self.orientation.2 = self
-            .orientation
-            .2
-            .clamp(-PI / 2.0 + 0.0001, PI / 2.0 - 0.0001);
+        let pitched_orientation =
+            self.orientation * Quat::from_rotation_x(self.look_sensitivity.y * -delta_y);
+        let pitched_vertical_look = (pitched_orientation * LOCAL_FORWARD).y.abs();
+        if pitched_vertical_look < MAX_VERTICAL_LOOK_COSINE
+            || pitched_vertical_look < self.get_forward().y.abs()
+        {
+            self.orientation = pitched_orientation;
+        }
+
+        let yaw = Quat::from_rotation_y(self.look_sensitivity.x * -delta_x);
+        self.orientation = (yaw * self.orientation).normalize();
     }
 }
@@ -1,56 +1,101 @@
 use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
 use std::time;
-use wgpu::Device;
 use winit::{
     dpi::PhysicalPosition,
-    event::{MouseButton, WindowEvent},
+    event::{MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
 };
 
-use math_helpers::reverse_z_matrix;
+use math_helpers::{BoundingSphere, Frustum, Line};
 
 use crate::{
-    bind_group_layout_descriptors,
-    buffer::{create_bind_group_from_buffer_entire_binding_init, GpuBufferCreationOptions},
-    camera::{Camera, CameraEvent},
+    camera::{AxisView, Camera, CameraEvent, Projection},
+    camera_path::{CameraKeyframe, CameraPath},
 };
 
-/// Contains the rendering-related concepts of the camera
+const DEFAULT_ORBIT_DISTANCE: f32 = 10.0;
+const MIN_ORBIT_DISTANCE: f32 = 0.1;
+const MIN_ORTHOGRAPHIC_HEIGHT: f32 = 0.01;
+/// The orbit distance is multiplied by this for each line scrolled towards the pivot
+const ZOOM_FACTOR: f32 = 0.85;
+/// Touchpads scroll in pixels, this many of them count as a line
+const PIXELS_PER_SCROLL_LINE: f32 = 40.0;
+/// The focused bounds are framed with some space around them
+const FOCUS_MARGIN: f32 = 1.2;
+/// Objects smaller than this (eg. lights without a mesh) are framed as if they had this radius
+const MIN_FOCUS_RADIUS: f32 = 0.5;
+const FOCUS_TRANSITION_DURATION: time::Duration = time::Duration::from_millis(300);
+
+/// How the camera is moved while the right mouse button is held
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum CameraMode {
+    /// The mouse turns the camera in place and WASDQE moves it
+    #[default]
+    Fly,
+    /// The mouse rotates the camera around a pivot in front of it, the middle mouse button pans and the scroll wheel
+    /// zooms
+    Orbit,
+}
+
+/// The camera glides to the target instead of jumping there
+struct FocusTransition {
+    start: CameraKeyframe,
+    target: CameraKeyframe,
+    elapsed: time::Duration,
+}
+
+/// The changes of the controller that are shown outside of the view (eg. on the GUI), see
+/// `CameraController::take_events`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CameraControllerEvent {
+    ModeChanged,
+    ProjectionChanged,
+    /// A path started or stopped playing
+    PathPlaybackChanged,
+}
+
+struct PathPlayback {
+    path: CameraPath,
+    elapsed: time::Duration,
+}
+
+/// Contains the rendering-related concepts of the camera. The GPU side of the camera is in `CameraRenderData`
 pub struct CameraController {
     pub camera: Camera,
-    pub binding_buffer: wgpu::Buffer,
-    pub bind_group: wgpu::BindGroup,
     is_movement_enabled: bool,
+    is_panning: bool,
+    modifiers: ModifiersState,
     cursor_position: Option<PhysicalPosition<f64>>,
+    mode: CameraMode,
+    /// The distance of the pivot in front of the camera in orbit mode
+    orbit_distance: f32,
+    focus_transition: Option<FocusTransition>,
+    path_playback: Option<PathPlayback>,
+    events: Vec<CameraControllerEvent>,
 
     width: u32,
     height: u32,
 }
 
 impl CameraController {
-    pub fn new(device: &Device, width: u32, height: u32) -> CameraController {
+    pub fn new(width: u32, height: u32) -> CameraController {
         let camera = Camera::new(width, height);
 
-        Self::from_camera(device, &camera, width, height)
+        Self::from_camera(&camera, width, height)
     }
 
-    pub fn from_camera(device: &Device, camera: &Camera, width: u32, height: u32) -> Self {
-        let (binding_buffer, bind_group) = create_bind_group_from_buffer_entire_binding_init(
-            device,
-            &GpuBufferCreationOptions {
-                bind_group_layout_descriptor:
-                    &bind_group_layout_descriptors::BUFFER_VISIBLE_EVERYWHERE,
-                usages: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                label: "Camera".into(),
-            },
-            bytemuck::cast_slice(&[Self::get_raw(camera)]),
-        );
-
+    pub fn from_camera(camera: &Camera, width: u32, height: u32) -> Self {
         Self {
             camera: camera.clone(),
-            binding_buffer,
-            bind_group,
             is_movement_enabled: false,
+            is_panning: false,
+            modifiers: ModifiersState::empty(),
             cursor_position: None,
+            mode: CameraMode::default(),
+            orbit_distance: DEFAULT_ORBIT_DISTANCE,
+            focus_transition: None,
+            path_playback: None,
+            events: Vec::new(),
             width,
             height,
         }
@@ -63,14 +108,37 @@ impl CameraController {
         self.height = height;
     }
 
-    pub fn update(&mut self, delta_time: time::Duration, render_queue: &wgpu::Queue) {
-        self.camera.update(delta_time);
+    pub fn update(&mut self, delta_time: time::Duration) {
+        if let Some(focus_transition) = &mut self.focus_transition {
+            focus_transition.elapsed += delta_time;
+            let progress = (focus_transition.elapsed.as_secs_f32()
+                / FOCUS_TRANSITION_DURATION.as_secs_f32())
+            .min(1.0);
+            let smoothed_progress = progress * progress * (3.0 - 2.0 * progress);
+            focus_transition
+                .start
+                .lerp(&focus_transition.target, smoothed_progress)
+                .apply_to(&mut self.camera);
 
-        render_queue.write_buffer(
-            &self.binding_buffer,
-            0,
-            bytemuck::cast_slice(&[self.to_raw()]),
-        );
+            if progress >= 1.0 {
+                self.focus_transition = None;
+            }
+        }
+
+        if let Some(path_playback) = &mut self.path_playback {
+            let time = path_playback.elapsed.as_secs_f32();
+            if let Some(keyframe) = path_playback.path.sample(time) {
+                keyframe.apply_to(&mut self.camera);
+            }
+
+            if time >= path_playback.path.duration {
+                self.stop_path_playback();
+            } else {
+                path_playback.elapsed += delta_time;
+            }
+        }
+
+        self.camera.update(delta_time);
     }
 
     pub fn set_is_movement_enabled(&mut self, value: bool) {
@@ -79,7 +147,143 @@ impl CameraController {
         if !self.is_movement_enabled {
             self.camera.stop_movement();
             self.cursor_position = None;
+        } else {
+            // The user takes over
+            self.stop_animation();
+        }
+    }
+
+    /// Stops the focus transition and the path playback, the camera stays where it is
+    fn stop_animation(&mut self) {
+        self.focus_transition = None;
+        self.stop_path_playback();
+    }
+
+    fn stop_path_playback(&mut self) {
+        if self.path_playback.take().is_some() {
+            self.events.push(CameraControllerEvent::PathPlaybackChanged);
+        }
+    }
+
+    /// The changes since the last call
+    pub fn take_events(&mut self) -> Vec<CameraControllerEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Replaces the camera (eg. with the one saved in the level), the animations of the previous
+    /// camera are stopped
+    pub fn set_camera(&mut self, camera: &Camera) {
+        self.stop_animation();
+        if camera.projection != self.camera.projection {
+            self.events.push(CameraControllerEvent::ProjectionChanged);
+        }
+        self.camera = camera.clone();
+    }
+
+    pub fn get_mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode != self.mode {
+            self.events.push(CameraControllerEvent::ModeChanged);
+        }
+        self.mode = mode;
+        self.camera.stop_movement();
+    }
+
+    pub fn get_projection(&self) -> Projection {
+        self.camera.projection
+    }
+
+    /// The orthographic view shows the same area at the pivot as the perspective view did
+    pub fn set_projection(&mut self, projection: Projection) {
+        if projection == Projection::Orthographic && self.camera.projection != projection {
+            self.camera.orthographic_height =
+                self.camera.get_orthographic_height_at(self.orbit_distance);
+        }
+        if projection != self.camera.projection {
+            self.events.push(CameraControllerEvent::ProjectionChanged);
+        }
+        self.camera.projection = projection;
+    }
+
+    /// Turns the camera around the pivot to look along the axis, and switches to orthographic projection
+    pub fn set_axis_view(&mut self, axis_view: AxisView) {
+        let pivot = self.camera.position + self.camera.get_forward() * self.orbit_distance;
+        let orientation = axis_view.get_orientation();
+
+        self.set_projection(Projection::Orthographic);
+        self.move_to(&CameraKeyframe {
+            position: pivot - orientation * Vec3::NEG_Z * self.orbit_distance,
+            orientation,
+        });
+    }
+
+    /// Moves the camera, so the sphere fills the view without changing the direction of the camera. The sphere's
+    /// center becomes the pivot of the orbit mode
+    pub fn focus_on(&mut self, bounding_sphere: &BoundingSphere) {
+        let proj = self.camera.get_perspective_matrix();
+        // The projection matrix scales the coordinates by cot(fov / 2), the narrower field of view has to contain
+        // the sphere
+        let tan_half_fov = (1.0 / proj.x_axis.x.abs()).min(1.0 / proj.y_axis.y.abs());
+        let distance =
+            bounding_sphere.radius.max(MIN_FOCUS_RADIUS) / tan_half_fov.atan().sin() * FOCUS_MARGIN;
+
+        self.orbit_distance = distance;
+        if self.camera.projection == Projection::Orthographic {
+            // The narrower side of the view has to contain the sphere
+            self.camera.orthographic_height =
+                2.0 * bounding_sphere.radius.max(MIN_FOCUS_RADIUS) * FOCUS_MARGIN
+                    / self.camera.aspect.min(1.0);
         }
+        self.move_to(&CameraKeyframe {
+            position: bounding_sphere.center - self.camera.get_forward() * distance,
+            orientation: self.camera.orientation,
+        });
+    }
+
+    /// Glides the camera to the keyframe (eg. a bookmarked viewpoint)
+    pub fn move_to(&mut self, keyframe: &CameraKeyframe) {
+        self.stop_path_playback();
+        self.focus_transition = Some(FocusTransition {
+            start: CameraKeyframe::from_camera(&self.camera),
+            target: *keyframe,
+            elapsed: time::Duration::ZERO,
+        });
+    }
+
+    /// Flies the camera along the path, the playback takes the duration of the path regardless of the frame rate.
+    /// Moving the camera stops the playback
+    pub fn play_path(&mut self, path: &CameraPath) {
+        self.focus_transition = None;
+        self.path_playback = Some(PathPlayback {
+            path: path.clone(),
+            elapsed: time::Duration::ZERO,
+        });
+        self.events.push(CameraControllerEvent::PathPlaybackChanged);
+    }
+
+    pub fn is_playing_path(&self) -> bool {
+        self.path_playback.is_some()
+    }
+
+    /// Moves the camera towards the pivot, or away from it with negative values. The orthographic projection zooms by
+    /// changing the size of the visible area instead
+    fn zoom(&mut self, scrolled_lines: f32) {
+        if self.camera.projection == Projection::Orthographic {
+            self.camera.orthographic_height = (self.camera.orthographic_height
+                * ZOOM_FACTOR.powf(scrolled_lines))
+            .max(MIN_ORTHOGRAPHIC_HEIGHT);
+            self.stop_animation();
+            return;
+        }
+
+        let pivot = self.camera.position + self.camera.get_forward() * self.orbit_distance;
+        self.orbit_distance =
+            (self.orbit_distance * ZOOM_FACTOR.powf(scrolled_lines)).max(MIN_ORBIT_DISTANCE);
+        self.camera.position = pivot - self.camera.get_forward() * self.orbit_distance;
+        self.stop_animation();
     }
 
     pub fn process_window_event(&mut self, event: &WindowEvent) -> bool {
@@ -89,21 +293,100 @@ impl CameraController {
                     self.set_is_movement_enabled(state.is_pressed());
                     return true;
                 }
+                if *button == MouseButton::Middle && self.mode == CameraMode::Orbit {
+                    self.is_panning = state.is_pressed();
+                    self.cursor_position = None;
+                    self.stop_animation();
+                    return true;
+                }
             }
             WindowEvent::CursorMoved { position, .. } => {
-                if self.is_movement_enabled {
+                if self.is_movement_enabled || self.is_panning {
                     if let Some(previous_position) = self.cursor_position {
-                        self.camera.process_event(&CameraEvent::Motion((
+                        let delta = (
                             position.x - previous_position.x,
                             position.y - previous_position.y,
-                        )));
+                        );
+                        if self.is_panning {
+                            let pan_distance =
+                                self.camera.get_perspective_distance(self.orbit_distance);
+                            self.camera
+                                .pan(pan_distance, (delta.0 as f32, delta.1 as f32));
+                        } else if self.mode == CameraMode::Orbit {
+                            self.camera
+                                .orbit(self.orbit_distance, (delta.0 as f32, delta.1 as f32));
+                        } else {
+                            self.camera.process_event(&CameraEvent::Motion(delta));
+                        }
                     }
 
                     self.cursor_position = Some(*position);
                     return true;
                 }
             }
-            WindowEvent::KeyboardInput { event, .. } if self.is_movement_enabled => {
+            WindowEvent::MouseWheel { delta, .. }
+                if self.mode == CameraMode::Orbit
+                    || self.camera.projection == Projection::Orthographic =>
+            {
+                let scrolled_lines = match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => *lines,
+                    MouseScrollDelta::PixelDelta(pixels) => {
+                        pixels.y as f32 / PIXELS_PER_SCROLL_LINE
+                    }
+                };
+                self.zoom(scrolled_lines);
+                return true;
+            }
+            WindowEvent::KeyboardInput { event, .. }
+                if event.physical_key == PhysicalKey::Code(KeyCode::KeyO) =>
+            {
+                if event.state.is_pressed() && !event.repeat {
+                    self.set_mode(match self.mode {
+                        CameraMode::Fly => CameraMode::Orbit,
+                        CameraMode::Orbit => CameraMode::Fly,
+                    });
+                }
+                return true;
+            }
+            WindowEvent::KeyboardInput { event, .. }
+                if event.physical_key == PhysicalKey::Code(KeyCode::Numpad5) =>
+            {
+                if event.state.is_pressed() && !event.repeat {
+                    self.set_projection(match self.camera.projection {
+                        Projection::Perspective => Projection::Orthographic,
+                        Projection::Orthographic => Projection::Perspective,
+                    });
+                }
+                return true;
+            }
+            WindowEvent::KeyboardInput { event, .. }
+                if matches!(
+                    event.physical_key,
+                    PhysicalKey::Code(KeyCode::Numpad1 | KeyCode::Numpad3 | KeyCode::Numpad7)
+                ) =>
+            {
+                if event.state.is_pressed() && !event.repeat {
+                    // Ctrl shows the opposite side
+                    let is_opposite = self.modifiers.control_key();
+                    let axis_view = match (event.physical_key, is_opposite) {
+                        (PhysicalKey::Code(KeyCode::Numpad1), false) => AxisView::Front,
+                        (PhysicalKey::Code(KeyCode::Numpad1), true) => AxisView::Back,
+                        (PhysicalKey::Code(KeyCode::Numpad3), false) => AxisView::Right,
+                        (PhysicalKey::Code(KeyCode::Numpad3), true) => AxisView::Left,
+                        (_, false) => AxisView::Top,
+                        (_, true) => AxisView::Bottom,
+                    };
+                    self.set_axis_view(axis_view);
+                }
+                return true;
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                // Others need the modifiers as well
+                self.modifiers = modifiers.state();
+            }
+            WindowEvent::KeyboardInput { event, .. }
+                if self.is_movement_enabled && self.mode == CameraMode::Fly =>
+            {
                 self.camera.process_event(&CameraEvent::Key(event.clone()));
                 return true;
             }
@@ -117,10 +400,44 @@ impl CameraController {
         Self::get_raw(&self.camera)
     }
 
+    /// The frustum of the camera in world space, used for culling the objects that are not visible
+    pub fn get_frustum(&self) -> Frustum {
+        Frustum::from_view_proj(&self.get_view_proj())
+    }
+
+    pub fn get_view_proj(&self) -> Mat4 {
+        let (view, proj) = Self::get_view_and_proj(&self.camera);
+        proj * view
+    }
+
+    /// How big the sphere is on the screen: the projected diameter relative to the height of the screen.
+    /// Used for selecting the level of detail of the objects
+    pub fn get_screen_size(&self, bounding_sphere: &BoundingSphere) -> f32 {
+        let (_, proj) = Self::get_view_and_proj(&self.camera);
+        if self.camera.projection == Projection::Orthographic {
+            // The projection matrix scales the y coordinate by 2 / orthographic_height, regardless of the distance
+            return bounding_sphere.radius * proj.y_axis.y.abs();
+        }
+
+        let distance = self.camera.position.distance(bounding_sphere.center);
+        if distance <= bounding_sphere.radius {
+            // The camera is inside of the sphere
+            return f32::INFINITY;
+        }
+
+        // The projection matrix scales the y coordinate by cot(fov_y / 2), the NDC range is 2 units high
+        bounding_sphere.radius * proj.y_axis.y.abs() / distance
+    }
+
+    fn get_view_and_proj(camera: &Camera) -> (Mat4, Mat4) {
+        let view = camera.get_view_matrix();
+        let proj = camera.get_projection_matrix();
+
+        (view, proj)
+    }
+
     fn get_raw(camera: &Camera) -> CameraRaw {
-        let view = Mat4::look_at_rh(camera.position, camera.get_target(), camera.up);
-        let proj = reverse_z_matrix()
-            * Mat4::perspective_rh(camera.fov_y, camera.aspect, camera.znear, camera.zfar);
+        let (view, proj) = Self::get_view_and_proj(camera);
 
         let pos = camera.get_position();
 
@@ -134,20 +451,9 @@ impl CameraController {
         }
     }
 
+    /// The z coordinate is the depth of the point, which is reversed: 1 is on the near plane, 0 is on the far plane
     pub fn deproject_screen_to_world(&self, screen_coords: Vec3) -> Vec3 {
-        let view = Mat4::look_at_rh(
-            self.camera.position,
-            self.camera.get_target(),
-            self.camera.up,
-        );
-        let proj = Mat4::perspective_rh(
-            self.camera.fov_y,
-            self.camera.aspect,
-            self.camera.znear,
-            self.camera.zfar,
-        );
-
-        let result = (proj * view).inverse()
+        let result = self.get_view_proj().inverse()
             * Vec4::new(
                 screen_coords.x / (self.width as f32) * 2.0 - 1.0, // Clip space goes from -1 to 1, so transform there
                 (screen_coords.y / (self.height as f32) * 2.0 - 1.0) * -1.0, // Clip space goes from -1 to 1, so transform there
@@ -156,6 +462,19 @@ impl CameraController {
             );
         result.xyz() / result.w
     }
+
+    /// The ray going through the pixel from the near plane into the screen. With orthographic projection the rays of
+    /// the pixels are parallel, they don't start from the camera position
+    pub fn get_screen_ray(&self, screen_position: &PhysicalPosition<f64>) -> Line {
+        let (x, y) = (screen_position.x as f32, screen_position.y as f32);
+        let near_point = self.deproject_screen_to_world(Vec3::new(x, y, 1.0));
+        let far_point = self.deproject_screen_to_world(Vec3::new(x, y, 0.0));
+
+        Line {
+            position: near_point,
+            direction: (far_point - near_point).normalize(),
+        }
+    }
 }
 
 #[repr(C)]
@@ -0,0 +1,199 @@
+use glam::{Quat, Vec3};
+
+use math_helpers::catmull_rom;
+
+use crate::camera::Camera;
+
+/// The hotkeys of the bookmarks are the digit keys
+pub const MAX_BOOKMARK_HOTKEY: u32 = 9;
+const DEFAULT_PATH_DURATION: f32 = 10.0;
+
+/// Where the camera is and where it looks. The projection settings of the camera are not part of it
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CameraKeyframe {
+    pub position: Vec3,
+    pub orientation: Quat,
+}
+
+impl CameraKeyframe {
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self {
+            position: camera.position,
+            orientation: camera.orientation,
+        }
+    }
+
+    pub fn apply_to(&self, camera: &mut Camera) {
+        camera.position = self.position;
+        camera.orientation = self.orientation;
+    }
+
+    /// Linear interpolation of the position and spherical interpolation of the orientation
+    pub fn lerp(&self, other: &CameraKeyframe, t: f32) -> Self {
+        Self {
+            position: self.position.lerp(other.position, t),
+            orientation: self.orientation.slerp(other.orientation, t),
+        }
+    }
+}
+
+/// A named viewpoint of the level that the camera can jump back to
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CameraBookmark {
+    pub name: String,
+    /// The digit key (1 to `MAX_BOOKMARK_HOTKEY`) that recalls the bookmark
+    pub hotkey: Option<u32>,
+    pub keyframe: CameraKeyframe,
+}
+
+/// The camera flies through the keyframes in order. The position follows a Catmull-Rom spline through the keyframe
+/// positions, the orientation is slerped between neighbouring keyframes
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CameraPath {
+    pub name: String,
+    pub keyframes: Vec<CameraKeyframe>,
+    /// The time it takes to play the whole path, in seconds. It doesn't depend on the frame rate, so the playback is
+    /// reproducible (eg. for capturing videos or benchmarking)
+    pub duration: f32,
+}
+
+impl CameraPath {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            keyframes: Vec::new(),
+            duration: DEFAULT_PATH_DURATION,
+        }
+    }
+
+    /// The state of the camera `time` seconds after the start of the playback. The keyframes are spread evenly over
+    /// the duration, times outside of the path are clamped to its ends. None if the path has no keyframes
+    pub fn sample(&self, time: f32) -> Option<CameraKeyframe> {
+        let last_index = self.keyframes.len().checked_sub(1)?;
+        if last_index == 0 {
+            return Some(self.keyframes[0]);
+        }
+
+        let progress = if self.duration > 0.0 {
+            (time / self.duration).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let segment_progress = progress * last_index as f32;
+        let segment = (segment_progress.floor() as usize).min(last_index - 1);
+        let t = segment_progress - segment as f32;
+
+        // The end points are repeated to have a neighbour for the first and last segment
+        let keyframe = |index: usize| &self.keyframes[index.min(last_index)];
+        let previous = keyframe(segment.saturating_sub(1));
+        let start = keyframe(segment);
+        let end = keyframe(segment + 1);
+        let next = keyframe(segment + 2);
+
+        Some(CameraKeyframe {
+            position: catmull_rom(
+                previous.position,
+                start.position,
+                end.position,
+                next.position,
+                t,
+            ),
+            ..start.lerp(end, t)
+        })
+    }
+}
+
+/// The bookmarks and paths saved with the level
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CameraViewpoints {
+    pub bookmarks: Vec<CameraBookmark>,
+    pub paths: Vec<CameraPath>,
+}
+
+impl CameraViewpoints {
+    pub fn get_bookmark_by_hotkey(&self, hotkey: u32) -> Option<&CameraBookmark> {
+        self.bookmarks
+            .iter()
+            .find(|bookmark| bookmark.hotkey == Some(hotkey))
+    }
+
+    /// The new bookmark gets the first hotkey that isn't used yet, if there is one
+    pub fn add_bookmark(&mut self, keyframe: CameraKeyframe) {
+        let hotkey =
+            (1..=MAX_BOOKMARK_HOTKEY).find(|hotkey| self.get_bookmark_by_hotkey(*hotkey).is_none());
+        self.bookmarks.push(CameraBookmark {
+            name: format!("Bookmark {}", self.bookmarks.len() + 1),
+            hotkey,
+            keyframe,
+        });
+    }
+
+    /// Overwrites the bookmark of the hotkey, or creates a new one if the hotkey isn't used yet
+    pub fn store_bookmark(&mut self, hotkey: u32, keyframe: CameraKeyframe) {
+        match self
+            .bookmarks
+            .iter_mut()
+            .find(|bookmark| bookmark.hotkey == Some(hotkey))
+        {
+            Some(bookmark) => bookmark.keyframe = keyframe,
+            None => self.bookmarks.push(CameraBookmark {
+                name: format!("Bookmark {hotkey}"),
+                hotkey: Some(hotkey),
+                keyframe,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_DIFF: f32 = 0.0001;
+
+    fn create_path() -> CameraPath {
+        let keyframe = |x: f32, angle: f32| CameraKeyframe {
+            position: Vec3::new(x, 1.0, 0.0),
+            orientation: Quat::from_rotation_y(angle),
+        };
+
+        CameraPath {
+            name: "Path".to_string(),
+            keyframes: vec![keyframe(0.0, 0.0), keyframe(2.0, 1.0), keyframe(4.0, 0.5)],
+            duration: 4.0,
+        }
+    }
+
+    #[test]
+    fn path_passes_through_the_keyframes() {
+        let path = create_path();
+
+        for (time, keyframe) in [0.0, 2.0, 4.0].iter().zip(&path.keyframes) {
+            let sample = path.sample(*time).unwrap();
+            assert!(sample.position.abs_diff_eq(keyframe.position, MAX_DIFF));
+            assert!(sample
+                .orientation
+                .abs_diff_eq(keyframe.orientation, MAX_DIFF));
+        }
+    }
+
+    #[test]
+    fn path_is_clamped_to_its_ends() {
+        let path = create_path();
+
+        assert_eq!(path.sample(-1.0), path.sample(0.0));
+        assert_eq!(path.sample(10.0), path.sample(4.0));
+        assert_eq!(CameraPath::new("Empty".to_string()).sample(1.0), None);
+    }
+
+    #[test]
+    fn path_orientation_is_slerped() {
+        let path = create_path();
+
+        let sample = path.sample(1.0).unwrap();
+
+        assert!(sample
+            .orientation
+            .abs_diff_eq(Quat::from_rotation_y(0.5), MAX_DIFF));
+    }
+}
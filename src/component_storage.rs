@@ -0,0 +1,181 @@
+use std::{any::Any, collections::HashMap};
+
+use crate::{world::World, world_events::ComponentTypeId};
+
+/// Anything that can be attached to a world object. Implementing this trait is enough to store a type in the world,
+/// there is no need to add it to `SceneComponentType` or to register it anywhere else.
+/// Components that are not part of `SceneComponentType` only exist at runtime, they are not saved into the level
+pub trait Component: Any {}
+
+/// Type-erased view of the components of one type, so all of them can be cleaned up when an object is removed
+trait ComponentColumn: Any {
+    fn contains_object(&self, object_id: u32) -> bool;
+    fn remove_object(&mut self, object_id: u32);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Component> ComponentColumn for HashMap<u32, T> {
+    fn contains_object(&self, object_id: u32) -> bool {
+        self.contains_key(&object_id)
+    }
+
+    fn remove_object(&mut self, object_id: u32) {
+        self.remove(&object_id);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Stores the components of every type, that is not built into `WorldObject`. At most one component of each type
+/// belongs to an object
+#[derive(Default)]
+pub struct ComponentStorage {
+    columns: HashMap<ComponentTypeId, Box<dyn ComponentColumn>>,
+}
+
+impl ComponentStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn get_column<T: Component>(&self) -> Option<&HashMap<u32, T>> {
+        self.columns
+            .get(&ComponentTypeId::of::<T>())
+            .and_then(|column| column.as_any().downcast_ref::<HashMap<u32, T>>())
+    }
+
+    fn get_column_mut<T: Component>(&mut self) -> Option<&mut HashMap<u32, T>> {
+        self.columns
+            .get_mut(&ComponentTypeId::of::<T>())
+            .and_then(|column| column.as_any_mut().downcast_mut::<HashMap<u32, T>>())
+    }
+
+    /// Returns the previous component of the same type, if the object had one
+    pub fn insert<T: Component>(&mut self, object_id: u32, component: T) -> Option<T> {
+        if self.get_column::<T>().is_none() {
+            self.columns.insert(
+                ComponentTypeId::of::<T>(),
+                Box::new(HashMap::<u32, T>::new()),
+            );
+        }
+
+        self.get_column_mut::<T>()
+            .and_then(|column| column.insert(object_id, component))
+    }
+
+    pub fn remove<T: Component>(&mut self, object_id: u32) -> Option<T> {
+        self.get_column_mut::<T>()
+            .and_then(|column| column.remove(&object_id))
+    }
+
+    /// Removes every component of the object
+    pub fn remove_object(&mut self, object_id: u32) {
+        for column in self.columns.values_mut() {
+            column.remove_object(object_id);
+        }
+    }
+
+    pub fn get_component_types(&self, object_id: u32) -> Vec<ComponentTypeId> {
+        self.columns
+            .iter()
+            .filter(|(_component_type, column)| column.contains_object(object_id))
+            .map(|(component_type, _column)| *component_type)
+            .collect()
+    }
+
+    pub fn get<T: Component>(&self, object_id: u32) -> Option<&T> {
+        self.get_column::<T>()
+            .and_then(|column| column.get(&object_id))
+    }
+
+    pub fn get_mut<T: Component>(&mut self, object_id: u32) -> Option<&mut T> {
+        self.get_column_mut::<T>()
+            .and_then(|column| column.get_mut(&object_id))
+    }
+}
+
+/// Describes a set of components to look up together, eg. `(&TransformComponent, &RenderableComponent)`.
+/// See `World::query`
+pub trait ComponentQuery {
+    type Item<'a>;
+
+    /// None if the object doesn't have all the components of the query
+    fn fetch(world: &World, object_id: u32) -> Option<Self::Item<'_>>;
+}
+
+impl<T: Component> ComponentQuery for &T {
+    type Item<'a> = &'a T;
+
+    fn fetch(world: &World, object_id: u32) -> Option<Self::Item<'_>> {
+        world.get_component::<T>(&object_id)
+    }
+}
+
+macro_rules! impl_component_query_for_tuple {
+    ( $( $query:ident ),+ ) => {
+        impl<$( $query: ComponentQuery ),+> ComponentQuery for ( $( $query, )+ ) {
+            type Item<'a> = ( $( $query::Item<'a>, )+ );
+
+            fn fetch(world: &World, object_id: u32) -> Option<Self::Item<'_>> {
+                Some(( $( $query::fetch(world, object_id)?, )+ ))
+            }
+        }
+    };
+}
+
+impl_component_query_for_tuple!(A);
+impl_component_query_for_tuple!(A, B);
+impl_component_query_for_tuple!(A, B, C);
+impl_component_query_for_tuple!(A, B, C, D);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+    impl Component for Name {}
+
+    #[test]
+    fn components_are_stored_by_type() {
+        let mut storage = ComponentStorage::new();
+        storage.insert(1, Health(10));
+        storage.insert(1, Name("lamp"));
+        storage.insert(2, Health(20));
+
+        assert_eq!(storage.get::<Health>(1), Some(&Health(10)));
+        assert_eq!(storage.get::<Name>(1), Some(&Name("lamp")));
+        assert_eq!(storage.get::<Health>(2), Some(&Health(20)));
+        assert_eq!(storage.get::<Name>(2), None);
+
+        assert_eq!(storage.insert(2, Health(30)), Some(Health(20)));
+        assert_eq!(storage.get::<Health>(2), Some(&Health(30)));
+    }
+
+    #[test]
+    fn removing_an_object_removes_all_of_its_components() {
+        let mut storage = ComponentStorage::new();
+        storage.insert(1, Health(10));
+        storage.insert(1, Name("lamp"));
+        storage.insert(2, Health(20));
+
+        storage.remove_object(1);
+
+        assert_eq!(storage.get::<Health>(1), None);
+        assert_eq!(storage.get::<Name>(1), None);
+        assert_eq!(storage.get::<Health>(2), Some(&Health(20)));
+        assert_eq!(storage.remove::<Health>(2), Some(Health(20)));
+        assert_eq!(storage.get::<Health>(2), None);
+    }
+}
@@ -1,11 +1,15 @@
-use std::mem;
+use std::{any::Any, mem};
 
 use glam::{Mat3, Mat4, Quat, Vec3};
 
 use crate::{
+    camera::DEFAULT_FOV_Y,
+    component_storage::Component,
     lights::{DirectionalLight, PointLight},
+    lod::LodSettings,
     material::PbrMaterialDescriptor,
     model::{MeshDescriptor, ModelDescriptor, ModelRenderingOptions},
+    world_events::ComponentTypeId,
 };
 
 use crate::buffer_content::BufferContent;
@@ -28,6 +32,8 @@ pub struct TransformComponent {
     rotation: Quat,
 }
 
+impl Component for TransformComponent {}
+
 impl Default for TransformComponent {
     fn default() -> Self {
         Self {
@@ -54,6 +60,28 @@ impl TransformComponent {
         }
     }
 
+    /// The shear that a non-uniformly scaled parent could cause on a rotated child is lost
+    pub fn from_model_matrix(model_matrix: Mat4) -> Self {
+        let (scale, rotation, position) = model_matrix.to_scale_rotation_translation();
+        Self {
+            position,
+            scale,
+            rotation,
+        }
+    }
+
+    /// The transform of a child in world space, if `self` is the world transform of its parent
+    pub fn combine(&self, child_local_transform: &TransformComponent) -> Self {
+        Self::from_model_matrix(self.get_model_matrix() * child_local_transform.get_model_matrix())
+    }
+
+    /// The inverse of `combine`: the local transform that results in `self` under the given parent
+    pub fn relative_to(&self, parent_world_transform: &TransformComponent) -> Self {
+        Self::from_model_matrix(
+            parent_world_transform.get_model_matrix().inverse() * self.get_model_matrix(),
+        )
+    }
+
     pub fn get_position(&self) -> Vec3 {
         self.position
     }
@@ -62,22 +90,35 @@ impl TransformComponent {
         self.position = new_position;
     }
 
+    pub fn get_scale(&self) -> Vec3 {
+        self.scale
+    }
+
     pub fn set_scale(&mut self, new_scale: Vec3) {
         self.scale = new_scale;
     }
 
-    pub fn to_raw(&self, object_id: u32) -> TransformComponentRaw {
+    pub fn get_rotation(&self) -> Quat {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, new_rotation: Quat) {
+        self.rotation = new_rotation;
+    }
+
+    pub fn get_model_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
+    }
+
+    /// See `TransformComponentRaw::lod_fade` for the meaning of `lod_fade`
+    pub fn to_raw_with_lod_fade(&self, object_id: u32, lod_fade: f32) -> TransformComponentRaw {
         TransformComponentRaw {
-            model_matrix: Mat4::from_scale_rotation_translation(
-                self.scale,
-                self.rotation,
-                self.position,
-            )
-            .to_cols_array_2d(),
+            model_matrix: self.get_model_matrix().to_cols_array_2d(),
             // Instead of the inverse transpose, we can just pass the rotation matrix
             // As non-uniform scaling is not supported, this is fine
             rotation_only_matrix: Mat3::from_quat(self.rotation).to_cols_array_2d(),
             object_id,
+            lod_fade,
         }
     }
 }
@@ -88,6 +129,9 @@ pub struct TransformComponentRaw {
     pub model_matrix: [[f32; 4]; 4],
     pub rotation_only_matrix: [[f32; 3]; 3],
     pub object_id: u32,
+    /// Controls the dithered cross-fade between two levels of detail. 0 means no cross-fade, positive values are
+    /// the visible ratio of the level that is fading in, negative values belong to the level that is fading out
+    pub lod_fade: f32,
 }
 
 impl BufferContent for TransformComponentRaw {
@@ -139,6 +183,11 @@ impl BufferContent for TransformComponentRaw {
                     shader_location: 12,
                     format: wgpu::VertexFormat::Uint32,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 26]>() as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Float32,
+                },
             ],
         }
     }
@@ -148,6 +197,7 @@ impl BufferContent for TransformComponentRaw {
     Default,
     Debug,
     Clone,
+    PartialEq,
     serde::Serialize,
     serde::Deserialize,
     ui_item_derive::UiDisplayable,
@@ -156,12 +206,16 @@ impl BufferContent for TransformComponentRaw {
 pub struct RenderableComponent {
     pub model_descriptor: ModelDescriptor,
     pub rendering_options: ModelRenderingOptions,
+    #[serde(default)]
+    pub lod_settings: LodSettings,
 
     #[serde(skip_serializing)]
     #[serde(default)]
     pub is_transient: bool,
 }
 
+impl Component for RenderableComponent {}
+
 impl RenderableComponent {
     pub fn new(
         mesh_descriptor: MeshDescriptor,
@@ -175,6 +229,7 @@ impl RenderableComponent {
                 material_descriptor,
             },
             rendering_options,
+            lod_settings: LodSettings::default(),
             is_transient,
         }
     }
@@ -189,6 +244,7 @@ impl RenderableComponent {
     Default,
     Debug,
     Clone,
+    PartialEq,
     serde::Serialize,
     serde::Deserialize,
     ui_item_derive::UiDisplayable,
@@ -198,22 +254,131 @@ pub struct LightObjectComponent {
     pub light: PointLight,
 }
 
+impl Component for LightObjectComponent {}
+
+/// Renders the world from the point of view of the object into a texture. Like the editor camera, it looks along the
+/// -Z axis of the object
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    ui_item_derive::UiDisplayable,
+    ui_item_derive::UiSettableNew,
+)]
+pub struct CameraComponent {
+    #[ui_param(min = "1.0", max = "120.0")]
+    pub fov_y: f32,
+    /// The size of the texture the camera renders into, in pixels
+    #[ui_param(min = "16", max = "2048")]
+    pub resolution_width: u32,
+    #[ui_param(min = "16", max = "2048")]
+    pub resolution_height: u32,
+}
+
+impl Component for CameraComponent {}
+
+impl Default for CameraComponent {
+    fn default() -> Self {
+        Self {
+            fov_y: DEFAULT_FOV_Y,
+            resolution_width: 512,
+            resolution_height: 288,
+        }
+    }
+}
+
 #[derive(
     Debug,
     Clone,
+    PartialEq,
     serde::Serialize,
     serde::Deserialize,
     ui_item_derive::UiDisplayable,
     ui_item_derive::UiSettableNew,
 )]
 pub enum SceneComponentType {
+    Camera(CameraComponent),
     LightObject(LightObjectComponent),
     Renderable(RenderableComponent),
 }
 
+/// Moves the value into `Target`, if `Source` and `Target` are the same type
+fn cast_component<Source: Any, Target: Any>(component: Source) -> Result<Target, Source> {
+    let mut component = Some(component);
+    if let Some(target) = (&mut component as &mut dyn Any).downcast_mut::<Option<Target>>() {
+        return Ok(target.take().unwrap());
+    }
+
+    Err(component.unwrap())
+}
+
 impl SceneComponentType {
+    /// Wraps the component, if its type is one of the variants. Otherwise gives it back
+    pub fn from_component<T: Component>(component: T) -> Result<Self, T> {
+        let component = match cast_component::<T, CameraComponent>(component) {
+            Ok(camera_component) => return Ok(SceneComponentType::Camera(camera_component)),
+            Err(component) => component,
+        };
+        let component = match cast_component::<T, LightObjectComponent>(component) {
+            Ok(light_object_component) => {
+                return Ok(SceneComponentType::LightObject(light_object_component))
+            }
+            Err(component) => component,
+        };
+
+        cast_component::<T, RenderableComponent>(component).map(SceneComponentType::Renderable)
+    }
+
+    /// Unwraps the component, if it's of type `T`. Otherwise gives it back
+    pub fn into_component<T: Component>(self) -> Result<T, Self> {
+        match self {
+            SceneComponentType::Camera(camera_component) => {
+                cast_component(camera_component).map_err(SceneComponentType::Camera)
+            }
+            SceneComponentType::LightObject(light_object_component) => {
+                cast_component(light_object_component).map_err(SceneComponentType::LightObject)
+            }
+            SceneComponentType::Renderable(renderable_component) => {
+                cast_component(renderable_component).map_err(SceneComponentType::Renderable)
+            }
+        }
+    }
+
+    pub fn get_component_type(&self) -> ComponentTypeId {
+        match self {
+            SceneComponentType::Camera(_camera_component) => {
+                ComponentTypeId::of::<CameraComponent>()
+            }
+            SceneComponentType::LightObject(_light_object_component) => {
+                ComponentTypeId::of::<LightObjectComponent>()
+            }
+            SceneComponentType::Renderable(_renderable_component) => {
+                ComponentTypeId::of::<RenderableComponent>()
+            }
+        }
+    }
+
+    pub fn as_any(&self) -> &dyn Any {
+        match self {
+            SceneComponentType::Camera(camera_component) => camera_component,
+            SceneComponentType::LightObject(light_object_component) => light_object_component,
+            SceneComponentType::Renderable(renderable_component) => renderable_component,
+        }
+    }
+
+    pub fn as_any_mut(&mut self) -> &mut dyn Any {
+        match self {
+            SceneComponentType::Camera(camera_component) => camera_component,
+            SceneComponentType::LightObject(light_object_component) => light_object_component,
+            SceneComponentType::Renderable(renderable_component) => renderable_component,
+        }
+    }
+
     pub fn is_transient(&self) -> bool {
         match self {
+            SceneComponentType::Camera(_camera_component) => false,
             SceneComponentType::LightObject(_light_object_component) => false,
             SceneComponentType::Renderable(renderable_component) => {
                 renderable_component.is_transient
@@ -229,7 +394,7 @@ pub struct SceneComponent {
     inner_component: SceneComponentType,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum OmnipresentComponentType {
     DirectionalLight(DirectionalLight),
 }
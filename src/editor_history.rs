@@ -0,0 +1,585 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crossbeam_channel::Receiver;
+
+use crate::{
+    component_storage::Component,
+    persistent_id::PersistentId,
+    prefab::{PrefabInstance, PrefabPart},
+    world::World,
+    world_events::{WorldEvent, WorldEventFilter},
+    world_object::WorldObject,
+};
+
+/// The maximum number of entries that can be undone
+const MAX_UNDO_ENTRY_COUNT: usize = 100;
+
+/// Consecutive changes with the same key are merged into one entry, until `EditorHistory::stop_merging` is called
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryMergeKey {
+    GizmoDrag(u32),
+    PropertyEdit(u32),
+}
+
+/// A world object as it's restored by undo or redo
+#[derive(Debug, Clone, PartialEq)]
+struct ObjectState {
+    persistent_id: PersistentId,
+    parent: Option<PersistentId>,
+    /// Without the transient components, they are recreated by the world (eg. the debug mesh of the lights)
+    object: WorldObject,
+    /// The prefab components are kept as well, so a restored prefab instance is still an instance
+    prefab_instance: Option<PrefabInstance>,
+    prefab_part: Option<PrefabPart>,
+}
+
+/// None means the object doesn't exist in that state, so the change is either an addition or a removal
+#[derive(Debug, Clone)]
+struct ObjectChange {
+    persistent_id: PersistentId,
+    before: Option<ObjectState>,
+    after: Option<ObjectState>,
+}
+
+#[derive(Debug, Clone)]
+struct HistoryEntry {
+    name: String,
+    changes: Vec<ObjectChange>,
+}
+
+/// Records the changes of the world objects, so they can be undone and redone. Every change made in a frame becomes one
+/// entry, named by `name_next_changes`. The objects are identified by their persistent IDs, as the runtime IDs of the
+/// objects recreated by undo are different.
+/// Besides the world objects, only the prefab components are restored, the other components that are not built into
+/// `WorldObject` are not
+pub struct EditorHistory {
+    undo_entries: Vec<HistoryEntry>,
+    redo_entries: Vec<HistoryEntry>,
+
+    /// The state of every non-transient world object when the last entry was recorded
+    recorded_states: HashMap<u32, ObjectState>,
+    world_event_receiver: Receiver<WorldEvent>,
+
+    next_entry_name: Option<String>,
+    next_merge_key: Option<HistoryMergeKey>,
+    /// The key of the last entry, while it can still be extended
+    open_merge_key: Option<HistoryMergeKey>,
+}
+
+impl EditorHistory {
+    pub fn new(world: &mut World) -> Self {
+        let mut history = Self {
+            undo_entries: vec![],
+            redo_entries: vec![],
+            recorded_states: HashMap::new(),
+            world_event_receiver: world.subscribe(WorldEventFilter::all()),
+            next_entry_name: None,
+            next_merge_key: None,
+            open_merge_key: None,
+        };
+        history.reset(world);
+
+        history
+    }
+
+    /// Forgets every entry and takes the current state of the world as the starting point (eg. after loading a level)
+    pub fn reset(&mut self, world: &World) {
+        self.undo_entries.clear();
+        self.redo_entries.clear();
+        self.open_merge_key = None;
+        self.world_event_receiver.try_iter().for_each(drop);
+
+        self.recorded_states.clear();
+        let mut ids_to_visit = world.get_root_world_object_ids();
+        while let Some(id) = ids_to_visit.pop() {
+            if let Some(state) = Self::get_object_state(world, id) {
+                self.recorded_states.insert(id, state);
+            }
+            ids_to_visit.extend_from_slice(world.get_children(&id));
+        }
+    }
+
+    /// Names the entry of the changes made in this frame. With a merge key, the changes are added to the last entry if
+    /// it has the same key
+    pub fn name_next_changes(&mut self, name: &str, merge_key: Option<HistoryMergeKey>) {
+        self.next_entry_name = Some(name.to_owned());
+        self.next_merge_key = merge_key;
+    }
+
+    /// The next changes start a new entry, even if they have the same merge key as the last one (eg. the drag ended)
+    pub fn stop_merging(&mut self) {
+        self.open_merge_key = None;
+    }
+
+    fn get_object_state(world: &World, id: u32) -> Option<ObjectState> {
+        Some(ObjectState {
+            persistent_id: world.get_persistent_id(&id)?,
+            parent: world
+                .get_parent(&id)
+                .and_then(|parent_id| world.get_persistent_id(&parent_id)),
+            object: world.get_world_object(&id)?.get_saved_copy()?,
+            prefab_instance: world.get_component::<PrefabInstance>(&id).cloned(),
+            prefab_part: world.get_component::<PrefabPart>(&id).cloned(),
+        })
+    }
+
+    /// Turns the changes of the world since the last call into an entry. Has to be called after
+    /// `World::publish_changes`, so every change is already published. Returns true if the entries
+    /// changed
+    pub fn record_changes(&mut self, world: &World) -> bool {
+        let changes = self.take_changes(world);
+
+        let name = self.next_entry_name.take();
+        let merge_key = self.next_merge_key.take();
+        if changes.is_empty() {
+            return false;
+        }
+
+        self.redo_entries.clear();
+        if merge_key.is_some() && merge_key == self.open_merge_key {
+            if let Some(last_entry) = self.undo_entries.last_mut() {
+                Self::merge_changes(last_entry, changes);
+                return true;
+            }
+        }
+
+        self.open_merge_key = merge_key;
+        self.undo_entries.push(HistoryEntry {
+            name: name.unwrap_or_else(|| "Edit".into()),
+            changes,
+        });
+        if self.undo_entries.len() > MAX_UNDO_ENTRY_COUNT {
+            self.undo_entries.remove(0);
+        }
+
+        true
+    }
+
+    /// The changes of the objects since the last call, the recorded states are updated to the current ones
+    fn take_changes(&mut self, world: &World) -> Vec<ObjectChange> {
+        let changed_ids = self
+            .world_event_receiver
+            .try_iter()
+            .map(|event| event.get_object_id())
+            .collect::<BTreeSet<_>>();
+
+        let mut changes = vec![];
+        for id in changed_ids {
+            let before = self.recorded_states.remove(&id);
+            let after = Self::get_object_state(world, id);
+            if let Some(after) = &after {
+                self.recorded_states.insert(id, after.clone());
+            }
+
+            let is_changed = match (&before, &after) {
+                (Some(before), Some(after)) => before != after,
+                (None, None) => false,
+                _ => true,
+            };
+            if let Some(persistent_id) = after
+                .as_ref()
+                .or(before.as_ref())
+                .map(|state| state.persistent_id)
+            {
+                if is_changed {
+                    changes.push(ObjectChange {
+                        persistent_id,
+                        before,
+                        after,
+                    });
+                }
+            }
+        }
+
+        changes
+    }
+
+    /// The entry keeps the earliest state of each object and takes the latest one
+    fn merge_changes(entry: &mut HistoryEntry, changes: Vec<ObjectChange>) {
+        for change in changes {
+            match entry
+                .changes
+                .iter_mut()
+                .find(|entry_change| entry_change.persistent_id == change.persistent_id)
+            {
+                Some(entry_change) => entry_change.after = change.after,
+                None => entry.changes.push(change),
+            }
+        }
+    }
+
+    /// The changes made in this frame before the undo are recorded first, so they are the ones undone
+    pub fn undo(&mut self, world: &mut World) {
+        self.record_pending_changes(world);
+        if let Some(entry) = self.undo_entries.pop() {
+            let states = entry
+                .changes
+                .iter()
+                .rev()
+                .map(|change| (change.persistent_id, change.before.as_ref()));
+            Self::restore_states(world, states);
+
+            self.redo_entries.push(entry);
+            self.finish_restoring(world);
+        }
+    }
+
+    pub fn redo(&mut self, world: &mut World) {
+        self.record_pending_changes(world);
+        if let Some(entry) = self.redo_entries.pop() {
+            let states = entry
+                .changes
+                .iter()
+                .map(|change| (change.persistent_id, change.after.as_ref()));
+            Self::restore_states(world, states);
+
+            self.undo_entries.push(entry);
+            self.finish_restoring(world);
+        }
+    }
+
+    fn record_pending_changes(&mut self, world: &mut World) {
+        world.publish_changes();
+        self.record_changes(world);
+    }
+
+    /// Only the changes made by the restore are published at this point, they are taken without becoming a new entry
+    fn finish_restoring(&mut self, world: &mut World) {
+        world.publish_changes();
+        self.take_changes(world);
+
+        self.open_merge_key = None;
+        self.next_entry_name = None;
+        self.next_merge_key = None;
+    }
+
+    fn restore_states<'a>(
+        world: &mut World,
+        states: impl Iterator<Item = (PersistentId, Option<&'a ObjectState>)>,
+    ) {
+        let mut objects_to_add = vec![];
+        for (persistent_id, state) in states {
+            let id = world.get_runtime_id(&persistent_id);
+            match (id, state) {
+                (Some(id), None) => world.remove_world_object(id),
+                (Some(id), Some(state)) => Self::restore_object(world, id, state),
+                (None, Some(state)) => objects_to_add.push(state),
+                (None, None) => {}
+            }
+        }
+
+        // The parents are added before their children, they might be in any order in the entry
+        while !objects_to_add.is_empty() {
+            let object_count = objects_to_add.len();
+            objects_to_add.retain(|state| !Self::try_to_add_object(world, state));
+            if objects_to_add.len() == object_count {
+                log::warn!(
+                    "{object_count} objects couldn't be restored, their parents are missing"
+                );
+                break;
+            }
+        }
+    }
+
+    fn restore_object(world: &mut World, id: u32, state: &ObjectState) {
+        let parent_id = state
+            .parent
+            .and_then(|parent| world.get_runtime_id(&parent));
+        if world.get_parent(&id) != parent_id {
+            if let Err(error) = world.set_parent(id, parent_id) {
+                log::warn!("Failed to restore the parent of object {id}: {error}");
+            }
+        }
+
+        if let Some(world_object) = world.get_world_object_mut(&id) {
+            let transient_components = world_object
+                .components
+                .drain(..)
+                .filter(|component| component.is_transient());
+            let mut restored_object = state.object.clone();
+            restored_object.components.extend(transient_components);
+            *world_object = restored_object;
+        }
+
+        Self::restore_component(world, id, state.prefab_instance.as_ref());
+        Self::restore_component(world, id, state.prefab_part.as_ref());
+    }
+
+    /// Adds, replaces or removes the component of the object, so it matches the restored one
+    fn restore_component<T: Component + Clone + PartialEq>(
+        world: &mut World,
+        id: u32,
+        component: Option<&T>,
+    ) {
+        if world.get_component::<T>(&id) == component {
+            return;
+        }
+
+        world.remove_component::<T>(id);
+        if let Some(component) = component {
+            if let Err(error) = world.add_component(id, component.clone()) {
+                log::warn!("Failed to restore a component of object {id}: {error}");
+            }
+        }
+    }
+
+    /// Returns false if the parent of the object doesn't exist yet
+    fn try_to_add_object(world: &mut World, state: &ObjectState) -> bool {
+        let id = match state.parent {
+            Some(parent) => match world.get_runtime_id(&parent) {
+                Some(parent_id) => world.add_child_world_object(parent_id, state.object.clone()),
+                None => return false,
+            },
+            None => Ok(world.add_world_object(state.object.clone())),
+        };
+
+        match id {
+            Ok(id) => {
+                if let Err(error) = world.set_persistent_id(id, state.persistent_id) {
+                    log::warn!("Restored object {id} got a new persistent ID: {error}");
+                }
+                Self::restore_component(world, id, state.prefab_instance.as_ref());
+                Self::restore_component(world, id, state.prefab_part.as_ref());
+            }
+            Err(error) => log::warn!("Failed to restore object: {error}"),
+        }
+
+        true
+    }
+
+    /// The names of every entry from the oldest one and the number of entries that are not undone
+    pub fn get_entry_names(&self) -> (Vec<String>, usize) {
+        let entry_names = self
+            .undo_entries
+            .iter()
+            .chain(self.redo_entries.iter().rev())
+            .map(|entry| entry.name.clone())
+            .collect();
+
+        (entry_names, self.undo_entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use glam::Vec3;
+
+    use super::*;
+    use crate::{
+        components::TransformComponent,
+        prefab::{add_prefab_instance, get_instance_parts, load_prefab},
+        test_helpers::create_test_world,
+    };
+
+    fn record_changes(
+        world: &mut World,
+        history: &mut EditorHistory,
+        name: &str,
+        merge_key: Option<HistoryMergeKey>,
+    ) {
+        history.name_next_changes(name, merge_key);
+        world.publish_changes();
+        history.record_changes(world);
+    }
+
+    fn add_object(world: &mut World, history: &mut EditorHistory, position: Vec3) -> u32 {
+        let id = world.add_world_object(WorldObject::new(
+            vec![],
+            TransformComponent::from_position(position),
+        ));
+        record_changes(world, history, "Add object", None);
+
+        id
+    }
+
+    fn move_object(world: &mut World, id: u32, position: Vec3) {
+        world
+            .get_world_object_mut(&id)
+            .unwrap()
+            .transform
+            .set_position(position);
+    }
+
+    fn get_position(world: &World, persistent_id: &PersistentId) -> Option<Vec3> {
+        let id = world.get_runtime_id(persistent_id)?;
+        Some(world.get_world_object(&id)?.transform.get_position())
+    }
+
+    #[test]
+    fn additions_can_be_undone_and_redone() {
+        let mut world = create_test_world();
+        let mut history = EditorHistory::new(&mut world);
+        let id = add_object(&mut world, &mut history, Vec3::X);
+        let persistent_id = world.get_persistent_id(&id).unwrap();
+
+        history.undo(&mut world);
+        assert!(world.get_world_object(&id).is_none());
+        assert_eq!(history.get_entry_names(), (vec!["Add object".into()], 0));
+
+        history.redo(&mut world);
+        assert_eq!(get_position(&world, &persistent_id), Some(Vec3::X));
+        assert_eq!(history.get_entry_names(), (vec!["Add object".into()], 1));
+    }
+
+    #[test]
+    fn deleted_objects_are_restored_with_their_persistent_ids() {
+        let mut world = create_test_world();
+        let mut history = EditorHistory::new(&mut world);
+        let parent_id = add_object(&mut world, &mut history, Vec3::X);
+        let child_id = world
+            .add_child_world_object(
+                parent_id,
+                WorldObject::new(vec![], TransformComponent::from_position(Vec3::Y)),
+            )
+            .unwrap();
+        record_changes(&mut world, &mut history, "Add object", None);
+        let parent_persistent_id = world.get_persistent_id(&parent_id).unwrap();
+        let child_persistent_id = world.get_persistent_id(&child_id).unwrap();
+
+        world.remove_world_object(parent_id);
+        record_changes(&mut world, &mut history, "Delete object", None);
+        history.undo(&mut world);
+
+        let restored_parent_id = world.get_runtime_id(&parent_persistent_id).unwrap();
+        let restored_child_id = world.get_runtime_id(&child_persistent_id).unwrap();
+        assert_eq!(
+            world.get_parent(&restored_child_id),
+            Some(restored_parent_id)
+        );
+        assert_eq!(get_position(&world, &child_persistent_id), Some(Vec3::Y));
+
+        history.redo(&mut world);
+        assert!(world.get_runtime_id(&parent_persistent_id).is_none());
+        assert!(world.get_runtime_id(&child_persistent_id).is_none());
+    }
+
+    #[test]
+    fn transform_changes_can_be_undone_and_redone() {
+        let mut world = create_test_world();
+        let mut history = EditorHistory::new(&mut world);
+        let id = add_object(&mut world, &mut history, Vec3::X);
+        let persistent_id = world.get_persistent_id(&id).unwrap();
+
+        move_object(&mut world, id, Vec3::Z);
+        record_changes(&mut world, &mut history, "Move object", None);
+
+        history.undo(&mut world);
+        assert_eq!(get_position(&world, &persistent_id), Some(Vec3::X));
+        history.redo(&mut world);
+        assert_eq!(get_position(&world, &persistent_id), Some(Vec3::Z));
+    }
+
+    #[test]
+    fn changes_with_the_same_merge_key_are_merged_until_stopped() {
+        let mut world = create_test_world();
+        let mut history = EditorHistory::new(&mut world);
+        let id = add_object(&mut world, &mut history, Vec3::X);
+        let persistent_id = world.get_persistent_id(&id).unwrap();
+        let merge_key = Some(HistoryMergeKey::GizmoDrag(id));
+
+        for position in [Vec3::Y, Vec3::Z] {
+            move_object(&mut world, id, position);
+            record_changes(&mut world, &mut history, "Move object", merge_key);
+        }
+        assert_eq!(history.get_entry_names().1, 2);
+
+        history.stop_merging();
+        move_object(&mut world, id, Vec3::ONE);
+        record_changes(&mut world, &mut history, "Move object", merge_key);
+        assert_eq!(history.get_entry_names().1, 3);
+
+        history.undo(&mut world);
+        assert_eq!(get_position(&world, &persistent_id), Some(Vec3::Z));
+        history.undo(&mut world);
+        assert_eq!(get_position(&world, &persistent_id), Some(Vec3::X));
+    }
+
+    #[test]
+    fn new_changes_drop_the_redo_entries() {
+        let mut world = create_test_world();
+        let mut history = EditorHistory::new(&mut world);
+        let id = add_object(&mut world, &mut history, Vec3::X);
+        move_object(&mut world, id, Vec3::Y);
+        record_changes(&mut world, &mut history, "Move object", None);
+
+        history.undo(&mut world);
+        move_object(&mut world, id, Vec3::Z);
+        record_changes(&mut world, &mut history, "Move again", None);
+
+        assert_eq!(
+            history.get_entry_names(),
+            (vec!["Add object".into(), "Move again".into()], 2)
+        );
+        history.redo(&mut world);
+        assert_eq!(
+            world
+                .get_world_object(&id)
+                .unwrap()
+                .transform
+                .get_position(),
+            Vec3::Z
+        );
+    }
+
+    #[test]
+    fn changes_made_in_the_frame_of_an_undo_are_kept() {
+        let mut world = create_test_world();
+        let mut history = EditorHistory::new(&mut world);
+        let id = add_object(&mut world, &mut history, Vec3::X);
+        let persistent_id = world.get_persistent_id(&id).unwrap();
+
+        move_object(&mut world, id, Vec3::Y);
+        history.name_next_changes("Move object", None);
+        history.undo(&mut world);
+        world.publish_changes();
+        history.record_changes(&world);
+
+        assert_eq!(get_position(&world, &persistent_id), Some(Vec3::X));
+        assert_eq!(
+            history.get_entry_names(),
+            (vec!["Add object".into(), "Move object".into()], 1)
+        );
+        history.redo(&mut world);
+        assert_eq!(get_position(&world, &persistent_id), Some(Vec3::Y));
+    }
+
+    #[test]
+    fn deleted_prefab_instances_are_restored_as_instances() {
+        let prefab_path = Path::new("levels/fixtures/lamp.prefab");
+        let mut world = create_test_world();
+        let mut history = EditorHistory::new(&mut world);
+        let root_id =
+            world.add_world_object(WorldObject::new(vec![], TransformComponent::default()));
+        add_prefab_instance(
+            &mut world,
+            root_id,
+            prefab_path,
+            load_prefab(prefab_path).unwrap(),
+            &[],
+        )
+        .unwrap();
+        record_changes(&mut world, &mut history, "Add prefab", None);
+        let root_persistent_id = world.get_persistent_id(&root_id).unwrap();
+        let part_persistent_ids = get_instance_parts(&world, root_id)
+            .into_iter()
+            .map(|(part, id)| (part, world.get_persistent_id(&id).unwrap()))
+            .collect::<HashMap<_, _>>();
+
+        world.remove_world_object(root_id);
+        record_changes(&mut world, &mut history, "Delete object", None);
+        history.undo(&mut world);
+
+        let restored_root_id = world.get_runtime_id(&root_persistent_id).unwrap();
+        let restored_instance = world
+            .get_component::<PrefabInstance>(&restored_root_id)
+            .unwrap();
+        assert_eq!(restored_instance.prefab_path, prefab_path);
+        let restored_part_persistent_ids = get_instance_parts(&world, restored_root_id)
+            .into_iter()
+            .map(|(part, id)| (part, world.get_persistent_id(&id).unwrap()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(restored_part_persistent_ids, part_persistent_ids);
+    }
+}
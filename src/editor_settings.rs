@@ -0,0 +1,34 @@
+use std::{fs, path::Path};
+
+use anyhow::Result;
+
+use crate::{gizmo::GizmoOrientation, gizmo_handler::SnapSettings};
+
+const EDITOR_SETTINGS_PATH: &str = "editor_settings.json";
+
+/// The preferences of the editor, they are kept between the sessions. Unlike the levels, they are not part of the
+/// project
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct EditorSettings {
+    pub gizmo_orientation: GizmoOrientation,
+    pub snap_settings: SnapSettings,
+}
+
+impl EditorSettings {
+    /// The default settings are used if they were never saved
+    pub fn load() -> Result<Self> {
+        let settings_path = Path::new(EDITOR_SETTINGS_PATH);
+        if !settings_path.exists() {
+            return Ok(Self::default());
+        }
+
+        Ok(serde_json::from_str(&fs::read_to_string(settings_path)?)?)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::write(EDITOR_SETTINGS_PATH, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+}
@@ -1,22 +1,52 @@
-use std::rc::Rc;
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
+use anyhow::anyhow;
 use wgpu::{CommandEncoder, Device, TextureFormat};
 
 use crate::{
     bind_group_layout_descriptors,
+    buffer::GpuBufferCreationOptions,
     cubemap_helpers::{create_cubemap_face_rendering_parameters, RenderingIntoCubemapResources},
+    gpu_buffer::GpuBuffer,
     model::Primitive,
     pipelines::{EquirectangularToCubemapRP, ShaderCompilationSuccess},
     renderer::Renderer,
     texture::SampledTexture,
+    world::EnvironmentSettings,
 };
 
 const CUBEMAP_RESOLUTION: u32 = 1024;
 
+/// Applied while converting the equirectangular map, so both the skybox and the image based lighting are affected
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct EnvironmentGpuParams {
+    intensity: f32,
+    /// Around the vertical axis, in radians
+    rotation: f32,
+    _padding: [f32; 2],
+}
+
+impl EnvironmentGpuParams {
+    fn new(environment: &EnvironmentSettings) -> Self {
+        Self {
+            intensity: environment.intensity,
+            rotation: environment.rotation.to_radians(),
+            _padding: [0.0; 2],
+        }
+    }
+}
+
 pub struct EquirectangularToCubemapRenderer {
     pipeline: EquirectangularToCubemapRP,
     mesh: Rc<Primitive>,
     hdr_map_bind_group: wgpu::BindGroup,
+    /// The file `hdr_map_bind_group` was loaded from
+    hdr_map_path: PathBuf,
+    environment_params: GpuBuffer<EnvironmentGpuParams>,
     render_params: Vec<RenderingIntoCubemapResources>,
     pub cube_map_to_sample: Rc<wgpu::BindGroup>,
     color_format: wgpu::TextureFormat,
@@ -27,15 +57,20 @@ impl EquirectangularToCubemapRenderer {
         renderer: &Renderer,
         color_format: TextureFormat,
         basic_mesh: Rc<Primitive>,
+        environment: &EnvironmentSettings,
     ) -> anyhow::Result<Self> {
         let pipeline = EquirectangularToCubemapRP::new(&renderer.device, color_format)?;
-        let hdr_texture_path = "assets/textures/skybox/golf_course.hdr";
-        let hdr_texture = SampledTexture::from_hdr_image(
-            renderer,
-            hdr_texture_path,
-            Some("HDR equirectangular map"),
-        )
-        .unwrap();
+        let hdr_map_bind_group = Self::load_hdr_map(renderer, &environment.skybox_path)?;
+        let environment_params = GpuBuffer::new(
+            EnvironmentGpuParams::new(environment),
+            &renderer.device,
+            &GpuBufferCreationOptions {
+                bind_group_layout_descriptor:
+                    &bind_group_layout_descriptors::BUFFER_VISIBLE_EVERYWHERE,
+                usages: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                label: "Environment params",
+            },
+        );
 
         let size = wgpu::Extent3d {
             width: CUBEMAP_RESOLUTION,
@@ -90,7 +125,30 @@ impl EquirectangularToCubemapRenderer {
                     label: None,
                 });
 
-        let hdr_map_bind_group = renderer
+        let render_params =
+            create_cubemap_face_rendering_parameters(&renderer.device, &cube_texture);
+
+        Ok(Self {
+            pipeline,
+            mesh: basic_mesh,
+            hdr_map_bind_group,
+            hdr_map_path: environment.skybox_path.clone(),
+            environment_params,
+            render_params,
+            cube_map_to_sample: Rc::new(sampled_cubemap_bind_group),
+            color_format,
+        })
+    }
+
+    fn load_hdr_map(renderer: &Renderer, path: &Path) -> anyhow::Result<wgpu::BindGroup> {
+        let hdr_texture = SampledTexture::from_hdr_image(
+            renderer,
+            &path.to_string_lossy(),
+            Some("HDR equirectangular map"),
+        )
+        .map_err(|error| anyhow!("Failed to load the environment map {path:?}: {error}"))?;
+
+        Ok(renderer
             .device
             .create_bind_group(&wgpu::BindGroupDescriptor {
                 layout: &renderer.device.create_bind_group_layout(
@@ -101,19 +159,25 @@ impl EquirectangularToCubemapRenderer {
                     hdr_texture.get_sampler_bind_group_entry(1),
                 ],
                 label: None,
-            });
+            }))
+    }
 
-        let render_params =
-            create_cubemap_face_rendering_parameters(&renderer.device, &cube_texture);
+    /// The cubemap is only updated by the next `render`. The HDR map is only reloaded if its path changed. If loading
+    /// it fails, the previous map is kept
+    pub fn set_environment(
+        &mut self,
+        renderer: &Renderer,
+        environment: &EnvironmentSettings,
+    ) -> anyhow::Result<()> {
+        *self.environment_params.get_mut_data(&renderer.queue) =
+            EnvironmentGpuParams::new(environment);
+
+        if self.hdr_map_path != environment.skybox_path {
+            self.hdr_map_bind_group = Self::load_hdr_map(renderer, &environment.skybox_path)?;
+            self.hdr_map_path = environment.skybox_path.clone();
+        }
 
-        Ok(Self {
-            pipeline,
-            mesh: basic_mesh,
-            hdr_map_bind_group,
-            render_params,
-            cube_map_to_sample: Rc::new(sampled_cubemap_bind_group),
-            color_format,
-        })
+        Ok(())
     }
 
     pub fn try_recompile_shader(
@@ -132,6 +196,7 @@ impl EquirectangularToCubemapRenderer {
                 &self.mesh,
                 &render_param.cube_face_viewproj_bind_group,
                 &self.hdr_map_bind_group,
+                &self.environment_params.bind_group,
             );
         }
     }
@@ -2,7 +2,7 @@ use wgpu::{BindGroup, Device, RenderPass};
 
 use crate::{
     bind_group_layout_descriptors,
-    model::Renderable,
+    model::{DrawMode, Renderable},
     pipelines::ShaderCompilationSuccess,
     render_pipeline::{
         PipelineFragmentState, PipelineVertexState, RenderPipeline, RenderPipelineDescriptor,
@@ -81,12 +81,14 @@ impl ForwardRenderer {
         renderables: T,
         camera_bind_group: &'a BindGroup,
         light_bind_group: &'a BindGroup,
+        draw_mode: &DrawMode<'a>,
     ) {
         self.pipeline.render(
             render_pass,
             &[light_bind_group, camera_bind_group],
             renderables,
             0,
+            draw_mode,
         );
     }
 }
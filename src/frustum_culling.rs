@@ -0,0 +1,72 @@
+use math_helpers::Frustum;
+
+use crate::model::Renderable;
+
+/// Where the visibility of the objects is decided
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    ui_item_derive::UiDisplayable,
+    ui_item_derive::UiSettableNew,
+)]
+pub enum CullingMode {
+    /// Each object is tested against the frustum on the CPU and only the visible ones are drawn
+    #[default]
+    Cpu,
+    /// A compute pass culls the objects against the frustum and writes indirect draw arguments
+    Gpu,
+    /// Same as `Gpu`, but the objects hidden behind the depth buffer of the previous frame are culled as well
+    GpuWithHiZ,
+}
+
+/// How many objects were tested against a frustum and how many of them were skipped
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CullingStats {
+    pub total: usize,
+    pub culled: usize,
+}
+
+impl CullingStats {
+    pub fn visible(&self) -> usize {
+        self.total - self.culled
+    }
+}
+
+/// Culling statistics of a single frame, displayed on the GUI
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameCullingStats {
+    /// The renderables submitted for drawing the main camera
+    pub submitted: usize,
+    /// Culling against the frustum of the main camera. None when culling on the GPU, the results are only known there
+    pub camera: Option<CullingStats>,
+    /// Culling against the frusta of the lights, summed for every shadow map (cube map face) that was rendered. None
+    /// when culling on the GPU
+    pub shadow: Option<CullingStats>,
+}
+
+/// Returns the renderables that are (at least partially) inside of the frustum
+pub fn cull_renderables<'a, T>(
+    renderables: T,
+    frustum: &Frustum,
+    stats: &mut CullingStats,
+) -> Vec<&'a Renderable>
+where
+    T: Iterator<Item = &'a Renderable>,
+{
+    let mut visible_renderables = Vec::new();
+
+    for renderable in renderables {
+        stats.total += 1;
+        if renderable.is_inside_frustum(frustum) {
+            visible_renderables.push(renderable);
+        } else {
+            stats.culled += 1;
+        }
+    }
+
+    visible_renderables
+}
@@ -10,7 +10,7 @@ use crate::{
     bind_group_layout_descriptors,
     components::RenderableComponent,
     material::PbrMaterialDescriptor,
-    model::{PbrRenderingType, Renderable},
+    model::{DrawMode, PbrRenderingType, Renderable},
     pipelines::ShaderCompilationSuccess,
     render_pipeline::{
         PipelineFragmentState, PipelineVertexState, RenderPipeline, RenderPipelineDescriptor,
@@ -39,6 +39,14 @@ pub struct GBufferTextures {
     pub metal_rough_ao: SampledTexture,
 }
 
+/// The textures the geometry of one view is rendered into, and the bind groups that read them in the later passes.
+/// Every view has its own, while the pipelines in `GBufferGeometryRenderer` are shared by all of them
+pub struct GBufferTargets {
+    pub textures: GBufferTextures,
+    pub gbuffer_textures_bind_group: wgpu::BindGroup,
+    pub depth_texture_bind_group: wgpu::BindGroup,
+}
+
 struct PipelineWithObjects {
     render_pipeline: RenderPipeline,
     objects: HashSet<u32>,
@@ -64,12 +72,8 @@ fn default_color_write_state(format: wgpu::TextureFormat) -> ColorTargetState {
     }
 }
 
+#[derive(Default)]
 pub struct GBufferGeometryRenderer {
-    pub textures: GBufferTextures,
-    pub gbuffer_textures_bind_group: wgpu::BindGroup,
-    pub depth_texture_bind_group: wgpu::BindGroup,
-    width: u32,
-    height: u32,
     render_pipelines: HashMap<GBufferRenderingParams, PipelineWithObjects>,
 }
 
@@ -93,24 +97,103 @@ impl From<&RenderableComponent> for GBufferRenderingParams {
     }
 }
 
-impl GBufferGeometryRenderer {
+impl GBufferTargets {
     pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
         let textures = Self::create_textures(device, width, height);
-        let bind_group = Self::create_gbuffer_bind_group(device, &textures);
-
+        let gbuffer_textures_bind_group = Self::create_gbuffer_bind_group(device, &textures);
         let depth_texture_bind_group =
             Self::create_depth_bind_group(device, &textures.depth_texture);
 
         Self {
             textures,
-            gbuffer_textures_bind_group: bind_group,
+            gbuffer_textures_bind_group,
+            depth_texture_bind_group,
+        }
+    }
+
+    fn create_textures(device: &wgpu::Device, width: u32, height: u32) -> GBufferTextures {
+        let texture_extents = Extent3d {
             width,
             height,
-            render_pipelines: HashMap::new(),
-            depth_texture_bind_group,
+            depth_or_array_layers: 1,
+        };
+        let descriptor = SampledTextureDescriptor {
+            format: GBUFFER_TEXTURE_FORMAT,
+            usages: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING,
+            extents: texture_extents,
+            dimension: TextureDimension::D2,
+            mip_count: 1,
+            sampling_type: SamplingType::Nearest,
+        };
+
+        let position_texture =
+            SampledTexture::new(device, descriptor.clone(), "GBuffer position texture");
+        let normal_texture =
+            SampledTexture::new(device, descriptor.clone(), "GBuffer normal texture");
+        let albedo_and_specular_texture = SampledTexture::new(
+            device,
+            descriptor.clone(),
+            "GBuffer albedo and specular texture",
+        );
+        let metal_rough_ao =
+            SampledTexture::new(device, descriptor.clone(), "GBuffer metal+rough+ao texture");
+
+        let depth_texture = SampledTexture::create_depth_texture(
+            device,
+            texture_extents,
+            None,
+            SamplingType::Nearest,
+            "GBuffer depth texture",
+        );
+
+        GBufferTextures {
+            position: position_texture,
+            normal: normal_texture,
+            albedo_and_specular: albedo_and_specular_texture,
+            depth_texture,
+            metal_rough_ao,
         }
     }
 
+    fn create_gbuffer_bind_group(
+        device: &wgpu::Device,
+        textures: &GBufferTextures,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &device.create_bind_group_layout(&bind_group_layout_descriptors::GBUFFER),
+            entries: &[
+                textures.position.get_texture_bind_group_entry(0),
+                textures.position.get_sampler_bind_group_entry(1),
+                textures.normal.get_texture_bind_group_entry(2),
+                textures.normal.get_sampler_bind_group_entry(3),
+                textures.albedo_and_specular.get_texture_bind_group_entry(4),
+                textures.albedo_and_specular.get_sampler_bind_group_entry(5),
+                textures.metal_rough_ao.get_texture_bind_group_entry(6),
+                textures.metal_rough_ao.get_sampler_bind_group_entry(7),
+            ],
+            label: Some("GBuffer bind group"),
+        })
+    }
+
+    fn create_depth_bind_group(device: &Device, depth_texture: &SampledTexture) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &device.create_bind_group_layout(&bind_group_layout_descriptors::DEPTH_TEXTURE),
+            entries: &[
+                depth_texture.get_texture_bind_group_entry(0),
+                depth_texture.get_sampler_bind_group_entry(1),
+            ],
+            label: Some("Main frame depth bind group"),
+        })
+    }
+}
+
+impl GBufferGeometryRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
     pub fn add_renderable(
         &mut self,
         device: &Device,
@@ -121,8 +204,7 @@ impl GBufferGeometryRenderer {
         if let Some(pipeline_with_objects) = self.render_pipelines.get_mut(&gbuffer_render_params) {
             pipeline_with_objects.objects.insert(id);
         } else {
-            let pipeline =
-                Self::create_render_pipeline(device, &gbuffer_render_params, &self.textures)?;
+            let pipeline = Self::create_render_pipeline(device, &gbuffer_render_params)?;
             self.render_pipelines
                 .insert(gbuffer_render_params, PipelineWithObjects::new(pipeline));
         }
@@ -160,65 +242,9 @@ impl GBufferGeometryRenderer {
         }
     }
 
-    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
-        self.textures = Self::create_textures(device, width, height);
-        self.gbuffer_textures_bind_group = Self::create_gbuffer_bind_group(device, &self.textures);
-        self.depth_texture_bind_group =
-            Self::create_depth_bind_group(device, &self.textures.depth_texture);
-        self.width = width;
-        self.height = height;
-    }
-
-    fn create_textures(device: &wgpu::Device, width: u32, height: u32) -> GBufferTextures {
-        let texture_extents = Extent3d {
-            width,
-            height,
-            depth_or_array_layers: 1,
-        };
-        let descriptor = SampledTextureDescriptor {
-            format: GBUFFER_TEXTURE_FORMAT,
-            usages: TextureUsages::RENDER_ATTACHMENT
-                | TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::STORAGE_BINDING,
-            extents: texture_extents,
-            dimension: TextureDimension::D2,
-            mip_count: 1,
-            sampling_type: SamplingType::Nearest,
-        };
-
-        let position_texture =
-            SampledTexture::new(device, descriptor.clone(), "GBuffer position texture");
-        let normal_texture =
-            SampledTexture::new(device, descriptor.clone(), "GBuffer normal texture");
-        let albedo_and_specular_texture = SampledTexture::new(
-            device,
-            descriptor.clone(),
-            "GBuffer albedo and specular texture",
-        );
-        let metal_rough_ao =
-            SampledTexture::new(device, descriptor.clone(), "GBuffer metal+rough+ao texture");
-
-        let depth_texture = SampledTexture::create_depth_texture(
-            device,
-            texture_extents,
-            None,
-            SamplingType::Nearest,
-            "GBuffer depth texture",
-        );
-
-        GBufferTextures {
-            position: position_texture,
-            normal: normal_texture,
-            albedo_and_specular: albedo_and_specular_texture,
-            depth_texture,
-            metal_rough_ao,
-        }
-    }
-
     fn create_render_pipeline(
         device: &Device,
         rendering_params: &GBufferRenderingParams,
-        textures: &GBufferTextures,
     ) -> anyhow::Result<RenderPipeline> {
         let vertex_state = PipelineVertexState {
             entry_point: "vs_main",
@@ -246,12 +272,8 @@ impl GBufferGeometryRenderer {
 
         let fragment_state = PipelineFragmentState {
             entry_point: "fs_main",
-            color_targets: vec![
-                default_color_write_state(textures.position.texture.format()),
-                default_color_write_state(textures.normal.texture.format()),
-                default_color_write_state(textures.albedo_and_specular.texture.format()),
-                default_color_write_state(textures.metal_rough_ao.texture.format()),
-            ],
+            // The position, normal, albedo+specular and metal+rough+ao textures
+            color_targets: vec![default_color_write_state(GBUFFER_TEXTURE_FORMAT); 4],
         };
 
         let bgroup_layouts = match rendering_params.pbr_rendering_type {
@@ -300,43 +322,16 @@ impl GBufferGeometryRenderer {
         RenderPipeline::new(device, render_pipeline_descriptor)
     }
 
-    fn create_gbuffer_bind_group(
-        device: &wgpu::Device,
-        textures: &GBufferTextures,
-    ) -> wgpu::BindGroup {
-        device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &device.create_bind_group_layout(&bind_group_layout_descriptors::GBUFFER),
-            entries: &[
-                textures.position.get_texture_bind_group_entry(0),
-                textures.position.get_sampler_bind_group_entry(1),
-                textures.normal.get_texture_bind_group_entry(2),
-                textures.normal.get_sampler_bind_group_entry(3),
-                textures.albedo_and_specular.get_texture_bind_group_entry(4),
-                textures.albedo_and_specular.get_sampler_bind_group_entry(5),
-                textures.metal_rough_ao.get_texture_bind_group_entry(6),
-                textures.metal_rough_ao.get_sampler_bind_group_entry(7),
-            ],
-            label: Some("GBuffer bind group"),
-        })
-    }
-
-    fn create_depth_bind_group(device: &Device, depth_texture: &SampledTexture) -> wgpu::BindGroup {
-        device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &device.create_bind_group_layout(&bind_group_layout_descriptors::DEPTH_TEXTURE),
-            entries: &[
-                depth_texture.get_texture_bind_group_entry(0),
-                depth_texture.get_sampler_bind_group_entry(1),
-            ],
-            label: Some("Main frame depth bind group"),
-        })
-    }
-
-    pub fn begin_render<'a>(&'a self, encoder: &'a mut CommandEncoder) -> RenderPass<'a> {
+    pub fn begin_render<'a>(
+        &'a self,
+        encoder: &'a mut CommandEncoder,
+        targets: &'a GBufferTargets,
+    ) -> RenderPass<'a> {
         encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("GBuffer pass"),
             color_attachments: &[
                 Some(RenderPassColorAttachment {
-                    view: &self.textures.position.view,
+                    view: &targets.textures.position.view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(GBUFFER_CLEAR_COLOR),
@@ -344,7 +339,7 @@ impl GBufferGeometryRenderer {
                     },
                 }),
                 Some(RenderPassColorAttachment {
-                    view: &self.textures.normal.view,
+                    view: &targets.textures.normal.view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(GBUFFER_CLEAR_COLOR),
@@ -352,7 +347,7 @@ impl GBufferGeometryRenderer {
                     },
                 }),
                 Some(RenderPassColorAttachment {
-                    view: &self.textures.albedo_and_specular.view,
+                    view: &targets.textures.albedo_and_specular.view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(GBUFFER_CLEAR_COLOR),
@@ -360,7 +355,7 @@ impl GBufferGeometryRenderer {
                     },
                 }),
                 Some(RenderPassColorAttachment {
-                    view: &self.textures.metal_rough_ao.view,
+                    view: &targets.textures.metal_rough_ao.view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(GBUFFER_CLEAR_COLOR),
@@ -369,7 +364,7 @@ impl GBufferGeometryRenderer {
                 }),
             ],
             depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                view: &self.textures.depth_texture.view,
+                view: &targets.textures.depth_texture.view,
                 depth_ops: Some(wgpu::Operations {
                     load: wgpu::LoadOp::Clear(0.0),
                     store: wgpu::StoreOp::Store,
@@ -387,6 +382,7 @@ impl GBufferGeometryRenderer {
         renderables: T,
         camera_bind_group: &'a BindGroup,
         global_gpu_params_bind_group: &'a BindGroup,
+        draw_mode: &DrawMode<'a>,
     ) {
         for pipeline_with_items in self.render_pipelines.values() {
             let items_for_current_pipeline = renderables
@@ -398,6 +394,7 @@ impl GBufferGeometryRenderer {
                 &[camera_bind_group, global_gpu_params_bind_group],
                 items_for_current_pipeline,
                 1,
+                draw_mode,
             );
         }
     }
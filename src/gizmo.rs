@@ -6,7 +6,9 @@ use glam::{Quat, Vec3};
 use math_helpers::Line;
 
 use crate::{
+    camera::Camera,
     components::{RenderableComponent, SceneComponentType, TransformComponent},
+    lod::LodSettings,
     material::PbrMaterialDescriptor,
     model::{
         MeshDescriptor, ModelRenderingOptions, PbrParameters, PbrRenderingType, RenderingPass,
@@ -30,6 +32,11 @@ const Z_AXIS_COLOR: Vec3 = Vec3 {
     y: 0.0,
     z: 1.0,
 };
+const SCREEN_HANDLE_COLOR: Vec3 = Vec3 {
+    x: 0.8,
+    y: 0.8,
+    z: 0.8,
+};
 const HOVERED_GIZMO_COLOR: Vec3 = Vec3 {
     x: 0.9,
     y: 0.9,
@@ -37,10 +44,86 @@ const HOVERED_GIZMO_COLOR: Vec3 = Vec3 {
 };
 const GIZMO_DISTANCE_SCALE: f32 = 0.06;
 
+/// What the handles of the gizmo do with the selected objects
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    /// The meshes are along the Y axis, they are rotated to the other axes
+    fn get_handle_mesh_path(&self) -> &'static str {
+        match self {
+            GizmoMode::Translate => "./assets/models/arrow/arrow.obj",
+            GizmoMode::Rotate => "./assets/models/rotation_ring/rotation_ring.obj",
+            GizmoMode::Scale => "./assets/models/scale_handle/scale_handle.obj",
+        }
+    }
+}
+
+/// The axes of the gizmo are either the world axes or the axes of the active object
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum GizmoOrientation {
+    #[default]
+    World,
+    Local,
+}
+
+/// What a part of the gizmo moves the selected objects along
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GizmoHandle {
+    Axis(Vec3),
+    /// The plane is given by its normal
+    Plane(Vec3),
+    /// Moves in the plane facing the camera
+    Screen,
+}
+
+impl GizmoHandle {
+    /// The plane and screen handles are only shown when translating
+    fn get_mesh_path(&self, mode: GizmoMode) -> Option<&'static str> {
+        match (self, mode) {
+            (GizmoHandle::Axis(_), mode) => Some(mode.get_handle_mesh_path()),
+            (GizmoHandle::Plane(_), GizmoMode::Translate) => {
+                Some("./assets/models/plane_handle/plane_handle.obj")
+            }
+            (GizmoHandle::Screen, GizmoMode::Translate) => {
+                Some("./assets/models/screen_handle/screen_handle.obj")
+            }
+            _ => None,
+        }
+    }
+
+    fn rotated(&self, rotation: Quat) -> Self {
+        match self {
+            GizmoHandle::Axis(axis_vec) => GizmoHandle::Axis(rotation * *axis_vec),
+            GizmoHandle::Plane(normal) => GizmoHandle::Plane(rotation * *normal),
+            GizmoHandle::Screen => GizmoHandle::Screen,
+        }
+    }
+
+    fn get_color(&self) -> Vec3 {
+        match self {
+            GizmoHandle::Axis(axis_vec) | GizmoHandle::Plane(axis_vec) => {
+                get_color_for_axis(*axis_vec)
+            }
+            GizmoHandle::Screen => SCREEN_HANDLE_COLOR,
+        }
+    }
+}
+
 pub enum GizmoUpdateResult {
     Nothing,
     GizmoAddedWithPosition,
     GizmoSelectedWithAxis(Line),
+    /// A plane or the screen handle was selected, the plane goes through the gizmo position
+    GizmoSelectedWithPlane {
+        gizmo_position: Vec3,
+        handle: GizmoHandle,
+    },
     GizmoRemoved,
 }
 
@@ -49,21 +132,29 @@ enum GizmoAxis {
     DragX,
     DragY,
     DragZ,
+    DragXY,
+    DragXZ,
+    DragYZ,
+    DragScreen,
 }
 
 struct GizmoAxisDescription {
-    axis_vec: Vec3,
-    // What rotation do we need to get to this axis from the default arrow, which is Y up
+    handle: GizmoHandle,
+    // What rotation do we need to get to this axis from the default arrow, which is Y up. The plane handles are in the
+    // XZ plane by default
     rotation: Quat,
     material: PbrMaterialDescriptor,
 }
 
 pub struct Gizmo {
     pub selected_object_id: Option<u32>,
+    mode: GizmoMode,
+    /// The rotation of the axes of the gizmo, see `GizmoOrientation`
+    rotation: Quat,
     pub hovered_gizmo_part_id: Option<u32>,
     pub gizmo_position: Option<Vec3>,
     gizmo_scale: f32,
-    gizmo_parts_drawn: HashMap<u32, Vec3>,
+    gizmo_parts_drawn: HashMap<u32, GizmoHandle>,
     gizmo_part_descriptions: HashMap<GizmoAxis, GizmoAxisDescription>,
 }
 
@@ -82,7 +173,7 @@ impl Gizmo {
             (
                 GizmoAxis::DragX,
                 GizmoAxisDescription {
-                    axis_vec: Vec3::X,
+                    handle: GizmoHandle::Axis(Vec3::X),
                     rotation: Quat::from_axis_angle(Vec3::Z, -f32::consts::FRAC_PI_2),
                     material: PbrMaterialDescriptor::from_color(get_color_for_axis(Vec3::X)),
                 },
@@ -90,7 +181,7 @@ impl Gizmo {
             (
                 GizmoAxis::DragY,
                 GizmoAxisDescription {
-                    axis_vec: Vec3::Y,
+                    handle: GizmoHandle::Axis(Vec3::Y),
                     rotation: Quat::IDENTITY,
                     material: PbrMaterialDescriptor::from_color(get_color_for_axis(Vec3::Y)),
                 },
@@ -98,15 +189,49 @@ impl Gizmo {
             (
                 GizmoAxis::DragZ,
                 GizmoAxisDescription {
-                    axis_vec: Vec3::Z,
+                    handle: GizmoHandle::Axis(Vec3::Z),
                     rotation: Quat::from_axis_angle(Vec3::X, f32::consts::FRAC_PI_2),
                     material: PbrMaterialDescriptor::from_color(get_color_for_axis(Vec3::Z)),
                 },
             ),
+            (
+                GizmoAxis::DragXY,
+                GizmoAxisDescription {
+                    handle: GizmoHandle::Plane(Vec3::Z),
+                    rotation: Quat::from_axis_angle(Vec3::X, -f32::consts::FRAC_PI_2),
+                    material: PbrMaterialDescriptor::from_color(get_color_for_axis(Vec3::Z)),
+                },
+            ),
+            (
+                GizmoAxis::DragXZ,
+                GizmoAxisDescription {
+                    handle: GizmoHandle::Plane(Vec3::Y),
+                    rotation: Quat::IDENTITY,
+                    material: PbrMaterialDescriptor::from_color(get_color_for_axis(Vec3::Y)),
+                },
+            ),
+            (
+                GizmoAxis::DragYZ,
+                GizmoAxisDescription {
+                    handle: GizmoHandle::Plane(Vec3::X),
+                    rotation: Quat::from_axis_angle(Vec3::Z, f32::consts::FRAC_PI_2),
+                    material: PbrMaterialDescriptor::from_color(get_color_for_axis(Vec3::X)),
+                },
+            ),
+            (
+                GizmoAxis::DragScreen,
+                GizmoAxisDescription {
+                    handle: GizmoHandle::Screen,
+                    rotation: Quat::IDENTITY,
+                    material: PbrMaterialDescriptor::from_color(SCREEN_HANDLE_COLOR),
+                },
+            ),
         ]);
 
         Self {
             selected_object_id: None,
+            mode: GizmoMode::default(),
+            rotation: Quat::IDENTITY,
             gizmo_parts_drawn: HashMap::new(),
             gizmo_position: None,
             hovered_gizmo_part_id: None,
@@ -115,40 +240,77 @@ impl Gizmo {
         }
     }
 
-    pub fn get_axis_with_id(&self, id: u32) -> Option<&Vec3> {
+    pub fn get_mode(&self) -> GizmoMode {
+        self.mode
+    }
+
+    /// The handles of the gizmo are replaced by the handles of the new mode
+    pub fn set_mode(&mut self, mode: GizmoMode, world: &mut World) {
+        if self.mode == mode {
+            return;
+        }
+
+        self.mode = mode;
+        if let Some(selected_object_id) = self.selected_object_id {
+            let gizmo_position = self.gizmo_position;
+            self.update_with_new_object_id(None, world);
+            self.update_with_new_object_id(Some(selected_object_id), world);
+            if let Some(gizmo_position) = gizmo_position {
+                self.update_position(gizmo_position, world);
+            }
+        }
+    }
+
+    pub fn get_rotation(&self) -> Quat {
+        self.rotation
+    }
+
+    /// Rotates the axes of the gizmo, the handles are dragged along the rotated axes
+    pub fn set_rotation(&mut self, rotation: Quat, world: &mut World) {
+        self.rotation = rotation;
+        for (id, handle) in &self.gizmo_parts_drawn {
+            let description = self
+                .gizmo_part_descriptions
+                .values()
+                .find(|description| description.handle == *handle);
+            if let (Some(object), Some(description)) = (world.get_world_object_mut(id), description)
+            {
+                object
+                    .transform
+                    .set_rotation(rotation * description.rotation);
+            }
+        }
+    }
+
+    pub fn get_handle_with_id(&self, id: u32) -> Option<&GizmoHandle> {
         self.gizmo_parts_drawn.get(&id)
     }
 
-    fn calculate_gizmo_scale(camera_position: Vec3, selected_object_position: Vec3) -> f32 {
-        camera_position.distance(selected_object_position) * GIZMO_DISTANCE_SCALE
+    /// The gizmo keeps its size on the screen, with orthographic projection it's scaled by the zoom instead of the
+    /// distance
+    fn calculate_gizmo_scale(camera: &Camera, gizmo_position: Vec3) -> f32 {
+        camera.get_perspective_distance(camera.get_position().distance(gizmo_position))
+            * GIZMO_DISTANCE_SCALE
     }
 
+    /// The gizmo is scaled by its own position, it's not necessarily at the selected object (eg. it's at the center of
+    /// the selected objects)
     pub fn update(&mut self, world: &mut World) {
-        if let Some(selected_object_id) = self.selected_object_id {
-            let maybe_selected_object_position =
-                if let Some(selected_object) = world.get_world_object(&selected_object_id) {
-                    Some(selected_object.transform.get_position())
-                } else {
-                    None
-                };
-
-            if let Some(selected_object_position) = maybe_selected_object_position {
-                let camera_position = world.camera_controller.camera.get_position();
-                let new_gizmo_scale =
-                    Self::calculate_gizmo_scale(selected_object_position, camera_position);
+        if let Some(gizmo_position) = self.gizmo_position {
+            let new_gizmo_scale =
+                Self::calculate_gizmo_scale(&world.camera_controller.camera, gizmo_position);
 
-                if self.gizmo_scale == new_gizmo_scale {
-                    return;
-                } else {
-                    self.gizmo_scale = new_gizmo_scale;
-                }
+            if self.gizmo_scale == new_gizmo_scale {
+                return;
+            } else {
+                self.gizmo_scale = new_gizmo_scale;
+            }
 
-                for (gizmo_object_id, _axis) in &self.gizmo_parts_drawn {
-                    if let Some(gizmo_object) = world.get_world_object_mut(gizmo_object_id) {
-                        gizmo_object
-                            .transform
-                            .set_scale(Vec3::splat(new_gizmo_scale));
-                    }
+            for (gizmo_object_id, _handle) in &self.gizmo_parts_drawn {
+                if let Some(gizmo_object) = world.get_world_object_mut(gizmo_object_id) {
+                    gizmo_object
+                        .transform
+                        .set_scale(Vec3::splat(new_gizmo_scale));
                 }
             }
         }
@@ -157,9 +319,9 @@ impl Gizmo {
     fn restore_hovered_gizmo_material_if_any(&self, world: &mut World) {
         if let Some(hovered_gizmo_part_id) = self.hovered_gizmo_part_id {
             if let Some(object) = world.get_world_object_mut(&hovered_gizmo_part_id) {
-                if let Some(axis) = self.gizmo_parts_drawn.get(&hovered_gizmo_part_id) {
-                    if let Some(renderable) = object.get_renderable_component_mut() {
-                        let color = get_color_for_axis(*axis);
+                if let Some(handle) = self.gizmo_parts_drawn.get(&hovered_gizmo_part_id) {
+                    if let Some(renderable) = object.get_component_mut::<RenderableComponent>() {
+                        let color = handle.get_color();
                         renderable.update_material(PbrMaterialDescriptor::Flat(
                             PbrParameters::new(color, 1.0, 0.0),
                         ));
@@ -184,7 +346,7 @@ impl Gizmo {
             if self.gizmo_parts_drawn.contains_key(&hovered_gizmo_part_id) {
                 self.hovered_gizmo_part_id = hovered_object_id;
                 if let Some(object) = world.get_world_object_mut(&hovered_gizmo_part_id) {
-                    if let Some(renderable) = object.get_renderable_component_mut() {
+                    if let Some(renderable) = object.get_component_mut::<RenderableComponent>() {
                         renderable.update_material(PbrMaterialDescriptor::from_color(
                             HOVERED_GIZMO_COLOR,
                         ));
@@ -231,40 +393,50 @@ impl Gizmo {
         // Add new gizmo
         match new_selected_object_id {
             Some(object_id) => {
-                if let Some(axis) = self.get_axis_with_id(object_id) {
+                if let Some(handle) = self.get_handle_with_id(object_id) {
                     if let Some(gizmo_position) = self.gizmo_position {
                         // Gizmo was selected, don't show new gizmo
-                        return GizmoUpdateResult::GizmoSelectedWithAxis(Line {
-                            position: gizmo_position,
-                            direction: *axis,
-                        });
+                        return match handle.rotated(self.rotation) {
+                            GizmoHandle::Axis(axis_vec) => {
+                                GizmoUpdateResult::GizmoSelectedWithAxis(Line {
+                                    position: gizmo_position,
+                                    direction: axis_vec,
+                                })
+                            }
+                            handle => GizmoUpdateResult::GizmoSelectedWithPlane {
+                                gizmo_position,
+                                handle,
+                            },
+                        };
                     } else {
                         log::warn!("This should not happen! When selecting a gizmo, we should have a valid position");
                         self.selected_object_id = None;
                         GizmoUpdateResult::Nothing
                     }
                 } else {
-                    if let Some(object) = world.get_world_object(&object_id) {
+                    if let Some(selected_object_transform) = world.get_world_transform(&object_id) {
                         self.selected_object_id = Some(object_id);
-                        let selected_object_transform = object.transform;
-                        let arrow_source = MeshDescriptor::FromFile(
-                            PathBuf::from_str("./assets/models/arrow/arrow.obj").unwrap(),
-                        );
-
                         self.gizmo_position = Some(selected_object_transform.get_position());
 
                         for (_axis, gizmo_description) in &self.gizmo_part_descriptions {
+                            let Some(mesh_path) = gizmo_description.handle.get_mesh_path(self.mode)
+                            else {
+                                continue;
+                            };
+                            let handle_source =
+                                MeshDescriptor::FromFile(PathBuf::from_str(mesh_path).unwrap());
+
                             let gizmo_transform = TransformComponent::new(
                                 selected_object_transform.get_position(),
                                 Vec3::splat(Self::calculate_gizmo_scale(
-                                    world.camera_controller.camera.get_position(),
+                                    &world.camera_controller.camera,
                                     selected_object_transform.get_position(),
                                 )),
-                                gizmo_description.rotation,
+                                self.rotation * gizmo_description.rotation,
                             );
 
-                            let renderable_component = RenderableComponent::new(
-                                arrow_source.clone(),
+                            let mut renderable_component = RenderableComponent::new(
+                                handle_source,
                                 gizmo_description.material.clone(),
                                 ModelRenderingOptions {
                                     pass: RenderingPass::ForceForwardAfterDeferred,
@@ -274,6 +446,8 @@ impl Gizmo {
                                 },
                                 true,
                             );
+                            // The gizmo has the same size on the screen from any distance
+                            renderable_component.lod_settings = LodSettings::disabled();
 
                             let world_object = WorldObject::new(
                                 vec![SceneComponentType::Renderable(renderable_component)],
@@ -282,7 +456,7 @@ impl Gizmo {
 
                             let gizmo_id = world.add_world_object(world_object);
                             self.gizmo_parts_drawn
-                                .insert(gizmo_id, gizmo_description.axis_vec);
+                                .insert(gizmo_id, gizmo_description.handle);
                         }
 
                         GizmoUpdateResult::GizmoAddedWithPosition
@@ -305,7 +479,7 @@ impl Gizmo {
 
     pub fn update_position(&mut self, new_position: Vec3, world: &mut World) {
         self.gizmo_position = Some(new_position);
-        for (id, _axis) in &self.gizmo_parts_drawn {
+        for (id, _handle) in &self.gizmo_parts_drawn {
             if let Some(object) = world.get_world_object_mut(id) {
                 object.transform.set_position(new_position);
             }
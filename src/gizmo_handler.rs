@@ -1,20 +1,25 @@
-use glam::{Vec2, Vec3};
+use std::collections::HashSet;
+
+use glam::{Quat, Vec2, Vec3};
 use winit::{
     dpi::PhysicalPosition,
     event::{ElementState, MouseButton, WindowEvent},
-    keyboard::{KeyCode, PhysicalKey},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
 };
 
-use math_helpers::Line;
+use math_helpers::{Line, Plane};
 
 use crate::{
-    camera_controller::CameraController,
-    gizmo::{Gizmo, GizmoUpdateResult},
+    components::TransformComponent,
+    gizmo::{Gizmo, GizmoHandle, GizmoMode, GizmoOrientation, GizmoUpdateResult},
     object_picker::ObjectPickManager,
     world::World,
+    world_renderer::WorldRenderer,
 };
 
 const GIZMO_DRAG_SQUARAED_DISTANCE_THRESHOLD: f32 = 25.0;
+/// The scale handles can't flip or collapse the objects
+const MIN_GIZMO_SCALE_FACTOR: f32 = 0.01;
 
 fn squared_distance(pos1: &PhysicalPosition<f64>, pos2: &PhysicalPosition<f64>) -> f32 {
     let pos1 = Vec2::new(pos1.x as f32, pos1.y as f32);
@@ -23,24 +28,102 @@ fn squared_distance(pos1: &PhysicalPosition<f64>, pos2: &PhysicalPosition<f64>)
     pos1.distance_squared(pos2)
 }
 
-fn get_world_position_from_screen_position(
-    camera_controller: &CameraController,
-    screen_position: &PhysicalPosition<f64>,
-) -> Vec3 {
-    camera_controller.deproject_screen_to_world(Vec3::new(
-        screen_position.x as f32,
-        screen_position.y as f32,
-        0.5,
-    ))
+/// Where the gizmo is placed when several objects are selected, the selected objects are moved together with it
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum SelectionPivot {
+    #[default]
+    SelectionCenter,
+    ActiveObject,
+}
+
+/// How the gizmo drags are snapped while Ctrl is held
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SnapSettings {
+    /// The size of the cells of the grid the translation snaps to
+    pub grid_size: f32,
+    /// In degrees
+    pub angle_increment: f32,
+    /// The scale is changed in multiples of this
+    pub scale_step: f32,
+    /// The translation places the objects on the surface under the cursor instead of the grid, their up axis is
+    /// aligned to the normal of the surface
+    pub snap_to_surface: bool,
+}
+
+impl Default for SnapSettings {
+    fn default() -> Self {
+        Self {
+            grid_size: 1.0,
+            angle_increment: 15.0,
+            scale_step: 0.1,
+            snap_to_surface: false,
+        }
+    }
+}
+
+fn snap_value(value: f32, step: f32) -> f32 {
+    if step > 0.0 {
+        (value / step).round() * step
+    } else {
+        value
+    }
+}
+
+fn snap_to_grid(position: Vec3, grid_size: f32) -> Vec3 {
+    Vec3::new(
+        snap_value(position.x, grid_size),
+        snap_value(position.y, grid_size),
+        snap_value(position.z, grid_size),
+    )
+}
+
+fn get_camera_ray(world: &World, screen_position: &PhysicalPosition<f64>) -> Line {
+    world.camera_controller.get_screen_ray(screen_position)
+}
+
+/// The component of the scale that belongs to the axis
+fn get_axis_index(axis: Vec3) -> usize {
+    let axis = axis.abs();
+    if axis.x >= axis.y && axis.x >= axis.z {
+        0
+    } else if axis.y >= axis.z {
+        1
+    } else {
+        2
+    }
+}
+
+/// What dragging the pressed handle does with the selected objects
+#[derive(Debug, Copy, Clone)]
+enum GizmoDragKind {
+    Translate {
+        /// Represents the starting point of the gizmo interaction and the axis of it
+        gizmo_movement_axis: Line,
+        /// Contains the difference between the interaction start point and the gizmo position
+        /// This is needed, so we can calculate the final gizmo position from the interaction point in each frame
+        gizmo_interaction_and_object_position_difference: Vec3,
+    },
+    /// The gizmo follows the point where the camera ray crosses the plane
+    TranslateInPlane {
+        plane: Plane,
+        gizmo_interaction_and_object_position_difference: Vec3,
+    },
+    /// The cursor is followed along the tangent of the ring at the pressed point, the distance travelled along the
+    /// tangent is turned into the angle of the rotation
+    Rotate { tangent_line: Line, radius: f32 },
+    /// The objects are scaled by the ratio of the distances of the cursor from the gizmo along the axis, now and when
+    /// the handle was pressed
+    Scale { start_distance: f32 },
 }
 
 #[derive(Debug, Copy, Clone)]
 struct GizmoMoveInfo {
-    /// Represents the starting point of the gizmo interaction and the axis of it
-    gizmo_movement_axis: Line,
-    /// Contains the difference between the interaction start point and the object position
-    /// This is needed, so we can calculate the final object position from the gizmo position in each frame
-    gizmo_interaction_and_object_position_difference: Vec3,
+    /// The world space axis of the pressed handle, or the normal of its plane
+    axis: Vec3,
+    /// The selected objects are moved, rotated and scaled around this point
+    gizmo_start_position: Vec3,
+    kind: GizmoDragKind,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -48,12 +131,29 @@ enum GizmoInteractionState {
     Idle,
     WaitingForThresholdAfterPress(PhysicalPosition<f64>, GizmoMoveInfo),
     Moving(GizmoMoveInfo),
+    /// The button was pressed outside of the gizmo, dragging selects the objects in a rectangle
+    WaitingForSelectionRectangle(PhysicalPosition<f64>),
+    SelectingRectangle(PhysicalPosition<f64>),
 }
 
 pub struct GizmoHandler {
     gizmo: Gizmo,
     interaction_state: GizmoInteractionState,
     cursor_position: Option<PhysicalPosition<f64>>,
+    modifiers: ModifiersState,
+
+    /// The last one is the active object, the gizmo is attached to it (see `SelectionPivot`)
+    selected_object_ids: Vec<u32>,
+    pivot: SelectionPivot,
+    /// The world transforms of the dragged objects when the drag started
+    drag_start_transforms: Vec<(u32, TransformComponent)>,
+    snap_settings: SnapSettings,
+    orientation: GizmoOrientation,
+    /// The selectable object under the cursor
+    hovered_object_id: Option<u32>,
+    /// The selection, the selection rectangle or the settings of the gizmo changed since the last
+    /// `take_selection_change`
+    is_selection_changed: bool,
 }
 
 impl GizmoHandler {
@@ -62,14 +162,36 @@ impl GizmoHandler {
             gizmo: Gizmo::new(),
             interaction_state: GizmoInteractionState::Idle,
             cursor_position: None,
+            modifiers: ModifiersState::empty(),
+            selected_object_ids: vec![],
+            pivot: SelectionPivot::default(),
+            drag_start_transforms: vec![],
+            snap_settings: SnapSettings::default(),
+            orientation: GizmoOrientation::default(),
+            hovered_object_id: None,
+            is_selection_changed: false,
         }
     }
 
     pub fn remove_object_selection(&mut self, world: &mut World) {
-        self.gizmo.update_with_new_object_id(None, world);
+        self.select_objects(&[], world);
     }
 
     pub fn update(&mut self, world: &mut World) {
+        // The selected objects might have been moved by something else than the gizmo (eg. undo)
+        if !matches!(self.interaction_state, GizmoInteractionState::Moving(_)) {
+            if let Some(pivot_position) = self.get_pivot_position(world) {
+                if self.gizmo.gizmo_position != Some(pivot_position) {
+                    self.gizmo.update_position(pivot_position, world);
+                }
+            }
+
+            let gizmo_rotation = self.get_gizmo_rotation(world);
+            if self.gizmo.get_rotation() != gizmo_rotation {
+                self.gizmo.set_rotation(gizmo_rotation, world);
+            }
+        }
+
         self.gizmo.update(world);
     }
 
@@ -78,6 +200,7 @@ impl GizmoHandler {
         event: &WindowEvent,
         world: &mut World,
         object_picker: &ObjectPickManager,
+        world_renderer: &WorldRenderer,
     ) -> bool {
         match event {
             WindowEvent::CursorMoved { position, .. } => {
@@ -96,17 +219,31 @@ impl GizmoHandler {
                         {
                             self.interaction_state = GizmoInteractionState::Moving(gizmo_move_info);
 
-                            self.perform_move(world, position, &gizmo_move_info);
+                            self.perform_move(world, world_renderer, position, &gizmo_move_info);
                         }
                     }
                     GizmoInteractionState::Moving(gizmo_move_info) => {
-                        self.perform_move(world, position, &gizmo_move_info);
+                        self.perform_move(world, world_renderer, position, &gizmo_move_info);
+                    }
+                    GizmoInteractionState::WaitingForSelectionRectangle(start_position) => {
+                        if squared_distance(&start_position, position)
+                            >= GIZMO_DRAG_SQUARAED_DISTANCE_THRESHOLD
+                        {
+                            self.interaction_state =
+                                GizmoInteractionState::SelectingRectangle(start_position);
+                            self.is_selection_changed = true;
+                        }
+                    }
+                    GizmoInteractionState::SelectingRectangle(_start_position) => {
+                        self.is_selection_changed = true;
                     }
                     GizmoInteractionState::Idle => {
                         if let Some(pos) = self.cursor_position {
                             let hovered_object_id =
                                 object_picker.get_object_id_at(pos.x as u32, pos.y as u32);
                             self.gizmo.set_hovered_object_id(hovered_object_id, world);
+                            self.hovered_object_id = hovered_object_id
+                                .filter(|object_id| Self::is_selectable(*object_id, world));
                         }
                     }
                 }
@@ -114,51 +251,50 @@ impl GizmoHandler {
                 // Pretend we didn't handle this event, so others will get it as well and can update the position
                 false
             }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                false
+            }
             WindowEvent::MouseInput { state, button, .. } => match button {
                 MouseButton::Left => {
                     match state {
                         ElementState::Pressed => {
                             if let Some(pos) = self.cursor_position {
-                                let selected_object_id =
+                                let picked_object_id =
                                     object_picker.get_object_id_at(pos.x as u32, pos.y as u32);
 
-                                match self
-                                    .gizmo
-                                    .update_with_new_object_id(selected_object_id, world)
-                                {
-                                    GizmoUpdateResult::GizmoSelectedWithAxis(gizmo_axis_line) => {
-                                        if let Some(cursor_position) = self.cursor_position {
-                                            let position_on_camera_ray =
-                                                get_world_position_from_screen_position(
-                                                    &world.camera_controller,
-                                                    &cursor_position,
-                                                );
-                                            let camera_line = Line {
-                                                position: world.camera_controller.camera.position,
-                                                direction: (position_on_camera_ray
-                                                    - world.camera_controller.camera.position)
-                                                    .normalize(),
-                                            };
-
-                                            let (
-                                                gizmo_axis_line_closest_point,
-                                                _camera_line_closest_point,
-                                            ) = gizmo_axis_line.distance(&camera_line);
-
-                                            self.interaction_state =
-                                        GizmoInteractionState::WaitingForThresholdAfterPress(
-                                            self.cursor_position.unwrap(),
-                                            GizmoMoveInfo { gizmo_movement_axis: Line{position: gizmo_axis_line_closest_point, direction: gizmo_axis_line.direction }, gizmo_interaction_and_object_position_difference:  self.gizmo.gizmo_position.unwrap() - gizmo_axis_line_closest_point }
-                                        );
-                                        }
+                                match picked_object_id {
+                                    Some(picked_object_id)
+                                        if self
+                                            .gizmo
+                                            .get_handle_with_id(picked_object_id)
+                                            .is_some() =>
+                                    {
+                                        self.start_gizmo_interaction(picked_object_id, world)
+                                    }
+                                    _ => {
+                                        self.click_object(picked_object_id, world);
+                                        self.interaction_state =
+                                            GizmoInteractionState::WaitingForSelectionRectangle(
+                                                pos,
+                                            );
                                     }
-                                    _ => {}
                                 }
                             } else {
-                                self.gizmo.update_with_new_object_id(None, world);
+                                self.select_objects(&[], world);
                             }
                         }
                         ElementState::Released => {
+                            if let GizmoInteractionState::SelectingRectangle(start_position) =
+                                self.interaction_state
+                            {
+                                self.select_objects_in_rectangle(
+                                    start_position,
+                                    world,
+                                    object_picker,
+                                );
+                                self.is_selection_changed = true;
+                            }
                             self.interaction_state = GizmoInteractionState::Idle;
                         }
                     }
@@ -169,8 +305,9 @@ impl GizmoHandler {
             },
             WindowEvent::KeyboardInput { event, .. } => {
                 if let PhysicalKey::Code(KeyCode::Escape) = event.physical_key {
-                    let result = self.gizmo.update_with_new_object_id(None, world);
-                    matches!(result, GizmoUpdateResult::GizmoRemoved)
+                    let had_selection = !self.selected_object_ids.is_empty();
+                    self.select_objects(&[], world);
+                    had_selection
                 } else {
                     false
                 }
@@ -179,39 +316,529 @@ impl GizmoHandler {
         }
     }
 
+    fn start_gizmo_interaction(&mut self, gizmo_part_id: u32, world: &mut World) {
+        let Some(cursor_position) = self.cursor_position else {
+            return;
+        };
+        let camera_ray = get_camera_ray(world, &cursor_position);
+
+        let (gizmo_position, axis, drag_kind) = match self
+            .gizmo
+            .update_with_new_object_id(Some(gizmo_part_id), world)
+        {
+            GizmoUpdateResult::GizmoSelectedWithAxis(gizmo_axis_line) => (
+                gizmo_axis_line.position,
+                gizmo_axis_line.direction,
+                self.get_axis_drag_kind(&gizmo_axis_line, &camera_ray),
+            ),
+            GizmoUpdateResult::GizmoSelectedWithPlane {
+                gizmo_position,
+                handle,
+            } => {
+                let normal = match handle {
+                    GizmoHandle::Plane(normal) => normal,
+                    _ => world.camera_controller.camera.get_forward(),
+                };
+                let plane = Plane::from_point_and_normal(gizmo_position, normal);
+                let drag_kind = camera_ray.intersect_plane(&plane).map(|pressed_position| {
+                    GizmoDragKind::TranslateInPlane {
+                        plane,
+                        gizmo_interaction_and_object_position_difference: gizmo_position
+                            - pressed_position,
+                    }
+                });
+
+                (gizmo_position, normal, drag_kind)
+            }
+            _ => return,
+        };
+        // Eg. the ring or the plane is seen from the side
+        let Some(drag_kind) = drag_kind else {
+            return;
+        };
+
+        self.drag_start_transforms = self
+            .get_top_level_selected_object_ids(world)
+            .into_iter()
+            .filter_map(|object_id| {
+                world
+                    .get_world_transform(&object_id)
+                    .map(|transform| (object_id, transform))
+            })
+            .collect();
+        self.interaction_state = GizmoInteractionState::WaitingForThresholdAfterPress(
+            cursor_position,
+            GizmoMoveInfo {
+                axis,
+                gizmo_start_position: gizmo_position,
+                kind: drag_kind,
+            },
+        );
+    }
+
+    fn get_axis_drag_kind(
+        &self,
+        gizmo_axis_line: &Line,
+        camera_ray: &Line,
+    ) -> Option<GizmoDragKind> {
+        let gizmo_position = gizmo_axis_line.position;
+        let axis = gizmo_axis_line.direction;
+
+        match self.gizmo.get_mode() {
+            GizmoMode::Translate => {
+                let (gizmo_axis_line_closest_point, _camera_line_closest_point) =
+                    gizmo_axis_line.distance(camera_ray);
+
+                Some(GizmoDragKind::Translate {
+                    gizmo_movement_axis: Line {
+                        position: gizmo_axis_line_closest_point,
+                        direction: axis,
+                    },
+                    gizmo_interaction_and_object_position_difference: gizmo_position
+                        - gizmo_axis_line_closest_point,
+                })
+            }
+            GizmoMode::Rotate => camera_ray
+                .intersect_plane(&Plane::from_point_and_normal(gizmo_position, axis))
+                .and_then(|pressed_position| {
+                    let radius_vector = pressed_position - gizmo_position;
+                    let radius = radius_vector.length();
+
+                    (radius > f32::EPSILON).then(|| GizmoDragKind::Rotate {
+                        tangent_line: Line {
+                            position: pressed_position,
+                            direction: axis.cross(radius_vector / radius),
+                        },
+                        radius,
+                    })
+                }),
+            GizmoMode::Scale => {
+                let (gizmo_axis_line_closest_point, _camera_line_closest_point) =
+                    gizmo_axis_line.distance(camera_ray);
+                let start_distance = (gizmo_axis_line_closest_point - gizmo_position).dot(axis);
+
+                (start_distance.abs() > f32::EPSILON)
+                    .then_some(GizmoDragKind::Scale { start_distance })
+            }
+        }
+    }
+
+    /// Without Shift the clicked object becomes the only selected one, with Shift it's added to or removed from the
+    /// selection
+    fn click_object(&mut self, clicked_object_id: Option<u32>, world: &mut World) {
+        let clicked_object_id =
+            clicked_object_id.filter(|object_id| Self::is_selectable(*object_id, world));
+
+        if !self.modifiers.shift_key() {
+            self.select_objects(clicked_object_id.as_slice(), world);
+            return;
+        }
+
+        if let Some(clicked_object_id) = clicked_object_id {
+            let mut selected_object_ids = self.selected_object_ids.clone();
+            if selected_object_ids.contains(&clicked_object_id) {
+                selected_object_ids.retain(|object_id| *object_id != clicked_object_id);
+            } else {
+                selected_object_ids.push(clicked_object_id);
+            }
+            self.select_objects(&selected_object_ids, world);
+        }
+    }
+
+    fn select_objects_in_rectangle(
+        &mut self,
+        start_position: PhysicalPosition<f64>,
+        world: &mut World,
+        object_picker: &ObjectPickManager,
+    ) {
+        let Some(end_position) = self.cursor_position else {
+            return;
+        };
+
+        let mut object_ids_in_rectangle = object_picker
+            .get_object_ids_in_rectangle(
+                (start_position.x as u32, start_position.y as u32),
+                (end_position.x as u32, end_position.y as u32),
+            )
+            .into_iter()
+            .filter(|object_id| Self::is_selectable(*object_id, world))
+            .collect::<Vec<_>>();
+        // The order of the rectangle doesn't mean anything, but the selection should be the same every time
+        object_ids_in_rectangle.sort();
+
+        let mut selected_object_ids = if self.modifiers.shift_key() {
+            self.selected_object_ids.clone()
+        } else {
+            vec![]
+        };
+        for object_id in object_ids_in_rectangle {
+            if !selected_object_ids.contains(&object_id) {
+                selected_object_ids.push(object_id);
+            }
+        }
+        self.select_objects(&selected_object_ids, world);
+    }
+
+    /// The gizmo (and every other transient object) can't be selected
+    fn is_selectable(object_id: u32, world: &World) -> bool {
+        world
+            .get_world_object(&object_id)
+            .is_some_and(|world_object| !world_object.is_transient())
+    }
+
+    /// The selected objects whose ancestors are not selected. Moving these moves every selected object
+    pub fn get_top_level_selected_object_ids(&self, world: &World) -> Vec<u32> {
+        let selected_object_ids = self
+            .selected_object_ids
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+
+        self.selected_object_ids
+            .iter()
+            .copied()
+            .filter(|object_id| !world.has_ancestor_in(*object_id, &selected_object_ids))
+            .collect()
+    }
+
+    fn get_pivot_position(&self, world: &World) -> Option<Vec3> {
+        let selected_object_positions = self
+            .selected_object_ids
+            .iter()
+            .filter_map(|object_id| world.get_world_transform(object_id))
+            .map(|transform| transform.get_position())
+            .collect::<Vec<_>>();
+
+        match self.pivot {
+            SelectionPivot::SelectionCenter if !selected_object_positions.is_empty() => Some(
+                selected_object_positions.iter().sum::<Vec3>()
+                    / selected_object_positions.len() as f32,
+            ),
+            _ => selected_object_positions.last().copied(),
+        }
+    }
+
     fn perform_move(
         &mut self,
         world: &mut World,
+        world_renderer: &WorldRenderer,
         screen_position: &PhysicalPosition<f64>,
         gizmo_move_info: &GizmoMoveInfo,
     ) {
-        let camera_ray_world_position =
-            get_world_position_from_screen_position(&world.camera_controller, screen_position);
-        let camera_ray_direction =
-            camera_ray_world_position - world.camera_controller.camera.position;
-        let camera_ray = Line {
-            position: world.camera_controller.camera.position,
-            direction: camera_ray_direction.normalize(),
+        let camera_ray = get_camera_ray(world, screen_position);
+        let gizmo_start_position = gizmo_move_info.gizmo_start_position;
+        let is_snapping = self.modifiers.contains(ModifiersState::CONTROL);
+
+        if is_snapping
+            && self.snap_settings.snap_to_surface
+            && matches!(
+                gizmo_move_info.kind,
+                GizmoDragKind::Translate { .. } | GizmoDragKind::TranslateInPlane { .. }
+            )
+        {
+            self.place_selection_on_surface(
+                world,
+                world_renderer,
+                &camera_ray,
+                gizmo_start_position,
+            );
+            return;
+        }
+
+        // The world transforms are only updated at the end of the frame, so the objects are transformed from where
+        // they were when the drag started
+        match gizmo_move_info.kind {
+            GizmoDragKind::Translate {
+                gizmo_movement_axis,
+                gizmo_interaction_and_object_position_difference,
+            } => {
+                let (gizmo_axis_point, _camera_axis_point) =
+                    gizmo_movement_axis.distance(&camera_ray);
+                // The camera ray is parallel to the axis
+                if !gizmo_axis_point.is_finite() {
+                    return;
+                }
+
+                let mut new_gizmo_position =
+                    gizmo_axis_point + gizmo_interaction_and_object_position_difference;
+                if is_snapping {
+                    // Only the coordinate along the axis is snapped, the gizmo stays on the axis
+                    let snap_offset =
+                        snap_to_grid(new_gizmo_position, self.snap_settings.grid_size)
+                            - new_gizmo_position;
+                    new_gizmo_position +=
+                        snap_offset.dot(gizmo_move_info.axis) * gizmo_move_info.axis;
+                }
+
+                self.translate_selection(world, new_gizmo_position, gizmo_start_position);
+            }
+            GizmoDragKind::TranslateInPlane {
+                plane,
+                gizmo_interaction_and_object_position_difference,
+            } => {
+                // The plane is seen from the side or it's behind the camera
+                let Some(plane_point) = camera_ray
+                    .intersect_plane(&plane)
+                    .filter(|point| (*point - camera_ray.position).dot(camera_ray.direction) > 0.0)
+                else {
+                    return;
+                };
+
+                let mut new_gizmo_position =
+                    plane_point + gizmo_interaction_and_object_position_difference;
+                if is_snapping {
+                    // The gizmo stays in the plane
+                    let snap_offset =
+                        snap_to_grid(new_gizmo_position, self.snap_settings.grid_size)
+                            - new_gizmo_position;
+                    new_gizmo_position +=
+                        snap_offset - snap_offset.dot(plane.normal) * plane.normal;
+                }
+
+                self.translate_selection(world, new_gizmo_position, gizmo_start_position);
+            }
+            GizmoDragKind::Rotate {
+                tangent_line,
+                radius,
+            } => {
+                let (tangent_point, _camera_ray_point) = tangent_line.distance(&camera_ray);
+                if !tangent_point.is_finite() {
+                    return;
+                }
+
+                let mut angle =
+                    (tangent_point - tangent_line.position).dot(tangent_line.direction) / radius;
+                if is_snapping {
+                    angle = snap_value(angle, self.snap_settings.angle_increment.to_radians());
+                }
+
+                let rotation = Quat::from_axis_angle(gizmo_move_info.axis, angle);
+                self.rotate_selection(world, rotation, gizmo_start_position, gizmo_start_position);
+            }
+            GizmoDragKind::Scale { start_distance } => {
+                let axis_line = Line {
+                    position: gizmo_start_position,
+                    direction: gizmo_move_info.axis,
+                };
+                let (axis_point, _camera_ray_point) = axis_line.distance(&camera_ray);
+                if !axis_point.is_finite() {
+                    return;
+                }
+
+                let mut scale_factor =
+                    (axis_point - gizmo_start_position).dot(gizmo_move_info.axis) / start_distance;
+                if is_snapping {
+                    scale_factor = snap_value(scale_factor, self.snap_settings.scale_step);
+                }
+                let scale_factor = scale_factor.max(MIN_GIZMO_SCALE_FACTOR);
+
+                for (object_id, start_transform) in &self.drag_start_transforms {
+                    // The axis might not be an axis of the object, the closest one is scaled
+                    let axis_index = get_axis_index(
+                        start_transform.get_rotation().inverse() * gizmo_move_info.axis,
+                    );
+                    // The distances from the gizmo along the axis are scaled as well, so the selected objects are
+                    // spread out or pulled together
+                    let offset_along_axis = (start_transform.get_position() - gizmo_start_position)
+                        .dot(gizmo_move_info.axis)
+                        * gizmo_move_info.axis;
+                    let mut scale = start_transform.get_scale();
+                    scale[axis_index] *= scale_factor;
+
+                    let mut transform = *start_transform;
+                    transform.set_position(
+                        start_transform.get_position() + offset_along_axis * (scale_factor - 1.0),
+                    );
+                    transform.set_scale(scale);
+                    world.set_world_transform(object_id, transform);
+                }
+            }
+        }
+    }
+
+    fn translate_selection(
+        &mut self,
+        world: &mut World,
+        new_gizmo_position: Vec3,
+        gizmo_start_position: Vec3,
+    ) {
+        let offset = new_gizmo_position - gizmo_start_position;
+        for (object_id, start_transform) in &self.drag_start_transforms {
+            world.set_world_position(object_id, start_transform.get_position() + offset);
+        }
+
+        self.gizmo.update_position(new_gizmo_position, world);
+    }
+
+    /// The objects are rotated around the start position of the gizmo, then moved together with it
+    fn rotate_selection(
+        &mut self,
+        world: &mut World,
+        rotation: Quat,
+        gizmo_start_position: Vec3,
+        new_gizmo_position: Vec3,
+    ) {
+        for (object_id, start_transform) in &self.drag_start_transforms {
+            let mut transform = *start_transform;
+            transform.set_position(
+                new_gizmo_position
+                    + rotation * (start_transform.get_position() - gizmo_start_position),
+            );
+            transform.set_rotation(rotation * start_transform.get_rotation());
+            world.set_world_transform(object_id, transform);
+        }
+
+        self.gizmo.update_position(new_gizmo_position, world);
+    }
+
+    /// The gizmo is moved to the surface under the cursor and the up axis of the active object is aligned to the
+    /// normal of the surface. The selected objects and the gizmo are not hit by the ray
+    fn place_selection_on_surface(
+        &mut self,
+        world: &mut World,
+        world_renderer: &WorldRenderer,
+        camera_ray: &Line,
+        gizmo_start_position: Vec3,
+    ) {
+        let selected_object_ids = self
+            .selected_object_ids
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+        let Some(surface_hit) = world_renderer.cast_ray(camera_ray, |object_id| {
+            selected_object_ids.contains(&object_id)
+                || world.has_ancestor_in(object_id, &selected_object_ids)
+                || self.gizmo.get_handle_with_id(object_id).is_some()
+        }) else {
+            return;
         };
 
-        let (gizmo_axis_point, _camera_axis_point) =
-            gizmo_move_info.gizmo_movement_axis.distance(&camera_ray);
+        let active_object_id = self.get_active_object_id();
+        let Some((_, alignment_transform)) = self
+            .drag_start_transforms
+            .iter()
+            .find(|(object_id, _)| Some(*object_id) == active_object_id)
+            .or(self.drag_start_transforms.first())
+        else {
+            return;
+        };
+        let up_axis = alignment_transform.get_rotation() * Vec3::Y;
+        let alignment = Quat::from_rotation_arc(up_axis, surface_hit.normal);
 
-        let new_position =
-            gizmo_axis_point + gizmo_move_info.gizmo_interaction_and_object_position_difference;
+        self.rotate_selection(world, alignment, gizmo_start_position, surface_hit.position);
+    }
 
-        {
-            if let Some(object) =
-                world.get_world_object_mut(&self.gizmo.selected_object_id.unwrap())
-            {
-                object.transform.set_position(new_position);
+    pub fn select_object(&mut self, object_id: Option<u32>, world: &mut World) {
+        self.select_objects(object_id.as_slice(), world);
+    }
+
+    /// Replaces the selection, the last object becomes the active one
+    pub fn select_objects(&mut self, object_ids: &[u32], world: &mut World) {
+        self.interaction_state = GizmoInteractionState::Idle;
+        self.selected_object_ids = object_ids.to_vec();
+        self.is_selection_changed = true;
+
+        // The gizmo is recreated, so it's not duplicated when the active object stays the same
+        self.gizmo.update_with_new_object_id(None, world);
+        if let Some(active_object_id) = self.get_active_object_id() {
+            self.gizmo
+                .update_with_new_object_id(Some(active_object_id), world);
+            if let Some(pivot_position) = self.get_pivot_position(world) {
+                self.gizmo.update_position(pivot_position, world);
             }
+            let gizmo_rotation = self.get_gizmo_rotation(world);
+            self.gizmo.set_rotation(gizmo_rotation, world);
+        }
+    }
+
+    pub fn set_pivot(&mut self, pivot: SelectionPivot, world: &mut World) {
+        self.pivot = pivot;
+        self.is_selection_changed = true;
+        if let Some(pivot_position) = self.get_pivot_position(world) {
+            self.gizmo.update_position(pivot_position, world);
+        }
+    }
+
+    /// The mode can't change while a handle is dragged
+    pub fn set_mode(&mut self, mode: GizmoMode, world: &mut World) {
+        if matches!(self.interaction_state, GizmoInteractionState::Idle) {
+            self.gizmo.set_mode(mode, world);
+            self.is_selection_changed = true;
+        }
+    }
+
+    pub fn get_mode(&self) -> GizmoMode {
+        self.gizmo.get_mode()
+    }
+
+    /// The rotation of the active object in local mode
+    fn get_gizmo_rotation(&self, world: &World) -> Quat {
+        match self.orientation {
+            GizmoOrientation::World => Quat::IDENTITY,
+            GizmoOrientation::Local => self
+                .get_active_object_id()
+                .and_then(|active_object_id| world.get_world_transform(&active_object_id))
+                .map(|transform| transform.get_rotation())
+                .unwrap_or(Quat::IDENTITY),
         }
+    }
+
+    pub fn set_orientation(&mut self, orientation: GizmoOrientation, world: &mut World) {
+        self.orientation = orientation;
+        self.is_selection_changed = true;
+        if matches!(self.interaction_state, GizmoInteractionState::Idle) {
+            let gizmo_rotation = self.get_gizmo_rotation(world);
+            self.gizmo.set_rotation(gizmo_rotation, world);
+        }
+    }
+
+    pub fn get_orientation(&self) -> GizmoOrientation {
+        self.orientation
+    }
 
-        self.gizmo.update_position(new_position, world);
+    pub fn set_snap_settings(&mut self, snap_settings: SnapSettings) {
+        self.snap_settings = snap_settings;
+    }
+
+    /// True if the selection changed since the last call, the GUI only shows the selection again then
+    pub fn take_selection_change(&mut self) -> bool {
+        std::mem::take(&mut self.is_selection_changed)
+    }
+
+    pub fn get_pivot(&self) -> SelectionPivot {
+        self.pivot
+    }
+
+    /// True while the selected objects are dragged by the gizmo
+    pub fn is_moving_object(&self) -> bool {
+        matches!(self.interaction_state, GizmoInteractionState::Moving(_))
     }
 
     pub fn get_active_object_id(&self) -> Option<u32> {
-        self.gizmo.selected_object_id
+        self.selected_object_ids.last().copied()
+    }
+
+    pub fn get_selected_object_ids(&self) -> &[u32] {
+        &self.selected_object_ids
+    }
+
+    /// Nothing is hovered while the gizmo or the selection rectangle is dragged
+    pub fn get_hovered_object_id(&self) -> Option<u32> {
+        match self.interaction_state {
+            GizmoInteractionState::Idle => self.hovered_object_id,
+            _ => None,
+        }
+    }
+
+    /// The corners of the selection rectangle on the screen, while it's being dragged
+    pub fn get_selection_rectangle(
+        &self,
+    ) -> Option<(PhysicalPosition<f64>, PhysicalPosition<f64>)> {
+        match self.interaction_state {
+            GizmoInteractionState::SelectingRectangle(start_position) => {
+                Some((start_position, self.cursor_position?))
+            }
+            _ => None,
+        }
     }
 }
@@ -1,6 +1,10 @@
+use glam::Vec3;
 use ui_item_derive::{UiDisplayable, UiSettableNew};
 
-/// These will be transferred to the GPU and can be used there
+use crate::frustum_culling::CullingMode;
+
+/// These will be transferred to the GPU and can be used there. The fields are ordered so the layout matches the WGSL
+/// one without padding (a vec3 is aligned to 16 bytes there)
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, UiDisplayable, UiSettableNew)]
 pub struct GlobalGPUParams {
@@ -10,6 +14,19 @@ pub struct GlobalGPUParams {
     pub tone_mapping_type: u32,
     #[ui_param(min = "0.01", max = "0.1")]
     pub ssr_thickness: f32,
+    /// In pixels, the outline around the selected and the hovered objects
+    #[ui_param(min = "0.0", max = "8.0")]
+    pub outline_width: f32,
+    #[ui_param(min = "0.0", max = "1.0")]
+    pub selection_outline_color: Vec3,
+    /// The outline of the parts hidden by other objects is drawn with this opacity
+    #[ui_param(min = "0.0", max = "1.0")]
+    pub occluded_outline_opacity: f32,
+    #[ui_param(min = "0.0", max = "1.0")]
+    pub hover_outline_color: Vec3,
+    #[ui_param(skip)]
+    #[ui_set(skip)]
+    pub padding: f32,
 }
 
 impl Default for GlobalGPUParams {
@@ -18,14 +35,24 @@ impl Default for GlobalGPUParams {
             random_param: 1.0,
             tone_mapping_type: 1,
             ssr_thickness: 0.01,
+            outline_width: 2.0,
+            selection_outline_color: Vec3::new(1.0, 0.6, 0.1),
+            occluded_outline_opacity: 0.3,
+            hover_outline_color: Vec3::new(0.6, 0.8, 1.0),
+            padding: 0.0,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, UiDisplayable, UiSettableNew)]
 pub struct GlobalCPUParams {
+    #[ui_param(skip)]
+    #[ui_set(skip)]
     pub shadow_bias: f32,
+    #[ui_param(skip)]
+    #[ui_set(skip)]
     pub scale: f32,
+    pub culling_mode: CullingMode,
 }
 
 impl Default for GlobalCPUParams {
@@ -33,6 +60,7 @@ impl Default for GlobalCPUParams {
         Self {
             shadow_bias: 1.0,
             scale: Default::default(),
+            culling_mode: CullingMode::default(),
         }
     }
 }
@@ -0,0 +1,610 @@
+use std::collections::HashMap;
+
+use glam::Mat4;
+use math_helpers::Frustum;
+use wgpu::{
+    util::DrawIndexedIndirectArgs, BindGroup, Buffer, BufferAddress, CommandEncoder,
+    ComputePassDescriptor, Device, Extent3d, Queue, Texture, TextureDimension, TextureFormat,
+    TextureUsages,
+};
+
+use crate::{
+    bind_group_layout_descriptors,
+    model::Renderable,
+    pipelines::{ShaderCompilationSuccess, SimpleCP},
+    texture::SampledTexture,
+};
+
+const GPU_CULLING_SHADER_SOURCE: &'static str = "src/shaders/gpu_culling.wgsl";
+const HI_Z_DOWNSAMPLE_SHADER_SOURCE: &'static str = "src/shaders/hi_z_downsample.wgsl";
+
+/// Must match the workgroup size in the culling shader
+const CULLING_WORKGROUP_SIZE: u32 = 64;
+const HI_Z_WORKGROUP_SIZE_PER_DIMENSION: u32 = 8;
+
+/// These must match the flags in the culling shader
+const DRAW_FLAG_CAST_SHADOWS: u32 = 1;
+const DRAW_FLAG_USE_DEPTH_TEST: u32 = 2;
+const VIEW_FLAG_SHADOW: u32 = 1;
+const VIEW_FLAG_USE_HI_Z: u32 = 2;
+
+const DRAW_INDEXED_INDIRECT_ARGS_SIZE: u64 = std::mem::size_of::<DrawIndexedIndirectArgs>() as u64;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawInputRaw {
+    bounding_sphere: [f32; 4],
+    aabb_min: [f32; 4],
+    aabb_max: [f32; 4],
+    /// The index count of the level of detail selected for the camera
+    index_count: u32,
+    instance_count: u32,
+    flags: u32,
+    /// The index count of the level of detail selected for the shadow maps
+    shadow_index_count: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullingViewRaw {
+    planes: [[f32; 4]; 6],
+    hi_z_view_proj: [[f32; 4]; 4],
+    flags: u32,
+    _padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullingParamsRaw {
+    draw_count: u32,
+    view_count: u32,
+    hi_z_mip_count: u32,
+    _padding: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CullingViewType {
+    /// The main camera. Every object is drawn, that is inside of the view
+    Camera,
+    /// A shadow map of a light. Only the shadow casters are drawn
+    Shadow,
+}
+
+/// A view to cull the draws against. The culling results of the view are available with the same index,
+/// that the view had in the slice passed to `GpuCulling::cull`
+#[derive(Debug, Clone, Copy)]
+pub struct CullingView {
+    pub view_proj: Mat4,
+    pub view_type: CullingViewType,
+}
+
+/// The indirect draw arguments of a single view, written by the culling pass
+#[derive(Clone, Copy)]
+pub struct IndirectDrawView<'a> {
+    buffer: &'a Buffer,
+    first_draw_indices: &'a HashMap<u32, u32>,
+    base_draw_index: u32,
+    supports_multi_draw: bool,
+}
+
+impl<'a> IndirectDrawView<'a> {
+    pub fn get_buffer(&self) -> &'a Buffer {
+        self.buffer
+    }
+
+    /// Can consecutive draws from the buffer be issued with a single `multi_draw_indexed_indirect` call?
+    pub fn supports_multi_draw(&self) -> bool {
+        self.supports_multi_draw
+    }
+
+    /// Where the draw arguments of the given part of the renderable are in the buffer. If the renderable was
+    /// not known when the culling ran, then None is returned
+    pub fn get_draw_args_offset(
+        &self,
+        renderable_id: u32,
+        part_index: usize,
+    ) -> Option<BufferAddress> {
+        self.first_draw_indices
+            .get(&renderable_id)
+            .map(|first_draw_index| {
+                (self.base_draw_index + first_draw_index + part_index as u32) as BufferAddress
+                    * DRAW_INDEXED_INDIRECT_ARGS_SIZE
+            })
+    }
+}
+
+struct CullingBuffers {
+    draw_input_buffer: Buffer,
+    view_buffer: Buffer,
+    indirect_args_buffer: Buffer,
+    bind_group: BindGroup,
+    draw_capacity: usize,
+    view_capacity: usize,
+}
+
+/// Hierarchical depth buffer, built from the depth buffer of the previous frame
+struct HiZBuffer {
+    texture: Texture,
+    mip_count: u32,
+    /// The bind group used by the culling pass, containing all the mip levels
+    culling_bind_group: BindGroup,
+    /// The source and the destination bind groups used for building each mip level
+    mip_level_bind_groups: Vec<(BindGroup, BindGroup)>,
+    /// The view-projection matrix of the frame that the buffer was built from. None if it wasn't built yet
+    view_proj: Option<Mat4>,
+}
+
+/// Culls the renderables on the GPU and writes indirect draw arguments for them, so the visibility of the objects
+/// never has to be decided on the CPU. Each part of every renderable is a separate draw
+pub struct GpuCulling {
+    culling_pipeline: SimpleCP,
+    hi_z_pipeline: SimpleCP,
+    params_buffer: Buffer,
+    buffers: CullingBuffers,
+    /// Bound instead of the Hi-Z buffer, when occlusion culling is not used
+    dummy_hi_z_bind_group: BindGroup,
+    hi_z_buffer: Option<HiZBuffer>,
+
+    /// Kept on the CPU as well, so the contents can be uploaded again if the buffers have to be recreated
+    draw_inputs: Vec<DrawInputRaw>,
+    /// The index of the draw of the first part of each renderable. The parts of a renderable are stored next to each other
+    first_draw_indices: HashMap<u32, u32>,
+    /// `MULTI_DRAW_INDIRECT` is an optional feature, it's only used if the adapter supports it
+    supports_multi_draw: bool,
+}
+
+impl GpuCulling {
+    pub fn new(device: &Device) -> Self {
+        let culling_pipeline = SimpleCP::new(
+            device,
+            &[
+                &bind_group_layout_descriptors::GPU_CULLING,
+                &bind_group_layout_descriptors::COMPUTE_SHADER_UNFILTERABLE_TEXTURE,
+            ],
+            GPU_CULLING_SHADER_SOURCE,
+            "GPU culling",
+        )
+        .unwrap();
+
+        let hi_z_pipeline = SimpleCP::new(
+            device,
+            &[
+                &bind_group_layout_descriptors::COMPUTE_SHADER_UNFILTERABLE_TEXTURE,
+                &bind_group_layout_descriptors::COMPUTE_SHADER_R32_FLOAT_TEXTURE_DESTINATION,
+            ],
+            HI_Z_DOWNSAMPLE_SHADER_SOURCE,
+            "Hi-Z downsample",
+        )
+        .unwrap();
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU culling params buffer"),
+            size: std::mem::size_of::<CullingParamsRaw>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let buffers = Self::create_buffers(device, &params_buffer, 64, 8);
+
+        let dummy_hi_z_texture = Self::create_hi_z_texture(device, 1, 1, 1);
+        let dummy_hi_z_bind_group = Self::create_texture_bind_group(
+            device,
+            &dummy_hi_z_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            &bind_group_layout_descriptors::COMPUTE_SHADER_UNFILTERABLE_TEXTURE,
+            "Dummy Hi-Z",
+        );
+
+        Self {
+            culling_pipeline,
+            hi_z_pipeline,
+            params_buffer,
+            buffers,
+            dummy_hi_z_bind_group,
+            hi_z_buffer: None,
+            draw_inputs: Vec::new(),
+            first_draw_indices: HashMap::new(),
+            supports_multi_draw: device
+                .features()
+                .contains(wgpu::Features::MULTI_DRAW_INDIRECT),
+        }
+    }
+
+    fn create_buffers(
+        device: &Device,
+        params_buffer: &Buffer,
+        draw_capacity: usize,
+        view_capacity: usize,
+    ) -> CullingBuffers {
+        let draw_input_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU culling draw input buffer"),
+            size: (draw_capacity * std::mem::size_of::<DrawInputRaw>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let view_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU culling view buffer"),
+            size: (view_capacity * std::mem::size_of::<CullingViewRaw>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let indirect_args_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU culling indirect args buffer"),
+            size: (draw_capacity * view_capacity) as u64 * DRAW_INDEXED_INDIRECT_ARGS_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("GPU culling bind group"),
+            layout: &device.create_bind_group_layout(&bind_group_layout_descriptors::GPU_CULLING),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: draw_input_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: view_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: indirect_args_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        CullingBuffers {
+            draw_input_buffer,
+            view_buffer,
+            indirect_args_buffer,
+            bind_group,
+            draw_capacity,
+            view_capacity,
+        }
+    }
+
+    /// Makes sure that the buffers can hold the given number of draws and views. The buffers are only ever grown
+    fn reserve(&mut self, device: &Device, queue: &Queue, draw_count: usize, view_count: usize) {
+        if draw_count > self.buffers.draw_capacity || view_count > self.buffers.view_capacity {
+            let draw_capacity = draw_count
+                .next_power_of_two()
+                .max(self.buffers.draw_capacity);
+            let view_capacity = view_count
+                .next_power_of_two()
+                .max(self.buffers.view_capacity);
+            self.buffers =
+                Self::create_buffers(device, &self.params_buffer, draw_capacity, view_capacity);
+
+            queue.write_buffer(
+                &self.buffers.draw_input_buffer,
+                0,
+                bytemuck::cast_slice(&self.draw_inputs),
+            );
+        }
+    }
+
+    fn get_draw_count(&self) -> u32 {
+        self.draw_inputs.len() as u32
+    }
+
+    /// Uploads the bounding volumes and the draw parameters of the renderables. Should be called when a renderable
+    /// was added, removed or moved, or when its level of detail changed
+    pub fn update_draws<'a, T>(&mut self, device: &Device, queue: &Queue, renderables: T)
+    where
+        T: Iterator<Item = &'a Renderable>,
+    {
+        self.first_draw_indices.clear();
+        self.draw_inputs.clear();
+
+        for renderable in renderables {
+            self.first_draw_indices
+                .insert(renderable.id, self.draw_inputs.len() as u32);
+
+            let rendering_options = &renderable.description.rendering_options;
+            let mut flags = 0;
+            if rendering_options.cast_shadows {
+                flags |= DRAW_FLAG_CAST_SHADOWS;
+            }
+            if rendering_options.use_depth_test {
+                flags |= DRAW_FLAG_USE_DEPTH_TEST;
+            }
+
+            // The parts are not culled one by one, but using the bounds of the whole renderable is
+            // conservative and keeps the bounds calculation on the CPU side simple
+            for part in &renderable.renderable_parts {
+                self.draw_inputs.push(DrawInputRaw {
+                    bounding_sphere: renderable
+                        .bounding_sphere
+                        .center
+                        .extend(renderable.bounding_sphere.radius)
+                        .into(),
+                    aabb_min: renderable.bounding_box.min.extend(1.0).into(),
+                    aabb_max: renderable.bounding_box.max.extend(1.0).into(),
+                    index_count: part
+                        .get_primitive(renderable.lod_state.camera.level)
+                        .index_data
+                        .count,
+                    instance_count: renderable.instance_data.count,
+                    flags,
+                    shadow_index_count: part
+                        .get_primitive(renderable.lod_state.shadow.level)
+                        .index_data
+                        .count,
+                });
+            }
+        }
+
+        self.reserve(
+            device,
+            queue,
+            self.draw_inputs.len(),
+            self.buffers.view_capacity,
+        );
+        queue.write_buffer(
+            &self.buffers.draw_input_buffer,
+            0,
+            bytemuck::cast_slice(&self.draw_inputs),
+        );
+    }
+
+    /// Culls all the draws against all the views and writes the indirect draw arguments.
+    /// Occlusion culling is only done for the camera views and only if `use_hi_z` is set and
+    /// the Hi-Z buffer was already built in a previous frame
+    pub fn cull(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        views: &[CullingView],
+        use_hi_z: bool,
+    ) {
+        if !use_hi_z {
+            // Don't keep an outdated Hi-Z buffer around, it will be rebuilt when it's needed again
+            self.hi_z_buffer = None;
+        }
+
+        let hi_z_view_proj = self
+            .hi_z_buffer
+            .as_ref()
+            .and_then(|hi_z_buffer| hi_z_buffer.view_proj);
+
+        let views_raw = views
+            .iter()
+            .map(|view| {
+                let frustum = Frustum::from_view_proj(&view.view_proj);
+                let mut flags = 0;
+                if view.view_type == CullingViewType::Shadow {
+                    flags |= VIEW_FLAG_SHADOW;
+                }
+                if view.view_type == CullingViewType::Camera && hi_z_view_proj.is_some() {
+                    flags |= VIEW_FLAG_USE_HI_Z;
+                }
+
+                CullingViewRaw {
+                    planes: frustum
+                        .planes
+                        .map(|plane| plane.normal.extend(plane.distance).into()),
+                    hi_z_view_proj: hi_z_view_proj.unwrap_or_default().to_cols_array_2d(),
+                    flags,
+                    _padding: [0; 3],
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.reserve(device, queue, self.buffers.draw_capacity, views_raw.len());
+        queue.write_buffer(
+            &self.buffers.view_buffer,
+            0,
+            bytemuck::cast_slice(&views_raw),
+        );
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[CullingParamsRaw {
+                draw_count: self.get_draw_count(),
+                view_count: views_raw.len() as u32,
+                hi_z_mip_count: self
+                    .hi_z_buffer
+                    .as_ref()
+                    .map_or(1, |hi_z_buffer| hi_z_buffer.mip_count),
+                _padding: 0,
+            }]),
+        );
+
+        if self.draw_inputs.is_empty() || views_raw.is_empty() {
+            return;
+        }
+
+        let hi_z_bind_group = match &self.hi_z_buffer {
+            Some(hi_z_buffer) if hi_z_buffer.view_proj.is_some() => &hi_z_buffer.culling_bind_group,
+            _ => &self.dummy_hi_z_bind_group,
+        };
+
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("GPU culling"),
+            timestamp_writes: None,
+        });
+
+        self.culling_pipeline.run_copmute_pass(
+            &mut compute_pass,
+            &[&self.buffers.bind_group, hi_z_bind_group],
+            (
+                self.get_draw_count().div_ceil(CULLING_WORKGROUP_SIZE),
+                views_raw.len() as u32,
+                1,
+            ),
+        );
+    }
+
+    /// The culling results of the view with the given index
+    pub fn get_view(&self, view_index: usize) -> IndirectDrawView<'_> {
+        IndirectDrawView {
+            buffer: &self.buffers.indirect_args_buffer,
+            first_draw_indices: &self.first_draw_indices,
+            base_draw_index: view_index as u32 * self.get_draw_count(),
+            supports_multi_draw: self.supports_multi_draw,
+        }
+    }
+
+    /// Builds the Hi-Z buffer from the depth buffer of the current frame. It will be used for occlusion culling
+    /// in the next frame
+    pub fn build_hi_z(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        depth_texture: &SampledTexture,
+        view_proj: Mat4,
+    ) {
+        let hi_z_buffer = self
+            .hi_z_buffer
+            .get_or_insert_with(|| Self::create_hi_z_buffer(device, depth_texture));
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Hi-Z generation"),
+                timestamp_writes: None,
+            });
+
+            for (mip_level, (source_bind_group, destination_bind_group)) in
+                hi_z_buffer.mip_level_bind_groups.iter().enumerate()
+            {
+                let mip_size = hi_z_buffer
+                    .texture
+                    .size()
+                    .mip_level_size(mip_level as u32, TextureDimension::D2);
+
+                self.hi_z_pipeline.run_copmute_pass(
+                    &mut compute_pass,
+                    &[source_bind_group, destination_bind_group],
+                    (
+                        mip_size.width.div_ceil(HI_Z_WORKGROUP_SIZE_PER_DIMENSION),
+                        mip_size.height.div_ceil(HI_Z_WORKGROUP_SIZE_PER_DIMENSION),
+                        1,
+                    ),
+                );
+            }
+        }
+
+        hi_z_buffer.view_proj = Some(view_proj);
+    }
+
+    /// The Hi-Z buffer references the depth texture, so it has to be recreated when that changes
+    pub fn handle_size_changed(&mut self) {
+        self.hi_z_buffer = None;
+    }
+
+    fn create_hi_z_texture(device: &Device, width: u32, height: u32, mip_count: u32) -> Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Hi-Z texture"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: mip_count,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R32Float,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING,
+            view_formats: &[],
+        })
+    }
+
+    fn create_texture_bind_group(
+        device: &Device,
+        view: &wgpu::TextureView,
+        layout_descriptor: &wgpu::BindGroupLayoutDescriptor,
+        label: &str,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{label} bind group")),
+            layout: &device.create_bind_group_layout(layout_descriptor),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            }],
+        })
+    }
+
+    fn create_hi_z_buffer(device: &Device, depth_texture: &SampledTexture) -> HiZBuffer {
+        let size = depth_texture.texture.size();
+        let mip_count = size.max_mips(TextureDimension::D2);
+        let texture = Self::create_hi_z_texture(device, size.width, size.height, mip_count);
+
+        let mip_views = (0..mip_count)
+            .map(|mip_level| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some(&format!("Hi-Z view for mip level {mip_level}")),
+                    base_mip_level: mip_level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // The first level is built from the depth buffer, every other level from the previous level
+        let mip_level_bind_groups = mip_views
+            .iter()
+            .enumerate()
+            .map(|(mip_level, destination_view)| {
+                let source_view = if mip_level == 0 {
+                    &depth_texture.view
+                } else {
+                    &mip_views[mip_level - 1]
+                };
+
+                (
+                    Self::create_texture_bind_group(
+                        device,
+                        source_view,
+                        &bind_group_layout_descriptors::COMPUTE_SHADER_UNFILTERABLE_TEXTURE,
+                        &format!("Hi-Z source for mip level {mip_level}"),
+                    ),
+                    Self::create_texture_bind_group(
+                        device,
+                        destination_view,
+                        &bind_group_layout_descriptors::COMPUTE_SHADER_R32_FLOAT_TEXTURE_DESTINATION,
+                        &format!("Hi-Z destination for mip level {mip_level}"),
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let culling_bind_group = Self::create_texture_bind_group(
+            device,
+            &texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            &bind_group_layout_descriptors::COMPUTE_SHADER_UNFILTERABLE_TEXTURE,
+            "Hi-Z",
+        );
+
+        HiZBuffer {
+            texture,
+            mip_count,
+            culling_bind_group,
+            mip_level_bind_groups,
+            view_proj: None,
+        }
+    }
+
+    pub fn try_recompile_shaders(
+        &mut self,
+        device: &Device,
+    ) -> anyhow::Result<ShaderCompilationSuccess> {
+        let culling_result = self.culling_pipeline.try_recompile_shader(device)?;
+        let hi_z_result = self.hi_z_pipeline.try_recompile_shader(device)?;
+
+        if culling_result == ShaderCompilationSuccess::Recompiled
+            || hi_z_result == ShaderCompilationSuccess::Recompiled
+        {
+            Ok(ShaderCompilationSuccess::Recompiled)
+        } else {
+            Ok(ShaderCompilationSuccess::AlreadyUpToDate)
+        }
+    }
+}
@@ -9,8 +9,9 @@ use std::{
 
 use crossbeam_channel::Sender;
 use egui::{
-    scroll_area, Align, Button, CollapsingHeader, FontId, Label, Layout, Response, SelectableLabel,
-    Separator, Slider, Ui, Vec2, Widget,
+    collapsing_header::CollapsingState, load::SizedTexture, scroll_area, Align, Button,
+    CollapsingHeader, FontId, Frame, Id, Image, Label, Layout, Response, SelectableLabel, Sense,
+    Separator, Slider, TextureId, Ui, Vec2, Widget,
 };
 use egui_wgpu::ScreenDescriptor;
 use glam::{Quat, Vec3};
@@ -21,27 +22,132 @@ use ui_item::{
     SetVecFromUiDescription, UiDisplayDescription,
 };
 use wgpu::{CommandEncoder, TextureFormat};
-use winit::event::WindowEvent;
+use winit::{dpi::PhysicalPosition, event::WindowEvent};
 
-use crate::gui_helpers::EguiRenderer;
+use crate::{
+    camera::{AxisView, Projection},
+    camera_controller::CameraMode,
+    camera_path::{CameraPath, CameraViewpoints, MAX_BOOKMARK_HOTKEY},
+    frustum_culling::FrameCullingStats,
+    gizmo::{GizmoMode, GizmoOrientation},
+    gizmo_handler::{SelectionPivot, SnapSettings},
+    gui_helpers::EguiRenderer,
+    persistent_id::PersistentId,
+    texture::SampledTexture,
+    world::EnvironmentSettings,
+};
 
 const LABEL_SIZE: [f32; 2] = [120.0, 10.0];
 const STANDARD_SLIDER_SIZE: [f32; 2] = [240.0, 10.0];
 const VEC3_SLIDER_SIZE: [f32; 2] = [80.0, 10.0];
 const UI_MIN_SIZE: Vec2 = Vec2 { x: 600.0, y: 0.0 };
+const CAMERA_VIEW_PREVIEW_WIDTH: f32 = 320.0;
 
 pub enum GuiButton {
+    NewLevel,
+    OpenLevel,
     SaveLevel,
+    SaveLevelAs,
+    AddEmptyObject,
+    AddCamera,
+    AddPrefab,
+    CreatePrefabFromSelection,
+    ApplyPrefabChanges,
+    Undo,
+    Redo,
+}
+
+/// The operations on the camera bookmarks and paths that need the current state of the camera. The bookmarks and
+/// paths are referenced by their index in `CameraViewpoints`
+pub enum CameraViewpointAction {
+    /// Adds a bookmark at the current viewpoint
+    AddBookmark,
+    /// Overwrites the bookmark of the hotkey with the current viewpoint, or adds one if the hotkey is unused
+    StoreBookmark(u32),
+    UpdateBookmark(usize),
+    GoToBookmark(usize),
+    RecallBookmark(u32),
+    /// Appends the current viewpoint to the path
+    AddKeyframe {
+        path_index: usize,
+    },
+    PlayPath(usize),
+}
+
+/// An object in the hierarchy shown on the outliner
+#[derive(Debug, Clone)]
+pub struct OutlinerItem {
+    pub id: u32,
+    /// Shown on hover, this is the ID that identifies the object in the level file
+    pub persistent_id: Option<PersistentId>,
+    pub name: String,
+    pub children: Vec<OutlinerItem>,
 }
 
 pub enum GuiUpdateEvent {
     ShaderCompilationResult(anyhow::Result<()>),
     LevelSaveResult(anyhow::Result<()>),
+    LevelLoadResult(anyhow::Result<()>),
+    PrefabResult(anyhow::Result<()>),
+    ClipboardResult(anyhow::Result<()>),
+    /// The file of the level being edited (None if it was never saved) and whether it has unsaved changes
+    Level {
+        path: Option<PathBuf>,
+        has_unsaved_changes: bool,
+    },
+    CullingStats(FrameCullingStats),
+    Environment(EnvironmentSettings),
+    Outliner(Vec<OutlinerItem>),
+    /// The selected objects (the last one is the active object) and the corners of the selection rectangle on the
+    /// screen, while it's being dragged
+    Selection {
+        object_ids: Vec<u32>,
+        pivot: SelectionPivot,
+        gizmo_mode: GizmoMode,
+        gizmo_orientation: GizmoOrientation,
+        rectangle: Option<(PhysicalPosition<f64>, PhysicalPosition<f64>)>,
+    },
+    /// The snap settings loaded from the editor settings
+    SnapSettings(SnapSettings),
+    /// The names of the history entries from the oldest one and the number of entries that are not undone
+    History {
+        entry_names: Vec<String>,
+        applied_entry_count: usize,
+    },
+    CameraViewpoints {
+        camera_viewpoints: CameraViewpoints,
+        is_playing_path: bool,
+    },
+    CameraProjection(Projection),
+    CameraMode(CameraMode),
 }
 
 pub enum GuiEvent {
     RecompileShaders,
     ButtonClicked(GuiButton),
+    /// With `add_to_selection`, the object is added to or removed from the selection instead of replacing it
+    ObjectSelected {
+        object_id: u32,
+        add_to_selection: bool,
+    },
+    SelectionPivotChanged(SelectionPivot),
+    GizmoModeChanged(GizmoMode),
+    GizmoOrientationChanged(GizmoOrientation),
+    SnapSettingsChanged(SnapSettings),
+    /// The object was dragged onto another object on the outliner, or to the root of the hierarchy if `new_parent` is None
+    ObjectReparented {
+        object_id: u32,
+        new_parent: Option<u32>,
+    },
+    EnvironmentChanged(EnvironmentSettings),
+    /// The bookmarks or paths were edited (eg. renamed or removed)
+    CameraViewpointsChanged(CameraViewpoints),
+    CameraViewpointAction(CameraViewpointAction),
+    CameraProjectionChanged(Projection),
+    CameraModeChanged(CameraMode),
+    AxisViewSelected(AxisView),
+    /// The selected objects show the image of the camera component of the object in place of their albedo texture
+    CameraViewShownOnSelection(u32),
 }
 
 struct GuiNotification {
@@ -83,6 +189,25 @@ struct AppInfo {
     recent_notification: Option<GuiNotification>,
     frame_time: f32,
     fps_counter: u32,
+    culling_stats: FrameCullingStats,
+    outliner_items: Vec<OutlinerItem>,
+    selected_object_ids: Vec<u32>,
+    selection_pivot: SelectionPivot,
+    gizmo_mode: GizmoMode,
+    gizmo_orientation: GizmoOrientation,
+    snap_settings: SnapSettings,
+    selection_rectangle: Option<(PhysicalPosition<f64>, PhysicalPosition<f64>)>,
+    level_path: Option<PathBuf>,
+    has_unsaved_changes: bool,
+    environment: EnvironmentSettings,
+    /// The environment is only changed when the slider is released, until then the world has the old settings
+    is_dragging_environment_slider: bool,
+    history_entry_names: Vec<String>,
+    applied_history_entry_count: usize,
+    camera_viewpoints: CameraViewpoints,
+    is_playing_camera_path: bool,
+    camera_projection: Projection,
+    camera_mode: CameraMode,
 }
 
 /// This is kind of a hacky solution.
@@ -157,12 +282,21 @@ impl SetItemFromUiParams {
     }
 }
 
+/// The image of a camera component, drawn on the UI
+struct CameraViewPreview {
+    object_id: u32,
+    texture_id: TextureId,
+    /// The camera view recreates its texture when its resolution changes
+    size: [u32; 2],
+}
+
 pub struct Gui {
     renderer: EguiRenderer,
     sender: Sender<GuiEvent>,
     app_info: AppInfo,
     dropped_file_handler: DroppedFileHandler,
     registered_items: HashMap<String, (UiDisplayDescription, Sender<SetItemFromUiParams>)>,
+    camera_view_previews: Vec<CameraViewPreview>,
 }
 
 impl Gui {
@@ -179,8 +313,27 @@ impl Gui {
                 recent_notification: None,
                 frame_time: 0.0,
                 fps_counter: 0,
+                culling_stats: FrameCullingStats::default(),
+                outliner_items: vec![],
+                selected_object_ids: vec![],
+                selection_pivot: SelectionPivot::default(),
+                gizmo_mode: GizmoMode::default(),
+                gizmo_orientation: GizmoOrientation::default(),
+                snap_settings: SnapSettings::default(),
+                selection_rectangle: None,
+                level_path: None,
+                has_unsaved_changes: false,
+                environment: EnvironmentSettings::default(),
+                is_dragging_environment_slider: false,
+                history_entry_names: vec![],
+                applied_history_entry_count: 0,
+                camera_viewpoints: CameraViewpoints::default(),
+                is_playing_camera_path: false,
+                camera_projection: Projection::default(),
+                camera_mode: CameraMode::default(),
             },
             registered_items: HashMap::new(),
+            camera_view_previews: vec![],
             dropped_file_handler: DroppedFileHandler {
                 dropped_file: None,
                 drop_time: std::time::Instant::now(),
@@ -220,19 +373,26 @@ impl Gui {
         range: RangeInclusive<f32>,
         max_size: [f32; 2],
     ) -> bool {
-        let slider_response = ui
-            .horizontal(|ui| {
-                if let Some(label_text) = slider_label {
-                    Self::add_label(ui, &label_text);
-                }
-                ui.add_sized(
-                    max_size,
-                    Slider::new(value, range).smart_aim(false).fixed_decimals(2),
-                )
-            })
-            .inner;
+        Self::add_float_slider_with_response(ui, slider_label, value, range, max_size).changed()
+    }
 
-        slider_response.changed()
+    fn add_float_slider_with_response(
+        ui: &mut Ui,
+        slider_label: Option<String>,
+        value: &mut f32,
+        range: RangeInclusive<f32>,
+        max_size: [f32; 2],
+    ) -> Response {
+        ui.horizontal(|ui| {
+            if let Some(label_text) = slider_label {
+                Self::add_label(ui, &label_text);
+            }
+            ui.add_sized(
+                max_size,
+                Slider::new(value, range).smart_aim(false).fixed_decimals(2),
+            )
+        })
+        .inner
     }
 
     fn add_vec3(
@@ -480,6 +640,527 @@ impl Gui {
         }
     }
 
+    /// The skybox can be picked with a file dialog, or by dropping an HDR file on the button. Every change regenerates
+    /// the environment map and bakes the irradiance again, so the sliders only send the change when they are released
+    fn add_environment_settings(
+        ui: &mut Ui,
+        environment: &mut EnvironmentSettings,
+        is_dragging_slider: &mut bool,
+        sender: &Sender<GuiEvent>,
+        dropped_file: &mut Option<PathBuf>,
+    ) {
+        let mut is_changed = false;
+
+        let skybox_name = environment
+            .skybox_path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        ui.horizontal(|ui| {
+            Self::add_label(ui, &"Skybox".to_owned());
+            let button_response = Button::new(skybox_name).ui(ui);
+            if button_response.clicked() {
+                if let Some(file) = FileDialog::new()
+                    .add_filter("hdr environment map", &["hdr"])
+                    .pick_file()
+                {
+                    environment.skybox_path = file;
+                    is_changed = true;
+                }
+            } else if button_response.hovered() {
+                if let Some(file) = dropped_file.take() {
+                    environment.skybox_path = file;
+                    is_changed = true;
+                }
+            }
+        });
+
+        let slider_responses = [
+            Self::add_float_slider_with_response(
+                ui,
+                Some("Skybox intensity".into()),
+                &mut environment.intensity,
+                0.0..=10.0,
+                STANDARD_SLIDER_SIZE,
+            ),
+            // In degrees
+            Self::add_float_slider_with_response(
+                ui,
+                Some("Skybox rotation".into()),
+                &mut environment.rotation,
+                -180.0..=180.0,
+                STANDARD_SLIDER_SIZE,
+            ),
+        ];
+        *is_dragging_slider = slider_responses.iter().any(Response::dragged);
+        // Clicking on the slider or changing it with the keyboard doesn't drag it
+        is_changed |= slider_responses
+            .iter()
+            .any(|response| response.drag_stopped() || (response.changed() && !response.dragged()));
+
+        if is_changed {
+            let _ = sender.try_send(GuiEvent::EnvironmentChanged(environment.clone()));
+        }
+    }
+
+    fn add_outliner_item(
+        item: &OutlinerItem,
+        ui: &mut Ui,
+        selected_object_ids: &[u32],
+        sender: &Sender<GuiEvent>,
+    ) {
+        let add_label = |ui: &mut Ui| {
+            let is_selected = selected_object_ids.contains(&item.id);
+            let response = ui
+                .add(SelectableLabel::new(
+                    is_selected,
+                    format!("{} (#{})", item.name, item.id),
+                ))
+                .interact(Sense::click_and_drag());
+            let response = match item.persistent_id {
+                Some(persistent_id) => response.on_hover_text(format!("ID: {persistent_id}")),
+                None => response,
+            };
+
+            if response.clicked() {
+                let add_to_selection = ui.input(|input| input.modifiers.shift);
+                if add_to_selection || !is_selected {
+                    let _ = sender.try_send(GuiEvent::ObjectSelected {
+                        object_id: item.id,
+                        add_to_selection,
+                    });
+                }
+            }
+
+            response.dnd_set_drag_payload(item.id);
+            if let Some(dragged_object_id) = response.dnd_release_payload::<u32>() {
+                if *dragged_object_id != item.id {
+                    let _ = sender.try_send(GuiEvent::ObjectReparented {
+                        object_id: *dragged_object_id,
+                        new_parent: Some(item.id),
+                    });
+                }
+            }
+        };
+
+        if item.children.is_empty() {
+            ui.horizontal(|ui| {
+                // Keep the leaves aligned with the labels that have a collapse button in front of them
+                ui.add_space(ui.spacing().indent);
+                add_label(ui);
+            });
+        } else {
+            CollapsingState::load_with_default_open(ui.ctx(), Id::new(("outliner", item.id)), true)
+                .show_header(ui, add_label)
+                .body(|ui| {
+                    for child in &item.children {
+                        Self::add_outliner_item(child, ui, selected_object_ids, sender);
+                    }
+                });
+        }
+    }
+
+    fn add_outliner(ui: &mut Ui, app_info: &AppInfo, sender: &Sender<GuiEvent>) {
+        for item in &app_info.outliner_items {
+            Self::add_outliner_item(item, ui, &app_info.selected_object_ids, sender);
+        }
+
+        ui.add(Separator::default().horizontal());
+
+        let (_, dropped_object_id) = ui.dnd_drop_zone::<u32, ()>(Frame::group(ui.style()), |ui| {
+            ui.label("Drop here to move to the top level");
+        });
+        if let Some(dropped_object_id) = dropped_object_id {
+            let _ = sender.try_send(GuiEvent::ObjectReparented {
+                object_id: *dropped_object_id,
+                new_parent: None,
+            });
+        }
+
+        ui.horizontal(|ui| {
+            if Button::new("Add empty object").ui(ui).clicked() {
+                let _ = sender.try_send(GuiEvent::ButtonClicked(GuiButton::AddEmptyObject));
+            }
+            if Button::new("Add camera")
+                .ui(ui)
+                .on_hover_text("Adds a camera component that sees what the editor camera sees")
+                .clicked()
+            {
+                let _ = sender.try_send(GuiEvent::ButtonClicked(GuiButton::AddCamera));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if Button::new("Add prefab").ui(ui).clicked() {
+                let _ = sender.try_send(GuiEvent::ButtonClicked(GuiButton::AddPrefab));
+            }
+
+            let has_selection = !app_info.selected_object_ids.is_empty();
+            if ui
+                .add_enabled(has_selection, Button::new("Create prefab from selection"))
+                .clicked()
+            {
+                let _ = sender.try_send(GuiEvent::ButtonClicked(
+                    GuiButton::CreatePrefabFromSelection,
+                ));
+            }
+            if ui
+                .add_enabled(has_selection, Button::new("Apply changes to prefab"))
+                .on_hover_text("Saves the selected prefab instance into its prefab file")
+                .clicked()
+            {
+                let _ = sender.try_send(GuiEvent::ButtonClicked(GuiButton::ApplyPrefabChanges));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Pivot of the selection:");
+            for (pivot, name) in [
+                (SelectionPivot::SelectionCenter, "Center"),
+                (SelectionPivot::ActiveObject, "Active object"),
+            ] {
+                if ui
+                    .selectable_label(app_info.selection_pivot == pivot, name)
+                    .clicked()
+                {
+                    let _ = sender.try_send(GuiEvent::SelectionPivotChanged(pivot));
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Gizmo:");
+            for (mode, name, shortcut) in [
+                (GizmoMode::Translate, "Move", "W"),
+                (GizmoMode::Rotate, "Rotate", "E"),
+                (GizmoMode::Scale, "Scale", "R"),
+            ] {
+                if ui
+                    .selectable_label(app_info.gizmo_mode == mode, name)
+                    .on_hover_text(shortcut)
+                    .clicked()
+                {
+                    let _ = sender.try_send(GuiEvent::GizmoModeChanged(mode));
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Gizmo axes:");
+            for (orientation, name) in [
+                (GizmoOrientation::World, "World"),
+                (GizmoOrientation::Local, "Local"),
+            ] {
+                if ui
+                    .selectable_label(app_info.gizmo_orientation == orientation, name)
+                    .clicked()
+                {
+                    let _ = sender.try_send(GuiEvent::GizmoOrientationChanged(orientation));
+                }
+            }
+        });
+    }
+
+    /// The snapping is applied while Ctrl is held during a gizmo drag
+    fn add_snap_settings(ui: &mut Ui, snap_settings: &mut SnapSettings, sender: &Sender<GuiEvent>) {
+        ui.label("Snapping (hold Ctrl while dragging):");
+        let mut is_changed = Self::add_float_slider(
+            ui,
+            Some("Grid size".into()),
+            &mut snap_settings.grid_size,
+            0.05..=10.0,
+            STANDARD_SLIDER_SIZE,
+        );
+        // In degrees
+        is_changed |= Self::add_float_slider(
+            ui,
+            Some("Angle increment".into()),
+            &mut snap_settings.angle_increment,
+            1.0..=90.0,
+            STANDARD_SLIDER_SIZE,
+        );
+        is_changed |= Self::add_float_slider(
+            ui,
+            Some("Scale step".into()),
+            &mut snap_settings.scale_step,
+            0.01..=1.0,
+            STANDARD_SLIDER_SIZE,
+        );
+        is_changed |= ui
+            .checkbox(&mut snap_settings.snap_to_surface, "Snap to surface")
+            .on_hover_text("Places the moved objects on the surface under the cursor")
+            .changed();
+
+        if is_changed {
+            let _ = sender.try_send(GuiEvent::SnapSettingsChanged(snap_settings.clone()));
+        }
+    }
+
+    /// Drawn over everything, the rectangle is in physical pixels
+    fn add_selection_rectangle(
+        ctx: &egui::Context,
+        rectangle: (PhysicalPosition<f64>, PhysicalPosition<f64>),
+    ) {
+        let pixels_per_point = ctx.pixels_per_point();
+        let to_point = |position: PhysicalPosition<f64>| {
+            egui::pos2(
+                position.x as f32 / pixels_per_point,
+                position.y as f32 / pixels_per_point,
+            )
+        };
+
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            Id::new("selection_rectangle"),
+        ));
+        let rectangle = egui::Rect::from_two_pos(to_point(rectangle.0), to_point(rectangle.1));
+        painter.rect(
+            rectangle,
+            0.0,
+            egui::Color32::from_white_alpha(16),
+            egui::Stroke::new(1.0, egui::Color32::WHITE),
+        );
+    }
+
+    fn add_history(ui: &mut Ui, app_info: &AppInfo, sender: &Sender<GuiEvent>) {
+        ui.horizontal(|ui| {
+            let applied_entry_count = app_info.applied_history_entry_count;
+            if ui
+                .add_enabled(applied_entry_count > 0, Button::new("Undo"))
+                .on_hover_text("Ctrl+Z")
+                .clicked()
+            {
+                let _ = sender.try_send(GuiEvent::ButtonClicked(GuiButton::Undo));
+            }
+            if ui
+                .add_enabled(
+                    applied_entry_count < app_info.history_entry_names.len(),
+                    Button::new("Redo"),
+                )
+                .on_hover_text("Ctrl+Y")
+                .clicked()
+            {
+                let _ = sender.try_send(GuiEvent::ButtonClicked(GuiButton::Redo));
+            }
+        });
+
+        ui.add(Separator::default().horizontal());
+
+        // The undone entries are greyed out, they are gone after the next change
+        for (index, entry_name) in app_info.history_entry_names.iter().enumerate() {
+            if index < app_info.applied_history_entry_count {
+                ui.label(entry_name);
+            } else {
+                ui.weak(entry_name);
+            }
+        }
+    }
+
+    /// How the camera is moved while the right mouse button is held, and the keys that control the camera
+    fn add_camera_mode(ui: &mut Ui, app_info: &AppInfo, sender: &Sender<GuiEvent>) {
+        ui.horizontal(|ui| {
+            ui.label("Mode:");
+            for (camera_mode, name) in [(CameraMode::Fly, "Fly"), (CameraMode::Orbit, "Orbit")] {
+                if ui
+                    .selectable_label(app_info.camera_mode == camera_mode, name)
+                    .on_hover_text("O")
+                    .clicked()
+                {
+                    let _ = sender.try_send(GuiEvent::CameraModeChanged(camera_mode));
+                }
+            }
+        });
+        ui.weak("F focuses on the selection, H hides the GUI");
+    }
+
+    /// The axis views switch to orthographic projection, Ctrl + their numpad key shows the opposite side
+    fn add_camera_projection(ui: &mut Ui, app_info: &AppInfo, sender: &Sender<GuiEvent>) {
+        ui.horizontal(|ui| {
+            ui.label("Projection:");
+            for (projection, name) in [
+                (Projection::Perspective, "Perspective"),
+                (Projection::Orthographic, "Orthographic"),
+            ] {
+                if ui
+                    .selectable_label(app_info.camera_projection == projection, name)
+                    .on_hover_text("Numpad 5")
+                    .clicked()
+                {
+                    let _ = sender.try_send(GuiEvent::CameraProjectionChanged(projection));
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("View:");
+            for (axis_view, name, shortcut) in [
+                (AxisView::Top, "Top", "Numpad 7"),
+                (AxisView::Front, "Front", "Numpad 1"),
+                (AxisView::Right, "Right", "Numpad 3"),
+            ] {
+                if ui.button(name).on_hover_text(shortcut).clicked() {
+                    let _ = sender.try_send(GuiEvent::AxisViewSelected(axis_view));
+                }
+            }
+        });
+    }
+
+    /// Bookmarks are recalled with their digit key and stored with Ctrl + the digit key as well
+    fn add_camera_viewpoints(
+        ui: &mut Ui,
+        camera_viewpoints: &mut CameraViewpoints,
+        is_playing_path: bool,
+        sender: &Sender<GuiEvent>,
+    ) {
+        let send_action = |action| {
+            let _ = sender.try_send(GuiEvent::CameraViewpointAction(action));
+        };
+        let mut is_changed = false;
+
+        ui.label("Bookmarks:");
+        let mut removed_bookmark = None;
+        for (index, bookmark) in camera_viewpoints.bookmarks.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                is_changed |= ui.text_edit_singleline(&mut bookmark.name).changed();
+
+                let hotkey_text = |hotkey: Option<u32>| {
+                    hotkey.map_or_else(|| "No hotkey".to_owned(), |hotkey| hotkey.to_string())
+                };
+                egui::ComboBox::from_id_salt(("bookmark_hotkey", index))
+                    .selected_text(hotkey_text(bookmark.hotkey))
+                    .show_ui(ui, |ui| {
+                        for hotkey in
+                            std::iter::once(None).chain((1..=MAX_BOOKMARK_HOTKEY).map(Some))
+                        {
+                            is_changed |= ui
+                                .selectable_value(&mut bookmark.hotkey, hotkey, hotkey_text(hotkey))
+                                .changed();
+                        }
+                    });
+
+                if ui.button("Go").clicked() {
+                    send_action(CameraViewpointAction::GoToBookmark(index));
+                }
+                if ui
+                    .button("Update")
+                    .on_hover_text("Moves the bookmark to the current viewpoint")
+                    .clicked()
+                {
+                    send_action(CameraViewpointAction::UpdateBookmark(index));
+                }
+                if ui.button("Remove").clicked() {
+                    removed_bookmark = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed_bookmark {
+            camera_viewpoints.bookmarks.remove(index);
+            is_changed = true;
+        }
+        if ui
+            .button("Add bookmark")
+            .on_hover_text("Ctrl+1 to Ctrl+9 stores the current viewpoint under the digit key")
+            .clicked()
+        {
+            send_action(CameraViewpointAction::AddBookmark);
+        }
+
+        ui.add(Separator::default().horizontal());
+
+        ui.label("Paths:");
+        let mut removed_path = None;
+        for (index, path) in camera_viewpoints.paths.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                is_changed |= ui.text_edit_singleline(&mut path.name).changed();
+                ui.label(format!("{} keyframes", path.keyframes.len()));
+            });
+            // In seconds
+            is_changed |= Self::add_float_slider(
+                ui,
+                Some("Duration".into()),
+                &mut path.duration,
+                0.5..=120.0,
+                STANDARD_SLIDER_SIZE,
+            );
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Add keyframe")
+                    .on_hover_text("Appends the current viewpoint to the path")
+                    .clicked()
+                {
+                    send_action(CameraViewpointAction::AddKeyframe { path_index: index });
+                }
+                if ui
+                    .add_enabled(
+                        !path.keyframes.is_empty(),
+                        Button::new("Remove last keyframe"),
+                    )
+                    .clicked()
+                {
+                    path.keyframes.pop();
+                    is_changed = true;
+                }
+                if ui
+                    .add_enabled(
+                        !is_playing_path && !path.keyframes.is_empty(),
+                        Button::new("Play"),
+                    )
+                    .on_hover_text("Moving the camera stops the playback")
+                    .clicked()
+                {
+                    send_action(CameraViewpointAction::PlayPath(index));
+                }
+                if ui.button("Remove").clicked() {
+                    removed_path = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed_path {
+            camera_viewpoints.paths.remove(index);
+            is_changed = true;
+        }
+        if ui.button("Add path").clicked() {
+            let name = format!("Path {}", camera_viewpoints.paths.len() + 1);
+            camera_viewpoints.paths.push(CameraPath::new(name));
+            is_changed = true;
+        }
+
+        if is_changed {
+            let _ = sender.try_send(GuiEvent::CameraViewpointsChanged(camera_viewpoints.clone()));
+        }
+    }
+
+    /// Clicking on the image of a camera selects its object
+    fn add_camera_view_previews(
+        ui: &mut Ui,
+        camera_view_previews: &[CameraViewPreview],
+        sender: &Sender<GuiEvent>,
+    ) {
+        for preview in camera_view_previews {
+            let [width, height] = preview.size;
+            let size = Vec2::new(
+                CAMERA_VIEW_PREVIEW_WIDTH,
+                CAMERA_VIEW_PREVIEW_WIDTH * height as f32 / width as f32,
+            );
+            let response = ui
+                .add(Image::new(SizedTexture::new(preview.texture_id, size)).sense(Sense::click()))
+                .on_hover_text(format!("Camera of object {}", preview.object_id));
+            if response.clicked() {
+                let _ = sender.try_send(GuiEvent::ObjectSelected {
+                    object_id: preview.object_id,
+                    add_to_selection: false,
+                });
+            }
+            if ui
+                .button("Show on the selected objects")
+                .on_hover_text("Replaces the albedo texture of the selected objects with this view")
+                .clicked()
+            {
+                let _ = sender.try_send(GuiEvent::CameraViewShownOnSelection(preview.object_id));
+            }
+        }
+    }
+
     fn set_default_style_params(ui: &mut Ui) {
         ui.set_min_size(UI_MIN_SIZE);
     }
@@ -506,6 +1187,55 @@ impl Gui {
             current_frame_texture_view,
             screen_descriptor,
             &mut |ctx| {
+                egui::Window::new("Outliner").show(&ctx, |ui| {
+                    scroll_area::ScrollArea::vertical().show(ui, |ui| {
+                        Self::add_outliner(ui, &self.app_info, &self.sender);
+                        Self::add_snap_settings(ui, &mut self.app_info.snap_settings, &self.sender);
+                    });
+                });
+
+                if let Some(selection_rectangle) = self.app_info.selection_rectangle {
+                    Self::add_selection_rectangle(ctx, selection_rectangle);
+                }
+
+                egui::Window::new("History")
+                    .default_open(false)
+                    .show(&ctx, |ui| {
+                        scroll_area::ScrollArea::vertical().show(ui, |ui| {
+                            Self::add_history(ui, &self.app_info, &self.sender);
+                        });
+                    });
+
+                egui::Window::new("Camera")
+                    .default_open(false)
+                    .show(&ctx, |ui| {
+                        scroll_area::ScrollArea::vertical().show(ui, |ui| {
+                            Self::add_camera_mode(ui, &self.app_info, &self.sender);
+                            Self::add_camera_projection(ui, &self.app_info, &self.sender);
+                            ui.add(Separator::default().horizontal());
+                            Self::add_camera_viewpoints(
+                                ui,
+                                &mut self.app_info.camera_viewpoints,
+                                self.app_info.is_playing_camera_path,
+                                &self.sender,
+                            );
+                        });
+                    });
+
+                if !self.camera_view_previews.is_empty() {
+                    egui::Window::new("Camera views")
+                        .default_open(false)
+                        .show(&ctx, |ui| {
+                            scroll_area::ScrollArea::vertical().show(ui, |ui| {
+                                Self::add_camera_view_previews(
+                                    ui,
+                                    &self.camera_view_previews,
+                                    &self.sender,
+                                );
+                            });
+                        });
+                }
+
                 egui::Window::new("Settings page").show(&ctx, |ui| {
                     Self::set_default_style_params(ui);
 
@@ -515,6 +1245,32 @@ impl Gui {
                         ui.label(format!("Frame time: {frame_time_string}"));
                         ui.label(format!("FPS: {fps_string}"));
 
+                        let culling_stats = &self.app_info.culling_stats;
+                        match culling_stats.camera {
+                            Some(camera_culling) => {
+                                ui.label(format!(
+                                    "Objects drawn: {}/{} ({} culled)",
+                                    camera_culling.visible(),
+                                    camera_culling.total,
+                                    camera_culling.culled
+                                ));
+                            }
+                            None => {
+                                ui.label(format!(
+                                    "Objects submitted: {} (culled on the GPU)",
+                                    culling_stats.submitted
+                                ));
+                            }
+                        }
+                        if let Some(shadow_culling) = culling_stats.shadow {
+                            ui.label(format!(
+                                "Shadow draws: {}/{} ({} culled)",
+                                shadow_culling.visible(),
+                                shadow_culling.total,
+                                shadow_culling.culled
+                            ));
+                        }
+
                         if ui.button("Recompile shaders").clicked() {
                             let _ = self.sender.try_send(GuiEvent::RecompileShaders);
                         }
@@ -536,29 +1292,42 @@ impl Gui {
 
                         ui.add(Separator::default().horizontal());
 
-                        let button_response = Button::new("Change skybox").ui(ui);
-                        if button_response.clicked() {
-                            if let Some(file) = FileDialog::new()
-                                .add_filter("hdr environment map", &["hdr"])
-                                .pick_file()
-                            {
-                                println!("Some file was picked: {file:?}");
-                            }
-                        } else if button_response.hovered() {
-                            if self.dropped_file_handler.dropped_file.is_some() {
-                                let file_path =
-                                    self.dropped_file_handler.dropped_file.as_ref().unwrap();
-                                println!("Some file was fropped: {file_path:?}");
-                            }
-                        }
+                        Self::add_environment_settings(
+                            ui,
+                            &mut self.app_info.environment,
+                            &mut self.app_info.is_dragging_environment_slider,
+                            &self.sender,
+                            &mut self.dropped_file_handler.dropped_file,
+                        );
 
                         ui.add(Separator::default().horizontal());
 
-                        if Button::new("Save current level").ui(ui).clicked() {
-                            let _ = self
-                                .sender
-                                .try_send(GuiEvent::ButtonClicked(GuiButton::SaveLevel));
-                        }
+                        let level_name = self
+                            .app_info
+                            .level_path
+                            .as_ref()
+                            .and_then(|level_path| level_path.file_name())
+                            .map(|file_name| file_name.to_string_lossy().into_owned())
+                            .unwrap_or_else(|| "Untitled".into());
+                        let unsaved_marker = if self.app_info.has_unsaved_changes {
+                            "*"
+                        } else {
+                            ""
+                        };
+                        ui.label(format!("Level: {level_name}{unsaved_marker}"));
+
+                        ui.horizontal(|ui| {
+                            for (text, button) in [
+                                ("New", GuiButton::NewLevel),
+                                ("Open", GuiButton::OpenLevel),
+                                ("Save", GuiButton::SaveLevel),
+                                ("Save as", GuiButton::SaveLevelAs),
+                            ] {
+                                if Button::new(text).ui(ui).clicked() {
+                                    let _ = self.sender.try_send(GuiEvent::ButtonClicked(button));
+                                }
+                            }
+                        });
 
                         if let Some(result) = &self.app_info.recent_notification {
                             let color = if result.auto_remove_after_time {
@@ -612,9 +1381,128 @@ impl Gui {
                     "Saving level result: ".into(),
                 ));
             }
+            GuiUpdateEvent::LevelLoadResult(result) => {
+                self.app_info.recent_notification = Some(GuiNotification::from_result(
+                    result,
+                    "Loading level result: ".into(),
+                ));
+            }
+            GuiUpdateEvent::PrefabResult(result) => {
+                self.app_info.recent_notification = Some(GuiNotification::from_result(
+                    result,
+                    "Prefab result: ".into(),
+                ));
+            }
+            GuiUpdateEvent::ClipboardResult(result) => {
+                self.app_info.recent_notification = Some(GuiNotification::from_result(
+                    result,
+                    "Clipboard result: ".into(),
+                ));
+            }
+            GuiUpdateEvent::Environment(environment) => {
+                if !self.app_info.is_dragging_environment_slider {
+                    self.app_info.environment = environment;
+                }
+            }
+            GuiUpdateEvent::Level {
+                path,
+                has_unsaved_changes,
+            } => {
+                self.app_info.level_path = path;
+                self.app_info.has_unsaved_changes = has_unsaved_changes;
+            }
+            GuiUpdateEvent::CullingStats(culling_stats) => {
+                self.app_info.culling_stats = culling_stats;
+            }
+            GuiUpdateEvent::Outliner(outliner_items) => {
+                self.app_info.outliner_items = outliner_items;
+            }
+            GuiUpdateEvent::Selection {
+                object_ids,
+                pivot,
+                gizmo_mode,
+                gizmo_orientation,
+                rectangle,
+            } => {
+                self.app_info.selected_object_ids = object_ids;
+                self.app_info.selection_pivot = pivot;
+                self.app_info.gizmo_mode = gizmo_mode;
+                self.app_info.gizmo_orientation = gizmo_orientation;
+                self.app_info.selection_rectangle = rectangle;
+            }
+            GuiUpdateEvent::SnapSettings(snap_settings) => {
+                self.app_info.snap_settings = snap_settings;
+            }
+            GuiUpdateEvent::History {
+                entry_names,
+                applied_entry_count,
+            } => {
+                self.app_info.history_entry_names = entry_names;
+                self.app_info.applied_history_entry_count = applied_entry_count;
+            }
+            GuiUpdateEvent::CameraViewpoints {
+                camera_viewpoints,
+                is_playing_path,
+            } => {
+                self.app_info.camera_viewpoints = camera_viewpoints;
+                self.app_info.is_playing_camera_path = is_playing_path;
+            }
+            GuiUpdateEvent::CameraProjection(projection) => {
+                self.app_info.camera_projection = projection;
+            }
+            GuiUpdateEvent::CameraMode(camera_mode) => {
+                self.app_info.camera_mode = camera_mode;
+            }
         };
     }
 
+    /// Registers the images of the camera components (by the id of their object) with the UI renderer, and frees the
+    /// images of the removed cameras
+    pub fn update_camera_view_textures<'a>(
+        &mut self,
+        device: &wgpu::Device,
+        camera_view_textures: impl Iterator<Item = (u32, &'a SampledTexture)>,
+    ) {
+        let mut camera_view_previews = vec![];
+        for (object_id, texture) in camera_view_textures {
+            let size = texture.texture.size();
+            let size = [size.width, size.height];
+            let registered_preview = self
+                .camera_view_previews
+                .iter()
+                .position(|preview| preview.object_id == object_id && preview.size == size);
+
+            camera_view_previews.push(match registered_preview {
+                Some(index) => self.camera_view_previews.swap_remove(index),
+                None => CameraViewPreview {
+                    object_id,
+                    texture_id: self.renderer.register_native_texture(device, &texture.view),
+                    size,
+                },
+            });
+        }
+
+        for preview in self.camera_view_previews.drain(..) {
+            self.renderer.free_texture(&preview.texture_id);
+        }
+        camera_view_previews.sort_by_key(|preview| preview.object_id);
+        self.camera_view_previews = camera_view_previews;
+    }
+
+    /// The editor operations driven by the pointer (eg. dragging a slider) last until this is false
+    pub fn is_using_pointer(&self) -> bool {
+        self.renderer.is_using_pointer()
+    }
+
+    /// The system clipboard, shared with the text fields of the GUI
+    pub fn get_clipboard_text(&mut self) -> Option<String> {
+        self.renderer.get_clipboard_text()
+    }
+
+    pub fn set_clipboard_text(&mut self, text: String) {
+        self.renderer.set_clipboard_text(text);
+    }
+
     pub fn handle_event(
         &mut self,
         window: &winit::window::Window,
@@ -46,6 +46,34 @@ impl EguiRenderer {
         self.state.on_window_event(window, &event)
     }
 
+    /// True while a widget is being dragged (eg. a slider), or the pointer is pressed on the UI
+    pub fn is_using_pointer(&self) -> bool {
+        self.state.egui_ctx().is_using_pointer()
+    }
+
+    pub fn get_clipboard_text(&mut self) -> Option<String> {
+        self.state.clipboard_text()
+    }
+
+    pub fn set_clipboard_text(&mut self, text: String) {
+        self.state.set_clipboard_text(text);
+    }
+
+    /// Makes a texture rendered by the app drawable on the UI. It has to be registered again if the texture is
+    /// recreated
+    pub fn register_native_texture(
+        &mut self,
+        device: &Device,
+        texture_view: &TextureView,
+    ) -> TextureId {
+        self.renderer
+            .register_native_texture(device, texture_view, wgpu::FilterMode::Linear)
+    }
+
+    pub fn free_texture(&mut self, texture_id: &TextureId) {
+        self.renderer.free_texture(texture_id);
+    }
+
     pub fn draw(
         &mut self,
         device: &Device,
@@ -0,0 +1,239 @@
+use std::f32::consts::FRAC_PI_2;
+
+use anyhow::anyhow;
+use glam::{EulerRot, Quat};
+use serde_json::{json, Value};
+
+/// The version written into the saved levels. Changing the level format requires a new version and a migration
+/// from the previous one
+pub const CURRENT_LEVEL_VERSION: u32 = 2;
+
+type Migration = fn(Value) -> anyhow::Result<Value>;
+
+/// `MIGRATIONS[n]` upgrades a level from version n to version n + 1
+const MIGRATIONS: [Migration; CURRENT_LEVEL_VERSION as usize] =
+    [migrate_v0_to_v1, migrate_v1_to_v2];
+
+/// Upgrades a level of any known version to the current format, so it can be deserialized as `LevelFileContent`
+pub fn migrate_to_current_version(mut level: Value) -> anyhow::Result<Value> {
+    let version = get_version(&level)?;
+    if version > CURRENT_LEVEL_VERSION {
+        return Err(anyhow!(
+            "Level version {version} is newer than the latest supported version {CURRENT_LEVEL_VERSION}"
+        ));
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        level = migration(level)?;
+    }
+    level["version"] = CURRENT_LEVEL_VERSION.into();
+
+    Ok(level)
+}
+
+/// The levels saved before the version was introduced don't have a version field. They are told apart by their
+/// structure
+fn get_version(level: &Value) -> anyhow::Result<u32> {
+    let Some(level) = level.as_object() else {
+        return Err(anyhow!("The level is not a JSON object"));
+    };
+
+    match level.get("version") {
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| anyhow!("Invalid level version {version}")),
+        None if level.contains_key("objects") => Ok(0),
+        None => Ok(1),
+    }
+}
+
+fn take_field(value: &mut Value, key: &str) -> anyhow::Result<Value> {
+    value
+        .get_mut(key)
+        .map(Value::take)
+        .ok_or_else(|| anyhow!("Missing field {key:?}"))
+}
+
+/// Version 0 stored the renderables in `objects` and every light in `lights`. Since version 1 the lights are objects
+/// as well: point lights are world objects with a light component, directional lights are omnipresent objects
+fn migrate_v0_to_v1(mut level: Value) -> anyhow::Result<Value> {
+    let mut world_objects = vec![];
+    for mut object in serde_json::from_value::<Vec<Value>>(take_field(&mut level, "objects")?)? {
+        let mut description = take_field(&mut object, "description")?;
+        let model_descriptor = take_field(&mut description, "model_descriptor")?;
+        let mut rendering_options = take_field(&mut description, "rendering_options")?;
+
+        // The pipeline used to be chosen from the material, now it's part of the rendering options
+        let pbr_resource_type = if model_descriptor
+            .pointer("/material_descriptor/Flat")
+            .is_some()
+        {
+            "FlatParameters"
+        } else {
+            "Textures"
+        };
+        if let Some(rendering_options) = rendering_options.as_object_mut() {
+            rendering_options.remove("needs_projection");
+            rendering_options.insert("pbr_resource_type".into(), pbr_resource_type.into());
+        }
+
+        world_objects.push(json!({
+            "components": [{
+                "Renderable": {
+                    "model_descriptor": model_descriptor,
+                    "rendering_options": rendering_options,
+                },
+            }],
+            "transform": take_field(&mut description, "transform")?,
+        }));
+    }
+
+    let mut omnipresent_objects = vec![];
+    for mut light in serde_json::from_value::<Vec<Value>>(take_field(&mut level, "lights")?)? {
+        if let Ok(mut point_light) = take_field(&mut light, "Point") {
+            world_objects.push(json!({
+                "components": [{
+                    "LightObject": {
+                        "light": { "color": take_field(&mut point_light, "color")? },
+                    },
+                }],
+                "transform": take_field(&mut point_light, "transform")?,
+            }));
+        } else if let Ok(directional_light) = take_field(&mut light, "Directional") {
+            omnipresent_objects.push(json!({
+                "components": [{ "DirectionalLight": directional_light }],
+            }));
+        } else {
+            return Err(anyhow!("Unknown kind of light {light}"));
+        }
+    }
+
+    level["world_objects"] = world_objects.into();
+    level["omnipresent_objects"] = omnipresent_objects.into();
+
+    Ok(level)
+}
+
+/// Version 2 added the environment settings, the camera bookmarks and paths, and the orthographic projection. The
+/// earlier levels get the defaults. The orientation of the camera became a quaternion: it used to be a (yaw, unused,
+/// pitch) tuple, the X axis was rotated by the pitch around Z and then by the yaw around Y. Now the camera looks along
+/// -Z and the up vector is part of the orientation. The prefab instances, the camera components and the materials
+/// showing a camera view are new as well, the earlier levels don't have any
+fn migrate_v1_to_v2(mut level: Value) -> anyhow::Result<Value> {
+    // Every earlier level used the same hardcoded environment map
+    level["environment"] = json!({
+        "skybox_path": "assets/textures/skybox/golf_course.hdr",
+        "intensity": 1.0,
+        "rotation": 0.0,
+    });
+    level["camera_viewpoints"] = json!({
+        "bookmarks": [],
+        "paths": [],
+    });
+
+    let Some(camera) = level.get_mut("camera") else {
+        return Ok(level);
+    };
+
+    let (yaw, _, pitch) =
+        serde_json::from_value::<(f32, f32, f32)>(take_field(camera, "orientation")?)?;
+    camera["orientation"] =
+        serde_json::to_value(Quat::from_euler(EulerRot::YXZ, yaw - FRAC_PI_2, pitch, 0.0))?;
+    if let Some(camera) = camera.as_object_mut() {
+        camera.remove("up");
+    }
+    camera["projection"] = "Perspective".into();
+    camera["orthographic_height"] = 10.0.into();
+
+    Ok(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_without_a_version_are_recognized() {
+        assert_eq!(
+            get_version(&json!({ "objects": [], "lights": [] })).unwrap(),
+            0
+        );
+        assert_eq!(
+            get_version(&json!({ "world_objects": [], "omnipresent_objects": [] })).unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let error = migrate_to_current_version(json!({ "version": CURRENT_LEVEL_VERSION + 1 }))
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("newer than the latest supported version"));
+
+        assert!(migrate_to_current_version(json!({ "version": "first" })).is_err());
+        assert!(migrate_to_current_version(json!([])).is_err());
+    }
+
+    #[test]
+    fn lights_become_objects() {
+        let level = migrate_to_current_version(json!({
+            "objects": [],
+            "lights": [
+                { "Point": { "color": [1.0, 0.0, 0.0], "transform": { "position": [1.0, 2.0, 3.0] } } },
+                { "Directional": { "color": [1.0, 1.0, 1.0], "direction": [0.0, -1.0, 0.0] } },
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(level["version"], CURRENT_LEVEL_VERSION);
+        assert_eq!(
+            level["world_objects"][0]["components"][0]["LightObject"]["light"]["color"],
+            json!([1.0, 0.0, 0.0])
+        );
+        assert_eq!(
+            level["world_objects"][0]["transform"]["position"],
+            json!([1.0, 2.0, 3.0])
+        );
+        assert_eq!(
+            level["omnipresent_objects"][0]["components"][0]["DirectionalLight"]["direction"],
+            json!([0.0, -1.0, 0.0])
+        );
+    }
+
+    #[test]
+    fn older_levels_get_the_default_environment() {
+        let level = migrate_to_current_version(json!({
+            "world_objects": [],
+            "omnipresent_objects": [],
+        }))
+        .unwrap();
+
+        assert_eq!(level["environment"]["intensity"], 1.0);
+        assert_eq!(level["environment"]["rotation"], 0.0);
+        assert_eq!(level["camera_viewpoints"]["bookmarks"], json!([]));
+    }
+
+    #[test]
+    fn camera_orientation_becomes_a_quaternion() {
+        let (yaw, pitch) = (0.7_f32, -0.4_f32);
+        let level = migrate_to_current_version(json!({
+            "version": 1,
+            "world_objects": [],
+            "omnipresent_objects": [],
+            "camera": { "orientation": [yaw, 0.0, pitch], "up": [0.0, 1.0, 0.0] },
+        }))
+        .unwrap();
+
+        let orientation =
+            serde_json::from_value::<Quat>(level["camera"]["orientation"].clone()).unwrap();
+        let old_forward = Quat::from_rotation_y(yaw) * Quat::from_rotation_z(pitch) * glam::Vec3::X;
+        assert!((orientation * glam::Vec3::NEG_Z).abs_diff_eq(old_forward, 1e-5));
+        // There was no roll
+        assert!((orientation * glam::Vec3::X).y.abs() < 1e-5);
+        assert!(level["camera"].get("up").is_none());
+        assert_eq!(level["camera"]["projection"], "Perspective");
+    }
+}
@@ -1,16 +1,21 @@
 use std::collections::HashMap;
 
+use crossbeam_channel::Receiver;
+use glam::Mat4;
+use math_helpers::Frustum;
 use wgpu::util::align_to;
 use wgpu::{BindGroup, BufferAddress, CommandEncoder, Device};
 
+use crate::components::LightObjectComponent;
+use crate::frustum_culling::{cull_renderables, CullingStats};
 use crate::light_render_data::{GeneralLightRenderData, CUBE_FACE_COUNT};
 use crate::light_rendering_gpu_data::{LightCount, LightRenderData};
 use crate::lights::{DirectionalLight, DirectionalLightData};
 use crate::renderer::Renderer;
-use crate::world;
+use crate::world_events::{WorldEvent, WorldEventFilter};
 use crate::{
     lights::{Light, LightRawSmall, PointLightData, PointLightRenderData},
-    model::Renderable,
+    model::{DrawMode, Renderable},
     pipelines::{ShaderCompilationSuccess, ShadowRP},
     world::World,
 };
@@ -27,10 +32,11 @@ pub struct LightController {
     point_lights: HashMap<u32, PointLightData>,
     directional_lights: HashMap<u32, DirectionalLightData>,
     light_render_data: LightRenderData,
+    world_event_receiver: Receiver<WorldEvent>,
 }
 
 impl LightController {
-    pub fn new(device: &Device) -> LightController {
+    pub fn new(device: &Device, world: &mut World) -> LightController {
         // Make the `uniform_alignment` >= sizeof`LightRawSmall` and aligned to `min_uniform_buffer_offset_alignment`, as that is a requirement if we want to use dynamic offsets
         let matrix_size4x4 = core::mem::size_of::<LightRawSmall>() as u64;
         let uniform_buffer_alignment = {
@@ -43,12 +49,19 @@ impl LightController {
         let shadow_assets = Self::create_shadow_assets(device);
         let light_render_data = LightRenderData::new(device, uniform_buffer_alignment);
 
+        let world_event_receiver = world.subscribe(
+            WorldEventFilter::all()
+                .with_component::<LightObjectComponent>()
+                .with_component::<DirectionalLight>(),
+        );
+
         Self {
             shadow_rp,
             shadow_assets,
             point_lights: HashMap::new(),
             directional_lights: HashMap::new(),
             light_render_data,
+            world_event_receiver,
         }
     }
 
@@ -129,7 +142,11 @@ impl LightController {
                     point_light_render_data.light = point_light.clone();
                 }
             }
-            Light::Directional(directional_light) => todo!(),
+            Light::Directional(directional_light) => {
+                if let Some(directional_light_render_data) = self.directional_lights.get_mut(id) {
+                    directional_light_render_data.light = directional_light.clone();
+                }
+            }
         }
 
         self.light_render_data.update_light_gpu_data(
@@ -139,8 +156,24 @@ impl LightController {
         );
     }
 
-    pub fn remove_light(&mut self, device: &Device, id: u32) {
-        if let Some(light_data) = self.point_lights.remove(&id) {}
+    fn remove_light(&mut self, renderer: &Renderer, id: &u32) {
+        if let Some(point_light_data) = self.point_lights.remove(id) {
+            self.shadow_assets
+                .point_light_render_data
+                .free_resources_of_light(point_light_data.depth_texture_index);
+        } else if let Some(directional_light_data) = self.directional_lights.remove(id) {
+            self.shadow_assets
+                .directional_light_render_data
+                .free_resources_of_light(directional_light_data.depth_texture_index);
+        } else {
+            return;
+        }
+
+        self.update_shadow_assets(renderer);
+    }
+
+    fn has_light(&self, id: &u32) -> bool {
+        self.point_lights.contains_key(id) || self.directional_lights.contains_key(id)
     }
 
     fn create_shadow_assets(device: &Device) -> ShadowAssets {
@@ -155,7 +188,7 @@ impl LightController {
 
     fn get_light(world: &World, id: &u32) -> Option<Light> {
         if let Some(world_object) = world.get_world_object(id) {
-            Light::from_world_object(world_object)
+            Light::from_world_object(world_object, world.get_world_transform(id)?)
         } else if let Some(omnipresent_object) = world.get_omnipresent_object(id) {
             Light::from_omnipresent_object(omnipresent_object)
         } else {
@@ -169,43 +202,111 @@ impl LightController {
         renderer: &Renderer,
         world: &mut World,
     ) {
-        for modification in &world.dirty_objects {
-            if let Some(light) = Self::get_light(world, &modification.id) {
-                match modification.modification_type {
-                    world::ModificationType::Added => {
-                        self.add_light(renderer, modification.id, light);
-                    }
-                    world::ModificationType::Removed => todo!(),
-                    world::ModificationType::Modified => {
-                        self.update_light(renderer, &modification.id, &light);
-                    }
-                }
+        let events = self.world_event_receiver.try_iter().collect::<Vec<_>>();
+        for event in events {
+            let id = event.get_object_id();
+            match event {
+                WorldEvent::ObjectRemoved { .. } => self.remove_light(renderer, &id),
+                WorldEvent::ParentChanged { .. } | WorldEvent::MaterialChanged { .. } => {}
+                WorldEvent::ObjectAdded { .. }
+                | WorldEvent::ComponentAdded { .. }
+                | WorldEvent::ComponentRemoved { .. }
+                | WorldEvent::ComponentChanged { .. }
+                | WorldEvent::TransformChanged { .. } => match Self::get_light(world, &id) {
+                    // The light component might have been added after the object was added
+                    Some(light) if self.has_light(&id) => self.update_light(renderer, &id, &light),
+                    Some(light) => self.add_light(renderer, id, light),
+                    None => self.remove_light(renderer, &id),
+                },
             }
         }
     }
 
-    pub fn render_shadows<'a, T>(&self, encoder: &mut CommandEncoder, renderables: T)
+    /// The view-projection matrices of all the shadow maps (cube map faces in case of point lights),
+    /// in the order they are rendered in `render_shadows`
+    pub fn get_shadow_viewprojs(&self) -> Vec<Mat4> {
+        self.point_lights
+            .values()
+            .flat_map(|light| light.get_viewprojs())
+            .chain(
+                self.directional_lights
+                    .values()
+                    .map(|light| light.get_viewproj()),
+            )
+            .collect()
+    }
+
+    /// Renders the shadow maps of all the lights. If `gpu_culled_draw_modes` is given, then it contains the draw mode of
+    /// each shadow map in the order of `get_shadow_viewprojs` and the culling was already done on the GPU. Otherwise
+    /// only the shadow casters that are inside of the frustum of the given shadow map are rendered, and the culling
+    /// statistics are returned
+    pub fn render_shadows<'a, T>(
+        &self,
+        encoder: &mut CommandEncoder,
+        renderables: T,
+        gpu_culled_draw_modes: Option<&[DrawMode<'a>]>,
+    ) -> Option<CullingStats>
     where
-        T: Clone,
         T: Iterator<Item = &'a Renderable>,
     {
+        let shadow_casters = renderables
+            .filter(|renderable| renderable.description.rendering_options.cast_shadows)
+            .collect::<Vec<_>>();
+        let mut culling_stats = CullingStats::default();
+        let mut shadow_view_index = 0;
+
+        let mut render_shadow_view =
+            |encoder: &mut CommandEncoder,
+             view_proj: Mat4,
+             depth_target: &wgpu::TextureView,
+             light_bind_group_offset: u32| {
+                let gpu_culled_draw_mode =
+                    gpu_culled_draw_modes.map(|draw_modes| &draw_modes[shadow_view_index]);
+                shadow_view_index += 1;
+
+                if let Some(draw_mode) = gpu_culled_draw_mode {
+                    self.shadow_rp.render(
+                        encoder,
+                        shadow_casters.iter().copied(),
+                        &self.light_render_data.light_bind_group_viewproj_only,
+                        depth_target,
+                        light_bind_group_offset,
+                        draw_mode,
+                    );
+                } else {
+                    let visible_renderables = cull_renderables(
+                        shadow_casters.iter().copied(),
+                        &Frustum::from_view_proj(&view_proj),
+                        &mut culling_stats,
+                    );
+                    self.shadow_rp.render(
+                        encoder,
+                        visible_renderables.into_iter(),
+                        &self.light_render_data.light_bind_group_viewproj_only,
+                        depth_target,
+                        light_bind_group_offset,
+                        &DrawMode::Direct,
+                    );
+                }
+            };
+
         encoder.push_debug_group("Shadow rendering");
 
         {
             encoder.push_debug_group("Point shadows");
 
             for (light_index, light) in self.point_lights.values().enumerate() {
-                for (face_index, depth_target) in self
+                for (face_index, (depth_target, view_proj)) in self
                     .shadow_assets
                     .point_light_render_data
                     .get_depth_target_view(light.depth_texture_index)
                     .iter()
+                    .zip(light.get_viewprojs())
                     .enumerate()
                 {
-                    self.shadow_rp.render(
+                    render_shadow_view(
                         encoder,
-                        renderables.clone(),
-                        &self.light_render_data.light_bind_group_viewproj_only,
+                        view_proj,
                         depth_target,
                         (CUBE_FACE_COUNT * light_index + face_index) as u32
                             * self.light_render_data.uniform_buffer_alignment as u32,
@@ -227,10 +328,9 @@ impl LightController {
                     .shadow_assets
                     .directional_light_render_data
                     .get_depth_target_view(light.depth_texture_index);
-                self.shadow_rp.render(
+                render_shadow_view(
                     encoder,
-                    renderables.clone(),
-                    &self.light_render_data.light_bind_group_viewproj_only,
+                    light.get_viewproj(),
                     &target_view[0],
                     (base_offset_after_point_lights
                         + light_index * self.light_render_data.uniform_buffer_alignment as usize)
@@ -242,6 +342,8 @@ impl LightController {
         }
 
         encoder.pop_debug_group();
+
+        gpu_culled_draw_modes.is_none().then_some(culling_stats)
     }
 
     pub fn try_recompile_shaders(
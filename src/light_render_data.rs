@@ -151,6 +151,11 @@ impl<const DEPTH_TARGET_FACE_COUNT: usize> GeneralLightRenderData<DEPTH_TARGET_F
         }
     }
 
+    /// The resources are kept, they are given to the next light that is added
+    pub fn free_resources_of_light(&mut self, index: usize) {
+        self.free_indices.push(index);
+    }
+
     pub fn get_bind_group(&self) -> &BindGroup {
         &self.render_resources.bind_group
     }
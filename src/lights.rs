@@ -5,7 +5,8 @@ use glam::{Mat4, Vec3, Vec3Swizzles};
 use math_helpers::reverse_z_matrix;
 
 use crate::{
-    components::TransformComponent,
+    component_storage::Component,
+    components::{LightObjectComponent, TransformComponent},
     world_object::{OmnipresentObject, WorldObject},
 };
 
@@ -28,10 +29,14 @@ pub enum Light {
 }
 
 impl Light {
-    pub fn from_world_object(world_object: &WorldObject) -> Option<Self> {
-        if let Some(light_component) = world_object.get_light_component() {
+    /// The transform of the object is relative to its parent, so the world transform has to be given separately
+    pub fn from_world_object(
+        world_object: &WorldObject,
+        world_transform: TransformComponent,
+    ) -> Option<Self> {
+        if let Some(light_component) = world_object.get_component::<LightObjectComponent>() {
             let light = Light::Point(PointLightRenderData {
-                transform: world_object.transform,
+                transform: world_transform,
                 color: light_component.light.color,
             });
             Some(light)
@@ -67,6 +72,7 @@ pub struct CommonLightParams {
     Default,
     Copy,
     Clone,
+    PartialEq,
     serde::Serialize,
     serde::Deserialize,
     ui_item_derive::UiDisplayable,
@@ -91,12 +97,14 @@ pub struct PointLightData {
 }
 
 #[repr(C)]
-#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, PartialEq)]
 pub struct DirectionalLight {
     pub direction: Vec3,
     pub color: Vec3,
 }
 
+impl Component for DirectionalLight {}
+
 pub struct DirectionalLightData {
     pub light: DirectionalLight,
     pub depth_texture_index: usize,
@@ -137,7 +145,8 @@ impl PointLightData {
         }
     }
 
-    pub fn get_viewprojs_raw(&self) -> Vec<LightRawSmall> {
+    /// The view-projection matrices of the 6 faces of the cube map the light renders its shadows into
+    pub fn get_viewprojs(&self) -> Vec<Mat4> {
         let proj = reverse_z_matrix()
             * glam::Mat4::perspective_rh(
                 consts::FRAC_PI_2,
@@ -165,6 +174,12 @@ impl PointLightData {
                 );
                 proj * view
             })
+            .collect::<Vec<_>>()
+    }
+
+    pub fn get_viewprojs_raw(&self) -> Vec<LightRawSmall> {
+        self.get_viewprojs()
+            .into_iter()
             .map(|view_proj| {
                 let mut position_and_far_plane_distance =
                     self.light.transform.get_position().xyzz();
@@ -245,7 +260,8 @@ impl DirectionalLightData {
         }
     }
 
-    pub fn get_viewprojs_raw(&self) -> LightRawSmall {
+    /// The view-projection matrix used when rendering the shadow map of the light
+    pub fn get_viewproj(&self) -> Mat4 {
         let direction_vec = Vec3::from(self.light.direction);
         let right = direction_vec.cross(Vec3::new(1.0, 0.0, 0.0));
         // In case of directional lights, the eye is set to a number, so that when we are rendering shadows
@@ -263,7 +279,11 @@ impl DirectionalLightData {
             NEAR_PLANE,
             self.light_params.far_plane,
         );
-        let view_proj = proj * view;
+        proj * view
+    }
+
+    pub fn get_viewprojs_raw(&self) -> LightRawSmall {
+        let view_proj = self.get_viewproj();
 
         let mut position_and_far_plane_distance = self.light.direction.xyzz();
         position_and_far_plane_distance.z = self.light_params.far_plane;
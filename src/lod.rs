@@ -0,0 +1,215 @@
+use std::time::{Duration, Instant};
+
+use crate::model::MeshDescriptor;
+
+fn default_generated_lod_count() -> u32 {
+    3
+}
+
+fn default_screen_size_thresholds() -> Vec<f32> {
+    vec![0.3, 0.12, 0.05]
+}
+
+/// The level selection expects the thresholds from the largest to the smallest, so they are sorted when loaded
+fn deserialize_screen_size_thresholds<'de, D>(deserializer: D) -> Result<Vec<f32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let mut thresholds = <Vec<f32> as serde::Deserialize>::deserialize(deserializer)?;
+    thresholds.sort_by(|a, b| b.total_cmp(a));
+    Ok(thresholds)
+}
+
+fn default_hysteresis() -> f32 {
+    0.1
+}
+
+fn default_shadow_screen_size_bias() -> f32 {
+    0.5
+}
+
+fn default_cross_fade_duration() -> f32 {
+    0.25
+}
+
+/// Describes the levels of detail of a renderable and when to switch between them.
+/// The first level is always the mesh of the renderable itself
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    ui_item_derive::UiDisplayable,
+    ui_item_derive::UiSettableNew,
+)]
+pub struct LodSettings {
+    /// Meshes of the less detailed levels, from the most to the least detailed. If empty, the levels are
+    /// generated from the mesh of the renderable when it's loaded
+    #[serde(default)]
+    pub authored_meshes: Vec<MeshDescriptor>,
+    /// How many levels to generate when there are no authored meshes. Less levels might be generated,
+    /// if the mesh can't be simplified any further
+    #[serde(default = "default_generated_lod_count")]
+    pub generated_lod_count: u32,
+    /// The next level is used when the screen size of the renderable goes below the threshold. The screen size
+    /// is the diameter of the bounding sphere relative to the height of the screen.
+    /// The thresholds are sorted in descending order when loaded
+    #[serde(
+        default = "default_screen_size_thresholds",
+        deserialize_with = "deserialize_screen_size_thresholds"
+    )]
+    pub screen_size_thresholds: Vec<f32>,
+    /// How far (relative to the threshold) the screen size has to move past a threshold before the level changes.
+    /// Avoids switching back and forth when the renderable is right at a threshold
+    #[serde(default = "default_hysteresis")]
+    #[ui_param(min = "0.0", max = "0.5")]
+    pub hysteresis: f32,
+    /// The screen size is multiplied by this when selecting the level for the shadow maps,
+    /// so less detailed meshes can be used for the shadows than for the camera
+    #[serde(default = "default_shadow_screen_size_bias")]
+    #[ui_param(min = "0.0", max = "2.0")]
+    pub shadow_screen_size_bias: f32,
+    /// Length of the dithered transition between two levels in seconds. 0 switches the levels immediately
+    #[serde(default = "default_cross_fade_duration")]
+    #[ui_param(min = "0.0", max = "2.0")]
+    pub cross_fade_duration: f32,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            authored_meshes: Vec::new(),
+            generated_lod_count: default_generated_lod_count(),
+            screen_size_thresholds: default_screen_size_thresholds(),
+            hysteresis: default_hysteresis(),
+            shadow_screen_size_bias: default_shadow_screen_size_bias(),
+            cross_fade_duration: default_cross_fade_duration(),
+        }
+    }
+}
+
+impl LodSettings {
+    /// Only the mesh of the renderable is used, no levels are generated
+    pub fn disabled() -> Self {
+        Self {
+            generated_lod_count: 0,
+            screen_size_thresholds: Vec::new(),
+            ..Default::default()
+        }
+    }
+
+    /// Did the settings change in a way, that requires loading the meshes again?
+    pub fn are_meshes_different(&self, other: &LodSettings) -> bool {
+        self.authored_meshes != other.authored_meshes
+            || (self.authored_meshes.is_empty()
+                && self.generated_lod_count != other.generated_lod_count)
+    }
+
+    /// Selects the level for the given screen size. The level only changes if the screen size is
+    /// further than the hysteresis from the threshold between the current and the new level
+    fn select_level(&self, screen_size: f32, level_count: usize, current_level: usize) -> usize {
+        let max_level = level_count.saturating_sub(1);
+        let level_with_threshold_scale = |scale: f32| {
+            self.screen_size_thresholds
+                .iter()
+                .filter(|threshold| screen_size < **threshold * scale)
+                .count()
+                .min(max_level)
+        };
+
+        let coarser_level = level_with_threshold_scale(1.0 - self.hysteresis);
+        if coarser_level > current_level {
+            return coarser_level;
+        }
+
+        let finer_level = level_with_threshold_scale(1.0 + self.hysteresis);
+        if finer_level < current_level {
+            return finer_level;
+        }
+
+        current_level.min(max_level)
+    }
+}
+
+/// The level of detail that a renderable is rendered with from one kind of view
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LodSelection {
+    pub level: usize,
+    /// The previous level and the time when the transition from it started, while cross-fading
+    pub fading_from: Option<(usize, Instant)>,
+}
+
+impl LodSelection {
+    /// How far the cross-fade is from the previous level. None if there is no cross-fade in progress
+    pub fn get_fade_progress(&self, settings: &LodSettings, now: Instant) -> Option<(usize, f32)> {
+        self.fading_from.map(|(previous_level, fade_start)| {
+            let progress = now.duration_since(fade_start).as_secs_f32()
+                / settings.cross_fade_duration.max(f32::EPSILON);
+            (previous_level, progress.min(1.0))
+        })
+    }
+}
+
+/// The selected levels of a renderable. The shadow passes select their level independently from the camera
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LodState {
+    pub camera: LodSelection,
+    pub shadow: LodSelection,
+}
+
+impl LodState {
+    /// Selects the levels for the given screen size. Returns true if the level of either the camera or
+    /// the shadow passes changed
+    pub fn update(
+        &mut self,
+        settings: &LodSettings,
+        level_count: usize,
+        screen_size: f32,
+        now: Instant,
+    ) -> bool {
+        if let Some((_, fade_start)) = self.camera.fading_from {
+            if now.duration_since(fade_start)
+                >= Duration::from_secs_f32(settings.cross_fade_duration.max(0.0))
+            {
+                self.camera.fading_from = None;
+            }
+        }
+
+        let camera_level = settings.select_level(screen_size, level_count, self.camera.level);
+        let camera_level_changed = camera_level != self.camera.level;
+        if camera_level_changed {
+            // If the level changes again during a cross-fade, fade from the level that is fully visible at the moment
+            self.camera.fading_from = if settings.cross_fade_duration > 0.0 {
+                Some((self.camera.level, now))
+            } else {
+                None
+            };
+            self.camera.level = camera_level;
+        }
+
+        // The shadow maps are not cross-faded, dithering in them would be visible as noise in the shadows
+        let shadow_level = settings.select_level(
+            screen_size * settings.shadow_screen_size_bias,
+            level_count,
+            self.shadow.level,
+        );
+        let shadow_level_changed = shadow_level != self.shadow.level;
+        self.shadow.level = shadow_level;
+
+        camera_level_changed || shadow_level_changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screen_size_thresholds_are_sorted_when_loaded() {
+        let lod_settings: LodSettings =
+            serde_json::from_str(r#"{"screen_size_thresholds": [0.05, 0.3, 0.12]}"#).unwrap();
+
+        assert_eq!(lod_settings.screen_size_thresholds, vec![0.3, 0.12, 0.05]);
+    }
+}
@@ -8,45 +8,59 @@ mod buffer;
 mod buffer_content;
 mod camera;
 mod camera_controller;
+mod camera_path;
+mod component_storage;
 mod components;
 mod cubemap_helpers;
 mod custom_event;
 mod diffuse_irradiance_renderer;
+mod editor_history;
+mod editor_settings;
 mod equirectangular_to_cubemap_renderer;
 mod file_loader;
 mod forward_renderer;
 mod frame_timer;
+mod frustum_culling;
 mod gbuffer_geometry_renderer;
 mod gizmo;
 mod gizmo_handler;
 mod global_params;
 mod gpu_buffer;
+mod gpu_culling;
 mod gui;
 mod gui_helpers;
 mod gui_settable_value;
+mod level_migrations;
 mod light_controller;
 mod light_render_data;
 mod light_rendering_gpu_data;
 mod lights;
+mod lod;
 mod mainloop;
 mod mappable_gpu_buffer;
 mod material;
 mod mipmap_generator;
 mod model;
 mod object_picker;
+mod persistent_id;
 mod pipelines;
 mod player_controller;
 mod pollable_gpu_buffer;
 mod post_process_manager;
+mod prefab;
 mod primitive_shapes;
 mod render_pipeline;
 mod render_pipeline_layout;
+mod render_view;
 mod renderer;
 mod resource_loader;
 mod skybox;
+#[cfg(test)]
+mod test_helpers;
 mod texture;
 mod vertex;
 mod world;
+mod world_events;
 mod world_loader;
 mod world_renderer;
 
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use winit::{
     application::ApplicationHandler,
     dpi::PhysicalSize,
@@ -18,15 +20,18 @@ struct MainApplicationState {
     app: Option<App>,
     frame_number: i32,
     event_loop_proxy: EventLoopProxy<CustomEvent>,
+    /// The level to open on startup, given with the `--level` command line argument
+    level_path: Option<PathBuf>,
 }
 
 impl MainApplicationState {
-    pub fn new(event_loop_proxy: EventLoopProxy<CustomEvent>) -> Self {
+    pub fn new(event_loop_proxy: EventLoopProxy<CustomEvent>, level_path: Option<PathBuf>) -> Self {
         Self {
             window: None,
             app: None,
             frame_number: 0,
             event_loop_proxy,
+            level_path,
         }
     }
 }
@@ -44,7 +49,11 @@ impl ApplicationHandler<CustomEvent> for MainApplicationState {
                     .with_title("Rendering is fun!"),
             )
             .unwrap();
-        let app = App::new(&new_window, self.event_loop_proxy.clone());
+        let app = App::new(
+            &new_window,
+            self.event_loop_proxy.clone(),
+            self.level_path.as_deref(),
+        );
         self.window = Some(new_window);
         self.app = Some(app);
     }
@@ -87,11 +96,26 @@ impl ApplicationHandler<CustomEvent> for MainApplicationState {
     }
 }
 
+/// Accepts both `--level <path>` and `--level=<path>`
+fn get_level_path_argument(mut arguments: impl Iterator<Item = String>) -> Option<PathBuf> {
+    while let Some(argument) = arguments.next() {
+        if argument == "--level" {
+            return arguments.next().map(PathBuf::from);
+        }
+        if let Some(level_path) = argument.strip_prefix("--level=") {
+            return Some(PathBuf::from(level_path));
+        }
+    }
+
+    None
+}
+
 pub fn run_main_loop() {
     simple_logger::init_with_level(log::Level::Warn).unwrap();
+    let level_path = get_level_path_argument(std::env::args().skip(1));
     let event_loop = EventLoop::<CustomEvent>::with_user_event().build().unwrap();
     let event_loop_proxy = event_loop.create_proxy();
-    let mut app_state = MainApplicationState::new(event_loop_proxy);
+    let mut app_state = MainApplicationState::new(event_loop_proxy, level_path);
 
     event_loop.run_app(&mut app_state).unwrap();
 }
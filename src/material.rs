@@ -1,7 +1,6 @@
 use std::{
     collections::{HashMap, HashSet},
-    ops::Index,
-    rc::Rc,
+    ops::{Deref, Index},
 };
 
 use glam::Vec3;
@@ -11,6 +10,7 @@ use crate::{
     bind_group_layout_descriptors,
     buffer::{create_bind_group_from_buffer_entire_binding_init, GpuBufferCreationOptions},
     model::PbrParameters,
+    persistent_id::PersistentId,
     renderer::Renderer,
     resource_loader::ResourceLoader,
     texture::{MaterialSource, SampledTexture, TextureSourceDescriptor, TextureUsage},
@@ -40,6 +40,31 @@ impl PbrMaterialDescriptor {
     pub fn from_color(color: Vec3) -> Self {
         Self::Flat(PbrParameters::new(color, 1.0, 0.0))
     }
+
+    /// The material with the image of the camera component of the object as its albedo. The other textures are kept,
+    /// a flat material becomes a texture material with default textures
+    pub fn with_camera_view_albedo(&self, camera_id: PersistentId) -> Self {
+        let mut textures = match self {
+            PbrMaterialDescriptor::Texture(textures) => textures.clone(),
+            PbrMaterialDescriptor::Flat(_) => vec![],
+        };
+        textures.retain(|texture| texture.usage != TextureUsage::Albedo);
+        textures.push(TextureSourceDescriptor {
+            source: MaterialSource::CameraView(camera_id),
+            usage: TextureUsage::Albedo,
+        });
+
+        PbrMaterialDescriptor::Texture(textures)
+    }
+
+    pub fn shows_camera_view(&self) -> bool {
+        match self {
+            PbrMaterialDescriptor::Texture(textures) => textures
+                .iter()
+                .any(|texture| matches!(texture.source, MaterialSource::CameraView(_))),
+            PbrMaterialDescriptor::Flat(_) => false,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -48,9 +73,9 @@ pub struct MaterialRenderData {
 }
 
 impl MaterialRenderData {
-    pub fn new(
+    pub fn new<T: Deref<Target = SampledTexture>>(
         device: &wgpu::Device,
-        textures: &HashMap<TextureUsage, Rc<SampledTexture>>,
+        textures: &HashMap<TextureUsage, T>,
     ) -> Self {
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &device.create_bind_group_layout(&bind_group_layout_descriptors::PBR_TEXTURE),
@@ -98,6 +123,17 @@ impl MaterialRenderData {
         renderer: &Renderer,
         textures: &Vec<TextureSourceDescriptor>,
         resource_loader: &ResourceLoader,
+    ) -> anyhow::Result<Self> {
+        Self::from_textures_with_camera_views(renderer, textures, resource_loader, &HashMap::new())
+    }
+
+    /// The textures showing a camera view get the image of the view from `camera_view_textures`, by the persistent ID
+    /// of the camera object. The views that are not rendered yet are replaced by the default texture
+    pub fn from_textures_with_camera_views(
+        renderer: &Renderer,
+        textures: &[TextureSourceDescriptor],
+        resource_loader: &ResourceLoader,
+        camera_view_textures: &HashMap<PersistentId, &SampledTexture>,
     ) -> anyhow::Result<Self> {
         let mut texture_map = HashMap::new();
 
@@ -126,7 +162,19 @@ impl MaterialRenderData {
             }
         }
 
-        Ok(Self::new(&renderer.device, &texture_map))
+        let mut bound_textures = texture_map
+            .iter()
+            .map(|(usage, texture)| (*usage, texture.as_ref()))
+            .collect::<HashMap<_, _>>();
+        for texture_desc in textures {
+            if let MaterialSource::CameraView(camera_id) = &texture_desc.source {
+                if let Some(camera_view_texture) = camera_view_textures.get(camera_id) {
+                    bound_textures.insert(texture_desc.usage, *camera_view_texture);
+                }
+            }
+        }
+
+        Ok(Self::new(&renderer.device, &bound_textures))
     }
 
     pub fn from_flat_parameters(device: &wgpu::Device, pbr_parameters: &PbrParameters) -> Self {
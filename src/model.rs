@@ -1,15 +1,19 @@
-use std::{collections::HashMap, path::PathBuf, rc::Rc};
+use std::{collections::HashMap, path::PathBuf, rc::Rc, time::Instant};
 
 use glam::{Vec2, Vec3};
+use math_helpers::{Aabb, BoundingSphere, Frustum, Line};
 use serde::{Deserialize, Serialize};
 use wgpu::{util::DeviceExt, Device, Queue, RenderPass};
 
 use crate::{
-    components::TransformComponent,
+    components::{TransformComponent, TransformComponentRaw},
+    gpu_culling::IndirectDrawView,
+    lod::{LodSelection, LodSettings, LodState},
     material::{MaterialRenderData, PbrMaterialDescriptor},
+    persistent_id::PersistentId,
     renderer::Renderer,
     resource_loader::{PrimitiveShape, ResourceLoader},
-    texture::TextureUsage,
+    texture::{SampledTexture, TextureUsage},
     vertex::VertexRawWithTangents,
 };
 
@@ -179,7 +183,11 @@ pub struct ModelDescriptor {
 pub struct RenderableDescription {
     pub model_descriptor: ModelDescriptor,
     pub rendering_options: ModelRenderingOptions,
+    pub lod_settings: LodSettings,
     pub transform: TransformComponent,
+    /// Only drawn for the editor (eg. the gizmo handles), so it's left out of the views of the camera components
+    #[serde(default)]
+    pub is_transient: bool,
 }
 
 /// A part of a renderable object. If a renderable consists of multiple parts, then each part is described
@@ -192,6 +200,19 @@ pub struct RenderablePart {
     pub material_render_data: MaterialRenderData,
     /// Transformation relative to the parent renderable
     pub local_transform: TransformComponent,
+    /// The less detailed versions of `primitive`, from the most to the least detailed
+    pub lod_primitives: Vec<Rc<Primitive>>,
+}
+
+impl RenderablePart {
+    /// The primitive of the given level of detail. If the part has less levels, then its least detailed one is used
+    pub fn get_primitive(&self, lod_level: usize) -> &Primitive {
+        if lod_level == 0 || self.lod_primitives.is_empty() {
+            &self.primitive
+        } else {
+            &self.lod_primitives[(lod_level - 1).min(self.lod_primitives.len() - 1)]
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -203,6 +224,12 @@ pub struct Renderable {
     // Contains the data about the instances. The number of them and the transformation of each instance
     // Currently no instancing is used, so this will always contain a single transform
     pub instance_data: BufferWithLength,
+
+    /// World space bounding volumes of all the parts of the renderable. Kept up to date with the transform
+    pub bounding_box: Aabb,
+    pub bounding_sphere: BoundingSphere,
+
+    pub lod_state: LodState,
 }
 
 #[derive(
@@ -228,12 +255,25 @@ impl Default for MeshDescriptor {
     }
 }
 
+/// Where the arguments of the draw calls come from when rendering a renderable
+#[derive(Clone, Copy)]
+pub enum DrawMode<'a> {
+    /// The draw calls are issued directly, the CPU decides what is drawn
+    Direct,
+    /// The draw arguments were written by the GPU culling pass
+    Indirect(IndirectDrawView<'a>),
+}
+
 #[derive(Debug)]
 pub struct BufferWithLength {
     pub buffer: wgpu::Buffer,
     pub count: u32,
 }
 
+/// While cross-fading between two levels of detail, the level that is fading out is drawn with the second
+/// instance in the instance buffer, which has the opposite fade value
+const FADING_OUT_INSTANCE_INDEX: u32 = 1;
+
 impl Renderable {
     pub fn new(
         renderable_description: RenderableDescription,
@@ -244,37 +284,265 @@ impl Renderable {
         let instance_data =
             create_instance_buffer(&renderable_description.transform, object_id, device);
 
+        let (bounding_box, bounding_sphere) =
+            Self::calculate_world_bounds(&renderable_parts, &renderable_description.transform);
+
         Self {
             id: object_id,
             description: renderable_description,
             renderable_parts,
             instance_data,
+            bounding_box,
+            bounding_sphere,
+            lod_state: LodState::default(),
+        }
+    }
+
+    /// The number of levels of detail, which is the number of levels of the part with the most levels
+    pub fn get_lod_count(&self) -> usize {
+        1 + self
+            .renderable_parts
+            .iter()
+            .map(|part| part.lod_primitives.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Selects the levels of detail for the given screen size and updates the cross-fade.
+    /// Returns true if the level of either the camera or the shadow passes changed
+    pub fn update_lod(&mut self, queue: &Queue, screen_size: f32, now: Instant) -> bool {
+        let was_fading = self.lod_state.camera.fading_from.is_some();
+        let lod_count = self.get_lod_count();
+        let level_changed =
+            self.lod_state
+                .update(&self.description.lod_settings, lod_count, screen_size, now);
+
+        if was_fading || self.lod_state.camera.fading_from.is_some() {
+            self.write_instance_data(queue, self.id, now);
+        }
+
+        level_changed
+    }
+
+    fn write_instance_data(&self, queue: &Queue, object_id: u32, now: Instant) {
+        let lod_fade = self
+            .lod_state
+            .camera
+            .get_fade_progress(&self.description.lod_settings, now)
+            .map_or(0.0, |(_, progress)| {
+                // A fade of 0 would mean that there is no cross-fade, so both levels would be fully visible
+                progress.max(f32::EPSILON)
+            });
+
+        queue.write_buffer(
+            &self.instance_data.buffer,
+            0,
+            bytemuck::cast_slice(&create_instance_data(
+                &self.description.transform,
+                object_id,
+                lod_fade,
+            )),
+        );
+    }
+
+    /// The closest point where the ray hits the most detailed level of the parts. Returns the line parameter of the
+    /// hit and the world space normal of the surface
+    pub fn intersect_ray(&self, ray: &Line) -> Option<(f32, Vec3)> {
+        let model_matrix = self.description.transform.get_model_matrix();
+
+        self.renderable_parts
+            .iter()
+            .filter_map(|part| {
+                let inverse_matrix =
+                    (model_matrix * part.local_transform.get_model_matrix()).inverse();
+                // The direction is not normalized, so the line parameter is the same in model space
+                let model_space_ray = Line {
+                    position: inverse_matrix.transform_point3(ray.position),
+                    direction: inverse_matrix.transform_vector3(ray.direction),
+                };
+                let (distance, normal) = part.primitive.intersect_ray(&model_space_ray)?;
+                let world_normal = inverse_matrix
+                    .transpose()
+                    .transform_vector3(normal)
+                    .normalize();
+
+                Some((distance, world_normal))
+            })
+            .min_by(|(distance1, _), (distance2, _)| distance1.total_cmp(distance2))
+    }
+
+    fn calculate_world_bounds(
+        renderable_parts: &[RenderablePart],
+        transform: &TransformComponent,
+    ) -> (Aabb, BoundingSphere) {
+        let model_matrix = transform.get_model_matrix();
+
+        let part_bounds = renderable_parts
+            .iter()
+            .map(|part| {
+                let part_matrix = model_matrix * part.local_transform.get_model_matrix();
+                (
+                    part.primitive.bounding_box.transform(&part_matrix),
+                    part.primitive.bounding_sphere.transform(&part_matrix),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        match part_bounds.as_slice() {
+            [] => (
+                Aabb::new(transform.get_position(), transform.get_position()),
+                BoundingSphere::new(transform.get_position(), 0.0),
+            ),
+            // The common case: there is a single part, we can use its bounding volumes directly
+            [(bounding_box, bounding_sphere)] => (*bounding_box, *bounding_sphere),
+            [(first_bounding_box, _), rest @ ..] => {
+                let bounding_box = rest.iter().fold(
+                    *first_bounding_box,
+                    |bounding_box, (part_bounding_box, _)| bounding_box.union(part_bounding_box),
+                );
+                let center = bounding_box.center();
+                let radius = part_bounds.iter().fold(0.0_f32, |radius, (_, sphere)| {
+                    radius.max(center.distance(sphere.center) + sphere.radius)
+                });
+                (bounding_box, BoundingSphere::new(center, radius))
+            }
         }
     }
 
+    /// Is any part of the renderable inside of the frustum?
+    pub fn is_inside_frustum(&self, frustum: &Frustum) -> bool {
+        // The sphere test is cheaper and rejects most of the objects, only do the box test if it passes
+        frustum.intersects_sphere(&self.bounding_sphere)
+            && frustum.intersects_aabb(&self.bounding_box)
+    }
+
+    /// Renders the renderable with the level of detail selected for the camera. While cross-fading, the previous
+    /// level is rendered as well
     pub fn render<'a>(
         &'a self,
         render_pass: &mut RenderPass<'a>,
         material_group_index: Option<u32>,
+        draw_mode: &DrawMode<'a>,
     ) {
-        for part in &self.renderable_parts {
+        self.render_lod(
+            render_pass,
+            material_group_index,
+            draw_mode,
+            &self.lod_state.camera,
+        );
+    }
+
+    /// Renders the renderable with the level of detail selected for the shadow passes
+    pub fn render_shadow_caster<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        draw_mode: &DrawMode<'a>,
+    ) {
+        self.render_lod(render_pass, None, draw_mode, &self.lod_state.shadow);
+    }
+
+    fn render_lod<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        material_group_index: Option<u32>,
+        draw_mode: &DrawMode<'a>,
+        lod_selection: &LodSelection,
+    ) {
+        let mut part_index = 0;
+        while part_index < self.renderable_parts.len() {
+            let part = &self.renderable_parts[part_index];
             if let Some(material_group_index) = material_group_index {
                 part.material_render_data
                     .bind_render_pass(render_pass, material_group_index);
             }
 
-            render_pass.set_vertex_buffer(0, part.primitive.vertex_buffer.slice(..));
             render_pass.set_vertex_buffer(1, self.instance_data.buffer.slice(..));
-            render_pass.set_index_buffer(
-                part.primitive.index_data.buffer.slice(..),
-                wgpu::IndexFormat::Uint32,
-            );
-            render_pass.draw_indexed(
-                0..part.primitive.index_data.count,
-                0,
-                0..self.instance_data.count,
-            );
+
+            let primitive = part.get_primitive(lod_selection.level);
+            Self::bind_primitive(render_pass, primitive);
+
+            let indirect_draw = match draw_mode {
+                DrawMode::Direct => None,
+                DrawMode::Indirect(indirect_draw_view) => indirect_draw_view
+                    .get_draw_args_offset(self.id, part_index)
+                    .map(|offset| (indirect_draw_view, offset)),
+            };
+
+            // Renderables that were added after the culling pass ran are drawn directly
+            let drawn_part_count = if let Some((indirect_draw_view, offset)) = indirect_draw {
+                let batch_size = self.get_multi_draw_batch_size(
+                    part_index,
+                    material_group_index,
+                    indirect_draw_view,
+                    lod_selection,
+                );
+                if batch_size > 1 {
+                    render_pass.multi_draw_indexed_indirect(
+                        indirect_draw_view.get_buffer(),
+                        offset,
+                        batch_size as u32,
+                    );
+                } else {
+                    render_pass.draw_indexed_indirect(indirect_draw_view.get_buffer(), offset);
+                }
+                batch_size
+            } else {
+                render_pass.draw_indexed(
+                    0..primitive.index_data.count,
+                    0,
+                    0..self.instance_data.count,
+                );
+                1
+            };
+
+            // The level that is fading out only lasts for a couple of frames, so it's always drawn directly
+            if let Some((previous_level, _)) = lod_selection.fading_from {
+                let previous_primitive = part.get_primitive(previous_level);
+                Self::bind_primitive(render_pass, previous_primitive);
+                render_pass.draw_indexed(
+                    0..previous_primitive.index_data.count,
+                    0,
+                    FADING_OUT_INSTANCE_INDEX..FADING_OUT_INSTANCE_INDEX + 1,
+                );
+            }
+
+            part_index += drawn_part_count;
+        }
+    }
+
+    /// How many parts, starting with the given one, can be drawn with a single multi draw call. The draw arguments
+    /// of the parts are next to each other, so the parts after the first one can be merged into its draw as long as
+    /// nothing has to be bound between them: no material is bound and they use the same primitive
+    fn get_multi_draw_batch_size(
+        &self,
+        first_part_index: usize,
+        material_group_index: Option<u32>,
+        indirect_draw_view: &IndirectDrawView,
+        lod_selection: &LodSelection,
+    ) -> usize {
+        if !indirect_draw_view.supports_multi_draw()
+            || material_group_index.is_some()
+            || lod_selection.fading_from.is_some()
+        {
+            return 1;
         }
+
+        let first_primitive =
+            self.renderable_parts[first_part_index].get_primitive(lod_selection.level);
+        self.renderable_parts[first_part_index..]
+            .iter()
+            .take_while(|part| {
+                std::ptr::eq(part.get_primitive(lod_selection.level), first_primitive)
+            })
+            .count()
+    }
+
+    fn bind_primitive<'a>(render_pass: &mut RenderPass<'a>, primitive: &'a Primitive) {
+        render_pass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(
+            primitive.index_data.buffer.slice(..),
+            wgpu::IndexFormat::Uint32,
+        );
     }
 
     pub fn update_transform_render_state(
@@ -283,13 +551,12 @@ impl Renderable {
         new_transform: &TransformComponent,
         object_id: u32,
     ) {
-        queue.write_buffer(
-            &self.instance_data.buffer,
-            0,
-            bytemuck::cast_slice(&[new_transform.to_raw(object_id)]),
-        );
-        self.instance_data.count = 1;
         self.description.transform = new_transform.clone();
+        self.write_instance_data(queue, object_id, Instant::now());
+        self.instance_data.count = 1;
+
+        (self.bounding_box, self.bounding_sphere) =
+            Self::calculate_world_bounds(&self.renderable_parts, new_transform);
     }
 
     pub fn update_material_render_state(
@@ -323,12 +590,43 @@ impl Renderable {
 
         self.description.model_descriptor.material_descriptor = new_material.clone();
     }
+
+    /// Recreates the material with the current images of the camera views it shows
+    pub fn bind_camera_views(
+        &mut self,
+        renderer: &Renderer,
+        resource_loader: &ResourceLoader,
+        camera_view_textures: &HashMap<PersistentId, &SampledTexture>,
+    ) {
+        let PbrMaterialDescriptor::Texture(texture_sources) =
+            &self.description.model_descriptor.material_descriptor
+        else {
+            return;
+        };
+
+        for part in &mut self.renderable_parts {
+            match MaterialRenderData::from_textures_with_camera_views(
+                renderer,
+                texture_sources,
+                resource_loader,
+                camera_view_textures,
+            ) {
+                Ok(material) => part.material_render_data = material,
+                Err(error) => log::error!("Failed to bind the camera views: {error}"),
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Primitive {
     pub vertex_buffer: wgpu::Buffer,
     pub index_data: BufferWithLength,
+    /// Bounding volumes of the vertices in model space
+    pub bounding_box: Aabb,
+    pub bounding_sphere: BoundingSphere,
+    /// The triangles in model space. The vertices are only on the GPU, this copy is kept for casting rays
+    pub triangles: Vec<[Vec3; 3]>,
 }
 
 impl Primitive {
@@ -337,6 +635,24 @@ impl Primitive {
         render_pass.set_index_buffer(self.index_data.buffer.slice(..), wgpu::IndexFormat::Uint32);
         render_pass.draw_indexed(0..self.index_data.count, 0, 0..1);
     }
+
+    pub fn get_triangles(positions: &[Vec3], indices: &[u32]) -> Vec<[Vec3; 3]> {
+        indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|corner| positions[triangle[corner] as usize]))
+            .collect()
+    }
+
+    /// The closest triangle hit by the model space ray, with the line parameter of the hit and the normal of the
+    /// triangle
+    pub fn intersect_ray(&self, ray: &Line) -> Option<(f32, Vec3)> {
+        ray.intersect_aabb(&self.bounding_box)?;
+
+        self.triangles
+            .iter()
+            .filter_map(|triangle| ray.intersect_triangle(triangle))
+            .min_by(|(distance1, _), (distance2, _)| distance1.total_cmp(distance2))
+    }
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -344,6 +660,19 @@ pub struct InstanceData {
     pub instances: Vec<TransformComponent>,
 }
 
+/// The instance used for drawing normally (or the level of detail that is fading in), followed by the instance
+/// used for the level of detail that is fading out
+fn create_instance_data(
+    transform: &TransformComponent,
+    object_id: u32,
+    lod_fade: f32,
+) -> [TransformComponentRaw; 2] {
+    [
+        transform.to_raw_with_lod_fade(object_id, lod_fade),
+        transform.to_raw_with_lod_fade(object_id, -lod_fade),
+    ]
+}
+
 pub fn create_instance_buffer(
     transform: &TransformComponent,
     object_id: u32,
@@ -351,7 +680,7 @@ pub fn create_instance_buffer(
 ) -> BufferWithLength {
     let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("Square Instance Buffer"),
-        contents: bytemuck::cast_slice(&[transform.to_raw(object_id)]),
+        contents: bytemuck::cast_slice(&create_instance_data(transform, object_id, 0.0)),
         usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
     });
 
@@ -515,6 +844,9 @@ impl Primitive {
                 count: indices.len() as u32,
             },
             vertex_buffer,
+            bounding_box: Aabb::from_points(positions),
+            bounding_sphere: BoundingSphere::from_points(positions),
+            triangles: Self::get_triangles(positions, indices),
         }
     }
 }
@@ -1,13 +1,14 @@
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 
 use wgpu::{
-    BindGroup, CommandEncoder, Device, Extent3d, ImageCopyTexture, RenderPassColorAttachment,
-    RenderPassDepthStencilAttachment, TextureAspect, TextureDimension, TextureFormat,
-    TextureUsages, TextureView,
+    BindGroup, BindGroupDescriptor, CommandEncoder, Device, Extent3d, ImageCopyTexture,
+    RenderPassColorAttachment, RenderPassDepthStencilAttachment, TextureAspect, TextureDimension,
+    TextureFormat, TextureUsages, TextureView,
 };
 
 use crate::{
-    model::Renderable,
+    bind_group_layout_descriptors,
+    model::{DrawMode, Renderable},
     pipelines::{ObjectPickerRP, ShaderCompilationSuccess},
     pollable_gpu_buffer::PollableGpuBuffer,
     renderer::Renderer,
@@ -41,6 +42,12 @@ impl SingleDimensionPaddedImageBuffer {
 
 pub struct ObjectPickManager {
     pub object_id_texture: SampledTexture,
+    /// The object IDs of the selected objects, including their hidden parts
+    pub selection_mask_texture: SampledTexture,
+    /// The object ID of the hovered object, including its hidden parts
+    pub hover_mask_texture: SampledTexture,
+    /// The object ID texture and the masks, used to draw the outlines in the post-process
+    pub outline_masks_bind_group: BindGroup,
 
     width: u32,
     height: u32,
@@ -54,7 +61,15 @@ pub struct ObjectPickManager {
 
 impl ObjectPickManager {
     pub fn new(renderer: &Renderer) -> Self {
-        let texture = Self::create_texture(renderer);
+        let texture = Self::create_texture(renderer, "Texture for object picking");
+        let selection_mask_texture = Self::create_texture(renderer, "Selection mask texture");
+        let hover_mask_texture = Self::create_texture(renderer, "Hover mask texture");
+        let outline_masks_bind_group = Self::create_outline_masks_bind_group(
+            &renderer.device,
+            &texture,
+            &selection_mask_texture,
+            &hover_mask_texture,
+        );
 
         let render_pipeline = ObjectPickerRP::new(
             &renderer.device,
@@ -65,6 +80,9 @@ impl ObjectPickManager {
 
         Self {
             object_id_texture: texture,
+            selection_mask_texture,
+            hover_mask_texture,
+            outline_masks_bind_group,
             width: renderer.config.width,
             height: renderer.config.height,
             object_picker_rp: render_pipeline,
@@ -90,6 +108,27 @@ impl ObjectPickManager {
         }
     }
 
+    /// Every object visible in the rectangle between the two corners, in screen space
+    pub fn get_object_ids_in_rectangle(
+        &self,
+        corner1: (u32, u32),
+        corner2: (u32, u32),
+    ) -> HashSet<u32> {
+        let max_x = corner1.0.max(corner2.0).min(self.width.saturating_sub(1));
+        let max_y = corner1.1.max(corner2.1).min(self.height.saturating_sub(1));
+
+        let mut object_ids = HashSet::new();
+        for y in corner1.1.min(corner2.1)..=max_y {
+            for x in corner1.0.min(corner2.0)..=max_x {
+                if let Some(object_id) = self.get_object_id_at(x, y) {
+                    object_ids.insert(object_id);
+                }
+            }
+        }
+
+        object_ids
+    }
+
     pub fn try_recompile_shader(
         &mut self,
         device: &Device,
@@ -102,7 +141,15 @@ impl ObjectPickManager {
     }
 
     pub fn resize(&mut self, renderer: &Renderer) {
-        self.object_id_texture = Self::create_texture(renderer);
+        self.object_id_texture = Self::create_texture(renderer, "Texture for object picking");
+        self.selection_mask_texture = Self::create_texture(renderer, "Selection mask texture");
+        self.hover_mask_texture = Self::create_texture(renderer, "Hover mask texture");
+        self.outline_masks_bind_group = Self::create_outline_masks_bind_group(
+            &renderer.device,
+            &self.object_id_texture,
+            &self.selection_mask_texture,
+            &self.hover_mask_texture,
+        );
         self.width = renderer.config.width;
         self.height = renderer.config.height;
     }
@@ -139,7 +186,7 @@ impl ObjectPickManager {
         )
     }
 
-    fn create_texture(renderer: &Renderer) -> SampledTexture {
+    fn create_texture(renderer: &Renderer, label: &str) -> SampledTexture {
         let texture_extents = Extent3d {
             width: renderer.config.width,
             height: renderer.config.height,
@@ -157,7 +204,26 @@ impl ObjectPickManager {
             sampling_type: SamplingType::Nearest,
         };
 
-        SampledTexture::new(&renderer.device, descriptor, "Texture for object picking")
+        SampledTexture::new(&renderer.device, descriptor, label)
+    }
+
+    fn create_outline_masks_bind_group(
+        device: &Device,
+        object_id_texture: &SampledTexture,
+        selection_mask_texture: &SampledTexture,
+        hover_mask_texture: &SampledTexture,
+    ) -> BindGroup {
+        let layout = device.create_bind_group_layout(&bind_group_layout_descriptors::OUTLINE_MASKS);
+
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Outline masks bind group"),
+            entries: &[
+                object_id_texture.get_texture_bind_group_entry(0),
+                selection_mask_texture.get_texture_bind_group_entry(1),
+                hover_mask_texture.get_texture_bind_group_entry(2),
+            ],
+            layout: &layout,
+        })
     }
 
     pub fn render<'a, T>(
@@ -167,6 +233,7 @@ impl ObjectPickManager {
         renderables: T,
         camera_bind_group: &'a BindGroup,
         depth_texture: &'a TextureView,
+        draw_mode: &DrawMode<'a>,
     ) where
         T: Clone,
         T: Iterator<Item = &'a Renderable>,
@@ -201,6 +268,7 @@ impl ObjectPickManager {
                 &mut render_pass.forget_lifetime(),
                 renderables,
                 camera_bind_group,
+                draw_mode,
             );
         }
 
@@ -225,4 +293,57 @@ impl ObjectPickManager {
 
         self.output_buffers.push_back(readable_buffer);
     }
+
+    /// Renders the IDs of the selected and the hovered objects into their masks without depth test, so the outlines
+    /// can be drawn around the parts that are hidden by other objects as well
+    pub fn render_outline_masks<'a>(
+        &'a self,
+        encoder: &mut CommandEncoder,
+        selected_renderables: impl Iterator<Item = &'a Renderable>,
+        hovered_renderables: impl Iterator<Item = &'a Renderable>,
+        camera_bind_group: &'a BindGroup,
+        depth_texture: &'a TextureView,
+    ) {
+        for (mask_texture, renderables, label) in [
+            (
+                &self.selection_mask_texture,
+                selected_renderables.collect::<Vec<_>>(),
+                "Selection mask rendering pass",
+            ),
+            (
+                &self.hover_mask_texture,
+                hovered_renderables.collect::<Vec<_>>(),
+                "Hover mask rendering pass",
+            ),
+        ] {
+            let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &mask_texture.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(CLEAR_COLOR),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                // The pipeline doesn't test against it, but it has to match the pipeline's depth format
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: depth_texture,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            self.object_picker_rp.render_without_depth_test(
+                &mut render_pass.forget_lifetime(),
+                renderables.into_iter(),
+                camera_bind_group,
+            );
+        }
+    }
 }
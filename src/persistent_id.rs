@@ -0,0 +1,133 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use ui_item::{SetPropertyFromUiDescription, UiDisplayDescription, UiDisplayable, UiSettableNew};
+
+/// Identifies an object across sessions, it's saved into the level together with the object.
+/// The runtime IDs (eg. the ones used by the object picker) are only valid while the application is running,
+/// `World` maps them to the persistent ones. The default ID doesn't belong to any object
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(into = "String", try_from = "String")]
+pub struct PersistentId(u64);
+
+impl PersistentId {
+    /// Random, so the objects created in different sessions or levels don't collide when they end up in the same world
+    pub fn generate() -> Self {
+        static GENERATED_ID_COUNT: AtomicU64 = AtomicU64::new(0);
+
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(GENERATED_ID_COUNT.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        );
+
+        Self(hasher.finish())
+    }
+
+    /// An ID that only depends on this ID and the key, so it's the same in every session (eg. the objects of a prefab
+    /// instance are not saved into the level, but they get the same IDs every time the level is loaded)
+    pub fn derive(&self, key: &str) -> Self {
+        // FNV-1a, unlike the hashers of the standard library it's guaranteed to stay the same
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in self.0.to_le_bytes().into_iter().chain(key.bytes()) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+
+        Self(hash)
+    }
+}
+
+impl fmt::Display for PersistentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Saved as a hexadecimal string, as JSON numbers can't represent every u64 in most tools
+impl From<PersistentId> for String {
+    fn from(persistent_id: PersistentId) -> Self {
+        persistent_id.to_string()
+    }
+}
+
+impl TryFrom<String> for PersistentId {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        u64::from_str_radix(&value, 16)
+            .map(Self)
+            .map_err(|error| anyhow!("Invalid persistent ID {value:?}: {error}"))
+    }
+}
+
+/// The IDs can't be edited on the UI, the objects referencing others are set up by the editor (eg. the materials
+/// showing a camera view)
+impl UiDisplayable for PersistentId {
+    fn get_ui_description(&self) -> UiDisplayDescription {
+        UiDisplayDescription::Struct(vec![])
+    }
+}
+
+impl UiSettableNew for PersistentId {
+    fn set_value_from_ui(&mut self, _params: &[SetPropertyFromUiDescription]) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn persistent_ids_are_saved_as_strings() {
+        let persistent_id = PersistentId(0x2a);
+
+        let json = serde_json::to_string(&persistent_id).unwrap();
+        assert_eq!(json, "\"000000000000002a\"");
+        assert_eq!(
+            serde_json::from_str::<PersistentId>(&json).unwrap(),
+            persistent_id
+        );
+        assert!(serde_json::from_str::<PersistentId>("\"not an id\"").is_err());
+    }
+
+    #[test]
+    fn generated_ids_are_different() {
+        assert_ne!(PersistentId::generate(), PersistentId::generate());
+    }
+
+    #[test]
+    fn derived_ids_are_stable() {
+        let persistent_id = PersistentId(0x2a);
+
+        assert_eq!(
+            persistent_id.derive("0/1"),
+            PersistentId(0x0d6d8088dafb3395)
+        );
+        assert_ne!(persistent_id.derive("0/1"), persistent_id.derive("0/2"));
+        assert_ne!(
+            persistent_id.derive("0/1"),
+            PersistentId(0x2b).derive("0/1")
+        );
+    }
+}
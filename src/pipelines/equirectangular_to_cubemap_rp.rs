@@ -47,6 +47,9 @@ impl EquirectangularToCubemapRP {
                 &device.create_bind_group_layout(
                     &(bind_group_layout_descriptors::TEXTURE_2D_FRAGMENT_WITH_SAMPLER),
                 ),
+                &device.create_bind_group_layout(
+                    &(bind_group_layout_descriptors::BUFFER_VISIBLE_EVERYWHERE),
+                ),
             ],
             push_constant_ranges: &[],
         });
@@ -119,6 +122,7 @@ impl EquirectangularToCubemapRP {
         primitive: &Primitive,
         projection_bind_group: &wgpu::BindGroup,
         hdr_texture_bind_group: &wgpu::BindGroup,
+        environment_params_bind_group: &wgpu::BindGroup,
     ) {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Equirec to cubemap pass"),
@@ -143,6 +147,7 @@ impl EquirectangularToCubemapRP {
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_bind_group(0, projection_bind_group, &[]);
         render_pass.set_bind_group(1, hdr_texture_bind_group, &[]);
+        render_pass.set_bind_group(2, environment_params_bind_group, &[]);
 
         primitive.render(&mut render_pass);
     }
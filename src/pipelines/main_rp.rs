@@ -1,9 +1,6 @@
 use wgpu::{ComputePipeline, Device, PipelineCompilationOptions, ShaderModule};
 
-use crate::{
-    bind_group_layout_descriptors, camera_controller::CameraController,
-    light_controller::LightController,
-};
+use crate::{bind_group_layout_descriptors, light_controller::LightController};
 
 use super::shader_compiler::{ShaderCompilationResult, ShaderCompilationSuccess, ShaderCompiler};
 
@@ -91,7 +88,7 @@ impl MainRP {
     pub fn render<'a>(
         &'a self,
         render_pass: &mut wgpu::ComputePass<'a>,
-        camera_controller: &'a CameraController,
+        camera_bind_group: &'a wgpu::BindGroup,
         light_controller: &'a LightController,
         gbuffer_bind_group: &'a wgpu::BindGroup,
         directional_lights_depth_texture_bg: &'a wgpu::BindGroup,
@@ -103,7 +100,7 @@ impl MainRP {
     ) {
         render_pass.set_pipeline(&self.compute_pipeline);
         render_pass.set_bind_group(0, light_controller.get_light_bind_group(), &[]);
-        render_pass.set_bind_group(1, &camera_controller.bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
         render_pass.set_bind_group(2, gbuffer_bind_group, &[]);
         render_pass.set_bind_group(3, directional_lights_depth_texture_bg, &[]);
         render_pass.set_bind_group(4, point_lights_depth_texture_bg, &[]);
@@ -4,8 +4,11 @@ use wgpu::{
 };
 
 use crate::{
-    bind_group_layout_descriptors, buffer_content::BufferContent,
-    components::TransformComponentRaw, model::Renderable, vertex,
+    bind_group_layout_descriptors,
+    buffer_content::BufferContent,
+    components::TransformComponentRaw,
+    model::{DrawMode, Renderable},
+    vertex,
 };
 
 use super::shader_compiler::{ShaderCompilationResult, ShaderCompilationSuccess, ShaderCompiler};
@@ -153,6 +156,7 @@ impl ObjectPickerRP {
         render_pass: &mut RenderPass<'a>,
         renderables: T,
         camera_bind_group: &'a BindGroup,
+        draw_mode: &DrawMode<'a>,
     ) where
         T: Iterator<Item = &'a Renderable> + Clone,
     {
@@ -164,7 +168,7 @@ impl ObjectPickerRP {
             .clone()
             .filter(|renderable| renderable.description.rendering_options.use_depth_test)
         {
-            renderable.render(render_pass, None);
+            renderable.render(render_pass, None, draw_mode);
         }
 
         render_pass.set_pipeline(&self.render_pipeline_no_depth_test);
@@ -173,7 +177,24 @@ impl ObjectPickerRP {
             .clone()
             .filter(|renderable| !renderable.description.rendering_options.use_depth_test)
         {
-            renderable.render(render_pass, None);
+            renderable.render(render_pass, None, draw_mode);
+        }
+    }
+
+    /// Renders every renderable over whatever is in front of it, so the hidden parts are also written
+    pub fn render_without_depth_test<'a, T>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        renderables: T,
+        camera_bind_group: &'a BindGroup,
+    ) where
+        T: Iterator<Item = &'a Renderable>,
+    {
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_pipeline(&self.render_pipeline_no_depth_test);
+
+        for renderable in renderables {
+            renderable.render(render_pass, None, &DrawMode::Direct);
         }
     }
 }
@@ -4,8 +4,11 @@ use wgpu::{
 };
 
 use crate::{
-    bind_group_layout_descriptors, buffer_content::BufferContent,
-    components::TransformComponentRaw, model::Renderable, vertex,
+    bind_group_layout_descriptors,
+    buffer_content::BufferContent,
+    components::TransformComponentRaw,
+    model::{DrawMode, Renderable},
+    vertex,
 };
 
 use super::shader_compiler::{ShaderCompilationResult, ShaderCompilationSuccess, ShaderCompiler};
@@ -110,6 +113,7 @@ impl ShadowRP {
         light_bind_group: &BindGroup,
         depth_target: &wgpu::TextureView,
         light_bind_group_offset: u32,
+        draw_mode: &DrawMode<'a>,
     ) {
         let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
@@ -132,7 +136,7 @@ impl ShadowRP {
         for renderable in
             renderables.filter(|renderable| renderable.description.rendering_options.cast_shadows)
         {
-            renderable.render(&mut shadow_pass, None);
+            renderable.render_shadow_caster(&mut shadow_pass, draw_mode);
         }
     }
 }
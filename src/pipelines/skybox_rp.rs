@@ -1,6 +1,6 @@
 use wgpu::{Device, PipelineCompilationOptions, RenderPipeline, ShaderModule};
 
-use crate::{bind_group_layout_descriptors, camera_controller::CameraController, texture};
+use crate::{bind_group_layout_descriptors, texture};
 
 use super::shader_compiler::{ShaderCompilationResult, ShaderCompilationSuccess, ShaderCompiler};
 
@@ -109,12 +109,12 @@ impl SkyboxRP {
     pub fn render<'a, 'b: 'a>(
         &'b self,
         render_pass: &mut wgpu::RenderPass<'a>,
-        camera_controller: &'b CameraController,
+        camera_bind_group: &'b wgpu::BindGroup,
         skybox_texture_bind_group: &'a wgpu::BindGroup,
     ) {
         render_pass.set_pipeline(&self.pipeline);
         render_pass.set_bind_group(0, skybox_texture_bind_group, &[]);
-        render_pass.set_bind_group(1, &camera_controller.bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
         render_pass.draw(0..3, 0..1);
     }
 }
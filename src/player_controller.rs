@@ -1,4 +1,5 @@
-use ui_item::{UiDisplayable, UiSettableNew};
+use glam::Vec3;
+use ui_item::{SetPropertyFromUiDescription, UiDisplayable, UiSettableNew};
 use winit::{
     dpi::PhysicalPosition,
     event::{ElementState, MouseButton, WindowEvent},
@@ -10,17 +11,38 @@ use crate::{
     app::{WindowEventHandlingAction, WindowEventHandlingResult},
     components::{RenderableComponent, SceneComponentType, TransformComponent},
     custom_event::CustomEvent,
-    gizmo_handler::GizmoHandler,
+    editor_history::{EditorHistory, HistoryMergeKey},
+    gizmo::{GizmoMode, GizmoOrientation},
+    gizmo_handler::{GizmoHandler, SelectionPivot, SnapSettings},
     gui_settable_value::GuiSettableValue,
     material::PbrMaterialDescriptor,
     model::{MeshDescriptor, ModelRenderingOptions, PbrParameters},
     object_picker::ObjectPickManager,
     world::World,
     world_object::WorldObject,
+    world_renderer::WorldRenderer,
 };
 
 const SELECTED_OBJECT_GUI_CATEGORY: &str = "Selected object";
 
+/// The digit keys recall the camera bookmarks, see `CameraBookmark::hotkey`
+fn get_bookmark_hotkey(key_code: KeyCode) -> Option<u32> {
+    let hotkey = match key_code {
+        KeyCode::Digit1 => 1,
+        KeyCode::Digit2 => 2,
+        KeyCode::Digit3 => 3,
+        KeyCode::Digit4 => 4,
+        KeyCode::Digit5 => 5,
+        KeyCode::Digit6 => 6,
+        KeyCode::Digit7 => 7,
+        KeyCode::Digit8 => 8,
+        KeyCode::Digit9 => 9,
+        _ => return None,
+    };
+
+    Some(hotkey)
+}
+
 pub struct PlayerController {
     cursor_position: Option<PhysicalPosition<f64>>,
     is_left_button_pressed: bool,
@@ -71,37 +93,191 @@ impl PlayerController {
         }
     }
 
+    pub fn select_object(&mut self, object_id: Option<u32>, world: &mut World) {
+        self.gizmo_handler.select_object(object_id, world);
+    }
+
+    pub fn select_objects(&mut self, object_ids: &[u32], world: &mut World) {
+        self.gizmo_handler.select_objects(object_ids, world);
+    }
+
+    /// The active object of the selection, its properties are shown on the GUI
+    pub fn get_selected_object_id(&self) -> Option<u32> {
+        self.gizmo_handler.get_active_object_id()
+    }
+
+    pub fn get_selected_object_ids(&self) -> &[u32] {
+        self.gizmo_handler.get_selected_object_ids()
+    }
+
+    pub fn get_top_level_selected_object_ids(&self, world: &World) -> Vec<u32> {
+        self.gizmo_handler.get_top_level_selected_object_ids(world)
+    }
+
+    pub fn get_hovered_object_id(&self) -> Option<u32> {
+        self.gizmo_handler.get_hovered_object_id()
+    }
+
+    pub fn set_selection_pivot(&mut self, pivot: SelectionPivot, world: &mut World) {
+        self.gizmo_handler.set_pivot(pivot, world);
+    }
+
+    pub fn get_selection_pivot(&self) -> SelectionPivot {
+        self.gizmo_handler.get_pivot()
+    }
+
+    pub fn set_gizmo_mode(&mut self, mode: GizmoMode, world: &mut World) {
+        self.gizmo_handler.set_mode(mode, world);
+    }
+
+    pub fn get_gizmo_mode(&self) -> GizmoMode {
+        self.gizmo_handler.get_mode()
+    }
+
+    pub fn set_gizmo_orientation(&mut self, orientation: GizmoOrientation, world: &mut World) {
+        self.gizmo_handler.set_orientation(orientation, world);
+    }
+
+    pub fn get_gizmo_orientation(&self) -> GizmoOrientation {
+        self.gizmo_handler.get_orientation()
+    }
+
+    pub fn set_snap_settings(&mut self, snap_settings: SnapSettings) {
+        self.gizmo_handler.set_snap_settings(snap_settings);
+    }
+
+    pub fn take_selection_change(&mut self) -> bool {
+        self.gizmo_handler.take_selection_change()
+    }
+
+    pub fn get_selection_rectangle(
+        &self,
+    ) -> Option<(PhysicalPosition<f64>, PhysicalPosition<f64>)> {
+        self.gizmo_handler.get_selection_rectangle()
+    }
+
+    /// Where the ray from the camera through the cursor hits the horizontal plane at the given height. None if the
+    /// cursor is outside of the window or the plane is not in front of the camera
+    pub fn get_cursor_position_at_height(&self, world: &World, height: f32) -> Option<Vec3> {
+        let cursor_position = self.cursor_position?;
+        let ray = world.camera_controller.get_screen_ray(&cursor_position);
+        if ray.direction.y.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let distance = (height - ray.position.y) / ray.direction.y;
+        (distance > 0.0).then(|| ray.evaluate(distance))
+    }
+
+    pub fn is_moving_object(&self) -> bool {
+        self.gizmo_handler.is_moving_object()
+    }
+
+    /// The properties of the selected object are registered to the GUI again, so it shows their current values (eg.
+    /// after undo)
+    pub fn refresh_selected_object_gui(&mut self) {
+        self.gui_registered_object = None;
+    }
+
     pub fn update(
         &mut self,
         world: &mut World,
+        history: &mut EditorHistory,
         event_loop_proxy: &mut EventLoopProxy<CustomEvent>,
     ) {
         self.gizmo_handler.update(world);
 
+        if let Some(selected_object_id) = self.get_selected_object_id() {
+            if self.is_moving_object() {
+                let entry_name = match self.get_gizmo_mode() {
+                    GizmoMode::Translate => "Move object",
+                    GizmoMode::Rotate => "Rotate object",
+                    GizmoMode::Scale => "Scale object",
+                };
+                history.name_next_changes(
+                    entry_name,
+                    Some(HistoryMergeKey::GizmoDrag(selected_object_id)),
+                );
+            }
+        }
+
         self.update_registered_object(world, event_loop_proxy);
 
-        if let Some(selected_object_id) = &mut self.gui_registered_object {
-            let changes = selected_object_id.get_gui_changes();
+        if let Some(active_object_id) = &mut self.gui_registered_object {
+            let changes = active_object_id.get_gui_changes();
             if !changes.is_empty() {
-                if let Some(world_object) = world.get_world_object_mut(selected_object_id) {
-                    for change in changes {
-                        world_object.set_value_from_ui(&change);
-                    }
+                let active_object_id = **active_object_id;
+                Self::apply_gui_changes(
+                    world,
+                    active_object_id,
+                    self.gizmo_handler.get_selected_object_ids(),
+                    &changes,
+                );
+                history.name_next_changes(
+                    "Edit properties",
+                    Some(HistoryMergeKey::PropertyEdit(active_object_id)),
+                );
+            }
+        }
+    }
+
+    /// The GUI shows the properties of the active object, the changes are applied to every selected object that
+    /// shares the changed property
+    fn apply_gui_changes(
+        world: &mut World,
+        active_object_id: u32,
+        selected_object_ids: &[u32],
+        changes: &[Vec<SetPropertyFromUiDescription>],
+    ) {
+        let Some(active_object) = world.get_world_object(&active_object_id).cloned() else {
+            return;
+        };
+
+        for object_id in selected_object_ids {
+            let Some(world_object) = world.get_world_object_mut(object_id) else {
+                continue;
+            };
+            for change in changes {
+                if *object_id == active_object_id
+                    || Self::is_change_shared(change, &active_object, world_object)
+                {
+                    world_object.set_value_from_ui(change);
                 }
             }
         }
     }
 
+    /// Every object has a transform. The components are addressed by their index, so a change of a component is only
+    /// shared by the objects that have the same components
+    fn is_change_shared(
+        change: &[SetPropertyFromUiDescription],
+        active_object: &WorldObject,
+        world_object: &WorldObject,
+    ) -> bool {
+        let is_transform_change = matches!(
+            change.first(),
+            Some(SetPropertyFromUiDescription::Struct(struct_description))
+                if struct_description.field_name == "transform"
+        );
+
+        is_transform_change
+            || active_object.get_component_types() == world_object.get_component_types()
+    }
+
     pub fn handle_window_event(
         &mut self,
         window_event: &WindowEvent,
         world: &mut World,
+        history: &mut EditorHistory,
         object_picker: &ObjectPickManager,
+        world_renderer: &WorldRenderer,
     ) -> WindowEventHandlingResult {
-        if self
-            .gizmo_handler
-            .handle_window_event(window_event, world, object_picker)
-        {
+        if self.gizmo_handler.handle_window_event(
+            window_event,
+            world,
+            object_picker,
+            world_renderer,
+        ) {
             return WindowEventHandlingResult::Handled;
         }
 
@@ -135,30 +311,89 @@ impl PlayerController {
             },
             WindowEvent::KeyboardInput { event, .. } => match event.physical_key {
                 PhysicalKey::Code(KeyCode::Delete) => {
-                    if let Some(id) = self.gizmo_handler.get_active_object_id() {
-                        world.remove_world_object(id);
+                    let selected_object_ids = self.get_selected_object_ids().to_vec();
+                    if !selected_object_ids.is_empty() {
+                        for id in selected_object_ids {
+                            world.remove_world_object(id);
+                        }
                         self.gizmo_handler.remove_object_selection(world);
+                        history.name_next_changes("Delete object", None);
                         WindowEventHandlingResult::Handled
                     } else {
                         WindowEventHandlingResult::Unhandled
                     }
                 }
-                PhysicalKey::Code(KeyCode::KeyR) => {
-                    if self.modifiers.contains(ModifiersState::CONTROL) {
-                        WindowEventHandlingResult::RequestAction(
-                            WindowEventHandlingAction::RecompileShaders,
-                        )
+                PhysicalKey::Code(KeyCode::KeyR)
+                    if self.modifiers.contains(ModifiersState::CONTROL) =>
+                {
+                    WindowEventHandlingResult::RequestAction(
+                        WindowEventHandlingAction::RecompileShaders,
+                    )
+                }
+                PhysicalKey::Code(KeyCode::KeyZ)
+                    if self.modifiers.contains(ModifiersState::CONTROL) =>
+                {
+                    if event.state != ElementState::Pressed {
+                        WindowEventHandlingResult::Handled
+                    } else if self.modifiers.contains(ModifiersState::SHIFT) {
+                        WindowEventHandlingResult::RequestAction(WindowEventHandlingAction::Redo)
                     } else {
-                        WindowEventHandlingResult::Unhandled
+                        WindowEventHandlingResult::RequestAction(WindowEventHandlingAction::Undo)
                     }
                 }
-                PhysicalKey::Code(KeyCode::KeyW) => {
-                    if self.modifiers.contains(ModifiersState::CONTROL) {
-                        WindowEventHandlingResult::RequestAction(WindowEventHandlingAction::Exit)
+                PhysicalKey::Code(KeyCode::KeyY)
+                    if self.modifiers.contains(ModifiersState::CONTROL) =>
+                {
+                    if event.state == ElementState::Pressed {
+                        WindowEventHandlingResult::RequestAction(WindowEventHandlingAction::Redo)
                     } else {
-                        WindowEventHandlingResult::Unhandled
+                        WindowEventHandlingResult::Handled
+                    }
+                }
+                PhysicalKey::Code(key_code @ (KeyCode::KeyC | KeyCode::KeyV | KeyCode::KeyD))
+                    if self.modifiers.contains(ModifiersState::CONTROL) =>
+                {
+                    if event.state != ElementState::Pressed {
+                        WindowEventHandlingResult::Handled
+                    } else {
+                        WindowEventHandlingResult::RequestAction(match key_code {
+                            KeyCode::KeyC => WindowEventHandlingAction::Copy,
+                            KeyCode::KeyV => WindowEventHandlingAction::Paste,
+                            _ => WindowEventHandlingAction::Duplicate,
+                        })
                     }
                 }
+                PhysicalKey::Code(KeyCode::KeyW)
+                    if self.modifiers.contains(ModifiersState::CONTROL) =>
+                {
+                    WindowEventHandlingResult::RequestAction(WindowEventHandlingAction::Exit)
+                }
+                PhysicalKey::Code(key_code @ (KeyCode::KeyW | KeyCode::KeyE | KeyCode::KeyR)) => {
+                    if event.state == ElementState::Pressed {
+                        let mode = match key_code {
+                            KeyCode::KeyW => GizmoMode::Translate,
+                            KeyCode::KeyE => GizmoMode::Rotate,
+                            _ => GizmoMode::Scale,
+                        };
+                        self.set_gizmo_mode(mode, world);
+                    }
+
+                    WindowEventHandlingResult::Handled
+                }
+                PhysicalKey::Code(key_code) => match get_bookmark_hotkey(key_code) {
+                    Some(_) if event.state != ElementState::Pressed || event.repeat => {
+                        WindowEventHandlingResult::Handled
+                    }
+                    Some(hotkey) if self.modifiers.contains(ModifiersState::CONTROL) => {
+                        WindowEventHandlingResult::RequestAction(
+                            WindowEventHandlingAction::StoreCameraBookmark(hotkey),
+                        )
+                    }
+                    Some(hotkey) => WindowEventHandlingResult::RequestAction(
+                        WindowEventHandlingAction::RecallCameraBookmark(hotkey),
+                    ),
+                    None => WindowEventHandlingResult::Unhandled,
+                },
                 _ => WindowEventHandlingResult::Unhandled,
             },
             WindowEvent::ModifiersChanged(modifiers) => {
@@ -180,6 +415,7 @@ impl PlayerController {
                 );
 
                 world.add_world_object(object);
+                history.name_next_changes("Add object", None);
 
                 WindowEventHandlingResult::Handled
             }
@@ -11,6 +11,7 @@ use crate::{
 const POST_PROCESS_SHADER_SOURCE: &'static str = "src/shaders/post_process.wgsl";
 const SCREEN_SPACE_REFLECTION_SHADER_SOURCE: &'static str =
     "src/shaders/screen_space_reflection.wgsl";
+const SELECTION_OUTLINE_SHADER_SOURCE: &'static str = "src/shaders/selection_outline.wgsl";
 const TONE_MAPPING_SHADER_SOURCE: &'static str = "src/shaders/tone_mapping.wgsl";
 
 const POSTPROCESS_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
@@ -20,8 +21,13 @@ const INITIAL_BIND_GROUP_INDEX: usize = 1;
 pub struct PostProcessManager {
     dummy_pipeline: SimpleCP,
     screen_space_reflection_pipeline: SimpleCP,
+    selection_outline_pipeline: SimpleCP,
     tone_mapping_pipeline: SimpleCP,
+}
 
+/// The textures the post-process steps of one view ping-pong between. Every view has its own, while the pipelines in
+/// `PostProcessManager` are shared by all of them
+pub struct PostProcessTargets {
     // We have 2 bind groups and 2 textures and we ping-pong the post-process steps between them, so we don't have
     // to allocate a new texture/bind group for each post-process step
     pub full_screen_render_target_ping_pong_textures: Vec<SampledTexture>,
@@ -29,12 +35,12 @@ pub struct PostProcessManager {
     pub next_ping_pong_bind_group_index: usize,
 
     // The tone mapping is the last step and it needs a different format, so we can't just use the ping-pong textures
-    // for tone-mapping
-    pub tone_mapping_bind_group: BindGroup,
+    // for tone-mapping. There is one for each ping-pong texture, so it can read whichever was written last
+    pub tone_mapping_bind_groups: [BindGroup; 2],
 }
 
 impl PostProcessManager {
-    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+    pub fn new(device: &Device) -> Self {
         let dummy_pipeline = SimpleCP::new(
             device,
             &[
@@ -63,6 +69,18 @@ impl PostProcessManager {
         
         .unwrap();
 
+        let selection_outline_pipeline = SimpleCP::new(
+            device,
+            &[
+                &bind_group_layout_descriptors::COMPUTE_PING_PONG,
+                &bind_group_layout_descriptors::BUFFER_VISIBLE_EVERYWHERE,
+                &bind_group_layout_descriptors::OUTLINE_MASKS,
+            ],
+            SELECTION_OUTLINE_SHADER_SOURCE,
+            "selection outline",
+        )
+        .unwrap();
+
         let tone_mapping_pipeline = SimpleCP::new(
             device,
             &[
@@ -75,17 +93,11 @@ impl PostProcessManager {
         
         .unwrap();
 
-        let (textures, ping_pong_bind_groups, tone_mapping_bind_group) =
-            Self::create_pingpong_texture(&device, width, height);
-
         Self {
             dummy_pipeline,
             screen_space_reflection_pipeline,
+            selection_outline_pipeline,
             tone_mapping_pipeline,
-            full_screen_render_target_ping_pong_textures: textures,
-            compute_ping_pong_bind_groups: ping_pong_bind_groups,
-            tone_mapping_bind_group,
-            next_ping_pong_bind_group_index: INITIAL_BIND_GROUP_INDEX,
         }
     }
 
@@ -97,25 +109,127 @@ impl PostProcessManager {
         self.screen_space_reflection_pipeline
             .try_recompile_shader(device)
             ?;
+        self.selection_outline_pipeline.try_recompile_shader(device)?;
         self.tone_mapping_pipeline
             .try_recompile_shader(device)
             
     }
 
-    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
-        let (textures, ping_pong_bind_groups, tone_mapping_bind_group) =
+    fn get_invocation_dimensions(
+        render_target_width: u32,
+        render_target_height: u32,
+    ) -> (u32, u32, u32) {
+        let num_dispatches_x = render_target_width.div_ceil(WORKGROUP_SIZE_PER_DIMENSION);
+        let num_dispatches_y = render_target_height.div_ceil(WORKGROUP_SIZE_PER_DIMENSION);
+        (num_dispatches_x, num_dispatches_y, 1)
+    }
+
+    pub fn render_dummy<'a>(
+        &'a self,
+        targets: &'a mut PostProcessTargets,
+        compute_pass: &'a mut ComputePass<'a>,
+        render_target_width: u32,
+        render_target_height: u32,
+        global_gpu_params_bind_group: &'a BindGroup,
+    ) {
+        let next_bind_group_index = targets.get_next_ping_pong_bind_group_index();
+        self.dummy_pipeline.run_copmute_pass(
+            compute_pass,
+            &[
+                &targets.compute_ping_pong_bind_groups[next_bind_group_index],
+                global_gpu_params_bind_group,
+            ],
+            Self::get_invocation_dimensions(render_target_width, render_target_height),
+        );
+    }
+
+    pub fn render_screen_space_reflections<'a>(
+        &'a self,
+        targets: &'a mut PostProcessTargets,
+        compute_pass: &mut ComputePass<'a>,
+        render_target_width: u32,
+        render_target_height: u32,
+        global_gpu_params_bind_group: &'a BindGroup,
+        camera_bind_group: &'a BindGroup,
+        skybox_bind_group: &'a BindGroup,
+        gbuffer_bind_group: &'a BindGroup,
+        depth_texture_bind_group: &'a BindGroup,
+    ) {
+        let next_bind_group_index = targets.get_next_ping_pong_bind_group_index();
+        self.screen_space_reflection_pipeline.run_copmute_pass(
+            compute_pass,
+            &[
+                &targets.compute_ping_pong_bind_groups[next_bind_group_index],
+                global_gpu_params_bind_group,
+                camera_bind_group,
+                skybox_bind_group,
+                gbuffer_bind_group,
+                depth_texture_bind_group,
+            ],
+            Self::get_invocation_dimensions(render_target_width, render_target_height),
+        );
+    }
+
+    pub fn render_selection_outline<'a>(
+        &'a self,
+        targets: &'a mut PostProcessTargets,
+        compute_pass: &mut ComputePass<'a>,
+        render_target_width: u32,
+        render_target_height: u32,
+        global_gpu_params_bind_group: &'a BindGroup,
+        outline_masks_bind_group: &'a BindGroup,
+    ) {
+        let next_bind_group_index = targets.get_next_ping_pong_bind_group_index();
+        self.selection_outline_pipeline.run_copmute_pass(
+            compute_pass,
+            &[
+                &targets.compute_ping_pong_bind_groups[next_bind_group_index],
+                global_gpu_params_bind_group,
+                outline_masks_bind_group,
+            ],
+            Self::get_invocation_dimensions(render_target_width, render_target_height),
+        );
+    }
+
+    pub fn apply_tone_mapping<'a>(
+        &'a self,
+        targets: &'a mut PostProcessTargets,
+        compute_pass: &mut ComputePass<'a>,
+        render_target_width: u32,
+        render_target_height: u32,
+        global_gpu_params_bind_group: &'a BindGroup,
+    ) {
+        // The ping-pong bind group at the next index would read the texture that was written last
+        let last_written_texture_index = targets.next_ping_pong_bind_group_index;
+        self.tone_mapping_pipeline.run_copmute_pass(
+            compute_pass,
+            &[
+                &targets.tone_mapping_bind_groups[last_written_texture_index],
+                global_gpu_params_bind_group,
+            ],
+            Self::get_invocation_dimensions(render_target_width, render_target_height),
+        );
+    }
+}
+
+impl PostProcessTargets {
+    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+        let (textures, ping_pong_bind_groups, tone_mapping_bind_groups) =
             Self::create_pingpong_texture(device, width, height);
 
-        self.full_screen_render_target_ping_pong_textures = textures;
-        self.compute_ping_pong_bind_groups = ping_pong_bind_groups;
-        self.tone_mapping_bind_group = tone_mapping_bind_group;
+        Self {
+            full_screen_render_target_ping_pong_textures: textures,
+            compute_ping_pong_bind_groups: ping_pong_bind_groups,
+            tone_mapping_bind_groups,
+            next_ping_pong_bind_group_index: INITIAL_BIND_GROUP_INDEX,
+        }
     }
 
     fn create_pingpong_texture(
         device: &Device,
         width: u32,
         height: u32,
-    ) -> (Vec<SampledTexture>, [BindGroup; 2], BindGroup) {
+    ) -> (Vec<SampledTexture>, [BindGroup; 2], [BindGroup; 2]) {
         let full_screen_render_target_ping_pong_textures = (0..3)
             .map(|i| {
                 let mut usages = wgpu::TextureUsages::STORAGE_BINDING
@@ -186,24 +300,26 @@ impl PostProcessManager {
             })
         };
 
-        let tone_mapping_bind_group = {
+        let tone_mapping_bind_groups = [0, 1].map(|source_index| {
             let layout = device.create_bind_group_layout(&COMPUTE_FINAL_STAGE);
 
             device.create_bind_group(&BindGroupDescriptor {
-                label: Some("Tone mapping bind group"),
+                label: Some(&format!("Tone mapping bind group from {source_index}")),
                 entries: &[
                     full_screen_render_target_ping_pong_textures[2].get_texture_bind_group_entry(0),
-                    full_screen_render_target_ping_pong_textures[0].get_texture_bind_group_entry(1),
-                    full_screen_render_target_ping_pong_textures[0].get_sampler_bind_group_entry(2),
+                    full_screen_render_target_ping_pong_textures[source_index]
+                        .get_texture_bind_group_entry(1),
+                    full_screen_render_target_ping_pong_textures[source_index]
+                        .get_sampler_bind_group_entry(2),
                 ],
                 layout: &layout,
             })
-        };
+        });
 
         (
             full_screen_render_target_ping_pong_textures,
             [bind_group_0_to_1, bind_group_1_to_0],
-            tone_mapping_bind_group,
+            tone_mapping_bind_groups,
         )
     }
 
@@ -221,70 +337,8 @@ impl PostProcessManager {
         next_bind_group_index
     }
 
-    fn get_invocation_dimensions(
-        render_target_width: u32,
-        render_target_height: u32,
-    ) -> (u32, u32, u32) {
-        let num_dispatches_x = render_target_width.div_ceil(WORKGROUP_SIZE_PER_DIMENSION);
-        let num_dispatches_y = render_target_height.div_ceil(WORKGROUP_SIZE_PER_DIMENSION);
-        (num_dispatches_x, num_dispatches_y, 1)
-    }
-
-    pub fn render_dummy<'a>(
-        &'a mut self,
-        compute_pass: &'a mut ComputePass<'a>,
-        render_target_width: u32,
-        render_target_height: u32,
-        global_gpu_params_bind_group: &'a BindGroup,
-    ) {
-        let next_bind_group_index = self.get_next_ping_pong_bind_group_index();
-        self.dummy_pipeline.run_copmute_pass(
-            compute_pass,
-            &[
-                &self.compute_ping_pong_bind_groups[next_bind_group_index],
-                global_gpu_params_bind_group,
-            ],
-            Self::get_invocation_dimensions(render_target_width, render_target_height),
-        );
-    }
-
-    pub fn render_screen_space_reflections<'a>(
-        &'a mut self,
-        compute_pass: &mut ComputePass<'a>,
-        render_target_width: u32,
-        render_target_height: u32,
-        global_gpu_params_bind_group: &'a BindGroup,
-        camera_bind_group: &'a BindGroup,
-        skybox_bind_group: &'a BindGroup,
-        gbuffer_bind_group: &'a BindGroup,
-        depth_texture_bind_group: &'a BindGroup,
-    ) {
-        let next_bind_group_index = self.get_next_ping_pong_bind_group_index();
-        self.screen_space_reflection_pipeline.run_copmute_pass(
-            compute_pass,
-            &[
-                &self.compute_ping_pong_bind_groups[next_bind_group_index],
-                global_gpu_params_bind_group,
-                camera_bind_group,
-                skybox_bind_group,
-                gbuffer_bind_group,
-                depth_texture_bind_group,
-            ],
-            Self::get_invocation_dimensions(render_target_width, render_target_height),
-        );
-    }
-
-    pub fn apply_tone_mapping<'a>(
-        &'a mut self,
-        compute_pass: &mut ComputePass<'a>,
-        render_target_width: u32,
-        render_target_height: u32,
-        global_gpu_params_bind_group: &'a BindGroup,
-    ) {
-        self.tone_mapping_pipeline.run_copmute_pass(
-            compute_pass,
-            &[&self.tone_mapping_bind_group, global_gpu_params_bind_group],
-            Self::get_invocation_dimensions(render_target_width, render_target_height),
-        );
+    /// The texture the tone mapping writes, the final image of the view
+    pub fn get_output_texture(&self) -> &SampledTexture {
+        &self.full_screen_render_target_ping_pong_textures[2]
     }
 }
@@ -0,0 +1,552 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+
+use crate::{
+    component_storage::Component,
+    components::{SceneComponentType, TransformComponent},
+    world::World,
+    world_loader::{to_saved_json, write_saved_file, LevelSaveOptions},
+    world_object::WorldObject,
+};
+
+/// One object of a prefab together with its descendants. The transforms of the top level objects are relative to the
+/// root object of the instance, the transforms of the children are relative to their parent
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PrefabObject {
+    #[serde(flatten)]
+    pub object: WorldObject,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<PrefabObject>,
+}
+
+/// A reusable group of objects saved into its own file. The levels only reference the prefab file and store how each
+/// instance differs from the prefab, see `PrefabOverride`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Prefab {
+    pub objects: Vec<PrefabObject>,
+}
+
+/// Identifies an object of a prefab by the indices leading to it in the hierarchy of the prefab, eg. "0/2" is the
+/// third child of the first top level object
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(into = "String", try_from = "String")]
+pub struct PrefabPartPath(Vec<usize>);
+
+impl PrefabPartPath {
+    fn get_child(&self, index: usize) -> Self {
+        let mut indices = self.0.clone();
+        indices.push(index);
+
+        Self(indices)
+    }
+}
+
+impl fmt::Display for PrefabPartPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indices = self
+            .0
+            .iter()
+            .map(|index| index.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}", indices.join("/"))
+    }
+}
+
+impl From<PrefabPartPath> for String {
+    fn from(part_path: PrefabPartPath) -> Self {
+        part_path.to_string()
+    }
+}
+
+impl TryFrom<String> for PrefabPartPath {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value
+            .split('/')
+            .map(|index| index.parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self)
+            .map_err(|error| anyhow!("Invalid prefab part path {value:?}: {error}"))
+    }
+}
+
+/// How an object of a prefab instance differs from the prefab. Only the changed parts of the object are stored, the
+/// rest of it follows the changes of the prefab. The overrides are matched to the objects of the prefab by their
+/// position in the hierarchy, so they may end up on another object if the hierarchy of the prefab is rearranged
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PrefabOverride {
+    pub part: PrefabPartPath,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<TransformComponent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub components: Option<Vec<SceneComponentType>>,
+    /// The object and its descendants were deleted from the instance
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub removed: bool,
+}
+
+/// Attached to the root object of a prefab instance. The objects of the prefab are the children of the root
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefabInstance {
+    pub prefab_path: PathBuf,
+    /// The prefab as it was when the objects of the instance were created. The overrides are the differences from it
+    prefab: Prefab,
+}
+
+impl Component for PrefabInstance {}
+
+/// Attached to the objects that were created from a prefab
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefabPart {
+    pub part: PrefabPartPath,
+}
+
+impl Component for PrefabPart {}
+
+pub fn load_prefab(prefab_path: &Path) -> anyhow::Result<Prefab> {
+    let file_contents = fs::read_to_string(prefab_path)
+        .map_err(|error| anyhow!("Failed to read prefab {prefab_path:?}: {error}"))?;
+
+    Ok(serde_json::from_str::<Prefab>(&file_contents)?)
+}
+
+pub fn save_prefab(prefab: &Prefab, prefab_path: &Path) -> anyhow::Result<()> {
+    let contents = to_saved_json(prefab, &LevelSaveOptions::default())?;
+
+    write_saved_file(prefab_path, &contents)
+}
+
+/// The object with its non-transient descendants. The prefab instances among them are unpacked, the prefabs can't be
+/// nested. With `only_parts`, the objects the user added to a prefab instance are left out
+fn get_prefab_object(world: &World, object_id: u32, only_parts: bool) -> Option<PrefabObject> {
+    let object = world.get_world_object(&object_id)?.get_saved_copy()?;
+    let children = world
+        .get_children(&object_id)
+        .iter()
+        .filter(|child_id| !only_parts || world.get_component::<PrefabPart>(child_id).is_some())
+        .filter_map(|child_id| get_prefab_object(world, *child_id, only_parts))
+        .collect();
+
+    Some(PrefabObject { object, children })
+}
+
+/// The first object becomes the origin of the prefab, the others keep their placement relative to it
+fn create_prefab(world: &World, object_ids: &[u32]) -> anyhow::Result<Prefab> {
+    let first_id = object_ids
+        .first()
+        .ok_or_else(|| anyhow!("There are no objects to create a prefab from"))?;
+    let origin = world
+        .get_world_transform(first_id)
+        .ok_or_else(|| anyhow!("Object {first_id} doesn't exist"))?;
+
+    let objects = object_ids
+        .iter()
+        .map(|id| {
+            let mut prefab_object = get_prefab_object(world, *id, false)
+                .ok_or_else(|| anyhow!("Object {id} can't be part of a prefab"))?;
+            prefab_object.object.transform = match world.get_world_transform(id) {
+                Some(world_transform) if id != first_id => world_transform.relative_to(&origin),
+                _ => TransformComponent::default(),
+            };
+
+            Ok(prefab_object)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(Prefab { objects })
+}
+
+/// Saves the objects as a new prefab and replaces them with an instance of it. The instance takes the place of the
+/// first object. Returns the ID of the root object of the instance
+pub fn create_prefab_from_objects(
+    world: &mut World,
+    object_ids: &[u32],
+    prefab_path: &Path,
+) -> anyhow::Result<u32> {
+    let prefab = create_prefab(world, object_ids)?;
+    save_prefab(&prefab, prefab_path)?;
+
+    let first_id = object_ids[0];
+    let parent_id = world.get_parent(&first_id);
+    let transform = world
+        .get_world_object(&first_id)
+        .map(|world_object| world_object.transform)
+        .unwrap_or_default();
+    for id in object_ids {
+        world.remove_world_object(*id);
+    }
+
+    let root = WorldObject::new(vec![], transform);
+    let root_id = match parent_id {
+        Some(parent_id) => world.add_child_world_object(parent_id, root)?,
+        None => world.add_world_object(root),
+    };
+    add_prefab_instance(world, root_id, prefab_path, prefab, &[])?;
+
+    Ok(root_id)
+}
+
+/// Turns the object into the root of a prefab instance, the objects of the prefab are added as its children
+pub fn add_prefab_instance(
+    world: &mut World,
+    root_id: u32,
+    prefab_path: &Path,
+    prefab: Prefab,
+    overrides: &[PrefabOverride],
+) -> anyhow::Result<()> {
+    add_prefab_objects(
+        world,
+        root_id,
+        &prefab.objects,
+        &PrefabPartPath::default(),
+        overrides,
+    )?;
+    assign_part_persistent_ids(world, root_id);
+    world.add_component(
+        root_id,
+        PrefabInstance {
+            prefab_path: prefab_path.to_path_buf(),
+            prefab,
+        },
+    )
+}
+
+/// The objects of the prefab are not saved into the level, so their persistent IDs are derived from the ID of the root
+/// and their place in the prefab. This way they get the same IDs every time the level is loaded
+fn assign_part_persistent_ids(world: &mut World, root_id: u32) {
+    let Some(root_persistent_id) = world.get_persistent_id(&root_id) else {
+        return;
+    };
+
+    let mut parts = get_instance_parts(world, root_id)
+        .into_iter()
+        .collect::<Vec<_>>();
+    parts.sort();
+    for (part, part_id) in parts {
+        let persistent_id = root_persistent_id.derive(&part.to_string());
+        if let Err(error) = world.set_persistent_id(part_id, persistent_id) {
+            log::warn!(
+                "Object {part} of the prefab instance keeps a random persistent ID: {error}"
+            );
+        }
+    }
+}
+
+fn add_prefab_objects(
+    world: &mut World,
+    parent_id: u32,
+    prefab_objects: &[PrefabObject],
+    parent_part: &PrefabPartPath,
+    overrides: &[PrefabOverride],
+) -> anyhow::Result<()> {
+    for (index, prefab_object) in prefab_objects.iter().enumerate() {
+        let part = parent_part.get_child(index);
+        let prefab_override = overrides
+            .iter()
+            .find(|prefab_override| prefab_override.part == part);
+        if prefab_override.is_some_and(|prefab_override| prefab_override.removed) {
+            continue;
+        }
+
+        let mut object = prefab_object.object.clone();
+        if let Some(prefab_override) = prefab_override {
+            if let Some(transform) = prefab_override.transform {
+                object.transform = transform;
+            }
+            if let Some(components) = &prefab_override.components {
+                object.components = components.clone();
+            }
+        }
+
+        let object_id = world.add_child_world_object(parent_id, object)?;
+        world.add_component(object_id, PrefabPart { part: part.clone() })?;
+        add_prefab_objects(world, object_id, &prefab_object.children, &part, overrides)?;
+    }
+
+    Ok(())
+}
+
+/// The objects of the instance that were created from the prefab, by their place in the prefab
+pub fn get_instance_parts(world: &World, root_id: u32) -> HashMap<PrefabPartPath, u32> {
+    let mut parts = HashMap::new();
+    let mut ids_to_visit = get_part_children(world, root_id);
+    while let Some(id) = ids_to_visit.pop() {
+        if let Some(prefab_part) = world.get_component::<PrefabPart>(&id) {
+            parts.insert(prefab_part.part.clone(), id);
+            ids_to_visit.extend(get_part_children(world, id));
+        }
+    }
+
+    parts
+}
+
+/// The children that were created from the prefab. The objects added by the user are not among them
+pub fn get_part_children(world: &World, id: u32) -> Vec<u32> {
+    world
+        .get_children(&id)
+        .iter()
+        .copied()
+        .filter(|child_id| world.get_component::<PrefabPart>(child_id).is_some())
+        .collect()
+}
+
+/// The objects the user added under the objects of the prefab, grouped by the place of their parent in the prefab. The
+/// objects added directly under the root of the instance are not among them
+pub fn get_added_part_children(world: &World, root_id: u32) -> Vec<(PrefabPartPath, Vec<u32>)> {
+    let mut added_children = get_instance_parts(world, root_id)
+        .into_iter()
+        .filter_map(|(part, part_id)| {
+            let child_ids = world
+                .get_children(&part_id)
+                .iter()
+                .copied()
+                .filter(|child_id| world.get_component::<PrefabPart>(child_id).is_none())
+                .collect::<Vec<_>>();
+            (!child_ids.is_empty()).then_some((part, child_ids))
+        })
+        .collect::<Vec<_>>();
+    added_children
+        .sort_by(|(part, _child_ids), (other_part, _other_child_ids)| part.cmp(other_part));
+
+    added_children
+}
+
+/// How the instance differs from the prefab it was created from. Empty if the object is not a prefab instance
+pub fn get_prefab_overrides(world: &World, root_id: u32) -> Vec<PrefabOverride> {
+    let Some(instance) = world.get_component::<PrefabInstance>(&root_id) else {
+        return vec![];
+    };
+
+    let parts = get_instance_parts(world, root_id);
+    let mut overrides = vec![];
+    collect_prefab_overrides(
+        world,
+        &instance.prefab.objects,
+        &PrefabPartPath::default(),
+        &parts,
+        &mut overrides,
+    );
+
+    overrides
+}
+
+fn collect_prefab_overrides(
+    world: &World,
+    prefab_objects: &[PrefabObject],
+    parent_part: &PrefabPartPath,
+    parts: &HashMap<PrefabPartPath, u32>,
+    overrides: &mut Vec<PrefabOverride>,
+) {
+    for (index, prefab_object) in prefab_objects.iter().enumerate() {
+        let part = parent_part.get_child(index);
+        let object = parts
+            .get(&part)
+            .and_then(|id| world.get_world_object(id))
+            .and_then(|world_object| world_object.get_saved_copy());
+        let Some(object) = object else {
+            overrides.push(PrefabOverride {
+                part,
+                transform: None,
+                components: None,
+                removed: true,
+            });
+            continue;
+        };
+
+        let transform =
+            (object.transform != prefab_object.object.transform).then_some(object.transform);
+        let components =
+            (object.components != prefab_object.object.components).then_some(object.components);
+        if transform.is_some() || components.is_some() {
+            overrides.push(PrefabOverride {
+                part: part.clone(),
+                transform,
+                components,
+                removed: false,
+            });
+        }
+
+        collect_prefab_overrides(world, &prefab_object.children, &part, parts, overrides);
+    }
+}
+
+/// The root of the prefab instance the object belongs to, if any
+pub fn find_prefab_instance_root(world: &World, id: u32) -> Option<u32> {
+    let mut current_id = Some(id);
+    while let Some(id) = current_id {
+        if world.get_component::<PrefabInstance>(&id).is_some() {
+            return Some(id);
+        }
+        current_id = world.get_parent(&id);
+    }
+
+    None
+}
+
+/// Recreates the objects of the instance from the prefab. The objects the user added under the objects of the prefab
+/// are moved under the recreated objects, keeping their local transforms. If their parent is no longer in the prefab,
+/// they are moved under the root of the instance
+fn replace_instance_objects(
+    world: &mut World,
+    root_id: u32,
+    prefab: &Prefab,
+    overrides: &[PrefabOverride],
+) -> anyhow::Result<()> {
+    let mut added_children = vec![];
+    for (part, child_ids) in get_added_part_children(world, root_id) {
+        for child_id in child_ids {
+            let local_transform = world
+                .get_world_object(&child_id)
+                .map(|child| child.transform)
+                .unwrap_or_default();
+            world.set_parent(child_id, Some(root_id))?;
+            added_children.push((part.clone(), child_id, local_transform));
+        }
+    }
+
+    for part_id in get_part_children(world, root_id) {
+        world.remove_world_object(part_id);
+    }
+
+    add_prefab_objects(
+        world,
+        root_id,
+        &prefab.objects,
+        &PrefabPartPath::default(),
+        overrides,
+    )?;
+    assign_part_persistent_ids(world, root_id);
+    if let Some(instance) = world.get_component_mut::<PrefabInstance>(&root_id) {
+        instance.prefab = prefab.clone();
+    }
+
+    let parts = get_instance_parts(world, root_id);
+    for (part, child_id, local_transform) in added_children {
+        let Some(part_id) = parts.get(&part) else {
+            log::warn!("Object {part} was removed from the prefab, its added children are moved to the root");
+            continue;
+        };
+        world.set_parent(child_id, Some(*part_id))?;
+        if let Some(child) = world.get_world_object_mut(&child_id) {
+            child.transform = local_transform;
+        }
+    }
+
+    Ok(())
+}
+
+/// Saves the current state of the instance into the prefab file and updates every other instance of the prefab. The
+/// other instances keep their overrides. The objects the user added to the instances stay in the instances, they are
+/// not saved into the prefab
+pub fn apply_instance_to_prefab(world: &mut World, root_id: u32) -> anyhow::Result<()> {
+    let prefab_path = world
+        .get_component::<PrefabInstance>(&root_id)
+        .ok_or_else(|| anyhow!("Object {root_id} is not a prefab instance"))?
+        .prefab_path
+        .clone();
+
+    let prefab = Prefab {
+        objects: get_part_children(world, root_id)
+            .into_iter()
+            .filter_map(|part_id| get_prefab_object(world, part_id, true))
+            .collect(),
+    };
+    save_prefab(&prefab, &prefab_path)?;
+
+    let other_instance_ids = world
+        .query::<&PrefabInstance>()
+        .filter(|(id, instance)| *id != root_id && instance.prefab_path == prefab_path)
+        .map(|(id, _instance)| id)
+        .collect::<Vec<_>>();
+
+    // The instance matches the new prefab, its overrides are part of the prefab now
+    replace_instance_objects(world, root_id, &prefab, &[])?;
+    for id in other_instance_ids {
+        let overrides = get_prefab_overrides(world, id);
+        replace_instance_objects(world, id, &prefab, &overrides)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::test_helpers::create_test_world;
+
+    #[test]
+    fn part_paths_are_saved_as_strings() {
+        let part = PrefabPartPath::default().get_child(0).get_child(2);
+
+        let json = serde_json::to_string(&part).unwrap();
+        assert_eq!(json, "\"0/2\"");
+        assert_eq!(serde_json::from_str::<PrefabPartPath>(&json).unwrap(), part);
+        assert!(serde_json::from_str::<PrefabPartPath>("\"0/first\"").is_err());
+    }
+
+    #[test]
+    fn prefab_files_can_be_loaded() {
+        let prefab = load_prefab(Path::new("levels/fixtures/lamp.prefab")).unwrap();
+
+        assert_eq!(prefab.objects.len(), 1);
+        assert_eq!(prefab.objects[0].children.len(), 1);
+    }
+
+    #[test]
+    fn applying_changes_keeps_the_objects_added_to_the_instances() {
+        let prefab_path = std::env::temp_dir()
+            .join("applying_changes_keeps_the_objects_added_to_the_instances.prefab");
+        fs::copy("levels/fixtures/lamp.prefab", &prefab_path).unwrap();
+        let prefab = load_prefab(&prefab_path).unwrap();
+        let mut world = create_test_world();
+        let instance_ids = [0, 1].map(|_index| {
+            let root_id =
+                world.add_world_object(WorldObject::new(vec![], TransformComponent::default()));
+            add_prefab_instance(&mut world, root_id, &prefab_path, prefab.clone(), &[]).unwrap();
+            root_id
+        });
+        let part = PrefabPartPath::default().get_child(0);
+        let added_object_transform = TransformComponent::from_position(Vec3::X);
+        let added_ids = instance_ids.map(|instance_id| {
+            let part_id = get_instance_parts(&world, instance_id)[&part];
+            let added_object = WorldObject::new(vec![], added_object_transform);
+            world.add_child_world_object(part_id, added_object).unwrap()
+        });
+
+        apply_instance_to_prefab(&mut world, instance_ids[0]).unwrap();
+        let applied_prefab = load_prefab(&prefab_path).unwrap();
+        fs::remove_file(&prefab_path).unwrap();
+
+        assert_eq!(applied_prefab.objects[0].children.len(), 1);
+        for (instance_id, added_id) in instance_ids.into_iter().zip(added_ids) {
+            let parent_id = world.get_parent(&added_id).unwrap();
+            assert_eq!(
+                get_instance_parts(&world, instance_id).get(&part),
+                Some(&parent_id)
+            );
+            assert_eq!(
+                world.get_world_object(&added_id).unwrap().transform,
+                added_object_transform
+            );
+        }
+    }
+}
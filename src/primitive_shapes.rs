@@ -1,5 +1,7 @@
 use crate::model::{BufferWithLength, Primitive};
 use crate::vertex::VertexRawWithTangents;
+use glam::Vec3;
+use math_helpers::{Aabb, BoundingSphere};
 use wgpu::util::DeviceExt;
 
 const SQUARE_VERTICES: &'static [VertexRawWithTangents] = &[
@@ -47,11 +49,19 @@ pub fn square(render_device: &wgpu::Device) -> Primitive {
         usage: wgpu::BufferUsages::INDEX,
     });
 
+    let positions = SQUARE_VERTICES
+        .iter()
+        .map(|vertex| Vec3::from(vertex.position))
+        .collect::<Vec<_>>();
+
     Primitive {
         index_data: BufferWithLength {
             buffer: index_buffer,
             count: SQUARE_INDICES.len() as u32,
         },
         vertex_buffer,
+        bounding_box: Aabb::from_points(&positions),
+        bounding_sphere: BoundingSphere::from_points(&positions),
+        triangles: Primitive::get_triangles(&positions, SQUARE_INDICES),
     }
 }
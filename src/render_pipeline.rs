@@ -7,7 +7,7 @@ use wgpu::{
 use crate::{
     buffer_content::BufferContent,
     components::TransformComponentRaw,
-    model::Renderable,
+    model::{DrawMode, Renderable},
     pipelines::{ShaderCompilationResult, ShaderCompilationSuccess, ShaderCompiler},
     texture,
     vertex::VertexRawWithTangents,
@@ -227,10 +227,15 @@ impl RenderPipeline {
         bind_groups: &[&'a BindGroup],
         renderables: T,
         offset: u32,
+        draw_mode: &DrawMode<'a>,
     ) {
         self.set_render_parameters(render_pass, bind_groups, offset);
         for renderable in renderables {
-            renderable.render(render_pass, self.descriptor.material_bind_group_index);
+            renderable.render(
+                render_pass,
+                self.descriptor.material_bind_group_index,
+                draw_mode,
+            );
         }
     }
 }
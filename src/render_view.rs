@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use wgpu::{BindGroup, Buffer, Device, Queue};
+
+use crate::{
+    bind_group_layout_descriptors,
+    buffer::{create_bind_group_from_buffer_entire_binding_init, GpuBufferCreationOptions},
+    camera::Camera,
+    camera_controller::CameraController,
+    components::{CameraComponent, TransformComponent},
+    gbuffer_geometry_renderer::GBufferTargets,
+    post_process_manager::PostProcessTargets,
+    renderer::Renderer,
+    texture::SampledTexture,
+};
+
+/// The uniform buffer the shaders see the camera of a view through
+pub struct CameraRenderData {
+    binding_buffer: Buffer,
+    pub bind_group: BindGroup,
+}
+
+impl CameraRenderData {
+    pub fn new(device: &Device, camera_controller: &CameraController) -> Self {
+        let (binding_buffer, bind_group) = create_bind_group_from_buffer_entire_binding_init(
+            device,
+            &GpuBufferCreationOptions {
+                bind_group_layout_descriptor:
+                    &bind_group_layout_descriptors::BUFFER_VISIBLE_EVERYWHERE,
+                usages: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                label: "Camera".into(),
+            },
+            bytemuck::cast_slice(&[camera_controller.to_raw()]),
+        );
+
+        Self {
+            binding_buffer,
+            bind_group,
+        }
+    }
+
+    pub fn update(&self, queue: &Queue, camera_controller: &CameraController) {
+        queue.write_buffer(
+            &self.binding_buffer,
+            0,
+            bytemuck::cast_slice(&[camera_controller.to_raw()]),
+        );
+    }
+}
+
+/// The textures one view of the world is rendered into. The main camera has a screen sized one, every camera
+/// component has its own at the resolution of the component
+pub struct RenderViewTargets {
+    pub gbuffer: GBufferTargets,
+    pub post_process: PostProcessTargets,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl RenderViewTargets {
+    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+        Self {
+            gbuffer: GBufferTargets::new(device, width, height),
+            post_process: PostProcessTargets::new(device, width, height),
+            width,
+            height,
+        }
+    }
+
+    /// The tone mapped image of the view
+    pub fn get_output_texture(&self) -> &SampledTexture {
+        self.post_process.get_output_texture()
+    }
+}
+
+/// A camera component rendered into a texture. It follows the component and the transform of its object
+pub struct CameraView {
+    pub camera_controller: CameraController,
+    pub camera_render_data: CameraRenderData,
+    pub targets: RenderViewTargets,
+}
+
+impl CameraView {
+    pub fn new(
+        renderer: &Renderer,
+        camera_component: &CameraComponent,
+        world_transform: &TransformComponent,
+    ) -> Self {
+        let (width, height) = Self::get_resolution(camera_component);
+        let mut camera = Camera::new(width, height);
+        Self::apply_to_camera(&mut camera, camera_component, world_transform);
+
+        let camera_controller = CameraController::from_camera(&camera, width, height);
+        let camera_render_data = CameraRenderData::new(&renderer.device, &camera_controller);
+
+        Self {
+            camera_controller,
+            camera_render_data,
+            targets: RenderViewTargets::new(&renderer.device, width, height),
+        }
+    }
+
+    /// The render targets are recreated, if the resolution of the component changed. Returns true if they were
+    pub fn update(
+        &mut self,
+        renderer: &Renderer,
+        camera_component: &CameraComponent,
+        world_transform: &TransformComponent,
+    ) -> bool {
+        let (width, height) = Self::get_resolution(camera_component);
+        let are_targets_recreated = (width, height) != (self.targets.width, self.targets.height);
+        if are_targets_recreated {
+            self.targets = RenderViewTargets::new(&renderer.device, width, height);
+            self.camera_controller.resize(width, height);
+        }
+
+        Self::apply_to_camera(
+            &mut self.camera_controller.camera,
+            camera_component,
+            world_transform,
+        );
+        self.camera_controller.update(Duration::ZERO);
+        self.camera_render_data
+            .update(&renderer.queue, &self.camera_controller);
+
+        are_targets_recreated
+    }
+
+    fn get_resolution(camera_component: &CameraComponent) -> (u32, u32) {
+        (
+            camera_component.resolution_width.max(1),
+            camera_component.resolution_height.max(1),
+        )
+    }
+
+    /// The scale of the object doesn't affect the camera
+    fn apply_to_camera(
+        camera: &mut Camera,
+        camera_component: &CameraComponent,
+        world_transform: &TransformComponent,
+    ) {
+        camera.position = world_transform.get_position();
+        camera.orientation = world_transform.get_rotation();
+        camera.fov_y = camera_component.fov_y;
+    }
+}
@@ -48,9 +48,11 @@ impl Renderer {
         if !supported_features.contains(required_features) {
             panic!("Not all required features are supported. \nRequired features: {:?}\nSupported features: {:?}", required_features, supported_features);
         }
+        // Used by the GPU driven rendering to merge indirect draws, but it can fall back to separate draws
+        let optional_features = supported_features & wgpu::Features::MULTI_DRAW_INDIRECT;
         let (device, queue) = block_on(adapter.request_device(
             &wgpu::DeviceDescriptor {
-                required_features,
+                required_features: required_features | optional_features,
                 // WebGL doesn't support all of wgpu's features, so if
                 // we're building for the web we'll have to disable some.
                 required_limits: if cfg!(target_arch = "wasm32") {
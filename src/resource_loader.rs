@@ -11,8 +11,10 @@ use ui_item_derive::{UiDisplayable, UiSettableNew};
 use wgpu::{CommandEncoderDescriptor, Device, Extent3d};
 
 use glam::{Vec2, Vec3};
+use math_helpers::simplify_by_vertex_clustering;
 
 use crate::components::TransformComponent;
+use crate::lod::LodSettings;
 use crate::model::{ModelDescriptor, RenderablePart};
 use crate::primitive_shapes::square;
 use crate::renderer::Renderer;
@@ -154,7 +156,9 @@ impl ResourceLoader {
                     Some(&format!("{path:?}")),
                 )))
             }
-            crate::texture::MaterialSource::Default => Ok(self
+            // The image of the camera view is bound by the world renderer, the default texture stands in for it
+            crate::texture::MaterialSource::Default
+            | crate::texture::MaterialSource::CameraView(_) => Ok(self
                 .default_textures
                 .get(&texture_usage)
                 .ok_or(anyhow!(
@@ -164,31 +168,64 @@ impl ResourceLoader {
         }
     }
 
-    pub fn load_model(
+    /// Loads the mesh and generates `generated_lod_count` less detailed versions of it. Only meshes loaded from
+    /// files get generated levels of detail, the primitive shapes are too simple to benefit from them
+    fn load_mesh(
         &self,
-        mesh_descriptor: &ModelDescriptor,
-        renderer: &Renderer,
-    ) -> anyhow::Result<Vec<RenderablePart>> {
-        let primitive = match &mesh_descriptor.mesh_descriptor {
-            MeshDescriptor::PrimitiveInCode(shape) => {
-                self.primitive_shapes.get(shape).unwrap().clone()
-            }
+        mesh_descriptor: &MeshDescriptor,
+        device: &Device,
+        generated_lod_count: u32,
+    ) -> anyhow::Result<(Rc<Primitive>, Vec<Rc<Primitive>>)> {
+        match mesh_descriptor {
+            MeshDescriptor::PrimitiveInCode(shape) => Ok((
+                self.primitive_shapes.get(shape).unwrap().clone(),
+                Vec::new(),
+            )),
             MeshDescriptor::FromFile(path) => {
                 if let Some(extension) = path.extension() {
                     if extension == "obj" {
-                        Rc::new(load_obj(&renderer.device, path.clone())?)
+                        let mesh_data = load_obj_mesh_data(path)?;
+                        let lod_primitives =
+                            generate_lod_primitives(device, path, &mesh_data, generated_lod_count);
+                        Ok((
+                            Rc::new(mesh_data.to_primitive(device, path.clone())),
+                            lod_primitives,
+                        ))
                     } else if extension == "gltf" {
-                        Rc::new(load_gltf(&renderer.device, path.clone())?)
+                        Ok((Rc::new(load_gltf(device, path.clone())?), Vec::new()))
                     } else {
-                        return Err(anyhow!(
+                        Err(anyhow!(
                             "Resource loading not yet implemented for file type {extension:?}"
-                        ));
+                        ))
                     }
                 } else {
-                    return Err(anyhow!("Failed to get extension of file {path:?}"));
+                    Err(anyhow!("Failed to get extension of file {path:?}"))
                 }
             }
+        }
+    }
+
+    pub fn load_model(
+        &self,
+        mesh_descriptor: &ModelDescriptor,
+        lod_settings: &LodSettings,
+        renderer: &Renderer,
+    ) -> anyhow::Result<Vec<RenderablePart>> {
+        // The levels are only generated if none were authored
+        let generated_lod_count = if lod_settings.authored_meshes.is_empty() {
+            lod_settings.generated_lod_count
+        } else {
+            0
         };
+        let (primitive, mut lod_primitives) = self.load_mesh(
+            &mesh_descriptor.mesh_descriptor,
+            &renderer.device,
+            generated_lod_count,
+        )?;
+        for authored_mesh in &lod_settings.authored_meshes {
+            let (lod_primitive, _) = self.load_mesh(authored_mesh, &renderer.device, 0)?;
+            lod_primitives.push(lod_primitive);
+        }
 
         let material_render_data = match &mesh_descriptor.material_descriptor {
             PbrMaterialDescriptor::Texture(textures) => {
@@ -233,6 +270,7 @@ impl ResourceLoader {
             primitive,
             material_render_data,
             local_transform: TransformComponent::default(),
+            lod_primitives,
         }])
     }
 }
@@ -313,8 +351,92 @@ pub fn load_gltf(device: &wgpu::Device, asset_path: PathBuf) -> anyhow::Result<P
     // ))
 }
 
-pub fn load_obj(device: &wgpu::Device, asset_path: PathBuf) -> anyhow::Result<Primitive> {
-    let mut file_reader = BufReader::new(File::open(&asset_path)?);
+/// The vertex attributes and the indices of a mesh, before they are uploaded to the GPU
+struct MeshData {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    tex_coords: Vec<Vec2>,
+    indices: Vec<u32>,
+}
+
+impl MeshData {
+    fn to_primitive(&self, device: &wgpu::Device, path: PathBuf) -> Primitive {
+        Primitive::new(
+            device,
+            path,
+            &self.positions,
+            &self.normals,
+            &self.tex_coords,
+            &self.indices,
+        )
+    }
+
+    fn simplify(&self, grid_resolution: u32) -> MeshData {
+        let simplified =
+            simplify_by_vertex_clustering(&self.positions, &self.indices, grid_resolution);
+        let select = |attributes: &[Vec3]| {
+            simplified
+                .vertex_indices
+                .iter()
+                .map(|index| attributes[*index as usize])
+                .collect()
+        };
+
+        MeshData {
+            positions: select(&self.positions),
+            normals: select(&self.normals),
+            tex_coords: simplified
+                .vertex_indices
+                .iter()
+                .map(|index| self.tex_coords[*index as usize])
+                .collect(),
+            indices: simplified.indices,
+        }
+    }
+}
+
+/// Generates at most `lod_count` less and less detailed versions of the mesh. Each level is simplified from the
+/// original mesh with a coarser grid than the previous one. A level is only kept if it has noticeably
+/// less triangles than the previous one
+fn generate_lod_primitives(
+    device: &wgpu::Device,
+    path: &PathBuf,
+    mesh_data: &MeshData,
+    lod_count: u32,
+) -> Vec<Rc<Primitive>> {
+    const FIRST_GRID_RESOLUTION: u32 = 64;
+    const MAX_TRIANGLE_RATIO_TO_PREVIOUS_LEVEL: f32 = 0.75;
+
+    let mut lod_primitives = Vec::new();
+    let mut previous_triangle_count = mesh_data.indices.len() / 3;
+    let mut grid_resolution = FIRST_GRID_RESOLUTION;
+
+    while lod_primitives.len() < lod_count as usize && grid_resolution > 1 {
+        let simplified_mesh = mesh_data.simplify(grid_resolution);
+        grid_resolution /= 2;
+
+        let triangle_count = simplified_mesh.indices.len() / 3;
+        if triangle_count == 0 {
+            break;
+        }
+        if triangle_count as f32
+            > previous_triangle_count as f32 * MAX_TRIANGLE_RATIO_TO_PREVIOUS_LEVEL
+        {
+            continue;
+        }
+
+        previous_triangle_count = triangle_count;
+        lod_primitives.push(Rc::new(simplified_mesh.to_primitive(
+            device,
+            path.with_extension(format!("lod{}", lod_primitives.len() + 1)),
+        )));
+    }
+
+    lod_primitives
+}
+
+fn load_obj_mesh_data(asset_path: &PathBuf) -> anyhow::Result<MeshData> {
+    let mut file_reader = BufReader::new(File::open(asset_path)?);
     let (models, _obj_materials) =
         tobj::load_obj_buf(&mut file_reader, &tobj::GPU_LOAD_OPTIONS, |_| {
             // We don't care about the mtl file, so this is just a dummy loader implementation
@@ -342,12 +464,14 @@ pub fn load_obj(device: &wgpu::Device, asset_path: PathBuf) -> anyhow::Result<Pr
         index_offset += (model.mesh.positions.len() / 3) as u32;
     }
 
-    Ok(Primitive::new(
-        device,
-        asset_path,
-        &vec_to_vec3s(positions),
-        &vec_to_vec3s(normals),
-        &vec_to_vec2s(tex_coords),
-        &indices,
-    ))
+    Ok(MeshData {
+        positions: vec_to_vec3s(positions),
+        normals: vec_to_vec3s(normals),
+        tex_coords: vec_to_vec2s(tex_coords),
+        indices,
+    })
+}
+
+pub fn load_obj(device: &wgpu::Device, asset_path: PathBuf) -> anyhow::Result<Primitive> {
+    Ok(load_obj_mesh_data(&asset_path)?.to_primitive(device, asset_path))
 }
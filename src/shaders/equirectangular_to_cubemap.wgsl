@@ -45,6 +45,22 @@ var t_equirectangular: texture_2d<f32>;
 @binding(1)
 var s_equirectangular: sampler;
 
+struct EnvironmentParams {
+    intensity: f32,
+    // Around the vertical axis, in radians
+    rotation: f32,
+};
+
+@group(2)
+@binding(0)
+var<uniform> environment: EnvironmentParams;
+
+fn rotate_around_y(v: vec3<f32>, angle: f32) -> vec3<f32> {
+    let s = sin(angle);
+    let c = cos(angle);
+    return vec3(c * v.x + s * v.z, v.y, -s * v.x + c * v.z);
+}
+
 const invAtan: vec2<f32> = vec2(0.1591, 0.3183);
 
 fn sample_spherical_map(v: vec3<f32>) -> vec2<f32> {
@@ -57,8 +73,9 @@ fn sample_spherical_map(v: vec3<f32>) -> vec2<f32> {
 
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    let uv = sample_spherical_map(normalize(input.local_position));
+    let direction = rotate_around_y(normalize(input.local_position), environment.rotation);
+    let uv = sample_spherical_map(direction);
     let color = textureSample(t_equirectangular, s_equirectangular, uv).rgb;
 
-    return vec4(hdr_decode(color), 1.0);
+    return vec4(hdr_decode(color) * environment.intensity, 1.0);
 }
@@ -16,6 +16,7 @@ struct InstanceInput {
     @location(11) normal_matrix_2: vec3<f32>,
     @interpolate(flat)
     @location(12) object_id: u32,
+    @location(13) lod_fade: f32,
 };
 
 struct PbrParameters {
@@ -49,6 +50,8 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) normal: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
+    @interpolate(flat)
+    @location(2) lod_fade: f32,
 };
 
 @vertex
@@ -77,13 +80,42 @@ fn vs_main(
     out.normal = normal_matrix * model.normal;
     out.tex_coords = model.tex_coord;
 
+    out.lod_fade = instance.lod_fade;
+
     return out;
 }
 
 @group(2) @binding(0)
 var<uniform> pbr_parameters: PbrParameters;
 
+// Screen-door transparency for cross-fading between two levels of detail. The level fading in has a positive fade
+// value, the level fading out has a negative one, so the two levels always cover complementary pixels
+fn is_discarded_by_lod_fade(lod_fade: f32, fragment_position: vec2<f32>) -> bool {
+    if lod_fade == 0.0 {
+        return false;
+    }
+
+    var bayer_matrix = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0,
+    );
+    let pixel = vec2<u32>(fragment_position) % vec2(4u);
+    let threshold = (bayer_matrix[pixel.y * 4u + pixel.x] + 0.5) / 16.0;
+
+    if lod_fade > 0.0 {
+        return threshold >= lod_fade;
+    } else {
+        return threshold < -lod_fade;
+    }
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if is_discarded_by_lod_fade(in.lod_fade, in.clip_position.xy) {
+        discard;
+    }
+
     return vec4<f32>(pbr_parameters.albedo, 1.0);
 }
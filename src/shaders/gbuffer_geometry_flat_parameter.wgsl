@@ -37,6 +37,7 @@ struct InstanceInput {
     @location(9) normal_matrix_0: vec3<f32>,
     @location(10) normal_matrix_1: vec3<f32>,
     @location(11) normal_matrix_2: vec3<f32>,
+    @location(13) lod_fade: f32,
 };
 
 struct VertexOutput {
@@ -46,6 +47,8 @@ struct VertexOutput {
     @location(2) tex_coord: vec2<f32>,
     @location(3) tangent: vec3<f32>,
     @location(4) bitangent: vec3<f32>,
+    @interpolate(flat)
+    @location(5) lod_fade: f32,
 };
 
 @group(1) @binding(0)
@@ -85,6 +88,8 @@ fn vs_main(
     out.bitangent = bitangent;
     out.world_normal = normal;
 
+    out.lod_fade = instance.lod_fade;
+
     return out;
 }
 
@@ -100,8 +105,35 @@ struct GBufferOutput {
   @location(3) rough_metal_ao: vec4<f32>,
 }
 
+// Screen-door transparency for cross-fading between two levels of detail. The level fading in has a positive fade
+// value, the level fading out has a negative one, so the two levels always cover complementary pixels
+fn is_discarded_by_lod_fade(lod_fade: f32, fragment_position: vec2<f32>) -> bool {
+    if lod_fade == 0.0 {
+        return false;
+    }
+
+    var bayer_matrix = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0,
+    );
+    let pixel = vec2<u32>(fragment_position) % vec2(4u);
+    let threshold = (bayer_matrix[pixel.y * 4u + pixel.x] + 0.5) / 16.0;
+
+    if lod_fade > 0.0 {
+        return threshold >= lod_fade;
+    } else {
+        return threshold < -lod_fade;
+    }
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> GBufferOutput {
+    if is_discarded_by_lod_fade(in.lod_fade, in.clip_position.xy) {
+        discard;
+    }
+
     var output: GBufferOutput;
     output.position = in.world_position;
 
@@ -0,0 +1,158 @@
+// Culls every draw against every view and writes the indirect draw arguments of the draws
+// The arguments of the draw with index `draw_index` for the view with index `view_index` are at
+// `view_index * draw_count + draw_index`
+
+const DRAW_FLAG_CAST_SHADOWS: u32 = 1u;
+const DRAW_FLAG_USE_DEPTH_TEST: u32 = 2u;
+
+const VIEW_FLAG_SHADOW: u32 = 1u;
+const VIEW_FLAG_USE_HI_Z: u32 = 2u;
+
+const WORKGROUP_SIZE: u32 = 64u;
+
+struct DrawInput {
+    // xyz: center, w: radius
+    bounding_sphere: vec4<f32>,
+    aabb_min: vec4<f32>,
+    aabb_max: vec4<f32>,
+    // The index counts of the levels of detail selected for the camera and for the shadow maps
+    index_count: u32,
+    instance_count: u32,
+    flags: u32,
+    shadow_index_count: u32,
+}
+
+struct CullingView {
+    // The normals point towards the inside of the frustum
+    planes: array<vec4<f32>, 6>,
+    // The view-projection matrix that was used when the Hi-Z buffer was rendered
+    hi_z_view_proj: mat4x4<f32>,
+    flags: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+struct CullingParams {
+    draw_count: u32,
+    view_count: u32,
+    hi_z_mip_count: u32,
+    _padding: u32,
+}
+
+struct DrawIndexedIndirectArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0)
+var<storage, read> draws: array<DrawInput>;
+@group(0) @binding(1)
+var<storage, read> views: array<CullingView>;
+@group(0) @binding(2)
+var<storage, read_write> draw_args: array<DrawIndexedIndirectArgs>;
+@group(0) @binding(3)
+var<uniform> params: CullingParams;
+
+@group(1) @binding(0)
+var hi_z_texture: texture_2d<f32>;
+
+fn is_inside_frustum(draw: DrawInput, view: CullingView) -> bool {
+    for (var i = 0u; i < 6u; i++) {
+        let plane = view.planes[i];
+
+        // Cheap sphere test first
+        if dot(plane.xyz, draw.bounding_sphere.xyz) + plane.w < -draw.bounding_sphere.w {
+            return false;
+        }
+
+        // The corner of the box that is the furthest along the normal of the plane
+        let positive_vertex = select(draw.aabb_min.xyz, draw.aabb_max.xyz, plane.xyz >= vec3(0.0));
+        if dot(plane.xyz, positive_vertex) + plane.w < 0.0 {
+            return false;
+        }
+    }
+
+    return true;
+}
+
+fn is_occluded(draw: DrawInput, view: CullingView) -> bool {
+    var ndc_min = vec2(1.0);
+    var ndc_max = vec2(-1.0);
+    // With reversed z the closest point has the biggest depth
+    var closest_depth = 0.0;
+
+    for (var i = 0u; i < 8u; i++) {
+        let corner = select(draw.aabb_min.xyz, draw.aabb_max.xyz, (vec3(i) & vec3(1u, 2u, 4u)) != vec3(0u));
+        let clip_position = view.hi_z_view_proj * vec4(corner, 1.0);
+
+        // The box intersects the near plane, the projected rectangle is not reliable
+        if clip_position.w <= 0.0 {
+            return false;
+        }
+
+        let ndc = clip_position.xyz / clip_position.w;
+        ndc_min = min(ndc_min, ndc.xy);
+        ndc_max = max(ndc_max, ndc.xy);
+        closest_depth = max(closest_depth, ndc.z);
+    }
+
+    // NDC y points up, texture coordinates point down
+    let uv_min = clamp(vec2(ndc_min.x, -ndc_max.y) * 0.5 + 0.5, vec2(0.0), vec2(1.0));
+    let uv_max = clamp(vec2(ndc_max.x, -ndc_min.y) * 0.5 + 0.5, vec2(0.0), vec2(1.0));
+
+    // Choose the mip level where the rectangle covers only a couple of texels
+    let rect_size = (uv_max - uv_min) * vec2<f32>(textureDimensions(hi_z_texture, 0));
+    let mip_level = min(
+        u32(ceil(log2(max(max(rect_size.x, rect_size.y), 1.0)))),
+        params.hi_z_mip_count - 1u,
+    );
+
+    let mip_size = textureDimensions(hi_z_texture, mip_level);
+    let texel_min = min(vec2<u32>(uv_min * vec2<f32>(mip_size)), mip_size - 1u);
+    let texel_max = min(vec2<u32>(uv_max * vec2<f32>(mip_size)), mip_size - 1u);
+
+    var furthest_occluder_depth = 1.0;
+    for (var y = texel_min.y; y <= texel_max.y; y++) {
+        for (var x = texel_min.x; x <= texel_max.x; x++) {
+            furthest_occluder_depth = min(furthest_occluder_depth, textureLoad(hi_z_texture, vec2(x, y), mip_level).r);
+        }
+    }
+
+    return closest_depth < furthest_occluder_depth;
+}
+
+@compute
+@workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let draw_index = id.x;
+    let view_index = id.y;
+
+    if draw_index >= params.draw_count || view_index >= params.view_count { return; }
+
+    let draw = draws[draw_index];
+    let view = views[view_index];
+
+    var is_visible = is_inside_frustum(draw, view);
+    let is_shadow_view = (view.flags & VIEW_FLAG_SHADOW) != 0u;
+
+    if is_shadow_view && (draw.flags & DRAW_FLAG_CAST_SHADOWS) == 0u {
+        is_visible = false;
+    }
+
+    // Objects without depth testing are drawn on top of everything, they can't be occluded
+    if is_visible && (view.flags & VIEW_FLAG_USE_HI_Z) != 0u && (draw.flags & DRAW_FLAG_USE_DEPTH_TEST) != 0u {
+        is_visible = !is_occluded(draw, view);
+    }
+
+    draw_args[view_index * params.draw_count + draw_index] = DrawIndexedIndirectArgs(
+        select(draw.index_count, draw.shadow_index_count, is_shadow_view),
+        select(0u, draw.instance_count, is_visible),
+        0u,
+        0,
+        0u,
+    );
+}
@@ -0,0 +1,45 @@
+// Builds one level of the hierarchical depth buffer used for occlusion culling
+// Each texel of the destination contains the furthest depth of the source texels it covers. With reversed z
+// the furthest depth is the smallest one, so this is a min reduction
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+
+@group(1) @binding(0)
+var destination_texture: texture_storage_2d<r32float, write>;
+
+@compute
+@workgroup_size(8,8,1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let destination_texture_size = textureDimensions(destination_texture);
+    let source_texture_size = textureDimensions(source_texture);
+
+    if any(id.xy >= destination_texture_size) { return; }
+
+    // The first level has the same size as the depth buffer, it's a simple copy
+    if all(source_texture_size == destination_texture_size) {
+        let depth = textureLoad(source_texture, id.xy, 0).r;
+        textureStore(destination_texture, id.xy, vec4(depth, 0.0, 0.0, 1.0));
+        return;
+    }
+
+    // When the source size is odd, the last row/column of the destination has to cover an extra texel,
+    // otherwise that texel wouldn't be part of any of the destination texels
+    let is_last_column_of_odd_width = (source_texture_size.x & 1u) == 1u && id.x == destination_texture_size.x - 1u;
+    let is_last_row_of_odd_height = (source_texture_size.y & 1u) == 1u && id.y == destination_texture_size.y - 1u;
+    let texel_count = vec2(
+        select(2u, 3u, is_last_column_of_odd_width),
+        select(2u, 3u, is_last_row_of_odd_height),
+    );
+
+    let source_base_coords = id.xy * 2u;
+    var furthest_depth = 1.0;
+    for (var y = 0u; y < texel_count.y; y++) {
+        for (var x = 0u; x < texel_count.x; x++) {
+            let coords = min(source_base_coords + vec2(x, y), source_texture_size - 1u);
+            furthest_depth = min(furthest_depth, textureLoad(source_texture, coords, 0).r);
+        }
+    }
+
+    textureStore(destination_texture, id.xy, vec4(furthest_depth, 0.0, 0.0, 1.0));
+}
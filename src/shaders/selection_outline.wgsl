@@ -0,0 +1,88 @@
+struct GlobalGpuParams {
+    random_parameter: f32,
+    tone_mapping_type: u32,
+    ssr_thickness: f32,
+    outline_width: f32,
+    selection_outline_color: vec3<f32>,
+    occluded_outline_opacity: f32,
+    hover_outline_color: vec3<f32>,
+}
+
+@group(0)
+@binding(0)
+var destination_texture: texture_storage_2d<rgba16float, write>;
+
+@group(0) @binding(1)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var source_texture_samp: sampler;
+
+@group(1) @binding(0)
+var<uniform> global_gpu_params: GlobalGpuParams;
+
+// The IDs of the visible objects
+@group(2) @binding(0)
+var object_id_texture: texture_2d<u32>;
+// The IDs of the selected and the hovered objects, rendered without depth test
+@group(2) @binding(1)
+var selection_mask_texture: texture_2d<u32>;
+@group(2) @binding(2)
+var hover_mask_texture: texture_2d<u32>;
+
+const max_outline_width: i32 = 8;
+const hover_outline_opacity: f32 = 0.5;
+
+// A pixel is on the outline if an object in the mask is within the outline width, but the pixel itself doesn't belong
+// to that object. The outline is drawn with full opacity where the object is visible in the neighbourhood
+fn get_outline_opacity(mask_texture: texture_2d<u32>, pixel: vec2<i32>, texture_size: vec2<i32>) -> f32 {
+    let own_id = textureLoad(mask_texture, pixel, 0).r;
+    let width = min(global_gpu_params.outline_width, f32(max_outline_width));
+    let search_radius = i32(ceil(width));
+
+    var opacity = 0.0;
+    for (var y = -search_radius; y <= search_radius; y++) {
+        for (var x = -search_radius; x <= search_radius; x++) {
+            if f32(x * x + y * y) > width * width {
+                continue;
+            }
+
+            let neighbour = pixel + vec2(x, y);
+            if any(neighbour < vec2(0)) || any(neighbour >= texture_size) {
+                continue;
+            }
+
+            let neighbour_id = textureLoad(mask_texture, neighbour, 0).r;
+            if neighbour_id == 0u || neighbour_id == own_id {
+                continue;
+            }
+
+            if textureLoad(object_id_texture, neighbour, 0).r == neighbour_id {
+                return 1.0;
+            }
+            opacity = global_gpu_params.occluded_outline_opacity;
+        }
+    }
+
+    return opacity;
+}
+
+@compute
+@workgroup_size(8,8,1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let source_texture_size = textureDimensions(source_texture);
+
+    // Check if we are not indexing out of our textures
+    if any(id.xy >= source_texture_size) { return; }
+
+    let pixel = vec2<i32>(id.xy);
+    let texture_size = vec2<i32>(source_texture_size);
+    var color = textureLoad(source_texture, pixel, 0);
+
+    let hover_opacity = get_outline_opacity(hover_mask_texture, pixel, texture_size) * hover_outline_opacity;
+    color = vec4(mix(color.rgb, global_gpu_params.hover_outline_color, hover_opacity), color.a);
+
+    let selection_opacity = get_outline_opacity(selection_mask_texture, pixel, texture_size);
+    color = vec4(mix(color.rgb, global_gpu_params.selection_outline_color, selection_opacity), color.a);
+
+    textureStore(destination_texture, id.xy, color);
+}
@@ -1,9 +1,6 @@
 use wgpu::{Device, RenderPass, TextureFormat};
 
-use crate::{
-    camera_controller::CameraController,
-    pipelines::{self, ShaderCompilationSuccess, SkyboxRP},
-};
+use crate::pipelines::{self, ShaderCompilationSuccess, SkyboxRP};
 
 pub struct Skybox {
     skybox_rp: SkyboxRP,
@@ -31,10 +28,10 @@ impl Skybox {
     pub fn render<'a, 'b: 'a>(
         &'b self,
         render_pass: &mut RenderPass<'a>,
-        camera_controller: &'b CameraController,
+        camera_bind_group: &'b wgpu::BindGroup,
         cubemap_bind_group: &'b wgpu::BindGroup,
     ) {
         self.skybox_rp
-            .render(render_pass, camera_controller, &cubemap_bind_group);
+            .render(render_pass, camera_bind_group, &cubemap_bind_group);
     }
 }
@@ -0,0 +1,5 @@
+use crate::{camera_controller::CameraController, world::World};
+
+pub fn create_test_world() -> World {
+    World::new(CameraController::new(800, 600))
+}
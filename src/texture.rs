@@ -4,7 +4,7 @@ use anyhow::*;
 use serde::{Deserialize, Serialize};
 use wgpu::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
 
-use crate::renderer::Renderer;
+use crate::{persistent_id::PersistentId, renderer::Renderer};
 
 const SKYBOX_TEXTURE_SIZE: u32 = 512;
 
@@ -39,6 +39,8 @@ pub struct SampledTexture {
 pub enum MaterialSource {
     FromFile(PathBuf),
     Default,
+    /// The image of the camera component of the object, updated every frame (eg. for screens)
+    CameraView(PersistentId),
 }
 
 #[derive(
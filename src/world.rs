@@ -1,38 +1,85 @@
-use std::{collections::HashMap, path::PathBuf, time::Duration};
+use std::{
+    any::type_name,
+    collections::{HashMap, HashSet},
+    mem,
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use crossbeam_channel::Receiver;
+use glam::Vec3;
 
 use crate::{
     camera::Camera,
     camera_controller::CameraController,
-    components::OmnipresentComponentType,
-    renderer::Renderer,
+    camera_path::CameraViewpoints,
+    component_storage::{Component, ComponentQuery, ComponentStorage},
+    components::{LightObjectComponent, SceneComponentType, TransformComponent},
+    persistent_id::PersistentId,
+    world_events::{ComponentTypeId, WorldEvent, WorldEventBus, WorldEventFilter},
     world_object::{OmnipresentObject, WorldObject},
 };
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-pub struct GlobalWorldSettings {
-    sykbox_path: Option<PathBuf>,
+pub const DEFAULT_SKYBOX_PATH: &str = "assets/textures/skybox/golf_course.hdr";
+
+/// The environment map of the level. It's shown as the skybox and it's the source of the image based lighting
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EnvironmentSettings {
+    /// Equirectangular HDR image
+    pub skybox_path: PathBuf,
+    /// Multiplies the color of the environment map
+    pub intensity: f32,
+    /// Rotation around the vertical axis, in degrees
+    pub rotation: f32,
 }
 
-#[derive(Debug, Clone)]
-pub enum ModificationType {
-    Added,
-    Removed,
-    Modified,
+impl Default for EnvironmentSettings {
+    fn default() -> Self {
+        Self {
+            skybox_path: PathBuf::from(DEFAULT_SKYBOX_PATH),
+            intensity: 1.0,
+            rotation: 0.0,
+        }
+    }
 }
 
+#[derive(Debug, Default)]
+pub struct GlobalWorldSettings {
+    environment: EnvironmentSettings,
+    camera_viewpoints: CameraViewpoints,
+}
+
+/// The place of a world object in the hierarchy
 #[derive(Debug, Clone)]
-pub struct DirtyObject {
-    pub id: u32,
-    pub modification_type: ModificationType,
+struct SceneNode {
+    parent: Option<u32>,
+    children: Vec<u32>,
+    /// The transform of the object is relative to its parent, this is the result of combining it with the transforms
+    /// of all the ancestors
+    world_transform: TransformComponent,
 }
 
 pub struct World {
     pub camera_controller: CameraController,
-    pub dirty_objects: Vec<DirtyObject>,
 
     world_objects: HashMap<u32, WorldObject>,
+    scene_nodes: HashMap<u32, SceneNode>,
+    /// Components of the types that are not built into `WorldObject`
+    component_storage: ComponentStorage,
     omnipresent_objects: HashMap<u32, OmnipresentObject>,
 
+    /// Every object (world and omnipresent) has a persistent ID besides its runtime ID
+    persistent_ids: HashMap<u32, PersistentId>,
+    runtime_ids: HashMap<PersistentId, u32>,
+
+    event_bus: WorldEventBus,
+    /// The state of the objects before they were first borrowed mutably since the last `publish_changes`. Comparing
+    /// them with the current state tells what actually changed
+    modified_objects: HashMap<u32, WorldObject>,
+    /// Like `modified_objects`, for the omnipresent objects
+    modified_omnipresent_objects: HashMap<u32, OmnipresentObject>,
+
     global_settings: GlobalWorldSettings,
 
     next_object_id: u32,
@@ -42,27 +89,64 @@ impl World {
     pub fn new(camera_controller: CameraController) -> Self {
         World {
             world_objects: HashMap::new(),
+            scene_nodes: HashMap::new(),
+            component_storage: ComponentStorage::new(),
             omnipresent_objects: HashMap::new(),
-            dirty_objects: vec![],
+            persistent_ids: HashMap::new(),
+            runtime_ids: HashMap::new(),
+            event_bus: WorldEventBus::new(),
+            modified_objects: HashMap::new(),
+            modified_omnipresent_objects: HashMap::new(),
             next_object_id: 1, // 0 stands for the placeholder "no object"
             camera_controller,
-            global_settings: GlobalWorldSettings { sykbox_path: None },
+            global_settings: GlobalWorldSettings::default(),
         }
     }
 
-    pub fn add_world_object(&mut self, mut world_object: WorldObject) -> u32 {
+    pub fn add_world_object(&mut self, world_object: WorldObject) -> u32 {
+        self.insert_world_object(world_object, None)
+    }
+
+    /// Adds the object to the world with its transform being relative to the parent object
+    pub fn add_child_world_object(
+        &mut self,
+        parent_id: u32,
+        world_object: WorldObject,
+    ) -> anyhow::Result<u32> {
+        if !self.world_objects.contains_key(&parent_id) {
+            return Err(anyhow!("Parent object {parent_id} doesn't exist"));
+        }
+
+        Ok(self.insert_world_object(world_object, Some(parent_id)))
+    }
+
+    fn insert_world_object(&mut self, mut world_object: WorldObject, parent: Option<u32>) -> u32 {
         let new_object_id = self.next_object_id;
 
-        if let Some(_light_component) = world_object.get_light_component() {
+        if let Some(_light_component) = world_object.get_component::<LightObjectComponent>() {
             world_object.add_light_debug_object();
         }
 
-        self.dirty_objects.push(DirtyObject {
-            id: new_object_id,
-            modification_type: ModificationType::Added,
-        });
+        let world_transform = self
+            .get_parent_world_transform(parent)
+            .combine(&world_object.transform);
+        if let Some(parent_id) = parent {
+            if let Some(parent_node) = self.scene_nodes.get_mut(&parent_id) {
+                parent_node.children.push(new_object_id);
+            }
+        }
+        self.scene_nodes.insert(
+            new_object_id,
+            SceneNode {
+                parent,
+                children: vec![],
+                world_transform,
+            },
+        );
 
         self.world_objects.insert(new_object_id, world_object);
+        self.assign_new_persistent_id(new_object_id);
+        self.publish(WorldEvent::ObjectAdded { id: new_object_id });
 
         self.next_object_id += 1;
 
@@ -72,80 +156,628 @@ impl World {
     pub fn add_omnipresent_object(&mut self, omnipresent_object: OmnipresentObject) -> u32 {
         let new_object_id = self.next_object_id;
 
-        for component in &omnipresent_object.components {
-            match component {
-                OmnipresentComponentType::DirectionalLight(_directional_light) => {
-                    self.dirty_objects.push(DirtyObject {
-                        id: new_object_id,
-                        modification_type: ModificationType::Added,
-                    });
-                }
-            }
-        }
-
         self.omnipresent_objects
             .insert(new_object_id, omnipresent_object);
+        self.assign_new_persistent_id(new_object_id);
+        self.publish(WorldEvent::ObjectAdded { id: new_object_id });
 
         self.next_object_id += 1;
 
         new_object_id
     }
 
+    /// Removes the object together with all of its descendants
     pub fn remove_world_object(&mut self, object_id_to_remove: u32) {
-        self.world_objects.remove(&object_id_to_remove);
-        self.dirty_objects.push(DirtyObject {
-            id: object_id_to_remove,
-            modification_type: ModificationType::Removed,
-        });
+        if let Some(node) = self.scene_nodes.remove(&object_id_to_remove) {
+            if let Some(parent_id) = node.parent {
+                if let Some(parent_node) = self.scene_nodes.get_mut(&parent_id) {
+                    parent_node
+                        .children
+                        .retain(|child| *child != object_id_to_remove);
+                }
+            }
+
+            for child in node.children {
+                self.remove_world_object(child);
+            }
+        }
+
+        // The subscribers filter by the components the object had
+        let component_types = self.get_object_component_types(object_id_to_remove);
+        if self.world_objects.remove(&object_id_to_remove).is_none() {
+            return;
+        }
+        self.modified_objects.remove(&object_id_to_remove);
+        self.finish_removing_object(object_id_to_remove, &component_types);
+    }
+
+    pub fn remove_omnipresent_object(&mut self, object_id_to_remove: u32) {
+        let component_types = self.get_object_component_types(object_id_to_remove);
+        if self
+            .omnipresent_objects
+            .remove(&object_id_to_remove)
+            .is_none()
+        {
+            return;
+        }
+        self.modified_omnipresent_objects
+            .remove(&object_id_to_remove);
+        self.finish_removing_object(object_id_to_remove, &component_types);
+    }
+
+    fn finish_removing_object(&mut self, id: u32, component_types: &[ComponentTypeId]) {
+        self.component_storage.remove_object(id);
+        if let Some(persistent_id) = self.persistent_ids.remove(&id) {
+            self.runtime_ids.remove(&persistent_id);
+        }
+
+        self.event_bus
+            .publish(&WorldEvent::ObjectRemoved { id }, component_types);
+    }
+
+    /// Removes every object from the world (eg. before loading another level) and resets the global settings. The
+    /// subscribers are notified about each removed object, so they can release what belongs to them
+    pub fn clear(&mut self) {
+        for id in self.get_root_world_object_ids() {
+            self.remove_world_object(id);
+        }
+        for id in self.get_omnipresent_object_ids() {
+            self.remove_omnipresent_object(id);
+        }
+        self.global_settings = GlobalWorldSettings::default();
+    }
+
+    pub fn get_environment_settings(&self) -> &EnvironmentSettings {
+        &self.global_settings.environment
+    }
+
+    /// The renderer picks up the change on its next update, see `WorldRenderer::update`
+    pub fn set_environment_settings(&mut self, environment: EnvironmentSettings) {
+        self.global_settings.environment = environment;
+    }
+
+    pub fn get_camera_viewpoints(&self) -> &CameraViewpoints {
+        &self.global_settings.camera_viewpoints
+    }
+
+    pub fn set_camera_viewpoints(&mut self, camera_viewpoints: CameraViewpoints) {
+        self.global_settings.camera_viewpoints = camera_viewpoints;
+    }
+
+    /// Moves the object under a new parent (or to the root of the hierarchy, if `new_parent` is None). The object keeps
+    /// its place in the world, its local transform is recalculated
+    pub fn set_parent(&mut self, child_id: u32, new_parent: Option<u32>) -> anyhow::Result<()> {
+        let Some(child_node) = self.scene_nodes.get(&child_id) else {
+            return Err(anyhow!("Object {child_id} doesn't exist"));
+        };
+
+        if child_node.parent == new_parent {
+            return Ok(());
+        }
+
+        if let Some(parent_id) = new_parent {
+            if !self.scene_nodes.contains_key(&parent_id) {
+                return Err(anyhow!("Parent object {parent_id} doesn't exist"));
+            }
+            if parent_id == child_id || self.is_ancestor_of(child_id, parent_id) {
+                return Err(anyhow!(
+                    "Object {child_id} can't be the child of its own descendant {parent_id}"
+                ));
+            }
+        }
+
+        let world_transform = child_node.world_transform;
+        let old_parent = child_node.parent;
+        if let Some(old_parent_id) = old_parent {
+            if let Some(old_parent_node) = self.scene_nodes.get_mut(&old_parent_id) {
+                old_parent_node.children.retain(|child| *child != child_id);
+            }
+        }
+        if let Some(parent_id) = new_parent {
+            if let Some(parent_node) = self.scene_nodes.get_mut(&parent_id) {
+                parent_node.children.push(child_id);
+            }
+        }
+
+        let local_transform =
+            world_transform.relative_to(&self.get_parent_world_transform(new_parent));
+        if let Some(child_node) = self.scene_nodes.get_mut(&child_id) {
+            child_node.parent = new_parent;
+        }
+        if let Some(child) = self.get_world_object_mut(&child_id) {
+            child.transform = local_transform;
+        }
+        self.publish(WorldEvent::ParentChanged { id: child_id });
+
+        Ok(())
+    }
+
+    fn assign_new_persistent_id(&mut self, id: u32) {
+        let mut persistent_id = PersistentId::generate();
+        while self.runtime_ids.contains_key(&persistent_id) {
+            persistent_id = PersistentId::generate();
+        }
+
+        self.persistent_ids.insert(id, persistent_id);
+        self.runtime_ids.insert(persistent_id, id);
+    }
+
+    /// Replaces the persistent ID the object got when it was added (eg. with the one saved in the level). Fails if
+    /// another object already has the given persistent ID, in that case the object keeps its current one
+    pub fn set_persistent_id(
+        &mut self,
+        id: u32,
+        persistent_id: PersistentId,
+    ) -> anyhow::Result<()> {
+        let Some(previous_persistent_id) = self.persistent_ids.get(&id).copied() else {
+            return Err(anyhow!("Object {id} doesn't exist"));
+        };
+
+        match self.runtime_ids.get(&persistent_id) {
+            Some(other_id) if *other_id == id => return Ok(()),
+            Some(other_id) => {
+                return Err(anyhow!(
+                    "Persistent ID {persistent_id} already belongs to object {other_id}"
+                ))
+            }
+            None => {}
+        }
+
+        self.runtime_ids.remove(&previous_persistent_id);
+        self.runtime_ids.insert(persistent_id, id);
+        self.persistent_ids.insert(id, persistent_id);
+
+        Ok(())
+    }
+
+    pub fn get_persistent_id(&self, id: &u32) -> Option<PersistentId> {
+        self.persistent_ids.get(id).copied()
+    }
+
+    /// The ID the object has while the application is running (eg. the one the object picker reports)
+    pub fn get_runtime_id(&self, persistent_id: &PersistentId) -> Option<u32> {
+        self.runtime_ids.get(persistent_id).copied()
+    }
+
+    pub fn get_parent(&self, id: &u32) -> Option<u32> {
+        self.scene_nodes.get(id).and_then(|node| node.parent)
+    }
+
+    pub fn get_children(&self, id: &u32) -> &[u32] {
+        self.scene_nodes
+            .get(id)
+            .map(|node| node.children.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// IDs of the objects without a parent, in the order they were added to the world
+    pub fn get_root_world_object_ids(&self) -> Vec<u32> {
+        let mut root_ids = self
+            .scene_nodes
+            .iter()
+            .filter(|(_id, node)| node.parent.is_none())
+            .map(|(id, _node)| *id)
+            .collect::<Vec<_>>();
+        root_ids.sort();
+
+        root_ids
+    }
+
+    fn is_ancestor_of(&self, ancestor_id: u32, id: u32) -> bool {
+        self.has_ancestor_in(id, &HashSet::from([ancestor_id]))
+    }
+
+    /// True if any of the ancestors of the object is in `ancestor_ids`
+    pub fn has_ancestor_in(&self, id: u32, ancestor_ids: &HashSet<u32>) -> bool {
+        let mut current_parent = self.get_parent(&id);
+        while let Some(parent_id) = current_parent {
+            if ancestor_ids.contains(&parent_id) {
+                return true;
+            }
+            current_parent = self.get_parent(&parent_id);
+        }
+
+        false
+    }
+
+    fn get_parent_world_transform(&self, parent: Option<u32>) -> TransformComponent {
+        parent
+            .and_then(|parent_id| self.get_world_transform(&parent_id))
+            .unwrap_or_default()
+    }
+
+    /// The transform of the object in world space. Up to date after `publish_changes` was called
+    pub fn get_world_transform(&self, id: &u32) -> Option<TransformComponent> {
+        self.scene_nodes.get(id).map(|node| node.world_transform)
+    }
+
+    /// Moves the object to the given world space position by changing its local transform
+    pub fn set_world_position(&mut self, id: &u32, world_position: Vec3) {
+        let parent_model_matrix = self
+            .get_parent_world_transform(self.get_parent(id))
+            .get_model_matrix();
+        let local_position = parent_model_matrix
+            .inverse()
+            .transform_point3(world_position);
+
+        if let Some(object) = self.get_world_object_mut(id) {
+            object.transform.set_position(local_position);
+        }
+    }
+
+    /// Moves, rotates and scales the object to the given world space transform by changing its local transform
+    pub fn set_world_transform(&mut self, id: &u32, world_transform: TransformComponent) {
+        let local_transform =
+            world_transform.relative_to(&self.get_parent_world_transform(self.get_parent(id)));
+
+        if let Some(object) = self.get_world_object_mut(id) {
+            object.transform = local_transform;
+        }
+    }
+
+    /// Receives the events matching the filter. The events are queued until the receiver processes them
+    pub fn subscribe(&mut self, filter: WorldEventFilter) -> Receiver<WorldEvent> {
+        self.event_bus.subscribe(filter)
+    }
+
+    fn get_object_component_types(&self, id: u32) -> Vec<ComponentTypeId> {
+        let mut component_types = if let Some(world_object) = self.world_objects.get(&id) {
+            world_object.get_component_types()
+        } else if let Some(omnipresent_object) = self.omnipresent_objects.get(&id) {
+            omnipresent_object.get_component_types()
+        } else {
+            vec![]
+        };
+        component_types.extend(self.component_storage.get_component_types(id));
+
+        component_types
+    }
+
+    fn publish(&mut self, event: WorldEvent) {
+        let component_types = self.get_object_component_types(event.get_object_id());
+        self.event_bus.publish(&event, &component_types);
+    }
+
+    /// Compares the objects that were borrowed mutably with their previous state and publishes what changed. The world
+    /// transforms of the moved objects and their descendants are recalculated as well, so everyone receiving the
+    /// events sees up to date world transforms
+    pub fn publish_changes(&mut self) {
+        let mut modified_objects = mem::take(&mut self.modified_objects)
+            .into_iter()
+            .collect::<Vec<_>>();
+        modified_objects.sort_by_key(|(id, _previous_state)| *id);
+
+        let mut moved_ids = HashSet::new();
+        for (id, previous_state) in modified_objects {
+            let Some(world_object) = self.world_objects.get(&id) else {
+                continue;
+            };
+
+            if previous_state.transform != world_object.transform {
+                moved_ids.insert(id);
+            }
+
+            for event in Self::get_component_change_events(id, &previous_state, world_object) {
+                self.publish(event);
+            }
+        }
+
+        let mut modified_omnipresent_objects = mem::take(&mut self.modified_omnipresent_objects)
+            .into_iter()
+            .collect::<Vec<_>>();
+        modified_omnipresent_objects.sort_by_key(|(id, _previous_state)| *id);
+        for (id, previous_state) in modified_omnipresent_objects {
+            let Some(omnipresent_object) = self.omnipresent_objects.get(&id) else {
+                continue;
+            };
+
+            let changed_component_types = omnipresent_object
+                .components
+                .iter()
+                .zip(omnipresent_object.get_component_types())
+                .filter(|(component, _component_type)| {
+                    !previous_state.components.contains(component)
+                })
+                .map(|(_component, component_type)| component_type)
+                .collect::<Vec<_>>();
+            for component_type in changed_component_types {
+                self.publish(WorldEvent::ComponentChanged { id, component_type });
+            }
+        }
+
+        // If an ancestor moved as well, then the object is updated when the subtree of the ancestor is updated
+        let mut subtree_roots = moved_ids
+            .iter()
+            .filter(|id| !self.has_ancestor_in(**id, &moved_ids))
+            .copied()
+            .collect::<Vec<_>>();
+        subtree_roots.sort();
+
+        for id in subtree_roots {
+            self.update_world_transform_recursive(id);
+        }
+    }
+
+    fn get_component_change_events(
+        id: u32,
+        previous_state: &WorldObject,
+        current_state: &WorldObject,
+    ) -> Vec<WorldEvent> {
+        let mut events = vec![];
+
+        for component in &current_state.components {
+            let component_type = component.get_component_type();
+            let previous_component = previous_state.components.iter().find(|previous_component| {
+                previous_component.get_component_type() == component_type
+            });
+
+            match (previous_component, component) {
+                (None, _) => events.push(WorldEvent::ComponentAdded { id, component_type }),
+                (Some(previous_component), _) if previous_component == component => {}
+                (
+                    Some(SceneComponentType::Renderable(previous_renderable)),
+                    SceneComponentType::Renderable(renderable),
+                ) => {
+                    let material = &renderable.model_descriptor.material_descriptor;
+                    if previous_renderable.model_descriptor.material_descriptor != *material {
+                        events.push(WorldEvent::MaterialChanged {
+                            id,
+                            material: material.clone(),
+                        });
+                    }
+
+                    // The material has its own event, so only the rest of the component is compared
+                    let mut previous_renderable = previous_renderable.clone();
+                    previous_renderable.update_material(material.clone());
+                    if previous_renderable != *renderable {
+                        events.push(WorldEvent::ComponentChanged { id, component_type });
+                    }
+                }
+                (Some(_previous_component), _) => {
+                    events.push(WorldEvent::ComponentChanged { id, component_type })
+                }
+            }
+        }
+
+        for previous_component in &previous_state.components {
+            let component_type = previous_component.get_component_type();
+            if !current_state
+                .components
+                .iter()
+                .any(|component| component.get_component_type() == component_type)
+            {
+                events.push(WorldEvent::ComponentRemoved { id, component_type });
+            }
+        }
+
+        events
+    }
+
+    fn update_world_transform_recursive(&mut self, id: u32) {
+        let Some(object) = self.world_objects.get(&id) else {
+            return;
+        };
+        let world_transform = self
+            .get_parent_world_transform(self.get_parent(&id))
+            .combine(&object.transform);
+
+        let Some(node) = self.scene_nodes.get_mut(&id) else {
+            return;
+        };
+        let children = node.children.clone();
+        if node.world_transform != world_transform {
+            node.world_transform = world_transform;
+            self.publish(WorldEvent::TransformChanged {
+                id,
+                world_transform,
+            });
+        }
+
+        // A descendant might have been moved, even if this object ends up in the same place
+        for child in children {
+            self.update_world_transform_recursive(child);
+        }
     }
 
     pub fn get_world_object(&self, id: &u32) -> Option<&WorldObject> {
         self.world_objects.get(id)
     }
 
+    /// The changes are published by the next `publish_changes`
     pub fn get_world_object_mut(&mut self, id: &u32) -> Option<&mut WorldObject> {
-        self.dirty_objects.push(DirtyObject {
-            id: *id,
-            modification_type: ModificationType::Modified,
+        let world_object = self.world_objects.get_mut(id)?;
+        self.modified_objects
+            .entry(*id)
+            .or_insert_with(|| world_object.clone());
+
+        Some(world_object)
+    }
+
+    /// Attaches the component to the object. Every object can have at most one component of each type
+    pub fn add_component<T: Component>(&mut self, id: u32, component: T) -> anyhow::Result<()> {
+        if !self.world_objects.contains_key(&id) {
+            return Err(anyhow!("Object {id} doesn't exist"));
+        }
+        if self.get_component::<T>(&id).is_some() {
+            return Err(anyhow!(
+                "Object {id} already has a component of type {}",
+                type_name::<T>()
+            ));
+        }
+
+        match SceneComponentType::from_component(component) {
+            Ok(scene_component) => {
+                if let Some(world_object) = self.get_world_object_mut(&id) {
+                    let is_light = matches!(scene_component, SceneComponentType::LightObject(_));
+                    world_object.components.push(scene_component);
+                    if is_light {
+                        world_object.add_light_debug_object();
+                    }
+                }
+            }
+            Err(component) => {
+                self.component_storage.insert(id, component);
+                self.publish(WorldEvent::ComponentAdded {
+                    id,
+                    component_type: ComponentTypeId::of::<T>(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Detaches the component of type `T` from the object. The transform can't be removed
+    pub fn remove_component<T: Component>(&mut self, id: u32) -> Option<T> {
+        let has_built_in_component = self
+            .world_objects
+            .get(&id)?
+            .components
+            .iter()
+            .any(|component| component.as_any().is::<T>());
+        if has_built_in_component {
+            return self.get_world_object_mut(&id)?.remove_component::<T>();
+        }
+
+        let removed_component = self.component_storage.remove::<T>(id)?;
+        self.publish(WorldEvent::ComponentRemoved {
+            id,
+            component_type: ComponentTypeId::of::<T>(),
         });
 
-        self.world_objects.get_mut(id)
+        Some(removed_component)
+    }
+
+    pub fn get_component<T: Component>(&self, id: &u32) -> Option<&T> {
+        let world_object = self.world_objects.get(id)?;
+        world_object
+            .get_component::<T>()
+            .or_else(|| self.component_storage.get::<T>(*id))
+    }
+
+    /// Like `get_world_object_mut`, the changes of the built-in components are published by the next
+    /// `publish_changes`. The other components can't be compared, so they are reported as changed right away
+    pub fn get_component_mut<T: Component>(&mut self, id: &u32) -> Option<&mut T> {
+        if self.world_objects.get(id)?.get_component::<T>().is_some() {
+            return self.get_world_object_mut(id)?.get_component_mut::<T>();
+        }
+
+        if self.component_storage.get::<T>(*id).is_some() {
+            self.publish(WorldEvent::ComponentChanged {
+                id: *id,
+                component_type: ComponentTypeId::of::<T>(),
+            });
+        }
+        self.component_storage.get_mut::<T>(*id)
+    }
+
+    /// Iterates over the objects that have all the components of the query, in the order they were added to the world.
+    /// Eg. `world.query::<(&TransformComponent, &RenderableComponent)>()`
+    pub fn query<Q: ComponentQuery>(&self) -> impl Iterator<Item = (u32, Q::Item<'_>)> + '_ {
+        let mut object_ids = self.world_objects.keys().copied().collect::<Vec<_>>();
+        object_ids.sort();
+
+        object_ids
+            .into_iter()
+            .filter_map(move |id| Q::fetch(self, id).map(|item| (id, item)))
     }
 
     pub fn get_omnipresent_object(&self, id: &u32) -> Option<&OmnipresentObject> {
         self.omnipresent_objects.get(id)
     }
 
-    pub fn get_omnipresent_object_mut(&mut self, id: &u32) -> Option<&mut WorldObject> {
-        self.dirty_objects.push(DirtyObject {
-            id: *id,
-            modification_type: ModificationType::Modified,
-        });
+    /// Like `get_world_object_mut`, the changed components are published by the next `publish_changes`
+    pub fn get_omnipresent_object_mut(&mut self, id: &u32) -> Option<&mut OmnipresentObject> {
+        let omnipresent_object = self.omnipresent_objects.get_mut(id)?;
+        self.modified_omnipresent_objects
+            .entry(*id)
+            .or_insert_with(|| omnipresent_object.clone());
 
-        self.world_objects.get_mut(id)
+        Some(omnipresent_object)
     }
 
     pub fn set_camera(&mut self, camera: &Camera) {
-        self.camera_controller.camera = camera.clone();
-    }
-
-    pub fn update(&mut self, delta: Duration, renderer: &Renderer) {
-        self.camera_controller.update(delta, &renderer.queue);
+        self.camera_controller.set_camera(camera);
     }
 
-    pub fn on_end_frame(&mut self) {
-        self.dirty_objects.clear();
+    pub fn update(&mut self, delta: Duration) {
+        self.camera_controller.update(delta);
     }
 
     pub fn handle_size_changed(&mut self, width: u32, height: u32) {
         self.camera_controller.resize(width, height);
     }
 
-    pub fn get_omnipresent_objects(&self) -> Vec<&OmnipresentObject> {
-        self.omnipresent_objects.values().collect::<Vec<_>>()
+    /// In the order they were added to the world
+    pub fn get_omnipresent_object_ids(&self) -> Vec<u32> {
+        let mut object_ids = self.omnipresent_objects.keys().copied().collect::<Vec<_>>();
+        object_ids.sort();
+
+        object_ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::OmnipresentComponentType, lights::DirectionalLight,
+        test_helpers::create_test_world,
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[test]
+    fn only_changed_omnipresent_components_are_published() {
+        let mut world = create_test_world();
+        let id = world.add_omnipresent_object(OmnipresentObject::new(vec![
+            OmnipresentComponentType::DirectionalLight(DirectionalLight {
+                direction: Vec3::NEG_Y,
+                color: Vec3::ONE,
+            }),
+        ]));
+        let events = world.subscribe(WorldEventFilter::all());
+
+        world.get_omnipresent_object_mut(&id);
+        world.publish_changes();
+        assert_eq!(events.try_iter().count(), 0);
+
+        if let Some(light) = world
+            .get_omnipresent_object_mut(&id)
+            .and_then(|object| object.get_light_component_mut())
+        {
+            light.color = Vec3::X;
+        }
+        world.publish_changes();
+        let events = events.try_iter().collect::<Vec<_>>();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0],
+            WorldEvent::ComponentChanged { id: event_id, component_type }
+                if event_id == id && component_type == ComponentTypeId::of::<DirectionalLight>()
+        ));
     }
 
-    pub fn get_world_objects(&self) -> Vec<&WorldObject> {
-        self.world_objects.values().collect::<Vec<_>>()
+    #[test]
+    fn removed_components_are_returned_and_published() {
+        let mut world = create_test_world();
+        let id = world.add_world_object(WorldObject::new(vec![], TransformComponent::default()));
+        world.add_component(id, Health(10)).unwrap();
+        let events = world.subscribe(WorldEventFilter::all());
+
+        assert_eq!(world.remove_component::<Health>(id), Some(Health(10)));
+        assert!(world.get_component::<Health>(&id).is_none());
+        let events = events.try_iter().collect::<Vec<_>>();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0],
+            WorldEvent::ComponentRemoved { id: event_id, component_type }
+                if event_id == id && component_type == ComponentTypeId::of::<Health>()
+        ));
+
+        assert_eq!(world.remove_component::<Health>(id), None);
+        assert!(world.get_world_object(&id).is_some());
     }
 }
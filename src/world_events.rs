@@ -0,0 +1,240 @@
+use std::any::{type_name, TypeId};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+
+use crate::{
+    component_storage::Component, components::TransformComponent, material::PbrMaterialDescriptor,
+};
+
+/// Identifies the type of a component in the world events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ComponentTypeId {
+    type_id: TypeId,
+    name: &'static str,
+}
+
+impl ComponentTypeId {
+    pub fn of<T: Component>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            name: type_name::<T>(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldEventKind {
+    ObjectAdded,
+    ObjectRemoved,
+    ParentChanged,
+    ComponentAdded,
+    ComponentRemoved,
+    ComponentChanged,
+    TransformChanged,
+    MaterialChanged,
+}
+
+#[derive(Debug, Clone)]
+pub enum WorldEvent {
+    ObjectAdded {
+        id: u32,
+    },
+    /// The object and all of its components are already removed from the world, when the event is received
+    ObjectRemoved {
+        id: u32,
+    },
+    ParentChanged {
+        id: u32,
+    },
+    ComponentAdded {
+        id: u32,
+        component_type: ComponentTypeId,
+    },
+    ComponentRemoved {
+        id: u32,
+        component_type: ComponentTypeId,
+    },
+    /// Any change of a component, that doesn't have its own event (eg. the material of a renderable has its own event)
+    ComponentChanged {
+        id: u32,
+        component_type: ComponentTypeId,
+    },
+    /// The world space transform changed, either because the object was moved or because one of its ancestors was
+    TransformChanged {
+        id: u32,
+        world_transform: TransformComponent,
+    },
+    /// The material of the renderable component changed, but the rest of the component is the same
+    MaterialChanged {
+        id: u32,
+        material: PbrMaterialDescriptor,
+    },
+}
+
+impl WorldEvent {
+    pub fn get_object_id(&self) -> u32 {
+        match self {
+            WorldEvent::ObjectAdded { id }
+            | WorldEvent::ObjectRemoved { id }
+            | WorldEvent::ParentChanged { id }
+            | WorldEvent::ComponentAdded { id, .. }
+            | WorldEvent::ComponentRemoved { id, .. }
+            | WorldEvent::ComponentChanged { id, .. }
+            | WorldEvent::TransformChanged { id, .. }
+            | WorldEvent::MaterialChanged { id, .. } => *id,
+        }
+    }
+
+    pub fn get_kind(&self) -> WorldEventKind {
+        match self {
+            WorldEvent::ObjectAdded { .. } => WorldEventKind::ObjectAdded,
+            WorldEvent::ObjectRemoved { .. } => WorldEventKind::ObjectRemoved,
+            WorldEvent::ParentChanged { .. } => WorldEventKind::ParentChanged,
+            WorldEvent::ComponentAdded { .. } => WorldEventKind::ComponentAdded,
+            WorldEvent::ComponentRemoved { .. } => WorldEventKind::ComponentRemoved,
+            WorldEvent::ComponentChanged { .. } => WorldEventKind::ComponentChanged,
+            WorldEvent::TransformChanged { .. } => WorldEventKind::TransformChanged,
+            WorldEvent::MaterialChanged { .. } => WorldEventKind::MaterialChanged,
+        }
+    }
+
+    fn get_component_type(&self) -> Option<ComponentTypeId> {
+        match self {
+            WorldEvent::ComponentAdded { component_type, .. }
+            | WorldEvent::ComponentRemoved { component_type, .. }
+            | WorldEvent::ComponentChanged { component_type, .. } => Some(*component_type),
+            _ => None,
+        }
+    }
+}
+
+/// Decides which events a subscriber receives
+#[derive(Debug, Clone, Default)]
+pub struct WorldEventFilter {
+    /// Every kind of event is received, if empty
+    kinds: Vec<WorldEventKind>,
+    /// Every object is considered, if empty
+    component_types: Vec<ComponentTypeId>,
+}
+
+impl WorldEventFilter {
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn with_kinds(mut self, kinds: &[WorldEventKind]) -> Self {
+        self.kinds.extend_from_slice(kinds);
+        self
+    }
+
+    /// Only receive events about objects that have a component of type `T` (or of any other type given to this
+    /// function), and about adding or removing such components
+    pub fn with_component<T: Component>(mut self) -> Self {
+        self.component_types.push(ComponentTypeId::of::<T>());
+        self
+    }
+
+    fn matches(&self, event: &WorldEvent, object_component_types: &[ComponentTypeId]) -> bool {
+        let is_kind_matching = self.kinds.is_empty() || self.kinds.contains(&event.get_kind());
+
+        let is_component_matching = self.component_types.is_empty()
+            || event
+                .get_component_type()
+                .is_some_and(|component_type| self.component_types.contains(&component_type))
+            || object_component_types
+                .iter()
+                .any(|component_type| self.component_types.contains(component_type));
+
+        is_kind_matching && is_component_matching
+    }
+}
+
+/// Delivers the changes of the world to the subscribers. Every subscriber has its own queue, so it doesn't matter
+/// in which order the subscribers process their events
+#[derive(Default)]
+pub struct WorldEventBus {
+    subscribers: Vec<(WorldEventFilter, Sender<WorldEvent>)>,
+}
+
+impl WorldEventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&mut self, filter: WorldEventFilter) -> Receiver<WorldEvent> {
+        let (sender, receiver) = unbounded();
+        self.subscribers.push((filter, sender));
+
+        receiver
+    }
+
+    /// `object_component_types` are the types of the components the object has (or had right before it was removed)
+    pub fn publish(&mut self, event: &WorldEvent, object_component_types: &[ComponentTypeId]) {
+        // Subscribers that dropped their receiver are removed
+        self.subscribers.retain(|(filter, sender)| {
+            !filter.matches(event, object_component_types) || sender.send(event.clone()).is_ok()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Health;
+    impl Component for Health {}
+
+    struct Name;
+    impl Component for Name {}
+
+    #[test]
+    fn subscribers_only_receive_matching_events() {
+        let mut event_bus = WorldEventBus::new();
+        let health_receiver =
+            event_bus.subscribe(WorldEventFilter::all().with_component::<Health>());
+        let added_receiver =
+            event_bus.subscribe(WorldEventFilter::all().with_kinds(&[WorldEventKind::ObjectAdded]));
+
+        event_bus.publish(
+            &WorldEvent::ObjectAdded { id: 1 },
+            &[ComponentTypeId::of::<Name>()],
+        );
+        event_bus.publish(
+            &WorldEvent::ObjectAdded { id: 2 },
+            &[ComponentTypeId::of::<Health>()],
+        );
+        // The object doesn't have the component anymore, but the event is about it
+        event_bus.publish(
+            &WorldEvent::ComponentRemoved {
+                id: 2,
+                component_type: ComponentTypeId::of::<Health>(),
+            },
+            &[],
+        );
+
+        let health_events = health_receiver.try_iter().collect::<Vec<_>>();
+        assert_eq!(health_events.len(), 2);
+        assert_eq!(health_events[0].get_kind(), WorldEventKind::ObjectAdded);
+        assert_eq!(
+            health_events[1].get_kind(),
+            WorldEventKind::ComponentRemoved
+        );
+
+        let added_ids = added_receiver
+            .try_iter()
+            .map(|event| event.get_object_id())
+            .collect::<Vec<_>>();
+        assert_eq!(added_ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn dropped_receivers_are_unsubscribed() {
+        let mut event_bus = WorldEventBus::new();
+        let receiver = event_bus.subscribe(WorldEventFilter::all());
+        drop(receiver);
+
+        event_bus.publish(&WorldEvent::ObjectAdded { id: 1 }, &[]);
+
+        assert!(event_bus.subscribers.is_empty());
+    }
+}
@@ -1,77 +1,696 @@
 use std::{
-    env,
+    collections::HashMap,
     fs::{self, File},
     io::Write,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-use serde_json::json;
+use anyhow::{anyhow, bail};
+use serde_json::{json, Number, Value};
 
 use crate::{
     camera::Camera,
-    world::World,
+    camera_path::CameraViewpoints,
+    level_migrations::{migrate_to_current_version, CURRENT_LEVEL_VERSION},
+    persistent_id::PersistentId,
+    prefab::{
+        add_prefab_instance, get_added_part_children, get_instance_parts, get_prefab_overrides,
+        load_prefab, Prefab, PrefabInstance, PrefabOverride, PrefabPart, PrefabPartPath,
+    },
+    world::{EnvironmentSettings, World},
     world_object::{OmnipresentObject, WorldObject},
 };
 
+/// A world object with its descendants. The transforms of the children are relative to the parent
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct LevelWorldObject {
+    /// Missing from the levels that were saved before the objects had persistent IDs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<PersistentId>,
+    #[serde(flatten)]
+    object: WorldObject,
+    /// Set for the root objects of prefab instances. The objects of the prefab are not saved into the level, only
+    /// their differences from the prefab
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prefab: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    overrides: Vec<PrefabOverride>,
+    /// For prefab instances, these are the objects added to the instance besides the objects of the prefab
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    children: Vec<LevelWorldObject>,
+    /// For prefab instances, the objects added under the objects of the prefab
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    part_children: Vec<LevelPartChildren>,
+}
+
+/// Objects added under an object of a prefab instance. The transforms are relative to that object
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct LevelPartChildren {
+    part: PrefabPartPath,
+    children: Vec<LevelWorldObject>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct LevelOmnipresentObject {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<PersistentId>,
+    #[serde(flatten)]
+    object: OmnipresentObject,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 struct LevelFileContent {
-    world_objects: Vec<WorldObject>,
-    omnipresent_objects: Vec<OmnipresentObject>,
+    /// Older levels are migrated to the current version before they are deserialized, see `level_migrations`
+    version: u32,
+    world_objects: Vec<LevelWorldObject>,
+    omnipresent_objects: Vec<LevelOmnipresentObject>,
+    environment: EnvironmentSettings,
     camera: Camera,
+    camera_viewpoints: CameraViewpoints,
 }
 
-pub fn load_level(world: &mut World, level_file_path: &Path) -> anyhow::Result<()> {
+/// Objects copied to the clipboard, they are in the same format as the objects of the level
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct ClipboardContent {
+    /// Content copied from an older level format is migrated like a level before it's pasted
+    level_version: u32,
+    world_objects: Vec<LevelWorldObject>,
+}
+
+/// Reads the prefab files of the instances among the objects and their descendants into `prefabs`, so adding the
+/// objects can't fail halfway because of a missing prefab
+fn load_level_prefabs(
+    level_world_objects: &[LevelWorldObject],
+    prefabs: &mut HashMap<PathBuf, Prefab>,
+) -> anyhow::Result<()> {
+    for level_world_object in level_world_objects {
+        if let Some(prefab_path) = &level_world_object.prefab {
+            if !prefabs.contains_key(prefab_path) {
+                prefabs.insert(prefab_path.clone(), load_prefab(prefab_path)?);
+            }
+        }
+
+        load_level_prefabs(&level_world_object.children, prefabs)?;
+        for part_children in &level_world_object.part_children {
+            load_level_prefabs(&part_children.children, prefabs)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `prefabs` caches the prefab files read while loading the level. Returns the ID of the added object
+fn add_level_world_object(
+    world: &mut World,
+    level_world_object: LevelWorldObject,
+    parent_id: Option<u32>,
+    prefabs: &mut HashMap<PathBuf, Prefab>,
+) -> anyhow::Result<u32> {
+    let object_id = match parent_id {
+        Some(parent_id) => world.add_child_world_object(parent_id, level_world_object.object)?,
+        None => world.add_world_object(level_world_object.object),
+    };
+    restore_persistent_id(world, object_id, level_world_object.id);
+
+    let mut parts = HashMap::new();
+    if let Some(prefab_path) = level_world_object.prefab {
+        let prefab = match prefabs.get(&prefab_path) {
+            Some(prefab) => prefab.clone(),
+            None => {
+                let prefab = load_prefab(&prefab_path)?;
+                prefabs.insert(prefab_path.clone(), prefab.clone());
+                prefab
+            }
+        };
+        add_prefab_instance(
+            world,
+            object_id,
+            &prefab_path,
+            prefab,
+            &level_world_object.overrides,
+        )?;
+        parts = get_instance_parts(world, object_id);
+    }
+
+    for child in level_world_object.children {
+        add_level_world_object(world, child, Some(object_id), prefabs)?;
+    }
+    for part_children in level_world_object.part_children {
+        let parent_id = parts.get(&part_children.part).copied().unwrap_or_else(|| {
+            log::warn!(
+                "Object {} is not in the prefab, the objects added under it are added to the root",
+                part_children.part
+            );
+            object_id
+        });
+        for child in part_children.children {
+            add_level_world_object(world, child, Some(parent_id), prefabs)?;
+        }
+    }
+
+    Ok(object_id)
+}
+
+/// If the saved persistent ID is already taken (eg. the same level is loaded twice into the world), then the object
+/// keeps the new persistent ID it got when it was added
+fn restore_persistent_id(world: &mut World, object_id: u32, persistent_id: Option<PersistentId>) {
+    if let Some(persistent_id) = persistent_id {
+        if let Err(error) = world.set_persistent_id(object_id, persistent_id) {
+            log::warn!("Object {object_id} got a new persistent ID: {error}");
+        }
+    }
+}
+
+/// Collects the non-transient part of the subtree of the object. Returns None if the object is transient
+fn get_level_world_object(world: &World, object_id: u32) -> Option<LevelWorldObject> {
+    let world_object = world.get_world_object(&object_id)?.get_saved_copy()?;
+    let get_level_world_objects = |object_ids: &[u32]| {
+        object_ids
+            .iter()
+            .filter_map(|object_id| get_level_world_object(world, *object_id))
+            .collect::<Vec<_>>()
+    };
+
+    // The objects created from a prefab are saved as the overrides of the instance
+    let children = world
+        .get_children(&object_id)
+        .iter()
+        .copied()
+        .filter(|child_id| world.get_component::<PrefabPart>(child_id).is_none())
+        .collect::<Vec<_>>();
+    let instance = world.get_component::<PrefabInstance>(&object_id);
+    let part_children = match instance {
+        Some(_instance) => get_added_part_children(world, object_id)
+            .into_iter()
+            .map(|(part, child_ids)| LevelPartChildren {
+                part,
+                children: get_level_world_objects(&child_ids),
+            })
+            .filter(|part_children| !part_children.children.is_empty())
+            .collect(),
+        None => vec![],
+    };
+
+    Some(LevelWorldObject {
+        id: world.get_persistent_id(&object_id),
+        object: world_object,
+        prefab: instance.map(|instance| instance.prefab_path.clone()),
+        overrides: get_prefab_overrides(world, object_id),
+        children: get_level_world_objects(&children),
+        part_children,
+    })
+}
+
+/// The copies get new persistent IDs when they are pasted
+fn remove_persistent_ids(level_world_object: &mut LevelWorldObject) {
+    level_world_object.id = None;
+    level_world_object
+        .children
+        .iter_mut()
+        .chain(
+            level_world_object
+                .part_children
+                .iter_mut()
+                .flat_map(|part_children| part_children.children.iter_mut()),
+        )
+        .for_each(remove_persistent_ids);
+}
+
+/// Serializes the objects with their descendants, so they can be put on the clipboard. The transforms of the copied
+/// objects are in world space, so the copies stay in place wherever they are pasted in the hierarchy
+pub fn copy_world_objects(world: &World, object_ids: &[u32]) -> anyhow::Result<String> {
+    let world_objects = object_ids
+        .iter()
+        .filter_map(|object_id| {
+            let mut level_world_object = get_level_world_object(world, *object_id)?;
+            level_world_object.object.transform = world.get_world_transform(object_id)?;
+            remove_persistent_ids(&mut level_world_object);
+            Some(level_world_object)
+        })
+        .collect::<Vec<_>>();
+    if world_objects.is_empty() {
+        bail!("There are no objects to copy");
+    }
+
+    Ok(serde_json::to_string_pretty(&ClipboardContent {
+        level_version: CURRENT_LEVEL_VERSION,
+        world_objects,
+    })?)
+}
+
+/// The objects are migrated as a level that only has world objects, so the objects copied from an older version can be
+/// pasted as well
+fn parse_clipboard_content(clipboard_text: &str) -> anyhow::Result<Vec<LevelWorldObject>> {
+    let mut clipboard_content = serde_json::from_str::<Value>(clipboard_text)
+        .ok()
+        .filter(|content| {
+            content.get("level_version").is_some() && content.get("world_objects").is_some()
+        })
+        .ok_or_else(|| anyhow!("The clipboard doesn't contain world objects"))?;
+
+    let mut level = migrate_to_current_version(json!({
+        "version": clipboard_content["level_version"].take(),
+        "world_objects": clipboard_content["world_objects"].take(),
+        "omnipresent_objects": [],
+    }))
+    .map_err(|error| anyhow!("The copied objects can't be pasted: {error}"))?;
+
+    Ok(serde_json::from_value::<Vec<LevelWorldObject>>(
+        level["world_objects"].take(),
+    )?)
+}
+
+/// Adds the objects copied by `copy_world_objects` to the root of the hierarchy. Returns the IDs of the added root
+/// objects. Nothing is added if any of the objects can't be pasted
+pub fn paste_world_objects(world: &mut World, clipboard_text: &str) -> anyhow::Result<Vec<u32>> {
+    let world_objects = parse_clipboard_content(clipboard_text)?;
+    let mut prefabs = HashMap::new();
+    load_level_prefabs(&world_objects, &mut prefabs)?;
+
+    world_objects
+        .into_iter()
+        .map(|object| add_level_world_object(world, object, None, &mut prefabs))
+        .collect()
+}
+
+fn parse_level(file_contents: &str) -> anyhow::Result<LevelFileContent> {
+    let level = migrate_to_current_version(serde_json::from_str::<Value>(file_contents)?)?;
+
+    Ok(serde_json::from_value::<LevelFileContent>(level)?)
+}
+
+/// A level read from its file and migrated to the current version, together with the prefabs of its instances. Reading
+/// it doesn't touch the world, so a level that can't be loaded doesn't replace the open one
+pub struct LevelFile {
+    contents: LevelFileContent,
+    prefabs: HashMap<PathBuf, Prefab>,
+}
+
+impl LevelFile {
+    fn new(contents: LevelFileContent) -> anyhow::Result<Self> {
+        let mut prefabs = HashMap::new();
+        load_level_prefabs(&contents.world_objects, &mut prefabs)?;
+
+        Ok(Self { contents, prefabs })
+    }
+}
+
+pub fn read_level_file(level_file_path: &Path) -> anyhow::Result<LevelFile> {
     let file_contents = fs::read_to_string(level_file_path)?;
-    let mut level_contents = serde_json::from_str::<LevelFileContent>(&file_contents)?;
-    for object in level_contents.world_objects.drain(..) {
-        world.add_world_object(object);
+
+    LevelFile::new(parse_level(&file_contents)?)
+}
+
+pub fn load_level(world: &mut World, level_file_path: &Path) -> anyhow::Result<()> {
+    add_level(world, read_level_file(level_file_path)?)
+}
+
+pub fn add_level(world: &mut World, level_file: LevelFile) -> anyhow::Result<()> {
+    let LevelFile {
+        contents: level_contents,
+        mut prefabs,
+    } = level_file;
+    for object in level_contents.world_objects {
+        add_level_world_object(world, object, None, &mut prefabs)?;
     }
 
-    for omnipresent_object in level_contents.omnipresent_objects.drain(..) {
-        world.add_omnipresent_object(omnipresent_object);
+    for omnipresent_object in level_contents.omnipresent_objects {
+        let object_id = world.add_omnipresent_object(omnipresent_object.object);
+        restore_persistent_id(world, object_id, omnipresent_object.id);
     }
 
+    world.set_environment_settings(level_contents.environment);
     world.set_camera(&level_contents.camera);
+    world.set_camera_viewpoints(level_contents.camera_viewpoints);
 
     Ok(())
 }
 
-pub fn save_level(world: &World, level_file_name: &str) -> anyhow::Result<()> {
-    let mut target_folder = env::current_dir()?;
-    target_folder.push("levels");
-    let target_file = target_folder.join(level_file_name);
-    if !target_folder.try_exists()? {
-        std::fs::create_dir(target_folder)?;
+/// Controls how the level file is written. The file only depends on the content of the world: the objects are written
+/// in the order they were added to the world and the keys are sorted, so saving the same world twice (or saving a
+/// level right after loading it) gives the same file
+#[derive(Debug, Clone, Copy)]
+pub struct LevelSaveOptions {
+    /// Write the floats with the fewest digits that load back to the same value, instead of their exact decimal
+    /// expansion (eg. 0.2 instead of 0.20000000298023224)
+    pub compact_floats: bool,
+}
+
+impl Default for LevelSaveOptions {
+    fn default() -> Self {
+        Self {
+            compact_floats: true,
+        }
     }
+}
 
-    log::info!("Saving into {:?}", target_file);
+/// Every float in the level is an `f32`, so the shortest representation of the `f32` value is enough to load it back
+fn compact_floats(value: &mut Value) {
+    match value {
+        Value::Number(number) if number.is_f64() => {
+            let compact_number = number
+                .as_f64()
+                .and_then(|float| (float as f32).to_string().parse::<f64>().ok())
+                .and_then(Number::from_f64);
+            if let Some(compact_number) = compact_number {
+                *number = compact_number;
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(compact_floats),
+        Value::Object(map) => map.values_mut().for_each(compact_floats),
+        _ => {}
+    }
+}
+
+/// Used for every file written by the editor (eg. levels, prefabs), so they only change where their content changes
+pub fn to_saved_json<T: serde::Serialize>(
+    contents: &T,
+    options: &LevelSaveOptions,
+) -> anyhow::Result<String> {
+    // The maps of `Value` are sorted by key, this takes care of the `HashMap`s in the content as well
+    let mut json = serde_json::to_value(contents)?;
+    if options.compact_floats {
+        compact_floats(&mut json);
+    }
+
+    let mut contents = serde_json::to_string_pretty(&json)?;
+    contents.push('\n');
+
+    Ok(contents)
+}
+
+fn get_level_file_content(world: &World) -> LevelFileContent {
+    let world_objects = world
+        .get_root_world_object_ids()
+        .into_iter()
+        .filter_map(|object_id| get_level_world_object(world, object_id))
+        .collect();
+    let omnipresent_objects = world
+        .get_omnipresent_object_ids()
+        .into_iter()
+        .filter_map(|object_id| {
+            Some(LevelOmnipresentObject {
+                id: world.get_persistent_id(&object_id),
+                object: world.get_omnipresent_object(&object_id)?.clone(),
+            })
+        })
+        .collect();
+
+    LevelFileContent {
+        version: CURRENT_LEVEL_VERSION,
+        world_objects,
+        omnipresent_objects,
+        environment: world.get_environment_settings().clone(),
+        camera: world.camera_controller.camera.clone(),
+        camera_viewpoints: world.get_camera_viewpoints().clone(),
+    }
+}
+
+/// The saved objects and settings of the world, without the camera. Comparing it with an earlier result tells whether
+/// the level changed since then
+pub fn serialize_level_objects(world: &World) -> anyhow::Result<String> {
+    let level_contents = get_level_file_content(world);
+
+    Ok(serde_json::to_string(&(
+        level_contents.world_objects,
+        level_contents.omnipresent_objects,
+        level_contents.environment,
+        level_contents.camera_viewpoints,
+    ))?)
+}
+
+/// Creates the folder of the file, if it doesn't exist yet
+pub fn write_saved_file(file_path: &Path, contents: &str) -> anyhow::Result<()> {
+    if let Some(target_folder) = file_path.parent() {
+        if !target_folder.as_os_str().is_empty() && !target_folder.try_exists()? {
+            fs::create_dir_all(target_folder)?;
+        }
+    }
+
+    log::info!("Saving into {:?}", file_path);
 
     let mut file = File::options()
         .create(true)
         .write(true)
         .truncate(true)
-        .open(target_file)?;
+        .open(file_path)?;
+    file.write_all(contents.as_bytes())?;
 
-    let omnipresent_objects = world.get_omnipresent_objects();
-    let meshes = world.get_world_objects();
+    Ok(())
+}
 
-    let mut meshes_to_save = vec![];
-    for mut world_object in meshes.into_iter().cloned().into_iter() {
-        let non_transient_components = world_object
-            .components
-            .into_iter()
-            .filter(|component| !component.is_transient())
-            .collect::<Vec<_>>();
+pub fn save_level(
+    world: &World,
+    level_file_path: &Path,
+    options: &LevelSaveOptions,
+) -> anyhow::Result<()> {
+    let contents = to_saved_json(&get_level_file_content(world), options)?;
+
+    write_saved_file(level_file_path, &contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        camera::Projection,
+        components::{CameraComponent, RenderableComponent, TransformComponent},
+        material::PbrMaterialDescriptor,
+        test_helpers::create_test_world,
+    };
+
+    /// A level saved before the level format had a version and one in the current format
+    const LEVEL_FIXTURES: [&str; 2] = ["levels/fixtures/v0.lvl", "levels/fixtures/v2.lvl"];
+
+    fn add_level_contents(
+        world: &mut World,
+        level_contents: LevelFileContent,
+    ) -> anyhow::Result<()> {
+        add_level(world, LevelFile::new(level_contents)?)
+    }
+
+    fn read_level(path: &str) -> LevelFileContent {
+        parse_level(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn resave(level_json: &str, options: &LevelSaveOptions) -> String {
+        to_saved_json(&parse_level(level_json).unwrap(), options).unwrap()
+    }
+
+    #[test]
+    fn every_level_format_can_be_loaded() {
+        for path in LEVEL_FIXTURES {
+            let level_contents = read_level(path);
+
+            assert_eq!(level_contents.version, CURRENT_LEVEL_VERSION, "{path}");
+            assert!(!level_contents.world_objects.is_empty(), "{path}");
+            assert_eq!(level_contents.omnipresent_objects.len(), 1, "{path}");
+        }
 
-        if !non_transient_components.is_empty() {
-            world_object.components = non_transient_components;
-            meshes_to_save.push(world_object.clone());
+        let level_contents = read_level("levels/fixtures/v0.lvl");
+        assert_eq!(level_contents.environment, EnvironmentSettings::default());
+        assert_eq!(
+            level_contents.camera_viewpoints,
+            CameraViewpoints::default()
+        );
+        assert_eq!(level_contents.camera.projection, Projection::Perspective);
+
+        let level_contents = read_level("levels/fixtures/v2.lvl");
+        assert!(level_contents.world_objects[0].id.is_some());
+        assert_eq!(level_contents.world_objects[0].children.len(), 1);
+        assert_eq!(level_contents.environment.intensity, 0.5);
+        assert_eq!(level_contents.camera.projection, Projection::Orthographic);
+        assert_eq!(
+            level_contents.camera_viewpoints.bookmarks[0].hotkey,
+            Some(1)
+        );
+        assert_eq!(level_contents.camera_viewpoints.paths[0].keyframes.len(), 3);
+
+        let prefab_instance = &level_contents.world_objects[2];
+        assert!(prefab_instance.prefab.is_some());
+        assert_eq!(prefab_instance.overrides.len(), 1);
+        assert_eq!(prefab_instance.part_children[0].children.len(), 1);
+
+        let camera_id = level_contents.world_objects[3].id.unwrap();
+        let camera_object = &level_contents.world_objects[3].object;
+        let camera_component = camera_object.get_component::<CameraComponent>().unwrap();
+        assert_eq!(camera_component.resolution_width, 320);
+
+        let screen_object = &level_contents.world_objects[4].object;
+        let screen_component = screen_object
+            .get_component::<RenderableComponent>()
+            .unwrap();
+        assert_eq!(
+            screen_component.model_descriptor.material_descriptor,
+            PbrMaterialDescriptor::Texture(vec![]).with_camera_view_albedo(camera_id)
+        );
+    }
+
+    #[test]
+    fn saving_a_loaded_level_gives_the_same_file() {
+        for path in LEVEL_FIXTURES {
+            let level_json = fs::read_to_string(path).unwrap();
+
+            for compact_floats in [true, false] {
+                let options = LevelSaveOptions { compact_floats };
+                let saved_once = resave(&level_json, &options);
+                let saved_twice = resave(&saved_once, &options);
+
+                assert_eq!(saved_once, saved_twice, "{path}");
+            }
+        }
+    }
+
+    fn has_persistent_ids(level_world_object: &LevelWorldObject) -> bool {
+        level_world_object.id.is_some()
+            && level_world_object.children.iter().all(has_persistent_ids)
+    }
+
+    fn save_world_loaded_from(level_json: &str) -> String {
+        let mut world = create_test_world();
+        add_level_contents(&mut world, parse_level(level_json).unwrap()).unwrap();
+
+        to_saved_json(
+            &get_level_file_content(&world),
+            &LevelSaveOptions::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn saving_a_world_loaded_from_a_level_gives_the_same_file() {
+        for path in LEVEL_FIXTURES {
+            let level_json = fs::read_to_string(path).unwrap();
+            let level_contents = parse_level(&level_json).unwrap();
+
+            let saved = save_world_loaded_from(&level_json);
+            // The objects without a persistent ID get a new one when they are loaded
+            if level_contents.world_objects.iter().all(has_persistent_ids)
+                && level_contents
+                    .omnipresent_objects
+                    .iter()
+                    .all(|omnipresent_object| omnipresent_object.id.is_some())
+            {
+                let expected = to_saved_json(&level_contents, &LevelSaveOptions::default());
+                assert_eq!(saved, expected.unwrap(), "{path}");
+            }
+            assert_eq!(save_world_loaded_from(&saved), saved, "{path}");
         }
     }
 
-    let json = json!({"world_objects": meshes_to_save, "omnipresent_objects": omnipresent_objects, "camera": world.camera_controller.camera});
-    let contents = serde_json::to_string_pretty(&json)?;
-    file.write(contents.as_bytes())?;
+    #[test]
+    fn objects_copied_from_an_older_version_can_be_pasted() {
+        let mut level =
+            serde_json::from_str::<Value>(&fs::read_to_string("levels/fixtures/v2.lvl").unwrap())
+                .unwrap();
+        let world_objects = level["world_objects"].take();
+        let object_count = world_objects.as_array().unwrap().len();
+        let clipboard_text = json!({
+            "level_version": 1,
+            "world_objects": world_objects,
+        })
+        .to_string();
 
-    Ok(())
+        let mut world = create_test_world();
+        let pasted_ids = paste_world_objects(&mut world, &clipboard_text).unwrap();
+
+        assert_eq!(pasted_ids.len(), object_count);
+        assert_eq!(world.query::<&PrefabInstance>().count(), 1);
+    }
+
+    #[test]
+    fn levels_with_a_missing_prefab_are_rejected_before_they_are_added() {
+        let mut level =
+            serde_json::from_str::<Value>(&fs::read_to_string("levels/fixtures/v2.lvl").unwrap())
+                .unwrap();
+        level["world_objects"][2]["prefab"] = "levels/fixtures/missing.prefab".into();
+        let level_contents = serde_json::from_value::<LevelFileContent>(level).unwrap();
+
+        assert!(LevelFile::new(level_contents).is_err());
+        assert!(read_level_file(Path::new("levels/fixtures/missing.lvl")).is_err());
+    }
+
+    #[test]
+    fn nothing_is_pasted_if_an_object_fails() {
+        let clipboard_text = json!({
+            "level_version": CURRENT_LEVEL_VERSION,
+            "world_objects": [
+                { "components": [], "transform": TransformComponent::default() },
+                {
+                    "components": [],
+                    "transform": TransformComponent::default(),
+                    "prefab": "levels/fixtures/missing.prefab",
+                },
+            ],
+        })
+        .to_string();
+
+        let mut world = create_test_world();
+        assert!(paste_world_objects(&mut world, &clipboard_text).is_err());
+        assert!(world.get_root_world_object_ids().is_empty());
+    }
+
+    #[test]
+    fn objects_added_under_prefab_objects_are_saved() {
+        let mut world = create_test_world();
+        add_level_contents(&mut world, read_level("levels/fixtures/v2.lvl")).unwrap();
+        let (instance_id, _instance) = world.query::<&PrefabInstance>().next().unwrap();
+        let (part, part_id) = get_instance_parts(&world, instance_id)
+            .into_iter()
+            .min()
+            .unwrap();
+        let added_id = world
+            .add_child_world_object(part_id, WorldObject::new(vec![], Default::default()))
+            .unwrap();
+        let added_persistent_id = world.get_persistent_id(&added_id).unwrap();
+        let saved = to_saved_json(
+            &get_level_file_content(&world),
+            &LevelSaveOptions::default(),
+        )
+        .unwrap();
+
+        let mut loaded_world = create_test_world();
+        add_level_contents(&mut loaded_world, parse_level(&saved).unwrap()).unwrap();
+
+        let loaded_id = loaded_world.get_runtime_id(&added_persistent_id).unwrap();
+        let loaded_parent_id = loaded_world.get_parent(&loaded_id).unwrap();
+        let loaded_part = loaded_world.get_component::<PrefabPart>(&loaded_parent_id);
+        assert_eq!(
+            loaded_part.map(|prefab_part| &prefab_part.part),
+            Some(&part)
+        );
+        assert_eq!(
+            to_saved_json(
+                &get_level_file_content(&loaded_world),
+                &LevelSaveOptions::default()
+            )
+            .unwrap(),
+            saved
+        );
+    }
+
+    #[test]
+    fn compact_floats_load_back_to_the_same_value() {
+        let level_contents = read_level("levels/fixtures/v2.lvl");
+
+        let exact = to_saved_json(
+            &level_contents,
+            &LevelSaveOptions {
+                compact_floats: false,
+            },
+        );
+        let compact = to_saved_json(
+            &level_contents,
+            &LevelSaveOptions {
+                compact_floats: true,
+            },
+        );
+        let exact = parse_level(&exact.unwrap()).unwrap();
+        let compact = parse_level(&compact.unwrap()).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(exact).unwrap(),
+            serde_json::to_value(compact).unwrap()
+        );
+    }
 }
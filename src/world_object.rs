@@ -1,21 +1,24 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{any::Any, path::PathBuf, str::FromStr};
 
 use crate::{
+    component_storage::Component,
     components::{
-        LightObjectComponent, OmnipresentComponentType, RenderableComponent, SceneComponentType,
-        TransformComponent,
+        CameraComponent, LightObjectComponent, OmnipresentComponentType, RenderableComponent,
+        SceneComponentType, TransformComponent,
     },
     lights::DirectionalLight,
     material::PbrMaterialDescriptor,
     model::{MeshDescriptor, ModelRenderingOptions, PbrRenderingType, RenderingPass},
     resource_loader::PrimitiveShape,
     texture::{MaterialSource, TextureSourceDescriptor, TextureUsage},
+    world_events::ComponentTypeId,
 };
 
 /// Describes an object in the world. Used for object that have a 3D position (eg. rendered meshes, lights)
 #[derive(
     Debug,
     Clone,
+    PartialEq,
     serde::Serialize,
     serde::Deserialize,
     ui_item_derive::UiDisplayable,
@@ -24,6 +27,7 @@ use crate::{
 pub struct WorldObject {
     pub components: Vec<SceneComponentType>,
 
+    /// Relative to the parent object, if the object has one. See `World::get_world_transform` for the world space transform
     pub transform: TransformComponent,
 }
 
@@ -41,6 +45,54 @@ impl WorldObject {
         }
     }
 
+    /// Transient objects only exist while the application is running (eg. gizmo), they are not saved into the level
+    pub fn is_transient(&self) -> bool {
+        !self.components.is_empty()
+            && self
+                .components
+                .iter()
+                .all(|component| component.is_transient())
+    }
+
+    /// The object without its transient components, as it's saved into a file. None if the object itself is transient
+    pub fn get_saved_copy(&self) -> Option<WorldObject> {
+        if self.is_transient() {
+            return None;
+        }
+
+        let mut world_object = self.clone();
+        world_object
+            .components
+            .retain(|component| !component.is_transient());
+
+        Some(world_object)
+    }
+
+    /// Short description of the object for listing it on the UI
+    pub fn get_display_name(&self) -> String {
+        if self.get_component::<LightObjectComponent>().is_some() {
+            return "Point light".into();
+        }
+        if self.get_component::<CameraComponent>().is_some() {
+            return "Camera".into();
+        }
+
+        match self.get_component::<RenderableComponent>() {
+            Some(renderable_component) => {
+                match &renderable_component.model_descriptor.mesh_descriptor {
+                    MeshDescriptor::PrimitiveInCode(primitive_shape) => {
+                        format!("{primitive_shape:?}")
+                    }
+                    MeshDescriptor::FromFile(path) => path
+                        .file_stem()
+                        .map(|file_stem| file_stem.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| "Mesh".into()),
+                }
+            }
+            None => "Empty".into(),
+        }
+    }
+
     fn get_light_debug_object() -> RenderableComponent {
         let texture_source_descriptor = TextureSourceDescriptor {
             source: MaterialSource::FromFile(
@@ -70,74 +122,66 @@ impl WorldObject {
         ));
     }
 
-    // TODO: general method for getting any component?
-    pub fn get_renderable_component(&self) -> Option<&RenderableComponent> {
-        for component in &self.components {
-            match component {
-                SceneComponentType::Renderable(renderable_component) => {
-                    return Some(&renderable_component)
-                }
-                _ => {}
-            }
-        }
+    /// The types of the built-in components, including the transform
+    pub fn get_component_types(&self) -> Vec<ComponentTypeId> {
+        let mut component_types = vec![ComponentTypeId::of::<TransformComponent>()];
+        component_types.extend(
+            self.components
+                .iter()
+                .map(|component| component.get_component_type()),
+        );
 
-        None
+        component_types
     }
 
-    pub fn get_renderable_component_mut(&mut self) -> Option<&mut RenderableComponent> {
-        for component in &mut self.components {
-            match component {
-                SceneComponentType::Renderable(renderable_component) => {
-                    return Some(renderable_component)
-                }
-                _ => {}
-            }
+    /// Finds the built-in component of type `T`. The transform counts as a component as well
+    pub fn get_component<T: Component>(&self) -> Option<&T> {
+        if let Some(transform) = (&self.transform as &dyn Any).downcast_ref::<T>() {
+            return Some(transform);
         }
 
-        None
+        self.components
+            .iter()
+            .find_map(|component| component.as_any().downcast_ref::<T>())
     }
 
-    pub fn get_light_component(&self) -> Option<&LightObjectComponent> {
-        for component in &self.components {
-            match component {
-                SceneComponentType::LightObject(light_component) => return Some(&light_component),
-                _ => {}
-            }
+    pub fn get_component_mut<T: Component>(&mut self) -> Option<&mut T> {
+        if let Some(transform) = (&mut self.transform as &mut dyn Any).downcast_mut::<T>() {
+            return Some(transform);
         }
 
-        None
+        self.components
+            .iter_mut()
+            .find_map(|component| component.as_any_mut().downcast_mut::<T>())
     }
 
-    pub fn get_light_component_mut(&mut self) -> Option<&mut LightObjectComponent> {
-        for component in &mut self.components {
-            match component {
-                SceneComponentType::LightObject(light_component) => return Some(light_component),
-                _ => {}
-            }
-        }
+    /// Removes the first built-in component of type `T`
+    pub fn remove_component<T: Component>(&mut self) -> Option<T> {
+        let index = self
+            .components
+            .iter()
+            .position(|component| component.as_any().is::<T>())?;
 
-        None
+        self.components.remove(index).into_component::<T>().ok()
     }
 }
 
-macro_rules! get_component {
-    ( $object_with_components:expr, $component_type:expr ) => {{
-        let mut maybe_component = None;
-        for component in &object_with_components.components {
-            match component {
-                $component_type(component) => maybe_component = Some(&component),
-                _ => {}
-            }
-        }
-        maybe_component
-    }};
-}
-
 impl OmnipresentObject {
     pub fn new(components: Vec<OmnipresentComponentType>) -> Self {
         Self { components }
     }
 
+    pub fn get_component_types(&self) -> Vec<ComponentTypeId> {
+        self.components
+            .iter()
+            .map(|component| match component {
+                OmnipresentComponentType::DirectionalLight(_directional_light) => {
+                    ComponentTypeId::of::<DirectionalLight>()
+                }
+            })
+            .collect()
+    }
+
     pub fn get_light_component(&self) -> Option<&DirectionalLight> {
         for component in &self.components {
             match component {
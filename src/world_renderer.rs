@@ -1,5 +1,12 @@
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    mem,
+    time::Instant,
+};
 
+use crossbeam_channel::Receiver;
+use glam::Vec3;
+use math_helpers::{Aabb, Line};
 use wgpu::{
     BindGroup, CommandEncoder, Device, Extent3d, RenderPassDepthStencilAttachment, SurfaceTexture,
 };
@@ -7,22 +14,43 @@ use wgpu::{
 use crate::{
     actions::RenderingAction,
     camera_controller::CameraController,
-    components::{RenderableComponent, TransformComponent},
+    components::{CameraComponent, RenderableComponent, TransformComponent},
     diffuse_irradiance_renderer::DiffuseIrradianceRenderer,
     equirectangular_to_cubemap_renderer::EquirectangularToCubemapRenderer,
     forward_renderer::ForwardRenderer,
+    frustum_culling::{cull_renderables, CullingMode, CullingStats, FrameCullingStats},
     gbuffer_geometry_renderer::GBufferGeometryRenderer,
+    gpu_culling::{CullingView, CullingViewType, GpuCulling},
     light_controller::LightController,
-    model::{Renderable, RenderableDescription, RenderingPass},
+    model::{DrawMode, Renderable, RenderableDescription, RenderingPass},
     object_picker::ObjectPickManager,
     pipelines::{self, MainRP, ShaderCompilationSuccess},
     post_process_manager::PostProcessManager,
+    render_view::{CameraRenderData, CameraView, RenderViewTargets},
     renderer::Renderer,
     resource_loader::{PrimitiveShape, ResourceLoader},
     skybox::Skybox,
-    world::{ModificationType, World},
+    texture::SampledTexture,
+    world::{EnvironmentSettings, World},
+    world_events::{ComponentTypeId, WorldEvent, WorldEventFilter},
 };
 
+/// Where a ray hit the scene
+#[derive(Debug, Copy, Clone)]
+pub struct SurfaceHit {
+    pub position: Vec3,
+    /// Faces the start of the ray
+    pub normal: Vec3,
+}
+
+/// The objects that get an outline in the post-process, the descendants of the selected and the hovered objects are
+/// expected to be listed as well
+#[derive(Debug, Default, Clone)]
+pub struct OutlinedObjects {
+    pub selected_object_ids: HashSet<u32>,
+    pub hovered_object_ids: HashSet<u32>,
+}
+
 pub struct WorldRenderer {
     diffuse_irradiance_renderer: DiffuseIrradianceRenderer,
     skybox: Skybox,
@@ -31,24 +59,40 @@ pub struct WorldRenderer {
     forward_renderer: ForwardRenderer,
     gbuffer_geometry_renderer: GBufferGeometryRenderer,
     equirec_to_cubemap_renderer: EquirectangularToCubemapRenderer,
+    gpu_culling: GpuCulling,
+
+    /// The render targets of the main camera, they have the size of the screen
+    main_view_targets: RenderViewTargets,
+    /// The camera of the world, written in every update
+    main_camera_render_data: CameraRenderData,
+    /// The views of the camera components by the id of their object
+    camera_views: HashMap<u32, CameraView>,
 
     actions_to_process: VecDeque<RenderingAction>,
 
     renderables: HashMap<u32, Renderable>,
+    world_event_receiver: Receiver<WorldEvent>,
+    /// The environment settings of the world the environment map was last generated with
+    environment: EnvironmentSettings,
+
+    /// How many renderables were skipped in the last frame, because they were outside of the view
+    pub culling_stats: FrameCullingStats,
+    pub culling_mode: CullingMode,
 }
 
 impl WorldRenderer {
-    pub fn new(renderer: &Renderer, resource_loader: &mut ResourceLoader) -> Self {
+    pub fn new(
+        renderer: &Renderer,
+        resource_loader: &mut ResourceLoader,
+        world: &mut World,
+    ) -> Self {
         let main_rp = pipelines::MainRP::new(&renderer.device).unwrap();
-        let gbuffer_geometry_renderer = GBufferGeometryRenderer::new(
-            &renderer.device,
-            renderer.config.width,
-            renderer.config.height,
-        );
+        let gbuffer_geometry_renderer = GBufferGeometryRenderer::new();
 
         let forward_rp = ForwardRenderer::new(&renderer.device, wgpu::TextureFormat::Rgba16Float);
 
-        let post_process_manager = PostProcessManager::new(
+        let post_process_manager = PostProcessManager::new(&renderer.device);
+        let main_view_targets = RenderViewTargets::new(
             &renderer.device,
             renderer.config.width,
             renderer.config.height,
@@ -57,7 +101,9 @@ impl WorldRenderer {
         // TODO: extract the format from here and don't reference full_screen_render_target_ping_pong_textures directly
         let skybox = Skybox::new(
             &renderer.device,
-            post_process_manager.full_screen_render_target_ping_pong_textures[0]
+            main_view_targets
+                .post_process
+                .full_screen_render_target_ping_pong_textures[0]
                 .texture
                 .format(),
         );
@@ -67,6 +113,7 @@ impl WorldRenderer {
             renderer,
             wgpu::TextureFormat::Rgba16Float,
             resource_loader.get_primitive_shape(PrimitiveShape::Cube),
+            world.get_environment_settings(),
         )
         .unwrap();
 
@@ -79,6 +126,14 @@ impl WorldRenderer {
         )
         .unwrap();
 
+        let gpu_culling = GpuCulling::new(&renderer.device);
+
+        let world_event_receiver = world.subscribe(
+            WorldEventFilter::all()
+                .with_component::<RenderableComponent>()
+                .with_component::<CameraComponent>(),
+        );
+
         WorldRenderer {
             skybox,
             main_rp,
@@ -88,8 +143,19 @@ impl WorldRenderer {
             post_process_manager,
             equirec_to_cubemap_renderer,
             diffuse_irradiance_renderer,
+            gpu_culling,
+            main_view_targets,
+            main_camera_render_data: CameraRenderData::new(
+                &renderer.device,
+                &world.camera_controller,
+            ),
+            camera_views: HashMap::new(),
             actions_to_process: VecDeque::new(),
             renderables: HashMap::new(),
+            world_event_receiver,
+            environment: world.get_environment_settings().clone(),
+            culling_stats: FrameCullingStats::default(),
+            culling_mode: CullingMode::default(),
         }
     }
 
@@ -106,12 +172,18 @@ impl WorldRenderer {
         renderer: &Renderer,
     ) {
         let renderable_parts = resource_loader
-            .load_model(&renderable_component.model_descriptor, renderer)
+            .load_model(
+                &renderable_component.model_descriptor,
+                &renderable_component.lod_settings,
+                renderer,
+            )
             .unwrap();
         let renderable_desc = RenderableDescription {
             model_descriptor: renderable_component.model_descriptor.clone(),
             rendering_options: renderable_component.rendering_options,
+            lod_settings: renderable_component.lod_settings.clone(),
             transform,
+            is_transient: renderable_component.is_transient,
         };
         let new_renderable = Renderable::new(
             renderable_desc,
@@ -132,63 +204,258 @@ impl WorldRenderer {
         }
     }
 
+    fn add_object_from_world(
+        &mut self,
+        id: u32,
+        world: &World,
+        resource_loader: &ResourceLoader,
+        renderer: &Renderer,
+    ) {
+        if let (Some(renderable_component), Some(world_transform)) = (
+            world.get_component::<RenderableComponent>(&id),
+            world.get_world_transform(&id),
+        ) {
+            self.add_object(
+                renderable_component,
+                world_transform,
+                id,
+                resource_loader,
+                renderer,
+            );
+        }
+    }
+
+    fn remove_object(&mut self, id: &u32) {
+        let _ = self.renderables.remove(id);
+        self.gbuffer_geometry_renderer.remove_renderable(id);
+    }
+
     pub fn update(&mut self, renderer: &Renderer, world: &World, resource_loader: &ResourceLoader) {
-        for modification in &world.dirty_objects {
-            match &modification.modification_type {
-                ModificationType::Added => {
-                    if let Some(world_object) = world.get_world_object(&modification.id) {
-                        if let Some(renderable) = world_object.get_renderable_component() {
-                            self.add_object(
-                                renderable,
-                                world_object.transform.clone(),
-                                modification.id,
-                                resource_loader,
-                                renderer,
+        self.main_camera_render_data
+            .update(&renderer.queue, &world.camera_controller);
+
+        let renderable_type = ComponentTypeId::of::<RenderableComponent>();
+        let events = self.world_event_receiver.try_iter().collect::<Vec<_>>();
+
+        for event in &events {
+            match event {
+                WorldEvent::ObjectAdded { id } => {
+                    self.add_object_from_world(*id, world, resource_loader, renderer);
+                }
+                WorldEvent::ComponentAdded { id, component_type }
+                    if *component_type == renderable_type =>
+                {
+                    self.add_object_from_world(*id, world, resource_loader, renderer);
+                }
+                WorldEvent::ObjectRemoved { id } => self.remove_object(id),
+                WorldEvent::ComponentRemoved { id, component_type }
+                    if *component_type == renderable_type =>
+                {
+                    self.remove_object(id);
+                }
+                WorldEvent::ComponentChanged { id, component_type }
+                    if *component_type == renderable_type =>
+                {
+                    let (Some(renderable), Some(renderable_component)) = (
+                        self.renderables.get_mut(id),
+                        world.get_component::<RenderableComponent>(id),
+                    ) else {
+                        continue;
+                    };
+
+                    // The mesh and the levels of detail are part of the loaded model, so the whole renderable is
+                    // recreated if they change
+                    let description = &renderable.description;
+                    let should_recreate_renderable = renderable_component
+                        .model_descriptor
+                        .mesh_descriptor
+                        != description.model_descriptor.mesh_descriptor
+                        || renderable_component.rendering_options != description.rendering_options
+                        || renderable_component
+                            .lod_settings
+                            .are_meshes_different(&description.lod_settings);
+
+                    if should_recreate_renderable {
+                        self.remove_object(id);
+                        self.add_object_from_world(*id, world, resource_loader, renderer);
+                    } else {
+                        renderable.description.lod_settings =
+                            renderable_component.lod_settings.clone();
+                        renderable.description.is_transient = renderable_component.is_transient;
+                    }
+                }
+                WorldEvent::MaterialChanged { id, material } => {
+                    let Some(renderable) = self.renderables.get_mut(id) else {
+                        continue;
+                    };
+
+                    // The pipeline only depends on the kind of the material
+                    let is_pipeline_different = mem::discriminant(material)
+                        != mem::discriminant(
+                            &renderable.description.model_descriptor.material_descriptor,
+                        );
+                    renderable.update_material_render_state(renderer, material, resource_loader);
+
+                    if is_pipeline_different {
+                        if let Some(renderable_component) =
+                            world.get_component::<RenderableComponent>(id)
+                        {
+                            self.gbuffer_geometry_renderer.remove_renderable(id);
+                            let _ = self.gbuffer_geometry_renderer.add_renderable(
+                                &renderer.device,
+                                *id,
+                                renderable_component,
                             );
                         }
                     }
                 }
-                ModificationType::Removed => {
-                    let _ = self.renderables.remove(&modification.id);
-                    self.gbuffer_geometry_renderer
-                        .remove_renderable(&modification.id);
+                WorldEvent::TransformChanged {
+                    id,
+                    world_transform,
+                } => {
+                    if let Some(renderable) = self.renderables.get_mut(id) {
+                        renderable.update_transform_render_state(
+                            &renderer.queue,
+                            world_transform,
+                            *id,
+                        );
+                    }
                 }
-                ModificationType::Modified => {
-                    if let Some(renderable) = self.renderables.get_mut(&modification.id) {
-                        if let Some(world_object) = world.get_world_object(&modification.id) {
-                            if let Some(renderable_component) =
-                                world_object.get_renderable_component()
-                            {
-                                if renderable_component.model_descriptor.material_descriptor
-                                    != renderable.description.model_descriptor.material_descriptor
-                                {
-                                    renderable.update_material_render_state(
-                                        renderer,
-                                        &renderable_component.model_descriptor.material_descriptor,
-                                        resource_loader,
-                                    );
-
-                                    self.gbuffer_geometry_renderer
-                                        .remove_renderable(&modification.id);
-                                    let _ = self.gbuffer_geometry_renderer.add_renderable(
-                                        &renderer.device,
-                                        modification.id,
-                                        renderable_component,
-                                    );
-                                }
-                                if world_object.transform != renderable.description.transform {
-                                    renderable.update_transform_render_state(
-                                        &renderer.queue,
-                                        &world_object.transform,
-                                        modification.id,
-                                    );
-                                }
-                            }
+                _ => {}
+            }
+        }
+
+        // The bounds and the draw slots of the renderables have to be kept up to date for the GPU culling
+        if !events.is_empty() {
+            self.gpu_culling.update_draws(
+                &renderer.device,
+                &renderer.queue,
+                self.renderables.values(),
+            );
+        }
+
+        let are_camera_views_changed = self.update_camera_views(renderer, world, &events);
+        self.bind_camera_views(
+            renderer,
+            world,
+            resource_loader,
+            &events,
+            are_camera_views_changed,
+        );
+        self.update_environment(renderer, world);
+    }
+
+    /// Creates, updates or removes the views of the camera components of the objects the events are about. Returns
+    /// true if a view was added or removed, or it recreated its texture
+    fn update_camera_views(
+        &mut self,
+        renderer: &Renderer,
+        world: &World,
+        events: &[WorldEvent],
+    ) -> bool {
+        let mut are_camera_views_changed = false;
+        for event in events {
+            let id = event.get_object_id();
+            match (
+                world.get_component::<CameraComponent>(&id),
+                world.get_world_transform(&id),
+            ) {
+                (Some(camera_component), Some(world_transform)) => {
+                    match self.camera_views.get_mut(&id) {
+                        Some(camera_view) => {
+                            are_camera_views_changed |=
+                                camera_view.update(renderer, camera_component, &world_transform);
+                        }
+                        None => {
+                            let camera_view =
+                                CameraView::new(renderer, camera_component, &world_transform);
+                            self.camera_views.insert(id, camera_view);
+                            are_camera_views_changed = true;
                         }
                     }
                 }
+                _ => {
+                    are_camera_views_changed |= self.camera_views.remove(&id).is_some();
+                }
             }
         }
+
+        are_camera_views_changed
+    }
+
+    /// The materials showing camera views are created with default textures in place of the views. The images of the
+    /// views are bound here, and bound again whenever the views or the materials are recreated
+    fn bind_camera_views(
+        &mut self,
+        renderer: &Renderer,
+        world: &World,
+        resource_loader: &ResourceLoader,
+        events: &[WorldEvent],
+        are_camera_views_changed: bool,
+    ) {
+        let recreated_material_ids = events
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event,
+                    WorldEvent::ObjectAdded { .. }
+                        | WorldEvent::ComponentAdded { .. }
+                        | WorldEvent::ComponentChanged { .. }
+                        | WorldEvent::MaterialChanged { .. }
+                )
+            })
+            .map(|event| event.get_object_id())
+            .collect::<HashSet<_>>();
+        if !are_camera_views_changed && recreated_material_ids.is_empty() {
+            return;
+        }
+
+        let camera_view_textures = self
+            .camera_views
+            .iter()
+            .filter_map(|(id, camera_view)| {
+                Some((
+                    world.get_persistent_id(id)?,
+                    camera_view.targets.get_output_texture(),
+                ))
+            })
+            .collect::<HashMap<_, _>>();
+        for (id, renderable) in &mut self.renderables {
+            let material = &renderable.description.model_descriptor.material_descriptor;
+            if material.shows_camera_view()
+                && (are_camera_views_changed || recreated_material_ids.contains(id))
+            {
+                renderable.bind_camera_views(renderer, resource_loader, &camera_view_textures);
+            }
+        }
+    }
+
+    /// The rendered images of the camera components by the id of their object
+    pub fn get_camera_view_textures(&self) -> impl Iterator<Item = (u32, &SampledTexture)> {
+        self.camera_views
+            .iter()
+            .map(|(id, camera_view)| (*id, camera_view.targets.get_output_texture()))
+    }
+
+    /// Regenerates the environment cubemap and the diffuse irradiance map, if the environment of the world changed
+    fn update_environment(&mut self, renderer: &Renderer, world: &World) {
+        let environment = world.get_environment_settings();
+        if *environment == self.environment {
+            return;
+        }
+
+        // Even if it fails, the same settings are not tried again until they change
+        self.environment = environment.clone();
+        match self
+            .equirec_to_cubemap_renderer
+            .set_environment(renderer, environment)
+        {
+            Ok(()) => {
+                self.add_action(RenderingAction::GenerateCubeMapFromEquirectangular);
+                self.add_action(RenderingAction::BakeDiffuseIrradianceMap);
+            }
+            Err(error) => log::error!("Failed to change the environment: {error}"),
+        }
     }
 
     pub fn render(
@@ -200,8 +467,9 @@ impl WorldRenderer {
         camera_controller: &CameraController,
         global_gpu_params_bind_group: &BindGroup,
         object_picker: &mut ObjectPickManager,
+        outlined_objects: &OutlinedObjects,
     ) -> Result<(), wgpu::SurfaceError> {
-        self.post_process_manager.begin_frame();
+        self.main_view_targets.post_process.begin_frame();
 
         for action in self.actions_to_process.drain(..) {
             match action {
@@ -220,143 +488,206 @@ impl WorldRenderer {
             }
         }
 
-        let renderables = self.renderables.values();
+        let now = Instant::now();
+        let mut lod_changed = false;
+        for renderable in self.renderables.values_mut() {
+            let screen_size = camera_controller.get_screen_size(&renderable.bounding_sphere);
+            lod_changed |= renderable.update_lod(&renderer.queue, screen_size, now);
+        }
 
-        light_controller.render_shadows(encoder, renderables.clone());
+        // The draw arguments written by the GPU culling contain the index counts of the selected levels
+        if lod_changed {
+            self.gpu_culling.update_draws(
+                &renderer.device,
+                &renderer.queue,
+                self.renderables.values(),
+            );
+        }
 
-        {
-            let deferred_pass_items = renderables.clone().filter(|renderable| {
-                renderable.description.rendering_options.pass == RenderingPass::DeferredMain
-            });
-            let mut render_pass = self.gbuffer_geometry_renderer.begin_render(encoder);
-            self.gbuffer_geometry_renderer.render(
-                &mut render_pass,
-                deferred_pass_items,
-                &camera_controller.bind_group,
-                global_gpu_params_bind_group,
+        let camera_view_proj = camera_controller.get_view_proj();
+        let use_gpu_culling = self.culling_mode != CullingMode::Cpu;
+        let mut shadow_view_count = 0;
+
+        if use_gpu_culling {
+            // The camera is the first view, the shadow maps come after it
+            let culling_views = std::iter::once(CullingView {
+                view_proj: camera_view_proj,
+                view_type: CullingViewType::Camera,
+            })
+            .chain(
+                light_controller
+                    .get_shadow_viewprojs()
+                    .into_iter()
+                    .map(|view_proj| CullingView {
+                        view_proj,
+                        view_type: CullingViewType::Shadow,
+                    }),
+            )
+            .collect::<Vec<_>>();
+            shadow_view_count = culling_views.len() - 1;
+
+            self.gpu_culling.cull(
+                &renderer.device,
+                &renderer.queue,
+                encoder,
+                &culling_views,
+                self.culling_mode == CullingMode::GpuWithHiZ,
             );
         }
 
-        object_picker.render(
-            encoder,
-            &renderer.device,
-            renderables.clone(),
-            &camera_controller.bind_group,
-            &self.gbuffer_geometry_renderer.textures.depth_texture.view,
-        );
+        let (visible_renderables, draw_mode, camera_culling_stats, shadow_culling_stats) =
+            if use_gpu_culling {
+                let shadow_draw_modes = (1..=shadow_view_count)
+                    .map(|view_index| DrawMode::Indirect(self.gpu_culling.get_view(view_index)))
+                    .collect::<Vec<_>>();
+                let shadow_culling_stats = light_controller.render_shadows(
+                    encoder,
+                    self.renderables.values(),
+                    Some(shadow_draw_modes.as_slice()),
+                );
 
-        {
-            let mut main_shading_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Main shading pass"),
-                timestamp_writes: None,
-            });
+                // The visibility is only known on the GPU, everything is submitted
+                let visible_renderables = self.renderables.values().collect::<Vec<_>>();
+
+                (
+                    visible_renderables,
+                    DrawMode::Indirect(self.gpu_culling.get_view(0)),
+                    None,
+                    shadow_culling_stats,
+                )
+            } else {
+                let shadow_culling_stats =
+                    light_controller.render_shadows(encoder, self.renderables.values(), None);
+
+                let mut camera_culling_stats = CullingStats::default();
+                let visible_renderables = cull_renderables(
+                    self.renderables.values(),
+                    &camera_controller.get_frustum(),
+                    &mut camera_culling_stats,
+                );
 
-            self.main_rp.render(
-                &mut main_shading_pass,
-                &camera_controller,
-                &light_controller,
-                &self.gbuffer_geometry_renderer.gbuffer_textures_bind_group,
-                light_controller.get_directional_lights_depth_texture_bgroup(),
-                light_controller.get_point_lights_depth_texture_bgroup(),
-                &self.diffuse_irradiance_renderer.diffuse_irradiance_cubemap,
-                self.post_process_manager.get_next_ping_pong_bind_group(),
-                renderer.config.width,
-                renderer.config.height,
-            );
-        }
+                (
+                    visible_renderables,
+                    DrawMode::Direct,
+                    Some(camera_culling_stats),
+                    shadow_culling_stats,
+                )
+            };
+
+        self.culling_stats = FrameCullingStats {
+            submitted: visible_renderables.len(),
+            camera: camera_culling_stats,
+            shadow: shadow_culling_stats,
+        };
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Skybox + forward rendering pass"),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self
-                        .post_process_manager
-                        .full_screen_render_target_ping_pong_textures[0]
-                        .view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                    view: &self.gbuffer_geometry_renderer.textures.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-            });
+        let context = ViewRenderContext {
+            main_rp: &self.main_rp,
+            gbuffer_geometry_renderer: &self.gbuffer_geometry_renderer,
+            post_process_manager: &self.post_process_manager,
+            forward_renderer: &self.forward_renderer,
+            skybox: &self.skybox,
+            environment_cube_map: &self.equirec_to_cubemap_renderer.cube_map_to_sample,
+            diffuse_irradiance_cube_map: &self
+                .diffuse_irradiance_renderer
+                .diffuse_irradiance_cubemap,
+            light_controller,
+            global_gpu_params_bind_group,
+        };
 
-            self.forward_renderer.render(
-                &mut render_pass,
-                renderables.filter(|renderable| {
-                    renderable.description.rendering_options.pass
-                        == RenderingPass::ForceForwardAfterDeferred
-                }),
-                &camera_controller.bind_group,
-                &light_controller.get_light_bind_group(),
+        // The GPU culling only knows about the main camera and the shadow maps, so the camera components are culled on
+        // the CPU. They see the levels of detail that were selected for the main camera, without the editor-only
+        // renderables
+        for camera_view in self.camera_views.values_mut() {
+            let visible_renderables = cull_renderables(
+                self.renderables
+                    .values()
+                    .filter(|renderable| !renderable.description.is_transient),
+                &camera_view.camera_controller.get_frustum(),
+                &mut CullingStats::default(),
+            );
+
+            camera_view.targets.post_process.begin_frame();
+            context.render_geometry(
+                encoder,
+                &camera_view.targets,
+                &camera_view.camera_render_data.bind_group,
+                &visible_renderables,
+                &DrawMode::Direct,
             );
-            self.skybox.render(
-                &mut render_pass,
-                &camera_controller,
-                &self.equirec_to_cubemap_renderer.cube_map_to_sample,
+            context.render_shading(
+                encoder,
+                &mut camera_view.targets,
+                &camera_view.camera_render_data.bind_group,
+                &visible_renderables,
+                &DrawMode::Direct,
+            );
+            context.render_post_process(
+                encoder,
+                &mut camera_view.targets,
+                &camera_view.camera_render_data.bind_group,
+                None,
             );
         }
 
-        {
-            // Unfortunately I can't do this in the same pass, because of the pass' and encoder's lifetime
-            {
-                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: Some("Postprocessing"),
-                    timestamp_writes: None,
-                });
-
-                self.post_process_manager.render_dummy(
-                    &mut compute_pass,
-                    renderer.config.width,
-                    renderer.config.height,
-                    global_gpu_params_bind_group,
-                );
-            }
+        context.render_geometry(
+            encoder,
+            &self.main_view_targets,
+            &self.main_camera_render_data.bind_group,
+            &visible_renderables,
+            &draw_mode,
+        );
 
-            {
-                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: Some("Postprocessing"),
-                    timestamp_writes: None,
-                });
-                self.post_process_manager.render_screen_space_reflections(
-                    &mut compute_pass,
-                    renderer.config.width,
-                    renderer.config.height,
-                    global_gpu_params_bind_group,
-                    &camera_controller.bind_group,
-                    &self.equirec_to_cubemap_renderer.cube_map_to_sample,
-                    &self.gbuffer_geometry_renderer.gbuffer_textures_bind_group,
-                    &self.gbuffer_geometry_renderer.depth_texture_bind_group,
-                );
-            }
+        object_picker.render(
+            encoder,
+            &renderer.device,
+            visible_renderables.iter().copied(),
+            &self.main_camera_render_data.bind_group,
+            &self.main_view_targets.gbuffer.textures.depth_texture.view,
+            &draw_mode,
+        );
 
-            {
-                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: Some("Postprocessing"),
-                    timestamp_writes: None,
-                });
-                self.post_process_manager.apply_tone_mapping(
-                    &mut compute_pass,
-                    renderer.config.width,
-                    renderer.config.height,
-                    global_gpu_params_bind_group,
-                );
-            }
+        object_picker.render_outline_masks(
+            encoder,
+            outlined_objects
+                .selected_object_ids
+                .iter()
+                .filter_map(|object_id| self.renderables.get(object_id)),
+            outlined_objects
+                .hovered_object_ids
+                .iter()
+                .filter_map(|object_id| self.renderables.get(object_id)),
+            &self.main_camera_render_data.bind_group,
+            &self.main_view_targets.gbuffer.textures.depth_texture.view,
+        );
+
+        context.render_shading(
+            encoder,
+            &mut self.main_view_targets,
+            &self.main_camera_render_data.bind_group,
+            &visible_renderables,
+            &draw_mode,
+        );
+
+        // Built after all the geometry was rendered, the next frame uses it for occlusion culling
+        if self.culling_mode == CullingMode::GpuWithHiZ {
+            self.gpu_culling.build_hi_z(
+                &renderer.device,
+                encoder,
+                &self.main_view_targets.gbuffer.textures.depth_texture,
+                camera_view_proj,
+            );
         }
 
+        context.render_post_process(
+            encoder,
+            &mut self.main_view_targets,
+            &self.main_camera_render_data.bind_group,
+            Some(&object_picker.outline_masks_bind_group),
+        );
+
         encoder.copy_texture_to_texture(
-            self.post_process_manager
-                .full_screen_render_target_ping_pong_textures[2]
+            self.main_view_targets
+                .get_output_texture()
                 .texture
                 .as_image_copy(),
             final_fbo_image_texture.texture.as_image_copy(),
@@ -370,6 +701,29 @@ impl WorldRenderer {
         Ok(())
     }
 
+    /// The closest surface hit by the ray, the renderables for which `is_ignored` returns true are skipped. The ray is
+    /// tested against the triangles of the most detailed level of the renderables
+    pub fn cast_ray(&self, ray: &Line, is_ignored: impl Fn(u32) -> bool) -> Option<SurfaceHit> {
+        self.renderables
+            .iter()
+            .filter(|(id, renderable)| {
+                !is_ignored(**id) && ray.intersect_aabb(&renderable.bounding_box).is_some()
+            })
+            .filter_map(|(_id, renderable)| renderable.intersect_ray(ray))
+            .min_by(|(distance1, _), (distance2, _)| distance1.total_cmp(distance2))
+            .map(|(distance, normal)| SurfaceHit {
+                position: ray.evaluate(distance),
+                normal,
+            })
+    }
+
+    /// The world space bounds of the object's mesh, None if it isn't rendered
+    pub fn get_bounding_box(&self, object_id: &u32) -> Option<Aabb> {
+        self.renderables
+            .get(object_id)
+            .map(|renderable| renderable.bounding_box)
+    }
+
     pub fn recompile_shaders_if_needed(&mut self, device: &Device) -> anyhow::Result<()> {
         // Note, that we stop at the first error and don't process the other shaders if something goes wrong.
         // This is a conscious decision, as for now one usually touches one shader at a time, so
@@ -390,6 +744,7 @@ impl WorldRenderer {
             self.post_process_manager.try_recompile_shader(device)?;
             self.skybox.try_recompile_shader(device)?;
             self.forward_renderer.try_recompile_shader(device)?;
+            self.gpu_culling.try_recompile_shaders(device)?;
             if self
                 .diffuse_irradiance_renderer
                 .try_recompile_shader(device)?
@@ -406,9 +761,191 @@ impl WorldRenderer {
         let width = renderer.config.width;
         let height = renderer.config.height;
 
-        self.gbuffer_geometry_renderer
-            .resize(&renderer.device, width, height);
-        self.post_process_manager
-            .resize(&renderer.device, width, height);
+        self.main_view_targets = RenderViewTargets::new(&renderer.device, width, height);
+        self.gpu_culling.handle_size_changed();
+    }
+}
+
+/// The pipelines and the resources that every view of the world is rendered with
+struct ViewRenderContext<'a> {
+    main_rp: &'a MainRP,
+    gbuffer_geometry_renderer: &'a GBufferGeometryRenderer,
+    post_process_manager: &'a PostProcessManager,
+    forward_renderer: &'a ForwardRenderer,
+    skybox: &'a Skybox,
+    environment_cube_map: &'a BindGroup,
+    diffuse_irradiance_cube_map: &'a BindGroup,
+    light_controller: &'a LightController,
+    global_gpu_params_bind_group: &'a BindGroup,
+}
+
+impl ViewRenderContext<'_> {
+    fn render_geometry(
+        &self,
+        encoder: &mut CommandEncoder,
+        targets: &RenderViewTargets,
+        camera_bind_group: &BindGroup,
+        renderables: &[&Renderable],
+        draw_mode: &DrawMode,
+    ) {
+        let deferred_pass_items = renderables.iter().copied().filter(|renderable| {
+            renderable.description.rendering_options.pass == RenderingPass::DeferredMain
+        });
+        let mut render_pass = self
+            .gbuffer_geometry_renderer
+            .begin_render(encoder, &targets.gbuffer);
+        self.gbuffer_geometry_renderer.render(
+            &mut render_pass,
+            deferred_pass_items,
+            camera_bind_group,
+            self.global_gpu_params_bind_group,
+            draw_mode,
+        );
+    }
+
+    /// The lit image of the view, without the post-process
+    fn render_shading(
+        &self,
+        encoder: &mut CommandEncoder,
+        targets: &mut RenderViewTargets,
+        camera_bind_group: &BindGroup,
+        renderables: &[&Renderable],
+        draw_mode: &DrawMode,
+    ) {
+        {
+            let mut main_shading_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Main shading pass"),
+                timestamp_writes: None,
+            });
+
+            self.main_rp.render(
+                &mut main_shading_pass,
+                camera_bind_group,
+                self.light_controller,
+                &targets.gbuffer.gbuffer_textures_bind_group,
+                self.light_controller
+                    .get_directional_lights_depth_texture_bgroup(),
+                self.light_controller
+                    .get_point_lights_depth_texture_bgroup(),
+                self.diffuse_irradiance_cube_map,
+                targets.post_process.get_next_ping_pong_bind_group(),
+                targets.width,
+                targets.height,
+            );
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Skybox + forward rendering pass"),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &targets
+                    .post_process
+                    .full_screen_render_target_ping_pong_textures[0]
+                    .view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &targets.gbuffer.textures.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+        });
+
+        self.forward_renderer.render(
+            &mut render_pass,
+            renderables.iter().copied().filter(|renderable| {
+                renderable.description.rendering_options.pass
+                    == RenderingPass::ForceForwardAfterDeferred
+            }),
+            camera_bind_group,
+            self.light_controller.get_light_bind_group(),
+            draw_mode,
+        );
+        self.skybox.render(
+            &mut render_pass,
+            camera_bind_group,
+            self.environment_cube_map,
+        );
+    }
+
+    /// Ends with the tone mapping into the output texture of the view. The selection outline is only drawn if the
+    /// outline masks are given
+    fn render_post_process(
+        &self,
+        encoder: &mut CommandEncoder,
+        targets: &mut RenderViewTargets,
+        camera_bind_group: &BindGroup,
+        outline_masks_bind_group: Option<&BindGroup>,
+    ) {
+        // Unfortunately I can't do this in the same pass, because of the pass' and encoder's lifetime
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Postprocessing"),
+                timestamp_writes: None,
+            });
+
+            self.post_process_manager.render_dummy(
+                &mut targets.post_process,
+                &mut compute_pass,
+                targets.width,
+                targets.height,
+                self.global_gpu_params_bind_group,
+            );
+        }
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Postprocessing"),
+                timestamp_writes: None,
+            });
+            self.post_process_manager.render_screen_space_reflections(
+                &mut targets.post_process,
+                &mut compute_pass,
+                targets.width,
+                targets.height,
+                self.global_gpu_params_bind_group,
+                camera_bind_group,
+                self.environment_cube_map,
+                &targets.gbuffer.gbuffer_textures_bind_group,
+                &targets.gbuffer.depth_texture_bind_group,
+            );
+        }
+
+        if let Some(outline_masks_bind_group) = outline_masks_bind_group {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Postprocessing"),
+                timestamp_writes: None,
+            });
+            self.post_process_manager.render_selection_outline(
+                &mut targets.post_process,
+                &mut compute_pass,
+                targets.width,
+                targets.height,
+                self.global_gpu_params_bind_group,
+                outline_masks_bind_group,
+            );
+        }
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Postprocessing"),
+                timestamp_writes: None,
+            });
+            self.post_process_manager.apply_tone_mapping(
+                &mut targets.post_process,
+                &mut compute_pass,
+                targets.width,
+                targets.height,
+                self.global_gpu_params_bind_group,
+            );
+        }
     }
 }